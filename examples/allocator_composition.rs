@@ -0,0 +1,414 @@
+//! Build a custom allocator tree, allocate a realistic mixture of resources,
+//! print reports, defragment and trim, and tear everything down again.
+//!
+//! This is a template for applications which want more control than
+//! create_system_allocator() provides. Run with:
+//!
+//!     RUST_LOG=info cargo run --example allocator_composition
+
+use {
+    anyhow::{Context, Result},
+    ash::vk,
+    ccthw_ash_allocator::{
+        into_shared, Allocation, DedicatedAllocator, DeviceAllocator,
+        FrameBudget, FrameBudgetAllocator, MappedMemory, MemoryAllocator,
+        MemoryProperties, PoolAllocator, SizedAllocator, TraceAllocator,
+        ValidationAllocator,
+    },
+    ccthw_ash_instance::{
+        LogicalDevice, PhysicalDevice, PhysicalDeviceFeatures, QueueFamilyInfo,
+        VulkanHandle, VulkanInstance,
+    },
+};
+
+type Buffers = Vec<(vk::Buffer, Allocation)>;
+type Images = Vec<(vk::Image, Allocation)>;
+
+/// Compose allocators into a tree.
+///
+/// ```text
+/// Validation
+///  └ Trace ("Application Allocator")
+///     └ Dedicated
+///        ├ Sized (<= 64kb)
+///        │  ├ Pool (64kb chunks, 1kb pages)
+///        │  │  └ Pool (4mb chunks, 64kb pages)
+///        │  └ Pool (4mb chunks, 64kb pages)
+///        └ FrameBudget
+///           └ Trace ("Device Allocator")
+///              └ Device
+/// ```
+///
+/// # Safety
+///
+/// Unsafe because the device must outlive the allocator.
+unsafe fn create_allocator(
+    instance: &ash::Instance,
+    device: ash::Device,
+    physical_device: vk::PhysicalDevice,
+) -> MemoryAllocator {
+    let memory_properties = MemoryProperties::new(instance, physical_device);
+
+    // Every real device allocation goes through this allocator, so a frame
+    // budget here is an alarm for pool growth.
+    let device_allocator = into_shared(FrameBudgetAllocator::new(
+        TraceAllocator::new(
+            instance,
+            physical_device,
+            DeviceAllocator::new(device.clone()),
+            "Device Allocator",
+        ),
+        FrameBudget {
+            max_allocations: Some(8),
+            max_bytes: None,
+        },
+        "Device Allocator",
+    ));
+
+    let small_page_size = 1024; // 1kb
+    let small_chunk_size = small_page_size * 64; // 64kb
+    let medium_page_size = small_chunk_size; // 64kb
+    let medium_chunk_size = medium_page_size * 64; // 4mb
+
+    let medium_chunk_pool_allocator = into_shared(PoolAllocator::new(
+        memory_properties.clone(),
+        medium_chunk_size,
+        medium_page_size,
+        device_allocator.clone(),
+    ));
+
+    let small_chunk_pool_allocator = SizedAllocator::new(
+        small_chunk_size,
+        PoolAllocator::new(
+            memory_properties,
+            small_chunk_size,
+            small_page_size,
+            medium_chunk_pool_allocator.clone(),
+        ),
+        medium_chunk_pool_allocator,
+    );
+
+    let dedicated_allocator =
+        DedicatedAllocator::new(small_chunk_pool_allocator, device_allocator);
+
+    let application_allocator = ValidationAllocator::new(TraceAllocator::new(
+        instance,
+        physical_device,
+        dedicated_allocator,
+        "Application Allocator",
+    ));
+
+    MemoryAllocator::new(
+        instance,
+        device,
+        physical_device,
+        application_allocator,
+    )
+}
+
+/// Allocate a mixture of buffers and images which resembles a small scene.
+///
+/// # Safety
+///
+/// Unsafe because the returned resources must be freed before the device is
+/// destroyed.
+unsafe fn allocate_scene(
+    allocator: &MemoryAllocator,
+) -> Result<(Buffers, Images)> {
+    let mut buffers = vec![];
+    let mut images = vec![];
+
+    // Mesh data lives in device-local memory.
+    for size in [16_384, 65_536, 1_048_576] {
+        buffers.push(allocator.allocate_buffer(
+            &mesh_buffer_create_info(size),
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?);
+    }
+
+    // Per-frame uniforms are small and written by the host every frame.
+    for _ in 0..3 {
+        let create_info = vk::BufferCreateInfo {
+            size: 256,
+            usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        buffers.push(allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?);
+    }
+
+    // Textures and a full-screen render target. The render target is big
+    // enough that the driver will likely prefer a dedicated allocation.
+    for (width, height, usage) in [
+        (256, 256, vk::ImageUsageFlags::SAMPLED),
+        (1024, 1024, vk::ImageUsageFlags::SAMPLED),
+        (1920, 1080, vk::ImageUsageFlags::COLOR_ATTACHMENT),
+    ] {
+        let create_info = vk::ImageCreateInfo {
+            image_type: vk::ImageType::TYPE_2D,
+            format: vk::Format::R8G8B8A8_UNORM,
+            extent: vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: usage | vk::ImageUsageFlags::TRANSFER_DST,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        images.push(allocator.allocate_image(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?);
+    }
+
+    Ok((buffers, images))
+}
+
+/// The create info for a device-local mesh buffer. Meshes can be copied, so
+/// they can be moved to new memory when defragmenting.
+fn mesh_buffer_create_info(size: u64) -> vk::BufferCreateInfo {
+    vk::BufferCreateInfo {
+        size,
+        usage: vk::BufferUsageFlags::VERTEX_BUFFER
+            | vk::BufferUsageFlags::TRANSFER_SRC
+            | vk::BufferUsageFlags::TRANSFER_DST,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    }
+}
+
+/// Log the allocator's counters and the layout of every chunk.
+fn log_memory(allocator: &MemoryAllocator, label: &str) {
+    let stats = allocator.stats();
+    log::info!(
+        "{}: {} bytes used by {} allocations, {} bytes allocated from the \
+         device in {} allocations, {} pool chunks, largest free block {} \
+         bytes",
+        label,
+        stats.total.used_bytes,
+        stats.total.allocation_count,
+        stats.total.allocated_bytes,
+        stats.total.device_allocation_count,
+        stats.total.chunk_count,
+        stats.total.largest_free_block,
+    );
+
+    for chunk in allocator.generate_report().chunks {
+        let used_bytes: u64 = chunk
+            .runs
+            .iter()
+            .filter(|run| run.used)
+            .map(|run| run.size_in_bytes)
+            .sum();
+        log::info!(
+            "  {} {} (type {}): {} of {} bytes used in {} runs",
+            chunk.owner,
+            chunk.name,
+            chunk.memory_type_index,
+            used_bytes,
+            chunk.size_in_bytes,
+            chunk.runs.len(),
+        );
+    }
+}
+
+/// Load a batch of small meshes and unload every other one, which leaves
+/// holes in the pool chunks.
+///
+/// # Safety
+///
+/// Unsafe because the returned buffers must be freed before the device is
+/// destroyed.
+unsafe fn fragment(allocator: &MemoryAllocator) -> Result<Buffers> {
+    let mut survivors = vec![];
+    for index in 0..32 {
+        let (buffer, allocation) = allocator.allocate_buffer(
+            &mesh_buffer_create_info(16_384),
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        if index % 2 == 0 {
+            allocator.free_buffer(buffer, allocation);
+        } else {
+            survivors.push((buffer, allocation));
+        }
+    }
+    Ok(survivors)
+}
+
+/// Move buffers into new memory so the chunks they were in can be freed.
+///
+/// Pools never move allocations on their own. Compacting is done by copying
+/// each live buffer into a new allocation, which fills the holes left by
+/// freed buffers, then freeing the old copies once the GPU is done with them.
+/// Chunks which end up empty are returned to the device by
+/// [MemoryAllocator::trim].
+///
+/// # Safety
+///
+/// Unsafe because:
+/// - the buffers must not be in use by the GPU
+/// - the returned buffers must be freed before the device is destroyed
+unsafe fn defragment(
+    allocator: &MemoryAllocator,
+    device: &ash::Device,
+    queue_family_index: u32,
+    buffers: Buffers,
+) -> Result<Buffers> {
+    let queue = device.get_device_queue(queue_family_index, 0);
+    let command_pool = device.create_command_pool(
+        &vk::CommandPoolCreateInfo {
+            queue_family_index,
+            ..Default::default()
+        },
+        None,
+    )?;
+
+    let mut moved = vec![];
+    for (buffer, allocation) in buffers {
+        let create_info = mesh_buffer_create_info(16_384);
+        match allocator.resize_buffer(
+            buffer,
+            allocation,
+            &create_info,
+            create_info.size,
+            queue,
+            command_pool,
+        ) {
+            Ok(buffer) => moved.push(buffer),
+            Err((err, allocation)) => {
+                // The buffer keeps its old memory, free it with the others.
+                log::warn!("Unable to move a mesh buffer: {}", err);
+                moved.push((buffer, allocation));
+            }
+        }
+    }
+
+    // The old buffers are freed once the copies are complete.
+    device.queue_wait_idle(queue)?;
+    allocator.collect()?;
+    device.destroy_command_pool(command_pool, None);
+
+    Ok(moved)
+}
+
+/// Fill a host-visible staging buffer, as an application would before
+/// recording a copy into device-local memory.
+///
+/// # Safety
+///
+/// Unsafe because the staging memory must not be in use by the GPU.
+unsafe fn fill_staging_buffer(
+    allocator: &MemoryAllocator,
+) -> Result<(vk::Buffer, Allocation)> {
+    let data: Vec<u32> = (0..1024).collect();
+    let create_info = vk::BufferCreateInfo {
+        size: std::mem::size_of_val(data.as_slice()) as u64,
+        usage: vk::BufferUsageFlags::TRANSFER_SRC,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let (buffer, allocation) = allocator.allocate_buffer(
+        &create_info,
+        vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+
+    match allocator.map(&allocation)? {
+        MappedMemory::ReadWrite(ptr) => {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                ptr as *mut u32,
+                data.len(),
+            );
+        }
+        MappedMemory::WriteOnly(mut memory) => {
+            memory.copy_from_slice(0, &data);
+        }
+    }
+    allocator.unmap(&allocation)?;
+
+    Ok((buffer, allocation))
+}
+
+fn main() -> Result<()> {
+    let _logger = flexi_logger::Logger::try_with_env_or_str("info")?.start()?;
+
+    let mut instance = unsafe { VulkanInstance::new(&[], &[])? };
+    let physical_device = PhysicalDevice::enumerate_supported_devices(
+        &instance,
+        &PhysicalDeviceFeatures::default(),
+    )?
+    .first()
+    .cloned()
+    .context("Unable to find a usable physical device!")?;
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|props| props.queue_flags.contains(vk::QueueFlags::TRANSFER))
+        .context("Unable to find a transfer queue family!")?;
+    let mut logical_device = unsafe {
+        let mut queue_family_info =
+            QueueFamilyInfo::new(queue_family_index as u32);
+        queue_family_info.add_queue_priority(1.0);
+        LogicalDevice::new(
+            &instance,
+            physical_device,
+            &[],
+            &[queue_family_info],
+        )?
+    };
+
+    unsafe {
+        let allocator = create_allocator(
+            instance.ash(),
+            logical_device.raw().clone(),
+            *logical_device.physical_device().raw(),
+        );
+
+        let (buffers, images) = allocate_scene(&allocator)?;
+        let staging = fill_staging_buffer(&allocator)?;
+        log_memory(&allocator, "Scene loaded");
+
+        allocator.free_buffer(staging.0, staging.1);
+
+        let meshes = fragment(&allocator)?;
+        log_memory(&allocator, "Fragmented");
+
+        let meshes = defragment(
+            &allocator,
+            logical_device.raw(),
+            queue_family_index as u32,
+            meshes,
+        )?;
+        allocator.trim();
+        log_memory(&allocator, "Defragmented and trimmed");
+
+        for (buffer, allocation) in meshes {
+            allocator.free_buffer(buffer, allocation);
+        }
+        for (buffer, allocation) in buffers {
+            allocator.free_buffer(buffer, allocation);
+        }
+        for (image, allocation) in images {
+            allocator.free_image(image, allocation);
+        }
+
+        // Dropping the allocator prints the trace allocator reports.
+        drop(allocator);
+
+        logical_device.destroy();
+        instance.destroy();
+    }
+
+    Ok(())
+}
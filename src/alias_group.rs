@@ -0,0 +1,224 @@
+use {
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError, MemoryAllocator,
+    },
+    anyhow::anyhow,
+    ash::vk,
+};
+
+/// A group of resources which are never alive at the same time, so they can
+/// share the same memory.
+///
+/// Render-graph transient attachments are the typical use case. Attachments
+/// which are only used by passes that don't overlap can alias one another,
+/// which can halve the memory needed for a frame.
+///
+/// Resources are created by the application and added to the group. Then
+/// [Self::resolve] allocates a single region of memory which is large enough
+/// for every resource and binds all of them to it.
+#[derive(Debug, Clone)]
+pub struct AliasGroup {
+    memory_property_flags: vk::MemoryPropertyFlags,
+    images: Vec<vk::Image>,
+    buffers: Vec<vk::Buffer>,
+}
+
+impl AliasGroup {
+    /// Create an empty alias group.
+    ///
+    /// # Params
+    ///
+    /// * memory_property_flags: used to pick the memory type for the shared
+    ///   memory. Every resource in the group must support it.
+    pub fn new(memory_property_flags: vk::MemoryPropertyFlags) -> Self {
+        Self {
+            memory_property_flags,
+            images: vec![],
+            buffers: vec![],
+        }
+    }
+
+    /// Add an image to the group. The image must have OPTIMAL tiling and must
+    /// not already be bound to memory.
+    pub fn add_image(&mut self, image: vk::Image) -> &mut Self {
+        self.images.push(image);
+        self
+    }
+
+    /// Add a buffer to the group. The buffer must not already be bound to
+    /// memory.
+    pub fn add_buffer(&mut self, buffer: vk::Buffer) -> &mut Self {
+        self.buffers.push(buffer);
+        self
+    }
+
+    /// Returns true when no resources have been added to the group.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty() && self.buffers.is_empty()
+    }
+
+    /// Allocate memory for the group and bind every resource to it.
+    ///
+    /// The memory is large enough and aligned for the most demanding
+    /// resource, and must be a memory type that every resource supports.
+    /// When the group contains images, the region is padded to the device's
+    /// bufferImageGranularity so the aliased images never share a page with
+    /// linear resources in neighboring memory.
+    ///
+    /// # Params
+    ///
+    /// * allocator: the allocator which provides the shared memory.
+    ///
+    /// # Returns
+    ///
+    /// The shared allocation. It must be freed with [MemoryAllocator::free]
+    /// after every resource in the group is destroyed.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the application must ensure that no two resources in the group are in
+    ///   use at the same time, and must synchronize the transitions between
+    ///   them
+    /// - the contents of a resource are undefined after another resource in the
+    ///   group has been used
+    pub unsafe fn resolve(
+        &self,
+        allocator: &MemoryAllocator,
+    ) -> Result<Allocation, AllocatorError> {
+        if self.is_empty() {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to resolve an empty alias group"
+            )));
+        }
+
+        let requirements = self.combined_requirements(allocator)?;
+        let allocation = allocator.allocate(requirements)?;
+
+        let result = self.bind_all(allocator.device(), &allocation);
+        if let Err(err) = allocator.record_device_loss(result) {
+            allocator.free(allocation);
+            return Err(err);
+        }
+
+        Ok(allocation)
+    }
+}
+
+// Private API
+// -----------
+
+impl AliasGroup {
+    /// Get requirements which satisfy every resource in the group.
+    fn combined_requirements(
+        &self,
+        allocator: &MemoryAllocator,
+    ) -> Result<AllocationRequirements, AllocatorError> {
+        let device = allocator.device();
+        let memory_types = allocator.memory_properties().types();
+
+        let mut all_requirements = vec![];
+        for &image in &self.images {
+            all_requirements.push(AllocationRequirements::for_image(
+                device,
+                memory_types,
+                self.memory_property_flags,
+                image,
+            )?);
+        }
+        for &buffer in &self.buffers {
+            all_requirements.push(AllocationRequirements::for_buffer(
+                device,
+                memory_types,
+                self.memory_property_flags,
+                buffer,
+            )?);
+        }
+
+        if all_requirements
+            .iter()
+            .any(|requirements| requirements.requires_dedicated_allocation)
+        {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Resources which require a dedicated allocation can't be \
+                 aliased"
+            )));
+        }
+
+        let mut memory_requirements = vk::MemoryRequirements {
+            size: 0,
+            alignment: 1,
+            memory_type_bits: !0,
+        };
+        for requirements in &all_requirements {
+            memory_requirements.size =
+                memory_requirements.size.max(requirements.size_in_bytes);
+            memory_requirements.alignment =
+                memory_requirements.alignment.max(requirements.alignment);
+            memory_requirements.memory_type_bits &=
+                requirements.memory_type_bits;
+        }
+
+        let granularity =
+            allocator.memory_properties().buffer_image_granularity();
+        if !self.images.is_empty() && granularity > 1 {
+            memory_requirements.alignment =
+                memory_requirements.alignment.max(granularity);
+            memory_requirements.size = memory_requirements.size
+                + (granularity - memory_requirements.size % granularity)
+                    % granularity;
+        }
+
+        let memory_type_index = AllocationRequirements::pick_memory_type_index(
+            memory_types,
+            &memory_requirements,
+            self.memory_property_flags,
+        )?;
+
+        Ok(AllocationRequirements {
+            size_in_bytes: memory_requirements.size,
+            alignment: memory_requirements.alignment,
+            memory_type_bits: memory_requirements.memory_type_bits,
+            memory_type_index,
+            memory_properties: self.memory_property_flags,
+            ..AllocationRequirements::default()
+        })
+    }
+
+    /// Bind every resource in the group to the start of the allocation.
+    unsafe fn bind_all(
+        &self,
+        device: &ash::Device,
+        allocation: &Allocation,
+    ) -> Result<(), AllocatorError> {
+        for &image in &self.images {
+            device
+                .bind_image_memory(
+                    image,
+                    allocation.memory(),
+                    allocation.offset_in_bytes(),
+                )
+                .map_err(|err| {
+                    AllocatorError::from_vk_result(
+                        err,
+                        "Error binding an aliased image",
+                    )
+                })?;
+        }
+        for &buffer in &self.buffers {
+            device
+                .bind_buffer_memory(
+                    buffer,
+                    allocation.memory(),
+                    allocation.offset_in_bytes(),
+                )
+                .map_err(|err| {
+                    AllocatorError::from_vk_result(
+                        err,
+                        "Error binding an aliased buffer",
+                    )
+                })?;
+        }
+        Ok(())
+    }
+}
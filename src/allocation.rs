@@ -3,11 +3,19 @@ use {
         pretty_wrappers::PrettySize, AllocationRequirements, AllocatorError,
         DeviceMemory,
     },
+    anyhow::{anyhow, Context},
     ash::vk,
 };
 
+/// Identifies an allocation by its device memory and offset.
+///
+/// Ids are ordered by the device memory's generated id, see
+/// [crate::IdGenerator], so collections of allocations are ordered the same
+/// way every run. The Vulkan handle keeps ids from different allocators
+/// distinct.
 #[derive(Copy, Clone, PartialEq, Eq, Ord, PartialOrd, Debug, Hash)]
 pub(crate) struct AllocationId {
+    memory_id: u64,
     memory: vk::DeviceMemory,
     offset_in_bytes: vk::DeviceSize,
 }
@@ -21,6 +29,11 @@ pub struct Allocation {
     size_in_bytes: vk::DeviceSize,
     memory_type_index: usize,
     allocation_requirements: AllocationRequirements,
+    device_address: Option<vk::DeviceAddress>,
+    path: String,
+    name: Option<String>,
+    user_data: u64,
+    persistently_mapped: bool,
 }
 
 // Public API
@@ -52,11 +65,82 @@ impl Allocation {
         self.size_in_bytes
     }
 
+    /// A sparse memory bind which backs a range of a sparse resource with
+    /// this allocation, for use with vkQueueBindSparse.
+    ///
+    /// # Params
+    ///
+    /// * `resource_offset` - the offset within the sparse resource. It must be
+    ///   a multiple of the resource's sparse block size.
+    pub fn sparse_memory_bind(
+        &self,
+        resource_offset: vk::DeviceSize,
+    ) -> vk::SparseMemoryBind {
+        vk::SparseMemoryBind {
+            resource_offset,
+            size: self.size_in_bytes,
+            // SAFE because the bind only references the memory, ownership
+            // stays with the allocation
+            memory: unsafe { self.device_memory.memory() },
+            memory_offset: self.offset_in_bytes,
+            flags: vk::SparseMemoryBindFlags::empty(),
+        }
+    }
+
     /// The allocation requirements used when acquiring the device memory.
     pub fn allocation_requirements(&self) -> &AllocationRequirements {
         &self.allocation_requirements
     }
 
+    /// The device address of the buffer bound to this allocation.
+    ///
+    /// This is only available for buffers created with the
+    /// SHADER_DEVICE_ADDRESS usage flag. The address is queried once when the
+    /// buffer is allocated so bindless and raytracing code doesn't need to
+    /// query it every frame.
+    pub fn device_address(&self) -> Option<vk::DeviceAddress> {
+        self.device_address
+    }
+
+    /// The path through the allocator tree which produced this allocation.
+    ///
+    /// Each named node prepends a segment as the allocation is returned, so
+    /// the path reads from the root down to the node which carved out the
+    /// memory. e.g. `App/Pool[type 2]/chunk 7`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The name given to the allocation by the application, if any. See
+    /// [crate::MemoryAllocator::name_allocation].
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Application data which is stored with the allocation, e.g. the ID of
+    /// a resource in the application's asset database. This is set with
+    /// [AllocationRequirements::user_data] and is zero by default.
+    pub fn user_data(&self) -> u64 {
+        self.user_data
+    }
+
+    /// The host address of a persistently mapped allocation, see
+    /// [AllocationRequirements::persistently_mapped].
+    ///
+    /// # Returns
+    ///
+    /// None unless the allocation was persistently mapped by the allocator.
+    /// The pointer remains valid until the allocation is freed.
+    pub fn mapped_ptr(&self) -> Option<*mut std::ffi::c_void> {
+        if !self.persistently_mapped {
+            return None;
+        }
+        self.device_memory.mapped_ptr().map(|base_ptr| {
+            (base_ptr as usize + self.offset_in_bytes as usize)
+                as *mut std::ffi::c_void
+        })
+    }
+
     /// Map the allocation into application address space.
     ///
     /// # Safety
@@ -99,6 +183,188 @@ impl Allocation {
     ) -> Result<(), AllocatorError> {
         self.device_memory.unmap(device)
     }
+
+    /// Make host writes to a range of the allocation visible to the device.
+    ///
+    /// This is required for memory which is not HOST_COHERENT, e.g.
+    /// HOST_CACHED memory. The range is rounded out to nonCoherentAtomSize.
+    ///
+    /// # Params
+    ///
+    /// * device: the device which owns the memory.
+    /// * offset: the start of the range, relative to the allocation.
+    /// * size: the size of the range, or vk::WHOLE_SIZE for the rest of the
+    ///   allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - The allocation must be mapped.
+    /// - The application must synchronize access to the underlying memory.
+    ///
+    /// For details, see the specification at:
+    /// https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkFlushMappedMemoryRanges.html
+    pub unsafe fn flush(
+        &self,
+        device: &ash::Device,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<(), AllocatorError> {
+        let range = self.mapped_memory_range(offset, size)?;
+        device
+            .flush_mapped_memory_ranges(&[range])
+            .with_context(|| format!("Unable to flush {:#?}", range))?;
+        Ok(())
+    }
+
+    /// Make device writes to a range of the allocation visible to the host.
+    ///
+    /// This is required for memory which is not HOST_COHERENT, e.g.
+    /// HOST_CACHED memory used for readbacks. The range is rounded out to
+    /// nonCoherentAtomSize.
+    ///
+    /// # Params
+    ///
+    /// * device: the device which owns the memory.
+    /// * offset: the start of the range, relative to the allocation.
+    /// * size: the size of the range, or vk::WHOLE_SIZE for the rest of the
+    ///   allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - The allocation must be mapped.
+    /// - The application must synchronize access to the underlying memory.
+    ///
+    /// For details, see the specification at:
+    /// https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkInvalidateMappedMemoryRanges.html
+    pub unsafe fn invalidate(
+        &self,
+        device: &ash::Device,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<(), AllocatorError> {
+        let range = self.mapped_memory_range(offset, size)?;
+        device
+            .invalidate_mapped_memory_ranges(&[range])
+            .with_context(|| format!("Unable to invalidate {:#?}", range))?;
+        Ok(())
+    }
+
+    /// Copy bytes into the allocation.
+    ///
+    /// The allocation is mapped, written, flushed if the memory isn't
+    /// HOST_COHERENT, then unmapped. See [Self::write_slice].
+    ///
+    /// # Params
+    ///
+    /// * device: the device which owns the memory.
+    /// * offset_in_bytes: where to write, relative to the allocation.
+    /// * data: the bytes to write.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - The GPU must not be accessing the written range.
+    pub unsafe fn write_bytes(
+        &self,
+        device: &ash::Device,
+        offset_in_bytes: vk::DeviceSize,
+        data: &[u8],
+    ) -> Result<(), AllocatorError> {
+        self.write_slice(device, offset_in_bytes, data)
+    }
+
+    /// Copy a slice of values into the allocation.
+    ///
+    /// The allocation is mapped, written, flushed if the memory isn't
+    /// HOST_COHERENT, then unmapped.
+    ///
+    /// # Params
+    ///
+    /// * device: the device which owns the memory.
+    /// * offset_in_bytes: where to write, relative to the allocation.
+    /// * data: the values to write.
+    ///
+    /// # Returns
+    ///
+    /// [AllocatorError::NotHostVisible] when the allocation's memory
+    /// properties don't include HOST_VISIBLE, or an error when the data
+    /// doesn't fit in the allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - The GPU must not be accessing the written range.
+    pub unsafe fn write_slice<T: Copy>(
+        &self,
+        device: &ash::Device,
+        offset_in_bytes: vk::DeviceSize,
+        data: &[T],
+    ) -> Result<(), AllocatorError> {
+        let size_in_bytes = std::mem::size_of_val(data);
+        self.check_host_access(offset_in_bytes, size_in_bytes as u64)?;
+
+        let ptr = self.map(device)? as *mut u8;
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr() as *const u8,
+            ptr.add(offset_in_bytes as usize),
+            size_in_bytes,
+        );
+        let flushed = if self.is_host_coherent() {
+            Ok(())
+        } else {
+            self.flush(device, offset_in_bytes, size_in_bytes as u64)
+        };
+        self.unmap(device)?;
+        flushed
+    }
+
+    /// Copy bytes out of the allocation.
+    ///
+    /// The allocation is mapped, invalidated if the memory isn't
+    /// HOST_COHERENT, read, then unmapped.
+    ///
+    /// # Params
+    ///
+    /// * device: the device which owns the memory.
+    /// * offset_in_bytes: where to read, relative to the allocation.
+    /// * data: filled with the allocation's bytes.
+    ///
+    /// # Returns
+    ///
+    /// [AllocatorError::NotHostVisible] when the allocation's memory
+    /// properties don't include HOST_VISIBLE, or an error when the data
+    /// doesn't fit in the allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - GPU writes to the range must be complete.
+    pub unsafe fn read_bytes(
+        &self,
+        device: &ash::Device,
+        offset_in_bytes: vk::DeviceSize,
+        data: &mut [u8],
+    ) -> Result<(), AllocatorError> {
+        self.check_host_access(offset_in_bytes, data.len() as u64)?;
+
+        let ptr = self.map(device)? as *const u8;
+        let invalidated = if self.is_host_coherent() {
+            Ok(())
+        } else {
+            self.invalidate(device, offset_in_bytes, data.len() as u64)
+        };
+        if invalidated.is_ok() {
+            std::ptr::copy_nonoverlapping(
+                ptr.add(offset_in_bytes as usize),
+                data.as_mut_ptr(),
+                data.len(),
+            );
+        }
+        self.unmap(device)?;
+        invalidated
+    }
 }
 
 impl std::fmt::Debug for Allocation {
@@ -108,6 +374,11 @@ impl std::fmt::Debug for Allocation {
             .field("offset_in_bytes", &PrettySize(self.offset_in_bytes))
             .field("size_in_bytes", &PrettySize(self.size_in_bytes))
             .field("allocation_requirements", &self.allocation_requirements)
+            .field("device_address", &self.device_address)
+            .field("path", &self.path)
+            .field("name", &self.name)
+            .field("user_data", &self.user_data)
+            .field("persistently_mapped", &self.persistently_mapped)
             .finish()
     }
 }
@@ -137,9 +408,101 @@ impl Allocation {
             offset_in_bytes,
             size_in_bytes,
             allocation_requirements,
+            device_address: None,
+            path: String::new(),
+            name: None,
+            user_data: allocation_requirements.user_data,
+            persistently_mapped: false,
         }
     }
 
+    /// Check that the host can access a range of the allocation.
+    ///
+    /// Only the allocation's requested memory properties are known, so memory
+    /// which happens to be host-visible without being requested that way is
+    /// rejected.
+    fn check_host_access(
+        &self,
+        offset_in_bytes: vk::DeviceSize,
+        size_in_bytes: vk::DeviceSize,
+    ) -> Result<(), AllocatorError> {
+        let memory_properties = self.allocation_requirements.memory_properties;
+        if !memory_properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            return Err(AllocatorError::NotHostVisible(memory_properties));
+        }
+        self.mapped_memory_range(offset_in_bytes, size_in_bytes)
+            .map(|_| ())
+    }
+
+    /// Returns true when host writes are visible without a flush.
+    fn is_host_coherent(&self) -> bool {
+        self.allocation_requirements
+            .memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    /// Get the range of device memory to flush or invalidate, rounded out to
+    /// the memory's nonCoherentAtomSize.
+    ///
+    /// # Params
+    ///
+    /// * offset: the start of the range, relative to the allocation.
+    /// * size: the size of the range, or vk::WHOLE_SIZE for the rest of the
+    ///   allocation.
+    pub(crate) fn mapped_memory_range(
+        &self,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<vk::MappedMemoryRange, AllocatorError> {
+        let size = if size == vk::WHOLE_SIZE {
+            self.size_in_bytes.saturating_sub(offset)
+        } else {
+            size
+        };
+        if offset + size > self.size_in_bytes {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "The range at offset {} with size {} is outside of an \
+                 allocation with size {}",
+                offset,
+                size,
+                self.size_in_bytes
+            )));
+        }
+
+        let atom = self.device_memory.non_coherent_atom_size();
+        let start = (self.offset_in_bytes + offset) / atom * atom;
+        let end = self.offset_in_bytes + offset + size;
+        let end = end + (atom - end % atom) % atom;
+
+        // The rounded range can't extend beyond the memory, but
+        // vk::WHOLE_SIZE is always allowed.
+        let size = if end >= self.device_memory.size_in_bytes() {
+            vk::WHOLE_SIZE
+        } else {
+            end - start
+        };
+        Ok(vk::MappedMemoryRange {
+            memory: unsafe { self.memory() },
+            offset: start,
+            size,
+            ..Default::default()
+        })
+    }
+
+    /// Returns true when the allocation holds a persistent mapping which must
+    /// be released when it's freed.
+    pub(crate) fn is_persistently_mapped(&self) -> bool {
+        self.persistently_mapped
+    }
+
+    /// Record that the allocation holds a persistent mapping.
+    pub(crate) fn set_persistently_mapped(
+        &mut self,
+        persistently_mapped: bool,
+    ) {
+        self.persistently_mapped = persistently_mapped;
+    }
+
     /// A unique ID for non-overlapping allocations.
     ///
     /// # Safety
@@ -148,6 +511,7 @@ impl Allocation {
     ///   - IDs may not be unique if there is a bug in a memory allocator.
     pub(crate) unsafe fn id(&self) -> AllocationId {
         AllocationId {
+            memory_id: self.device_memory.id(),
             memory: self.memory(),
             offset_in_bytes: self.offset_in_bytes(),
         }
@@ -205,16 +569,190 @@ impl Allocation {
             offset_in_bytes: full_offset,
             size_in_bytes,
             memory_type_index: allocation.memory_type_index(),
+            // Suballocations don't inherit the parent's user data, it belongs
+            // to whichever allocation the parent was made for.
             allocation_requirements: AllocationRequirements {
                 size_in_bytes,
                 alignment: offset_alignment,
+                user_data: 0,
                 ..allocation.allocation_requirements
             },
+            device_address: None,
+            path: String::new(),
+            name: None,
+            user_data: 0,
+            persistently_mapped: false,
         }
     }
 
+    /// Cache the device address of the buffer bound to this allocation.
+    pub(crate) fn set_device_address(&mut self, address: vk::DeviceAddress) {
+        self.device_address = Some(address);
+    }
+
+    /// Copy the application's tag, location, frame, and user data from the
+    /// requirements it asked for. Suballocations start out with their
+    /// chunk's requirements, which describe whichever request created the
+    /// chunk.
+    pub(crate) fn set_metadata(
+        &mut self,
+        allocation_requirements: &AllocationRequirements,
+    ) {
+        self.user_data = allocation_requirements.user_data;
+        self.allocation_requirements = AllocationRequirements {
+            tag: allocation_requirements.tag,
+            location: allocation_requirements.location,
+            frame: allocation_requirements.frame,
+            user_data: allocation_requirements.user_data,
+            ..self.allocation_requirements
+        };
+    }
+
+    /// Set the allocation's name.
+    pub(crate) fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_owned());
+    }
+
+    /// Add a segment to the front of the allocation's path.
+    pub(crate) fn prepend_path_segment(&mut self, segment: &str) {
+        self.path = if self.path.is_empty() {
+            segment.to_owned()
+        } else {
+            format!("{}/{}", segment, self.path)
+        };
+    }
+
+    /// The id of the device memory which contains the allocation, see
+    /// [crate::IdGenerator].
+    pub(crate) fn memory_id(&self) -> u64 {
+        self.device_memory.id()
+    }
+
     /// The index for the memory type used to allocate this chunk of memory.
     pub(crate) fn memory_type_index(&self) -> usize {
         self.memory_type_index
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn allocation(
+        memory_size: u64,
+        offset_in_bytes: u64,
+        size_in_bytes: u64,
+    ) -> Allocation {
+        Allocation::new(
+            DeviceMemory::new(vk::DeviceMemory::null(), memory_size)
+                .with_non_coherent_atom_size(64),
+            0,
+            offset_in_bytes,
+            size_in_bytes,
+            AllocationRequirements::default(),
+        )
+    }
+
+    #[test]
+    fn test_mapped_memory_range_is_rounded_to_atoms() {
+        let allocation = allocation(1024, 100, 200);
+
+        let range = allocation.mapped_memory_range(10, 20).unwrap();
+        assert_eq!(range.offset, 64);
+        assert_eq!(range.size, 128);
+
+        let range = allocation.mapped_memory_range(0, vk::WHOLE_SIZE).unwrap();
+        assert_eq!(range.offset, 64);
+        assert_eq!(range.size, 256);
+    }
+
+    #[test]
+    fn test_mapped_memory_range_is_clamped_to_the_memory() {
+        let allocation = allocation(300, 100, 200);
+
+        let range = allocation.mapped_memory_range(150, 50).unwrap();
+        assert_eq!(range.offset, 192);
+        assert_eq!(range.size, vk::WHOLE_SIZE);
+    }
+
+    #[test]
+    fn test_host_access_requires_host_visible_memory() {
+        let allocation = allocation(1024, 100, 200);
+
+        assert!(matches!(
+            allocation.check_host_access(0, 4),
+            Err(AllocatorError::NotHostVisible(_))
+        ));
+    }
+
+    #[test]
+    fn test_mapped_memory_range_must_be_inside_the_allocation() {
+        let allocation = allocation(1024, 100, 200);
+
+        assert!(allocation.mapped_memory_range(150, 51).is_err());
+    }
+
+    #[test]
+    fn test_ids_are_ordered_by_generated_memory_id() {
+        use ash::vk::Handle;
+
+        let first = Allocation::new(
+            DeviceMemory::new(vk::DeviceMemory::from_raw(2), 64).with_id(0),
+            0,
+            0,
+            64,
+            AllocationRequirements::default(),
+        );
+        let second = Allocation::new(
+            DeviceMemory::new(vk::DeviceMemory::from_raw(1), 64).with_id(1),
+            0,
+            0,
+            64,
+            AllocationRequirements::default(),
+        );
+
+        unsafe {
+            assert!(first.id() < second.id());
+
+            let suballocation = Allocation::suballocate(&first, 32, 32, 1);
+            assert_eq!(suballocation.parent_id(), Some(first.id()));
+            assert!(suballocation.id() < second.id());
+        }
+    }
+
+    #[test]
+    fn test_suballocations_take_the_metadata_of_their_request() {
+        let chunk = Allocation::new(
+            DeviceMemory::new(vk::DeviceMemory::null(), 256),
+            0,
+            0,
+            256,
+            AllocationRequirements {
+                tag: Some("chunk"),
+                user_data: 1,
+                ..AllocationRequirements::default()
+            },
+        );
+
+        let mut allocation =
+            unsafe { Allocation::suballocate(&chunk, 0, 64, 1) };
+        assert_eq!(allocation.user_data(), 0);
+
+        let requirements = AllocationRequirements {
+            tag: Some("textures"),
+            location: Some(std::panic::Location::caller()),
+            frame: Some(2),
+            user_data: 42,
+            ..AllocationRequirements::default()
+        };
+        allocation.set_metadata(&requirements);
+        assert_eq!(allocation.user_data(), 42);
+        assert_eq!(allocation.allocation_requirements().tag, Some("textures"));
+        assert_eq!(
+            allocation.allocation_requirements().location,
+            requirements.location
+        );
+        assert_eq!(allocation.allocation_requirements().frame, Some(2));
+        assert_eq!(allocation.size_in_bytes(), 64);
+    }
+}
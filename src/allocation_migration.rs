@@ -0,0 +1,143 @@
+use {
+    crate::{Allocation, AllocatorError, ComposableAllocator},
+    ash::vk,
+};
+
+/// A plan for moving the contents of an allocation into memory owned by a
+/// different allocator with the same memory type.
+///
+/// This is how a resource can be "promoted" from a short-lived streaming pool
+/// to a persistent pool once it turns out to be long-lived (or demoted back).
+/// The migration owns both the source and the destination allocation until it
+/// is either finished or cancelled, so the application never holds an
+/// allocation which the migration frees.
+pub struct AllocationMigration {
+    source: Allocation,
+    destination: Allocation,
+}
+
+impl AllocationMigration {
+    /// Allocate replacement memory for an allocation from a different
+    /// allocator.
+    ///
+    /// # Params
+    ///
+    /// * source: the allocation being migrated. It is returned by
+    ///   [Self::cancel], or along with the error when the destination can't be
+    ///   allocated.
+    /// * destination_allocator: the allocator which will own the new memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the migration must be finished or cancelled, otherwise the destination
+    ///   memory is leaked.
+    #[allow(clippy::result_large_err)]
+    pub unsafe fn new<A: ComposableAllocator>(
+        source: Allocation,
+        destination_allocator: &mut A,
+    ) -> Result<Self, (AllocatorError, Allocation)> {
+        let destination = match destination_allocator
+            .allocate(*source.allocation_requirements())
+        {
+            Ok(destination) => destination,
+            Err(err) => return Err((err, source)),
+        };
+        debug_assert!(
+            destination.memory_type_index() == source.memory_type_index()
+        );
+        Ok(Self {
+            source,
+            destination,
+        })
+    }
+
+    /// The allocation whose contents are being migrated.
+    pub fn source(&self) -> &Allocation {
+        &self.source
+    }
+
+    /// The newly allocated memory which will replace the source.
+    pub fn destination(&self) -> &Allocation {
+        &self.destination
+    }
+
+    /// The copy required to migrate a buffer. This assumes that both the
+    /// source and destination buffers are bound at the beginning of their
+    /// respective allocations.
+    pub fn buffer_copy(&self) -> vk::BufferCopy {
+        vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size: self.source.size_in_bytes(),
+        }
+    }
+
+    /// Copy the contents of the source allocation into the destination
+    /// allocation on the host. Both allocations must be HOST_VISIBLE.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the application must synchronize access to both allocations. Any GPU
+    ///   work which writes to the source must be complete.
+    pub unsafe fn copy_on_host(
+        &self,
+        device: &ash::Device,
+    ) -> Result<(), AllocatorError> {
+        let src = self.source.map(device)?;
+        let dst = match self.destination.map(device) {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                self.source.unmap(device)?;
+                return Err(err);
+            }
+        };
+        std::ptr::copy_nonoverlapping(
+            src as *const u8,
+            dst as *mut u8,
+            self.source.size_in_bytes() as usize,
+        );
+        self.destination.unmap(device)?;
+        self.source.unmap(device)
+    }
+
+    /// Complete the migration by returning the source allocation to its
+    /// allocator.
+    ///
+    /// # Returns
+    ///
+    /// The destination allocation which now replaces the source.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the contents must already have been copied, and resources must be
+    ///   rebound to the destination memory.
+    /// - the source memory is freed, see [ComposableAllocator::free].
+    pub unsafe fn finish<A: ComposableAllocator>(
+        self,
+        source_allocator: &mut A,
+    ) -> Allocation {
+        source_allocator.free(self.source);
+        self.destination
+    }
+
+    /// Abandon the migration and free the destination memory.
+    ///
+    /// # Returns
+    ///
+    /// The source allocation, which remains valid.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because the destination memory is freed, see
+    /// [ComposableAllocator::free].
+    pub unsafe fn cancel<A: ComposableAllocator>(
+        self,
+        destination_allocator: &mut A,
+    ) -> Allocation {
+        destination_allocator.free(self.destination);
+        self.source
+    }
+}
@@ -0,0 +1,61 @@
+#[cfg(feature = "backtrace")]
+use std::{backtrace::Backtrace, sync::Arc};
+
+/// The code which made or freed an allocation, used to point leak and
+/// double-free reports at the caller.
+///
+/// A backtrace is only captured when the `backtrace` feature is enabled.
+/// Otherwise this is empty and costs nothing. Capturing a backtrace for every
+/// allocation is slow, so the feature is intended for debug builds. Allocation
+/// names, see [crate::MemoryAllocator::name_allocation], are a cheaper way to
+/// label allocations in the same reports.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AllocationOrigin {
+    #[cfg(feature = "backtrace")]
+    backtrace: Option<Arc<Backtrace>>,
+}
+
+impl AllocationOrigin {
+    /// Capture the current call stack, if the `backtrace` feature is enabled.
+    pub(crate) fn capture() -> Self {
+        Self {
+            #[cfg(feature = "backtrace")]
+            backtrace: Some(Arc::new(Backtrace::force_capture())),
+        }
+    }
+
+    /// Describe the origin for a report.
+    ///
+    /// # Params
+    ///
+    /// * label: what happened at the origin, e.g. "Allocated".
+    ///
+    /// # Returns
+    ///
+    /// An empty string when no backtrace was captured, so the description
+    /// can be appended to any report.
+    pub(crate) fn describe(&self, label: &str) -> String {
+        #[cfg(feature = "backtrace")]
+        if let Some(backtrace) = &self.backtrace {
+            return format!("\n\n{} at:\n{}", label, backtrace);
+        }
+        let _ = label;
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_describe_includes_the_backtrace_when_enabled() {
+        let description = AllocationOrigin::capture().describe("Allocated");
+        if cfg!(feature = "backtrace") {
+            assert!(description.starts_with("\n\nAllocated at:\n"));
+        } else {
+            assert!(description.is_empty());
+        }
+        assert!(AllocationOrigin::default().describe("Freed").is_empty());
+    }
+}
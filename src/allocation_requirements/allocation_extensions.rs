@@ -0,0 +1,200 @@
+use ash::vk;
+
+/// Memory owned by another API or process which is imported rather than
+/// allocated, see [crate::MemoryAllocator::import_memory_fd].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ImportedHandle {
+    /// A POSIX file descriptor for VkImportMemoryFdInfoKHR.
+    Fd(vk::ExternalMemoryHandleTypeFlags, std::os::raw::c_int),
+
+    /// A Win32 handle for VkImportMemoryWin32HandleInfoKHR. The handle is
+    /// kept as an address so requirements can be sent between threads.
+    Win32(vk::ExternalMemoryHandleTypeFlags, usize),
+}
+
+/// Extension structures which are added to the VkMemoryAllocateInfo chain
+/// when device memory is allocated.
+///
+/// Allocations are only suballocated from memory which was allocated with
+/// compatible extensions, see [Self::can_use_memory_from].
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct AllocationExtensions {
+    memory_allocate_flags: vk::MemoryAllocateFlags,
+    device_mask: Option<u32>,
+    export_handle_types: vk::ExternalMemoryHandleTypeFlags,
+    priority: Option<f32>,
+    import: Option<ImportedHandle>,
+}
+
+// The priority is checked when it's set, so it's never NaN.
+impl Eq for AllocationExtensions {}
+
+impl AllocationExtensions {
+    /// Add flags to VkMemoryAllocateFlagsInfo, e.g. DEVICE_ADDRESS for memory
+    /// bound to buffers with SHADER_DEVICE_ADDRESS usage.
+    pub fn with_memory_allocate_flags(
+        self,
+        memory_allocate_flags: vk::MemoryAllocateFlags,
+    ) -> Self {
+        Self {
+            memory_allocate_flags: self.memory_allocate_flags
+                | memory_allocate_flags,
+            ..self
+        }
+    }
+
+    /// Allocate the memory on a subset of the physical devices in a device
+    /// group. This also adds the DEVICE_MASK allocate flag.
+    pub fn with_device_mask(self, device_mask: u32) -> Self {
+        Self {
+            device_mask: Some(device_mask),
+            ..self
+        }
+        .with_memory_allocate_flags(vk::MemoryAllocateFlags::DEVICE_MASK)
+    }
+
+    /// Allocate memory which can be exported with the given handle types
+    /// with VkExportMemoryAllocateInfo.
+    pub fn with_export_handle_types(
+        self,
+        export_handle_types: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Self {
+        Self {
+            export_handle_types,
+            ..self
+        }
+    }
+
+    /// Set the memory priority with VkMemoryPriorityAllocateInfoEXT.
+    /// VK_EXT_memory_priority must be enabled on the device.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the priority is not between 0.0 and 1.0.
+    pub fn with_priority(self, priority: f32) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&priority),
+            "Memory priority must be between 0.0 and 1.0, got {}",
+            priority
+        );
+        Self {
+            priority: Some(priority),
+            ..self
+        }
+    }
+
+    /// Import memory from another API or process instead of allocating it.
+    pub(crate) fn with_import(self, import: ImportedHandle) -> Self {
+        Self {
+            import: Some(import),
+            ..self
+        }
+    }
+
+    /// The flags for VkMemoryAllocateFlagsInfo.
+    pub fn memory_allocate_flags(&self) -> vk::MemoryAllocateFlags {
+        self.memory_allocate_flags
+    }
+
+    /// The device mask for VkMemoryAllocateFlagsInfo, if any.
+    pub fn device_mask(&self) -> Option<u32> {
+        self.device_mask
+    }
+
+    /// The handle types for VkExportMemoryAllocateInfo.
+    pub fn export_handle_types(&self) -> vk::ExternalMemoryHandleTypeFlags {
+        self.export_handle_types
+    }
+
+    /// The priority for VkMemoryPriorityAllocateInfoEXT, if any.
+    pub fn priority(&self) -> Option<f32> {
+        self.priority
+    }
+
+    /// The handle to import instead of allocating memory, if any.
+    pub(crate) fn import(&self) -> Option<ImportedHandle> {
+        self.import
+    }
+
+    /// Returns true when an allocation with these extensions can be
+    /// suballocated from memory which was allocated with the other
+    /// extensions.
+    ///
+    /// The memory must have at least the allocate flags that are needed
+    /// here, e.g. memory with DEVICE_ADDRESS can be used by any allocation.
+    /// Every other extension must match exactly. Imported memory is never
+    /// shared.
+    pub fn can_use_memory_from(&self, memory_extensions: &Self) -> bool {
+        self.import.is_none()
+            && memory_extensions.import.is_none()
+            && memory_extensions
+                .memory_allocate_flags
+                .contains(self.memory_allocate_flags)
+            && self.device_mask == memory_extensions.device_mask
+            && self.export_handle_types == memory_extensions.export_handle_types
+            && self.priority == memory_extensions.priority
+    }
+
+    /// Returns true when no extension structures are needed.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_address_memory_can_be_shared() {
+        let plain = AllocationExtensions::default();
+        let device_address = plain.with_memory_allocate_flags(
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS,
+        );
+        assert!(plain.can_use_memory_from(&device_address));
+        assert!(!device_address.can_use_memory_from(&plain));
+        assert!(device_address.can_use_memory_from(&device_address));
+    }
+
+    #[test]
+    fn test_other_extensions_must_match() {
+        let plain = AllocationExtensions::default();
+        let exported = plain.with_export_handle_types(
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+        );
+        let prioritized = plain.with_priority(1.0);
+        assert!(!plain.can_use_memory_from(&exported));
+        assert!(!exported.can_use_memory_from(&plain));
+        assert!(!plain.can_use_memory_from(&prioritized));
+        assert!(prioritized.can_use_memory_from(&plain.with_priority(1.0)));
+    }
+
+    #[test]
+    fn test_imported_memory_is_never_shared() {
+        let plain = AllocationExtensions::default();
+        let imported = plain.with_import(ImportedHandle::Fd(
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+            3,
+        ));
+        assert!(!plain.can_use_memory_from(&imported));
+        assert!(!imported.can_use_memory_from(&plain));
+        assert!(!imported.can_use_memory_from(&imported));
+        assert!(!imported.is_empty());
+    }
+
+    #[test]
+    fn test_device_mask_adds_the_allocate_flag() {
+        let extensions = AllocationExtensions::default().with_device_mask(0b10);
+        assert_eq!(extensions.device_mask(), Some(0b10));
+        assert!(extensions
+            .memory_allocate_flags()
+            .contains(vk::MemoryAllocateFlags::DEVICE_MASK));
+        assert!(!extensions.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_priority_must_be_in_range() {
+        AllocationExtensions::default().with_priority(f32::NAN);
+    }
+}
@@ -1,11 +1,23 @@
 use {
-    crate::{AllocatorError, PrettyBitflag, PrettySize},
+    crate::{
+        memory_properties::find_memory_type, AllocatorError, PrettyBitflag,
+        PrettySize,
+    },
+    anyhow::anyhow,
     ash::vk,
+    std::panic::Location,
 };
 
+mod allocation_extensions;
 mod dedicated_resource_handle;
+mod resource_kind;
 
-pub use self::dedicated_resource_handle::DedicatedResourceHandle;
+pub(crate) use self::allocation_extensions::ImportedHandle;
+pub use self::{
+    allocation_extensions::AllocationExtensions,
+    dedicated_resource_handle::DedicatedResourceHandle,
+    resource_kind::ResourceKind,
+};
 
 /// All supported memory requirements.
 ///
@@ -21,6 +33,38 @@ pub struct AllocationRequirements {
     pub prefers_dedicated_allocation: bool,
     pub requires_dedicated_allocation: bool,
     pub dedicated_resource_handle: DedicatedResourceHandle,
+
+    /// Whether the resource is linear or optimal, used to keep resources
+    /// apart by bufferImageGranularity when they share memory.
+    pub resource_kind: ResourceKind,
+
+    /// Map the memory when it's allocated and keep it mapped until it's
+    /// freed, see [crate::Allocation::mapped_ptr]. Requires HOST_VISIBLE
+    /// memory properties.
+    pub persistently_mapped: bool,
+
+    /// Extension structures for the VkMemoryAllocateInfo chain.
+    pub extensions: AllocationExtensions,
+
+    /// Groups allocations for accounting, e.g. "textures" or "meshes". See
+    /// [crate::BudgetTarget::Tag].
+    pub tag: Option<&'static str>,
+
+    /// The application code which requested the allocation, used by trace
+    /// and leak reports. [crate::MemoryAllocator] fills this in with
+    /// `#[track_caller]` when it's None.
+    pub location: Option<&'static Location<'static>>,
+
+    /// The frame when the allocation was requested, used to group
+    /// allocations by frame in stats and reports.
+    /// [crate::MemoryAllocator] fills this in with the current frame when
+    /// it's None, see [crate::MemoryAllocator::begin_frame].
+    pub frame: Option<u64>,
+
+    /// Application data which is stored with the allocation and shown in
+    /// reports, e.g. the ID of a resource in the application's asset
+    /// database. See [crate::Allocation::user_data].
+    pub user_data: u64,
 }
 
 // Public API
@@ -40,6 +84,43 @@ impl AllocationRequirements {
         memory_types: &[vk::MemoryType],
         memory_property_flags: vk::MemoryPropertyFlags,
         buffer: vk::Buffer,
+    ) -> Result<Self, AllocatorError> {
+        Self::for_buffer_preferring(
+            device,
+            memory_types,
+            memory_property_flags,
+            vk::MemoryPropertyFlags::empty(),
+            buffer,
+        )
+    }
+
+    /// Get the memory requirements for a given buffer, preferring a memory type
+    /// with additional properties when one is available.
+    ///
+    /// For example, preferring DEVICE_LOCAL with HOST_VISIBLE required picks
+    /// resizable-BAR memory when the device has it, and falls back to plain
+    /// host-visible memory otherwise.
+    ///
+    /// # Params
+    ///
+    /// * `device` - the device used to create and interact with GPU resources
+    /// * `memory_types` - the memory types available on the physical device
+    /// * `required_memory_property_flags` - the memory properties required by
+    ///   the allocation
+    /// * `preferred_memory_property_flags` - additional memory properties which
+    ///   are used when a memory type supports them
+    /// * `buffer` - the buffer which needs a memory allocation
+    ///
+    /// # Returns
+    ///
+    /// The requirements, where `memory_properties` includes the preferred
+    /// flags only if the chosen memory type supports them.
+    pub fn for_buffer_preferring(
+        device: &ash::Device,
+        memory_types: &[vk::MemoryType],
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+        buffer: vk::Buffer,
     ) -> Result<Self, AllocatorError> {
         let mut dedicated_requirements =
             vk::MemoryDedicatedRequirements::default();
@@ -61,22 +142,28 @@ impl AllocationRequirements {
             );
         }
 
-        let memory_type_index = Self::pick_memory_type_index(
-            memory_types,
-            &memory_requirements2.memory_requirements,
-            memory_property_flags,
-        )?;
+        let (memory_type_index, memory_property_flags) =
+            Self::pick_preferred_memory_type_index(
+                memory_types,
+                &memory_requirements2.memory_requirements,
+                required_memory_property_flags,
+                preferred_memory_property_flags,
+            )?;
         Ok(Self::from_memory_requirements(
             &dedicated_requirements,
             &memory_requirements2.memory_requirements,
             memory_type_index,
             memory_property_flags,
             DedicatedResourceHandle::Buffer(buffer),
+            ResourceKind::Linear,
         ))
     }
 
     /// Get the memory requirements for a given image.
     ///
+    /// The image is assumed to have optimal tiling. Set the `resource_kind`
+    /// with [ResourceKind::for_image_tiling] for linear images.
+    ///
     /// # Params
     ///
     /// * `device` - the device used to create and interact with GPU resources
@@ -88,6 +175,43 @@ impl AllocationRequirements {
         memory_types: &[vk::MemoryType],
         memory_property_flags: vk::MemoryPropertyFlags,
         image: vk::Image,
+    ) -> Result<Self, AllocatorError> {
+        Self::for_image_preferring(
+            device,
+            memory_types,
+            memory_property_flags,
+            vk::MemoryPropertyFlags::empty(),
+            image,
+        )
+    }
+
+    /// Get the memory requirements for a given image, preferring a memory type
+    /// with additional properties when one is available.
+    ///
+    /// For example, preferring DEVICE_LOCAL with HOST_VISIBLE required picks
+    /// resizable-BAR memory when the device has it, and falls back to plain
+    /// host-visible memory otherwise.
+    ///
+    /// # Params
+    ///
+    /// * `device` - the device used to create and interact with GPU resources
+    /// * `memory_types` - the memory types available on the physical device
+    /// * `required_memory_property_flags` - the memory properties required by
+    ///   the allocation
+    /// * `preferred_memory_property_flags` - additional memory properties which
+    ///   are used when a memory type supports them
+    /// * `image` - the image which needs a memory allocation
+    ///
+    /// # Returns
+    ///
+    /// The requirements, where `memory_properties` includes the preferred
+    /// flags only if the chosen memory type supports them.
+    pub fn for_image_preferring(
+        device: &ash::Device,
+        memory_types: &[vk::MemoryType],
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+        image: vk::Image,
     ) -> Result<Self, AllocatorError> {
         let mut dedicated_requirements =
             vk::MemoryDedicatedRequirements::default();
@@ -109,24 +233,72 @@ impl AllocationRequirements {
             );
         }
 
-        let memory_type_index = Self::pick_memory_type_index(
-            memory_types,
-            &memory_requirements2.memory_requirements,
-            memory_property_flags,
-        )?;
+        let (memory_type_index, memory_property_flags) =
+            Self::pick_preferred_memory_type_index(
+                memory_types,
+                &memory_requirements2.memory_requirements,
+                required_memory_property_flags,
+                preferred_memory_property_flags,
+            )?;
         Ok(Self::from_memory_requirements(
             &dedicated_requirements,
             &memory_requirements2.memory_requirements,
             memory_type_index,
             memory_property_flags,
             DedicatedResourceHandle::Image(image),
+            ResourceKind::Optimal,
         ))
     }
 
+    /// Use an explicit memory type rather than the one picked from the memory
+    /// property flags.
+    ///
+    /// # Params
+    ///
+    /// * `memory_types` - the memory types available on the physical device
+    /// * `memory_type_index` - the memory type to use
+    ///
+    /// # Returns
+    ///
+    /// The updated requirements, whose memory properties are the chosen
+    /// type's property flags. An error is returned when the index is out of
+    /// range or isn't allowed by the requirements' memory type bits.
+    pub fn with_memory_type_index(
+        self,
+        memory_types: &[vk::MemoryType],
+        memory_type_index: usize,
+    ) -> Result<Self, AllocatorError> {
+        let memory_type = match memory_types.get(memory_type_index) {
+            Some(memory_type) => memory_type,
+            None => {
+                return Err(AllocatorError::RuntimeError(anyhow!(
+                    "Memory type index {} is out of range, there are {} \
+                     memory types",
+                    memory_type_index,
+                    memory_types.len()
+                )));
+            }
+        };
+        if memory_type_index >= 32
+            || self.memory_type_bits & (1 << memory_type_index) == 0
+        {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Memory type index {} is not allowed by memory type bits {}",
+                memory_type_index,
+                PrettyBitflag(self.memory_type_bits)
+            )));
+        }
+        Ok(Self {
+            memory_type_index,
+            memory_properties: memory_type.property_flags,
+            ..self
+        })
+    }
+
     /// Compute the maximum size which must be allocated to ensure an aligned
     /// offset for the resulting memory.
     pub fn aligned_size(&self) -> u64 {
-        self.size_in_bytes + self.alignment - 1
+        self.size_in_bytes + self.alignment.max(1) - 1
     }
 }
 
@@ -147,6 +319,13 @@ impl std::fmt::Debug for AllocationRequirements {
                 &self.requires_dedicated_allocation,
             )
             .field("dedicated_resource_handle", &self.dedicated_resource_handle)
+            .field("resource_kind", &self.resource_kind)
+            .field("persistently_mapped", &self.persistently_mapped)
+            .field("extensions", &self.extensions)
+            .field("tag", &self.tag)
+            .field("location", &self.location)
+            .field("frame", &self.frame)
+            .field("user_data", &self.user_data)
             .finish()
     }
 }
@@ -168,6 +347,7 @@ impl AllocationRequirements {
         memory_type_index: usize,
         memory_property_flags: vk::MemoryPropertyFlags,
         dedicated_resource_handle: DedicatedResourceHandle,
+        resource_kind: ResourceKind,
     ) -> Self {
         let prefers_dedicated_allocation =
             dedicated_requirements.prefers_dedicated_allocation == vk::TRUE;
@@ -189,7 +369,55 @@ impl AllocationRequirements {
             prefers_dedicated_allocation,
             requires_dedicated_allocation,
             dedicated_resource_handle: resource_handle,
+            resource_kind,
+            persistently_mapped: false,
+            extensions: AllocationExtensions::default(),
+            tag: None,
+            location: None,
+            frame: None,
+            user_data: 0,
+        }
+    }
+
+    /// Pick a memory type which has both the required and preferred
+    /// properties, falling back to a type with only the required properties.
+    ///
+    /// # Params
+    ///
+    /// - `memory_types` - a slice of all avialable memory types
+    /// - `memory_requirements` - the memory requirements for the resource
+    /// - `required_memory_property_flags` - the required memory properties
+    /// - `preferred_memory_property_flags` - memory properties which are used
+    ///   when available
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the memory type index and the memory properties which were
+    /// satisfied, or an [AllocatorError] when no memory type has the required
+    /// properties.
+    pub(crate) fn pick_preferred_memory_type_index(
+        memory_types: &[vk::MemoryType],
+        memory_requirements: &vk::MemoryRequirements,
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<(usize, vk::MemoryPropertyFlags), AllocatorError> {
+        let preferred_flags =
+            required_memory_property_flags | preferred_memory_property_flags;
+        if preferred_flags != required_memory_property_flags {
+            if let Ok(index) = Self::pick_memory_type_index(
+                memory_types,
+                memory_requirements,
+                preferred_flags,
+            ) {
+                return Ok((index, preferred_flags));
+            }
         }
+        let index = Self::pick_memory_type_index(
+            memory_types,
+            memory_requirements,
+            required_memory_property_flags,
+        )?;
+        Ok((index, required_memory_property_flags))
     }
 
     /// Pick the optimal memory type for the given memory requirements and
@@ -206,28 +434,119 @@ impl AllocationRequirements {
     /// A result containing either the index of the suitable memory type in
     /// `memory_types`, or an [AllocatorError] indicating that no suitable
     /// memory type could be found.
-    fn pick_memory_type_index(
+    pub(crate) fn pick_memory_type_index(
         memory_types: &[vk::MemoryType],
         memory_requirements: &vk::MemoryRequirements,
         memory_property_flags: vk::MemoryPropertyFlags,
     ) -> Result<usize, AllocatorError> {
-        memory_types
-            .iter()
-            .enumerate()
-            .find(|(index, memory_type)| {
-                let type_bits = 1 << index;
-                let is_required_type =
-                    type_bits & memory_requirements.memory_type_bits != 0;
-
-                let has_required_properties =
-                    memory_type.property_flags.contains(memory_property_flags);
-
-                is_required_type && has_required_properties
-            })
-            .map(|(i, _memory_type)| i)
-            .ok_or(AllocatorError::NoSupportedTypeForProperties(
-                PrettyBitflag(memory_requirements.memory_type_bits),
-                memory_property_flags,
-            ))
+        find_memory_type(
+            memory_types,
+            memory_requirements.memory_type_bits,
+            memory_property_flags,
+        )
+        .ok_or(AllocatorError::NoSupportedTypeForProperties(
+            PrettyBitflag(memory_requirements.memory_type_bits),
+            memory_property_flags,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn memory_types() -> Vec<vk::MemoryType> {
+        vec![
+            vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+            vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+                heap_index: 1,
+            },
+            vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
+                    | vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+                heap_index: 0,
+            },
+        ]
+    }
+
+    fn memory_requirements(memory_type_bits: u32) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size: 1024,
+            alignment: 1,
+            memory_type_bits,
+        }
+    }
+
+    #[test]
+    fn test_explicit_memory_type_index() {
+        let requirements = AllocationRequirements {
+            memory_type_bits: 0b110,
+            ..AllocationRequirements::default()
+        };
+
+        let updated = requirements
+            .with_memory_type_index(&memory_types(), 1)
+            .unwrap();
+        assert_eq!(updated.memory_type_index, 1);
+        assert_eq!(updated.memory_properties, memory_types()[1].property_flags);
+
+        // Not allowed by the memory type bits.
+        assert!(requirements
+            .with_memory_type_index(&memory_types(), 0)
+            .is_err());
+
+        // Out of range.
+        assert!(requirements
+            .with_memory_type_index(&memory_types(), 3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_preferred_flags_are_used_when_available() {
+        let result = AllocationRequirements::pick_preferred_memory_type_index(
+            &memory_types(),
+            &memory_requirements(!0),
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            (
+                2,
+                vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::DEVICE_LOCAL
+            )
+        );
+    }
+
+    #[test]
+    fn test_required_flags_are_used_as_a_fallback() {
+        // The resource can't use the device-local host-visible type.
+        let result = AllocationRequirements::pick_preferred_memory_type_index(
+            &memory_types(),
+            &memory_requirements(0b011),
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .unwrap();
+        assert_eq!(result, (1, vk::MemoryPropertyFlags::HOST_VISIBLE));
+    }
+
+    #[test]
+    fn test_required_flags_must_be_satisfied() {
+        let result = AllocationRequirements::pick_preferred_memory_type_index(
+            &memory_types(),
+            &memory_requirements(0b001),
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        assert!(result.is_err());
     }
 }
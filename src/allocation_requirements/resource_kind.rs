@@ -0,0 +1,35 @@
+use ash::vk;
+
+/// The kind of resource bound to an allocation.
+///
+/// Linear and optimal resources which share memory must be separated by
+/// VkPhysicalDeviceLimits::bufferImageGranularity.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResourceKind {
+    /// Buffers and images with VK_IMAGE_TILING_LINEAR.
+    Linear,
+
+    /// Images with VK_IMAGE_TILING_OPTIMAL.
+    Optimal,
+
+    /// The resource isn't known, e.g. raw memory from
+    /// [crate::MemoryAllocator::allocate]. The granularity is not enforced.
+    Unknown,
+}
+
+impl ResourceKind {
+    /// The resource kind for an image with the given tiling.
+    pub fn for_image_tiling(tiling: vk::ImageTiling) -> Self {
+        if tiling == vk::ImageTiling::LINEAR {
+            Self::Linear
+        } else {
+            Self::Optimal
+        }
+    }
+}
+
+impl Default for ResourceKind {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
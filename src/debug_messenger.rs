@@ -0,0 +1,255 @@
+use {
+    crate::{
+        pretty_wrappers::PrettySize, AllocationRequirements, AllocatorError,
+    },
+    anyhow::Context,
+    ash::{
+        extensions::ext::DebugUtils,
+        vk::{self, Handle},
+    },
+    std::{
+        collections::HashMap,
+        ffi::{c_void, CStr},
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Controls what happens when the validation layers report a message about
+/// device memory owned by the allocator.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValidationErrorBehavior {
+    /// Log the message along with details about the allocations which use the
+    /// memory.
+    Log,
+
+    /// Log the message and details, then abort the process. Panics cannot
+    /// unwind through the Vulkan callback, so this is the closest thing to an
+    /// assertion.
+    Abort,
+}
+
+/// Details about a live allocation which are useful when reading a
+/// validation message.
+#[derive(Debug, Copy, Clone)]
+struct AllocationSummary {
+    offset_in_bytes: u64,
+    size_in_bytes: u64,
+    memory_type_index: usize,
+    allocation_requirements: AllocationRequirements,
+}
+
+/// A shared record of every live allocation, indexed by the device memory
+/// handle.
+///
+/// Populated by an [crate::AnnotatingAllocator] and read by the
+/// [AllocatorDebugMessenger] to turn handle-based validation messages into
+/// actionable reports.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAnnotations {
+    live: Arc<Mutex<HashMap<u64, Vec<AllocationSummary>>>>,
+}
+
+/// A debug utils messenger which annotates validation messages that refer to
+/// memory owned by the allocator.
+pub struct AllocatorDebugMessenger {
+    debug_utils: DebugUtils,
+    messenger: vk::DebugUtilsMessengerEXT,
+    _state: Box<MessengerState>,
+}
+
+/// State which is shared with the Vulkan callback via the user data ptr.
+struct MessengerState {
+    annotations: MemoryAnnotations,
+    behavior: ValidationErrorBehavior,
+}
+
+// Public API
+// ----------
+
+impl MemoryAnnotations {
+    /// Describe every live allocation which uses the given device memory.
+    ///
+    /// # Returns
+    ///
+    /// None when the memory is not owned by the allocator.
+    pub fn describe(&self, memory: vk::DeviceMemory) -> Option<String> {
+        let live = self.live.lock().unwrap();
+        let summaries = live.get(&memory.as_raw())?;
+        let descriptions: Vec<String> = summaries
+            .iter()
+            .map(|summary| {
+                format!(
+                    "- offset: {}, size: {}, memory type: {}\n  {:?}",
+                    PrettySize(summary.offset_in_bytes),
+                    PrettySize(summary.size_in_bytes),
+                    summary.memory_type_index,
+                    summary.allocation_requirements,
+                )
+            })
+            .collect();
+        Some(format!(
+            "{:?} is used by {} allocation(s)\n{}",
+            memory,
+            summaries.len(),
+            descriptions.join("\n")
+        ))
+    }
+}
+
+impl AllocatorDebugMessenger {
+    /// Create a new debug messenger for validation warnings and errors.
+    ///
+    /// Messages which do not reference memory owned by the allocator are
+    /// ignored, so this can be used alongside the application's own
+    /// messenger.
+    ///
+    /// # Params
+    ///
+    /// * entry: the Vulkan entry used to create the instance.
+    /// * instance: an instance with the VK_EXT_debug_utils extension enabled.
+    /// * annotations: the annotations shared with an AnnotatingAllocator.
+    /// * behavior: what to do when an annotated message is reported.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the messenger must be destroyed before the instance.
+    pub unsafe fn new(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        annotations: MemoryAnnotations,
+        behavior: ValidationErrorBehavior,
+    ) -> Result<Self, AllocatorError> {
+        let debug_utils = DebugUtils::new(entry, instance);
+        let mut state = Box::new(MessengerState {
+            annotations,
+            behavior,
+        });
+        let create_info = vk::DebugUtilsMessengerCreateInfoEXT {
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_type: vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            pfn_user_callback: Some(annotate_validation_message),
+            p_user_data: state.as_mut() as *mut MessengerState as *mut c_void,
+            ..Default::default()
+        };
+        let messenger = debug_utils
+            .create_debug_utils_messenger(&create_info, None)
+            .with_context(|| "Unable to create the debug messenger!")?;
+        Ok(Self {
+            debug_utils,
+            messenger,
+            _state: state,
+        })
+    }
+
+    /// Destroy the debug messenger.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the instance must still be alive.
+    /// - the messenger must not be used after it is destroyed.
+    pub unsafe fn destroy(&mut self) {
+        self.debug_utils
+            .destroy_debug_utils_messenger(self.messenger, None);
+        self.messenger = vk::DebugUtilsMessengerEXT::null();
+    }
+}
+
+// Private API
+// -----------
+
+impl MemoryAnnotations {
+    /// Record a new live allocation.
+    pub(crate) fn insert(
+        &self,
+        memory: vk::DeviceMemory,
+        offset_in_bytes: u64,
+        size_in_bytes: u64,
+        memory_type_index: usize,
+        allocation_requirements: AllocationRequirements,
+    ) {
+        self.live
+            .lock()
+            .unwrap()
+            .entry(memory.as_raw())
+            .or_default()
+            .push(AllocationSummary {
+                offset_in_bytes,
+                size_in_bytes,
+                memory_type_index,
+                allocation_requirements,
+            });
+    }
+
+    /// Forget about an allocation once it's been freed.
+    pub(crate) fn remove(
+        &self,
+        memory: vk::DeviceMemory,
+        offset_in_bytes: u64,
+    ) {
+        let mut live = self.live.lock().unwrap();
+        let summaries = match live.get_mut(&memory.as_raw()) {
+            Some(summaries) => summaries,
+            None => return,
+        };
+        summaries.retain(|summary| summary.offset_in_bytes != offset_in_bytes);
+        if summaries.is_empty() {
+            live.remove(&memory.as_raw());
+        }
+    }
+}
+
+/// The Vulkan debug callback.
+unsafe extern "system" fn annotate_validation_message(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    _message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    let state = &*(p_user_data as *const MessengerState);
+    let callback_data = &*p_callback_data;
+
+    let objects = if callback_data.object_count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(
+            callback_data.p_objects,
+            callback_data.object_count as usize,
+        )
+    };
+    let annotations: Vec<String> = objects
+        .iter()
+        .filter(|object| object.object_type == vk::ObjectType::DEVICE_MEMORY)
+        .filter_map(|object| {
+            state
+                .annotations
+                .describe(vk::DeviceMemory::from_raw(object.object_handle))
+        })
+        .collect();
+    if annotations.is_empty() {
+        return vk::FALSE;
+    }
+
+    let message = if callback_data.p_message.is_null() {
+        "".into()
+    } else {
+        CStr::from_ptr(callback_data.p_message).to_string_lossy()
+    };
+    let report = format!(
+        "Validation message about allocator memory:\n\n{}\n\n{}",
+        message,
+        annotations.join("\n\n")
+    );
+    if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        log::error!("{}", report);
+        if state.behavior == ValidationErrorBehavior::Abort {
+            std::process::abort();
+        }
+    } else {
+        log::warn!("{}", report);
+    }
+
+    vk::FALSE
+}
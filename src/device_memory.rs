@@ -13,22 +13,83 @@ use {
 /// calls to vkMapMemory.
 #[derive(Clone)]
 pub struct DeviceMemory {
+    id: u64,
     memory: vk::DeviceMemory,
+    size_in_bytes: vk::DeviceSize,
+    non_coherent_atom_size: vk::DeviceSize,
     shared_mapped_ptr: Arc<Mutex<MappedPtr>>,
 }
 
+/// The largest nonCoherentAtomSize allowed by the Vulkan spec. Flush and
+/// invalidate ranges rounded to this size are valid on every device.
+const MAX_NON_COHERENT_ATOM_SIZE: vk::DeviceSize = 256;
+
 // Public Api
 // ----------
 
 impl DeviceMemory {
     /// Create a new DeviceMemory instance.
-    pub fn new(memory: vk::DeviceMemory) -> Self {
+    ///
+    /// # Params
+    ///
+    /// * memory: the Vulkan memory handle.
+    /// * size_in_bytes: the size passed to vkAllocateMemory.
+    pub fn new(
+        memory: vk::DeviceMemory,
+        size_in_bytes: vk::DeviceSize,
+    ) -> Self {
         Self {
+            id: 0,
             memory,
+            size_in_bytes,
+            non_coherent_atom_size: MAX_NON_COHERENT_ATOM_SIZE,
             shared_mapped_ptr: Arc::default(),
         }
     }
 
+    /// Round flush and invalidate ranges to the device's nonCoherentAtomSize
+    /// rather than the largest size allowed by the spec.
+    ///
+    /// # Params
+    ///
+    /// * non_coherent_atom_size: VkPhysicalDeviceLimits::nonCoherentAtomSize
+    pub fn with_non_coherent_atom_size(
+        self,
+        non_coherent_atom_size: vk::DeviceSize,
+    ) -> Self {
+        Self {
+            non_coherent_atom_size: non_coherent_atom_size.max(1),
+            ..self
+        }
+    }
+
+    /// Identify the device memory with an id from the allocator's
+    /// [crate::IdGenerator] rather than by its Vulkan handle, so allocations
+    /// are ordered the same way every run.
+    ///
+    /// # Params
+    ///
+    /// * id: the id for the device memory.
+    pub fn with_id(self, id: u64) -> Self {
+        Self { id, ..self }
+    }
+
+    /// The id given to the device memory by the allocator which created it.
+    /// Memory which was created without an id generator has the id 0.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The size of the device memory.
+    pub fn size_in_bytes(&self) -> vk::DeviceSize {
+        self.size_in_bytes
+    }
+
+    /// The granularity of flush and invalidate ranges.
+    pub fn non_coherent_atom_size(&self) -> vk::DeviceSize {
+        self.non_coherent_atom_size
+    }
+
     /// The underlying Vulkan memory handle.
     ///
     /// # Safety
@@ -41,6 +102,17 @@ impl DeviceMemory {
         self.memory
     }
 
+    /// The pointer to the beginning of the device memory, if it is currently
+    /// mapped.
+    pub fn mapped_ptr(&self) -> Option<*mut c_void> {
+        let lock = self.shared_mapped_ptr.lock().unwrap();
+        if lock.map_count == 0 {
+            None
+        } else {
+            Some(lock.host_accessible_ptr)
+        }
+    }
+
     /// Get a memory-mapped ptr to the beginning of the device memory
     /// allocation. The entire region of memory is always mapped.
     ///
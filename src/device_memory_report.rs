@@ -0,0 +1,230 @@
+use {
+    crate::{pretty_wrappers::PrettySize, AllocatorStats},
+    ash::vk,
+    std::{
+        collections::HashMap,
+        ffi::c_void,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// A heap where the driver and the allocator disagree about how much device
+/// memory is live.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeapDiscrepancy {
+    /// The heap with the discrepancy.
+    pub heap_index: usize,
+
+    /// The bytes of VkDeviceMemory the driver reported for the heap.
+    pub driver_bytes: u64,
+
+    /// The bytes the allocator allocated from the heap.
+    pub allocator_bytes: u64,
+}
+
+/// A VkDeviceMemory object reported by the driver.
+#[derive(Debug, Copy, Clone)]
+struct DriverAllocation {
+    size_in_bytes: u64,
+    heap_index: u32,
+}
+
+/// Records device memory events from VK_EXT_device_memory_report so the
+/// driver's view of device memory can be reconciled with the allocator's own
+/// accounting.
+///
+/// The callback can only be registered when the device is created, so chain
+/// [Self::create_info] into VkDeviceCreateInfo. The VK_EXT_device_memory_report
+/// extension and the deviceMemoryReport feature must be enabled.
+///
+/// Memory allocated behind the allocator's back, e.g. by another library or
+/// with [crate::MemoryAllocator::import_host_memory], shows up as a
+/// discrepancy in [Self::reconcile].
+#[derive(Debug, Clone, Default)]
+pub struct DeviceMemoryReport {
+    live: Arc<Mutex<HashMap<u64, DriverAllocation>>>,
+}
+
+// Public API
+// ----------
+
+impl DeviceMemoryReport {
+    /// Create an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The structure to chain into VkDeviceCreateInfo's pNext chain.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the report, or a clone of it, must outlive the device.
+    pub unsafe fn create_info(
+        &self,
+    ) -> vk::DeviceDeviceMemoryReportCreateInfoEXT {
+        vk::DeviceDeviceMemoryReportCreateInfoEXT {
+            pfn_user_callback: Some(record_device_memory_event),
+            p_user_data: Arc::as_ptr(&self.live) as *mut c_void,
+            ..Default::default()
+        }
+    }
+
+    /// The bytes of live VkDeviceMemory the driver has reported for each
+    /// heap.
+    ///
+    /// # Params
+    ///
+    /// * heap_count: the number of heaps on the device.
+    pub fn heap_usage(&self, heap_count: usize) -> Vec<u64> {
+        let mut usage = vec![0; heap_count];
+        for allocation in self.live.lock().unwrap().values() {
+            if let Some(bytes) = usage.get_mut(allocation.heap_index as usize) {
+                *bytes += allocation.size_in_bytes;
+            }
+        }
+        usage
+    }
+
+    /// Compare the driver's view of live device memory with the allocator's
+    /// stats, logging a warning for each heap where they disagree.
+    ///
+    /// # Params
+    ///
+    /// * stats: the allocator's stats, see [crate::MemoryAllocator::stats].
+    ///
+    /// # Returns
+    ///
+    /// The heaps where the driver and the allocator disagree.
+    pub fn reconcile(&self, stats: &AllocatorStats) -> Vec<HeapDiscrepancy> {
+        let driver_usage = self.heap_usage(stats.memory_heaps.len());
+        let discrepancies: Vec<HeapDiscrepancy> = stats
+            .memory_heaps
+            .iter()
+            .zip(driver_usage)
+            .enumerate()
+            .filter(|(_, (usage, driver_bytes))| {
+                usage.allocated_bytes != *driver_bytes
+            })
+            .map(|(heap_index, (usage, driver_bytes))| HeapDiscrepancy {
+                heap_index,
+                driver_bytes,
+                allocator_bytes: usage.allocated_bytes,
+            })
+            .collect();
+        for discrepancy in &discrepancies {
+            log::warn!(
+                "The driver reports {} of device memory in heap {}, but the \
+                 allocator accounts for {}",
+                PrettySize(discrepancy.driver_bytes),
+                discrepancy.heap_index,
+                PrettySize(discrepancy.allocator_bytes),
+            );
+        }
+        discrepancies
+    }
+}
+
+// Private API
+// -----------
+
+/// Update the live allocations for a single device memory event.
+fn record_event(
+    live: &Mutex<HashMap<u64, DriverAllocation>>,
+    data: &vk::DeviceMemoryReportCallbackDataEXT,
+) {
+    // Drivers also report internal allocations for other objects, only
+    // VkDeviceMemory is comparable with the allocator's accounting.
+    if data.object_type != vk::ObjectType::DEVICE_MEMORY {
+        return;
+    }
+    let mut live = live.lock().unwrap();
+    match data.ty {
+        vk::DeviceMemoryReportEventTypeEXT::ALLOCATE
+        | vk::DeviceMemoryReportEventTypeEXT::IMPORT => {
+            live.insert(
+                data.memory_object_id,
+                DriverAllocation {
+                    size_in_bytes: data.size,
+                    heap_index: data.heap_index,
+                },
+            );
+        }
+        vk::DeviceMemoryReportEventTypeEXT::FREE
+        | vk::DeviceMemoryReportEventTypeEXT::UNIMPORT => {
+            live.remove(&data.memory_object_id);
+        }
+        vk::DeviceMemoryReportEventTypeEXT::ALLOCATION_FAILED => {
+            log::warn!(
+                "The driver failed to allocate {} from memory heap {}",
+                PrettySize(data.size),
+                data.heap_index
+            );
+        }
+        _ => (),
+    }
+}
+
+/// The Vulkan device memory report callback.
+unsafe extern "system" fn record_device_memory_event(
+    p_callback_data: *const vk::DeviceMemoryReportCallbackDataEXT,
+    p_user_data: *mut c_void,
+) {
+    let live = &*(p_user_data as *const Mutex<HashMap<u64, DriverAllocation>>);
+    record_event(live, &*p_callback_data);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(
+        ty: vk::DeviceMemoryReportEventTypeEXT,
+        memory_object_id: u64,
+        size: u64,
+        heap_index: u32,
+    ) -> vk::DeviceMemoryReportCallbackDataEXT {
+        vk::DeviceMemoryReportCallbackDataEXT {
+            ty,
+            memory_object_id,
+            size,
+            object_type: vk::ObjectType::DEVICE_MEMORY,
+            heap_index,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reconcile_flags_unknown_memory() {
+        let report = DeviceMemoryReport::new();
+        let allocate = vk::DeviceMemoryReportEventTypeEXT::ALLOCATE;
+        record_event(&report.live, &event(allocate, 1, 1024, 0));
+        record_event(&report.live, &event(allocate, 2, 512, 1));
+        record_event(&report.live, &event(allocate, 3, 256, 1));
+        record_event(
+            &report.live,
+            &event(vk::DeviceMemoryReportEventTypeEXT::FREE, 3, 256, 1),
+        );
+        record_event(
+            &report.live,
+            &vk::DeviceMemoryReportCallbackDataEXT {
+                object_type: vk::ObjectType::IMAGE,
+                ..event(allocate, 4, 4096, 0)
+            },
+        );
+        assert_eq!(report.heap_usage(2), vec![1024, 512]);
+
+        let mut stats = AllocatorStats::default();
+        stats.memory_heaps = vec![Default::default(); 2];
+        stats.memory_heaps[0].allocated_bytes = 1024;
+        assert_eq!(
+            report.reconcile(&stats),
+            vec![HeapDiscrepancy {
+                heap_index: 1,
+                driver_bytes: 512,
+                allocator_bytes: 0,
+            }]
+        );
+    }
+}
@@ -1,10 +1,112 @@
-use {crate::pretty_wrappers::PrettyBitflag, ash::vk, thiserror::Error};
+use {
+    crate::{pretty_wrappers::PrettyBitflag, BudgetTarget},
+    ash::vk,
+    thiserror::Error,
+};
 
 #[derive(Error, Debug)]
 pub enum AllocatorError {
     #[error("No memory type for bits {0} and flags {1:#?}")]
     NoSupportedTypeForProperties(PrettyBitflag, vk::MemoryPropertyFlags),
 
+    #[error(
+        "Allocating {size_in_bytes} bytes would exceed the budget for \
+         {target}. {used_bytes} of {limit_bytes} bytes are already in use."
+    )]
+    BudgetExceeded {
+        target: BudgetTarget,
+        size_in_bytes: u64,
+        used_bytes: u64,
+        limit_bytes: u64,
+    },
+
+    #[error(
+        "Unable to allocate {size_in_bytes} bytes, the largest possible \
+         device memory allocation is {max_allocation_size} bytes."
+    )]
+    AllocationTooLarge {
+        size_in_bytes: u64,
+        max_allocation_size: u64,
+    },
+
+    #[error(
+        "The device already has {allocation_count} live memory allocations, \
+         the limit is {max_allocation_count}."
+    )]
+    TooManyDeviceAllocations {
+        allocation_count: u32,
+        max_allocation_count: u32,
+    },
+
+    #[error(
+        "A new {chunk_size} byte chunk would grow the pool for memory type \
+         {memory_type_index} past its limit. The pool already has \
+         {pool_bytes} of {max_pool_bytes} bytes."
+    )]
+    PoolLimitExceeded {
+        memory_type_index: usize,
+        chunk_size: u64,
+        pool_bytes: u64,
+        max_pool_bytes: u64,
+    },
+
+    #[error(
+        "The host can't access memory with flags {0:#?}, HOST_VISIBLE is \
+         required."
+    )]
+    NotHostVisible(vk::MemoryPropertyFlags),
+
+    #[error(
+        "The allocator is frozen, no new allocations are allowed until it is \
+         thawed."
+    )]
+    Frozen,
+
+    #[error("The device was lost, no new allocations are possible.")]
+    DeviceLost,
+
     #[error(transparent)]
     RuntimeError(#[from] anyhow::Error),
 }
+
+impl AllocatorError {
+    /// Convert a Vulkan error into an allocator error.
+    ///
+    /// Device loss gets a dedicated variant so applications can handle it
+    /// deterministically. Every other error becomes a RuntimeError with the
+    /// provided context.
+    pub(crate) fn from_vk_result(
+        result: vk::Result,
+        context: impl std::fmt::Display + Send + Sync + 'static,
+    ) -> Self {
+        if result == vk::Result::ERROR_DEVICE_LOST {
+            Self::DeviceLost
+        } else {
+            Self::RuntimeError(anyhow::Error::new(result).context(context))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_lost_gets_a_dedicated_variant() {
+        let err = AllocatorError::from_vk_result(
+            vk::Result::ERROR_DEVICE_LOST,
+            "allocating",
+        );
+        assert!(matches!(err, AllocatorError::DeviceLost));
+    }
+
+    #[test]
+    fn test_other_errors_keep_context() {
+        let err = AllocatorError::from_vk_result(
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+            "allocating",
+        );
+        assert!(matches!(err, AllocatorError::RuntimeError(_)));
+        assert!(err.to_string().contains("allocating"));
+    }
+}
@@ -0,0 +1,44 @@
+use {crate::MemoryProperties, ash::vk};
+
+/// A coarse classification of a device's memory architecture.
+///
+/// The best allocator composition depends heavily on how memory is laid out.
+/// Discrete GPUs have a large pool of dedicated video memory, while unified
+/// memory architectures share system memory with the host.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeapClass {
+    /// Separate device-local and host memory heaps, e.g. a desktop GPU.
+    Discrete,
+
+    /// Every heap is device-local and some memory is also host-visible, e.g.
+    /// an integrated GPU or APU.
+    Unified,
+
+    /// A unified memory architecture with lazily allocated memory for
+    /// transient attachments, e.g. a tile-based deferred renderer on mobile.
+    MobileTbdr,
+}
+
+impl HeapClass {
+    /// Detect the heap class from the device's memory properties.
+    pub fn detect(memory_properties: &MemoryProperties) -> Self {
+        if memory_properties.has_lazily_allocated_memory() {
+            return Self::MobileTbdr;
+        }
+
+        let has_host_visible_device_memory =
+            memory_properties.types().iter().any(|memory_type| {
+                memory_type.property_flags.contains(
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL
+                        | vk::MemoryPropertyFlags::HOST_VISIBLE,
+                )
+            });
+        if memory_properties.is_unified_memory()
+            && has_host_visible_device_memory
+        {
+            return Self::Unified;
+        }
+
+        Self::Discrete
+    }
+}
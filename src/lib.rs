@@ -1,39 +1,97 @@
 //! A general purpose Vulkan Memory allocator, written from scratch the hard
 //! way.
 
+mod alias_group;
 mod allocation;
+mod allocation_migration;
+mod allocation_origin;
 mod allocation_requirements;
+mod debug_messenger;
 mod device_memory;
+mod device_memory_report;
 mod error;
+mod heap_class;
+mod mapped_memory;
 mod memory_allocator;
 mod memory_properties;
+mod owned_resource;
 mod pretty_wrappers;
+mod readback;
+mod sparse;
+mod sparse_image_residency;
+mod staging_belt;
+mod system_allocator_builder;
+mod system_allocator_config;
+mod typed_buffer;
 
 use {
     self::{
         allocation::AllocationId,
+        allocation_origin::AllocationOrigin,
+        allocation_requirements::ImportedHandle,
         device_memory::DeviceMemory,
         pretty_wrappers::{PrettyBitflag, PrettySize},
     },
     ash::vk,
 };
 
+#[cfg(feature = "egui")]
+pub use self::memory_allocator::MemoryPanel;
 pub use self::{
+    alias_group::AliasGroup,
     allocation::Allocation,
+    allocation_migration::AllocationMigration,
     allocation_requirements::{
-        AllocationRequirements, DedicatedResourceHandle,
+        AllocationExtensions, AllocationRequirements, DedicatedResourceHandle,
+        ResourceKind,
     },
+    debug_messenger::{
+        AllocatorDebugMessenger, MemoryAnnotations, ValidationErrorBehavior,
+    },
+    device_memory_report::{DeviceMemoryReport, HeapDiscrepancy},
     error::AllocatorError,
+    heap_class::HeapClass,
+    mapped_memory::{MappedMemory, WriteOnlyMemory},
     memory_allocator::{
-        into_shared, ComposableAllocator, DedicatedAllocator, DeviceAllocator,
-        FakeAllocator, MemoryAllocator, MemoryTypePoolAllocator,
-        PageSuballocator, PoolAllocator, SizedAllocator, TraceAllocator,
+        into_shared, AlignmentAuditAllocator, AlignmentLimits,
+        AlignmentViolation, AllocationReport, AllocatorStats,
+        AnnotatingAllocator, BudgetAllocator, BudgetForecast, BudgetTarget,
+        CanaryAllocator, ChunkReport, ComposableAllocator, DedicatedAllocator,
+        DeviceAllocator, FailingAllocator, FailureMode, FakeAllocator,
+        FallbackAllocator, FrameBudget, FrameBudgetAllocator, FrameClock,
+        FrameUsage, GpuCompletion, HeapUsageAllocator, HeapUsageHook,
+        HeapUsageWarning, Histogram, IdGenerator, MemoryAllocator,
+        MemoryReport, MemoryRun, MemoryTypePoolAllocator, MemoryUsage,
+        NamedAllocator, PageSuballocator, PoolAllocator, PoolConfigurator,
+        PoolHandle, PoolSettings, QuarantineAllocator, QuarantinePolicy,
+        SizedAllocator, SoakTestAllocator, SoakTestFailureHook,
+        TilingAllocator, TraceAllocator, TraceMetrics, TraceReport, UploadPath,
+        UsageSample, UsageSampler, ValidationAllocator, VirtualAllocation,
+        VirtualBlock, VisualizationFormat,
     },
-    memory_properties::MemoryProperties,
+    memory_properties::{HeapBudget, MemoryProperties},
+    owned_resource::{OwnedBuffer, OwnedImage},
+    readback::Readback,
+    sparse::{sparse_memory_binds, sparse_memory_unbinds},
+    sparse_image_residency::{SparseImageResidency, SparseTile},
+    staging_belt::StagingBelt,
+    system_allocator_builder::SystemAllocatorBuilder,
+    system_allocator_config::SystemAllocatorConfig,
+    typed_buffer::Buffer,
 };
 
 /// Create an opinionated system allocator for GPU memoy.
 ///
+/// The composition depends on the device's [HeapClass]. Discrete devices get
+/// three tiers of pools which carve small allocations out of very large device
+/// allocations. Unified memory is shared with the rest of the system, so those
+/// devices get fewer, smaller tiers. Devices with only a single memory type
+/// (common for compute-only and virtualized devices) get a single tier.
+///
+/// Use [SystemAllocatorBuilder] to tune the tier sizes. Sizes can also be
+/// overridden with `ASH_ALLOC_` environment variables, see
+/// [SystemAllocatorConfig::with_env_overrides].
+///
 /// # Safety
 ///
 /// Unsafe because:
@@ -46,64 +104,32 @@ pub unsafe fn create_system_allocator(
     device: ash::Device,
     physical_device: vk::PhysicalDevice,
 ) -> MemoryAllocator {
-    let memory_properties = MemoryProperties::new(instance, physical_device);
-
-    let device_allocator = into_shared(TraceAllocator::new(
+    SystemAllocatorBuilder::new().env_overrides().build(
         instance,
+        device,
         physical_device,
-        DeviceAllocator::new(device.clone()),
-        "Device Allocator",
-    ));
-
-    let small_page_size = 1024; // 1kb
-    let small_chunk_size = small_page_size * 64; // 64kb
-    let medium_page_size = small_chunk_size; // 64kb
-    let medium_chunk_size = medium_page_size * 64; // 4mb
-    let root_page_size = medium_chunk_size; // 4mb
-    let root_chunk_size = medium_chunk_size * 128; // 0.5gb
-
-    let large_chunk_pool_allocator = into_shared(SizedAllocator::new(
-        root_chunk_size,
-        PoolAllocator::new(
-            memory_properties.clone(),
-            root_chunk_size,
-            root_page_size,
-            device_allocator.clone(),
-        ),
-        device_allocator.clone(),
-    ));
-
-    let medium_chunk_pool_allocator = into_shared(SizedAllocator::new(
-        medium_chunk_size,
-        PoolAllocator::new(
-            memory_properties.clone(),
-            medium_chunk_size,
-            medium_page_size,
-            large_chunk_pool_allocator.clone(),
-        ),
-        large_chunk_pool_allocator,
-    ));
-
-    let small_chunk_pool_allocator = SizedAllocator::new(
-        small_chunk_size,
-        PoolAllocator::new(
-            memory_properties,
-            small_chunk_size,
-            small_page_size,
-            medium_chunk_pool_allocator.clone(),
-        ),
-        medium_chunk_pool_allocator,
-    );
-
-    let dedicated_allocator =
-        DedicatedAllocator::new(small_chunk_pool_allocator, device_allocator);
+    )
+}
 
-    let system_allocator = TraceAllocator::new(
+/// Create the system allocator with custom settings. See
+/// [create_system_allocator].
+///
+/// # Safety
+///
+/// Unsafe because:
+/// - The application must keep the device alive for as long as the allocator is
+///   alive.
+/// - The application must free any memory it allocates prior to dropping the
+///   memory allocator or device.
+pub unsafe fn create_system_allocator_with_config(
+    instance: &ash::Instance,
+    device: ash::Device,
+    physical_device: vk::PhysicalDevice,
+    config: SystemAllocatorConfig,
+) -> MemoryAllocator {
+    SystemAllocatorBuilder::from_config(config).build(
         instance,
+        device,
         physical_device,
-        dedicated_allocator,
-        "Application Allocator",
-    );
-
-    MemoryAllocator::new(instance, device, physical_device, system_allocator)
+    )
 }
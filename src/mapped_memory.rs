@@ -0,0 +1,130 @@
+use std::ffi::c_void;
+
+/// A host-accessible view of a mapped allocation.
+#[derive(Debug)]
+pub enum MappedMemory {
+    /// Memory which can be freely read and written by the host.
+    ReadWrite(*mut c_void),
+
+    /// Write-combined memory (HOST_VISIBLE but not HOST_CACHED).
+    ///
+    /// Host reads from write-combined memory are uncached and can be orders
+    /// of magnitude slower than writes, so only write operations are exposed.
+    WriteOnly(WriteOnlyMemory),
+}
+
+/// A write-only view of mapped memory.
+#[derive(Debug)]
+pub struct WriteOnlyMemory {
+    ptr: *mut u8,
+    size_in_bytes: u64,
+}
+
+// Public API
+// ----------
+
+impl WriteOnlyMemory {
+    /// The size of the mapped region in bytes.
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size_in_bytes
+    }
+
+    /// Write a single value at the given byte offset.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the value would be written outside of the mapped region.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the memory must still be mapped
+    /// - the application must synchronize access to the underlying memory
+    pub unsafe fn write<T: Copy>(&mut self, offset_in_bytes: u64, value: T) {
+        self.check_bounds(offset_in_bytes, std::mem::size_of::<T>());
+        let dst = self.ptr.add(offset_in_bytes as usize) as *mut T;
+        std::ptr::write_unaligned(dst, value);
+    }
+
+    /// Copy a slice of values into the mapped memory, starting at the given
+    /// byte offset.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the data would be written outside of the mapped region.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the memory must still be mapped
+    /// - the application must synchronize access to the underlying memory
+    pub unsafe fn copy_from_slice<T: Copy>(
+        &mut self,
+        offset_in_bytes: u64,
+        data: &[T],
+    ) {
+        let size = std::mem::size_of_val(data);
+        self.check_bounds(offset_in_bytes, size);
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr() as *const u8,
+            self.ptr.add(offset_in_bytes as usize),
+            size,
+        );
+    }
+}
+
+// Private API
+// -----------
+
+impl WriteOnlyMemory {
+    /// Create a write-only view of mapped memory.
+    pub(crate) fn new(ptr: *mut c_void, size_in_bytes: u64) -> Self {
+        Self {
+            ptr: ptr as *mut u8,
+            size_in_bytes,
+        }
+    }
+
+    fn check_bounds(&self, offset_in_bytes: u64, size: usize) {
+        let end = offset_in_bytes.checked_add(size as u64);
+        assert!(
+            end.is_some_and(|end| end <= self.size_in_bytes),
+            "Attempted to write outside of the mapped memory!"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_and_copy() {
+        let mut backing = vec![0_u8; 8];
+        let mut memory =
+            WriteOnlyMemory::new(backing.as_mut_ptr() as *mut c_void, 8);
+        unsafe {
+            memory.write(1, 0xAB_u8);
+            memory.copy_from_slice(4, &[1_u8, 2, 3, 4]);
+        }
+        assert_eq!(backing, [0, 0xAB, 0, 0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_write_out_of_bounds() {
+        let mut backing = vec![0_u8; 4];
+        let mut memory =
+            WriteOnlyMemory::new(backing.as_mut_ptr() as *mut c_void, 4);
+        unsafe { memory.write(2, 0_u32) };
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the mapped memory")]
+    fn test_write_with_an_overflowing_offset() {
+        let mut backing = vec![0_u8; 4];
+        let mut memory =
+            WriteOnlyMemory::new(backing.as_mut_ptr() as *mut c_void, 4);
+        unsafe { memory.write(u64::MAX, 0_u32) };
+    }
+}
@@ -0,0 +1,266 @@
+use {
+    crate::{
+        Allocation, AllocationId, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, MemoryProperties, MemoryReport,
+        PoolConfigurator,
+    },
+    anyhow::anyhow,
+    ash::vk,
+    std::collections::BTreeMap,
+};
+
+/// Device limits which constrain where an allocation may be placed in device
+/// memory.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct AlignmentLimits {
+    /// The alignment of pointers returned by vkMapMemory.
+    pub min_memory_map_alignment: u64,
+
+    /// The granularity of flush and invalidate ranges for memory which is not
+    /// host coherent.
+    pub non_coherent_atom_size: u64,
+
+    /// The required offset alignment for texel buffers.
+    pub min_texel_buffer_offset_alignment: u64,
+}
+
+impl AlignmentLimits {
+    /// Query the alignment limits for the given physical device.
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        let limits = unsafe {
+            instance
+                .get_physical_device_properties(physical_device)
+                .limits
+        };
+        Self {
+            min_memory_map_alignment: limits.min_memory_map_alignment as u64,
+            non_coherent_atom_size: limits.non_coherent_atom_size,
+            min_texel_buffer_offset_alignment: limits
+                .min_texel_buffer_offset_alignment,
+        }
+    }
+}
+
+/// A live allocation whose offset does not satisfy one of its alignment
+/// constraints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentViolation {
+    /// The allocation's path through the allocator tree.
+    pub path: String,
+
+    /// The allocation's offset in device memory.
+    pub offset_in_bytes: u64,
+
+    /// The alignment which the offset does not satisfy.
+    pub required_alignment: u64,
+
+    /// Where the required alignment comes from.
+    pub constraint: &'static str,
+}
+
+impl std::fmt::Display for AlignmentViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at offset {} is not aligned to {} ({})",
+            self.path,
+            self.offset_in_bytes,
+            self.required_alignment,
+            self.constraint
+        )
+    }
+}
+
+/// An allocator decorator which keeps track of every live allocation so their
+/// placement can be audited.
+///
+/// Each allocation's offset is checked against the alignment in its
+/// requirements and against the device limits which apply to its memory
+/// type. This catches alignment bugs in new suballocation strategies before
+/// they corrupt rendering.
+///
+/// Unlike the [crate::ValidationAllocator], violations are reported rather
+/// than causing a panic. See [Self::audit].
+pub struct AlignmentAuditAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    memory_properties: MemoryProperties,
+    limits: AlignmentLimits,
+    check_texel_buffer_alignment: bool,
+    live: BTreeMap<AllocationId, (Allocation, AllocationRequirements)>,
+}
+
+impl<T: ComposableAllocator> AlignmentAuditAllocator<T> {
+    /// Create a new alignment audit allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator whose allocations are audited.
+    /// * memory_properties: used to decide which device limits apply to each
+    ///   allocation's memory type.
+    /// * limits: the device limits to check allocations against.
+    pub fn new(
+        wrapped_allocator: T,
+        memory_properties: MemoryProperties,
+        limits: AlignmentLimits,
+    ) -> Self {
+        Self {
+            wrapped_allocator,
+            memory_properties,
+            limits,
+            check_texel_buffer_alignment: false,
+            live: BTreeMap::new(),
+        }
+    }
+
+    /// Also check every allocation against the texel buffer offset alignment.
+    /// Use this when the wrapped allocator provides memory for texel buffers.
+    pub fn with_texel_buffer_alignment(self) -> Self {
+        Self {
+            check_texel_buffer_alignment: true,
+            ..self
+        }
+    }
+
+    /// The number of allocations which have not been freed yet.
+    pub fn live_allocations(&self) -> usize {
+        self.live.len()
+    }
+
+    /// Walk every live allocation and check its alignment.
+    ///
+    /// # Returns
+    ///
+    /// Every violation found, ordered by memory and offset. The result is
+    /// empty when all live allocations are correctly aligned.
+    pub fn audit(&self) -> Vec<AlignmentViolation> {
+        let mut violations = vec![];
+        for (allocation, requirements) in self.live.values() {
+            let constraints = self.constraints(allocation, requirements);
+            for (required_alignment, constraint) in constraints {
+                if allocation.offset_in_bytes() % required_alignment != 0 {
+                    violations.push(AlignmentViolation {
+                        path: allocation.path().to_owned(),
+                        offset_in_bytes: allocation.offset_in_bytes(),
+                        required_alignment,
+                        constraint,
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// The alignments which apply to an allocation, along with a description
+    /// of where each one comes from. Zero alignments are skipped.
+    ///
+    /// The requested requirements are used rather than the requirements
+    /// recorded on the allocation because suballocators record their own.
+    fn constraints(
+        &self,
+        allocation: &Allocation,
+        requirements: &AllocationRequirements,
+    ) -> Vec<(u64, &'static str)> {
+        let property_flags = self
+            .memory_properties
+            .types()
+            .get(allocation.memory_type_index())
+            .map(|memory_type| memory_type.property_flags)
+            .unwrap_or_default();
+
+        let mut constraints =
+            vec![(requirements.alignment, "allocation requirements")];
+        if property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            constraints.push((
+                self.limits.min_memory_map_alignment,
+                "minMemoryMapAlignment",
+            ));
+            if !property_flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+            {
+                constraints.push((
+                    self.limits.non_coherent_atom_size,
+                    "nonCoherentAtomSize",
+                ));
+            }
+        }
+        if self.check_texel_buffer_alignment {
+            constraints.push((
+                self.limits.min_texel_buffer_offset_alignment,
+                "minTexelBufferOffsetAlignment",
+            ));
+        }
+        constraints.retain(|&(alignment, _)| alignment > 0);
+        constraints
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator
+    for AlignmentAuditAllocator<T>
+{
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        self.live.insert(
+            allocation.id(),
+            (allocation.clone(), allocation_requirements),
+        );
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.live.remove(&allocation.id());
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        let violations = self.audit();
+        if !violations.is_empty() {
+            let report = violations
+                .iter()
+                .map(|violation| format!("- {}", violation))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Found {} misaligned allocations:\n{}",
+                violations.len(),
+                report
+            )));
+        }
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
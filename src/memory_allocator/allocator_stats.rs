@@ -0,0 +1,398 @@
+use {
+    crate::{
+        Allocation, AllocationId, AllocationReport, AllocationRequirements,
+        AllocatorError, BudgetForecast, ComposableAllocator, HeapBudget,
+        MemoryProperties, MemoryReport, PoolConfigurator,
+    },
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        time::Instant,
+    },
+};
+
+/// The number of recent frames with per-frame counters, see
+/// [AllocatorStats::frames].
+const FRAME_HISTORY: usize = 256;
+
+/// Allocations made during a single frame, see
+/// [crate::MemoryAllocator::begin_frame].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrameUsage {
+    /// The number of allocations given to the application during the frame.
+    pub allocation_count: usize,
+
+    /// The number of bytes given to the application during the frame,
+    /// whether or not the allocations are still live.
+    pub used_bytes: u64,
+}
+
+/// Usage counters for a memory type, a memory heap, or the whole allocator.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryUsage {
+    /// The number of bytes allocated from the device with vkAllocateMemory.
+    pub allocated_bytes: u64,
+
+    /// The number of live device memory allocations.
+    pub device_allocation_count: usize,
+
+    /// The number of bytes in live allocations given to the application.
+    pub used_bytes: u64,
+
+    /// The number of live allocations given to the application.
+    pub allocation_count: usize,
+
+    /// The number of chunks owned by pools.
+    pub chunk_count: usize,
+
+    /// The size of the largest contiguous free range in any pool chunk.
+    pub largest_free_block: u64,
+}
+
+impl MemoryUsage {
+    /// Add another set of counters to this one.
+    fn accumulate(&mut self, other: &MemoryUsage) {
+        self.allocated_bytes += other.allocated_bytes;
+        self.device_allocation_count += other.device_allocation_count;
+        self.used_bytes += other.used_bytes;
+        self.allocation_count += other.allocation_count;
+        self.chunk_count += other.chunk_count;
+        self.largest_free_block =
+            self.largest_free_block.max(other.largest_free_block);
+    }
+}
+
+/// A snapshot of an allocator's memory usage, see
+/// [crate::MemoryAllocator::stats].
+///
+/// Composable allocators add their own counters in
+/// [ComposableAllocator::stats].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AllocatorStats {
+    /// Usage for each memory type, indexed by memory type index.
+    pub memory_types: Vec<MemoryUsage>,
+
+    /// Usage for each memory heap, indexed by heap index.
+    pub memory_heaps: Vec<MemoryUsage>,
+
+    /// Usage across every memory type.
+    pub total: MemoryUsage,
+
+    /// The limit on live device memory allocations, if a
+    /// [crate::DeviceAllocator] knows it. Compare with
+    /// `total.device_allocation_count`.
+    pub max_device_allocation_count: Option<u32>,
+
+    /// The budget and process-wide usage for each memory heap, indexed by
+    /// heap index. None when VK_EXT_memory_budget isn't supported. Compare
+    /// with `memory_heaps` to see how much of the usage belongs to this
+    /// allocator.
+    pub heap_budgets: Option<Vec<HeapBudget>>,
+
+    /// Allocations made during each of the most recent frames, keyed by
+    /// frame. Use this to find the frame which allocated an unexpected
+    /// amount of memory.
+    pub frames: BTreeMap<u64, FrameUsage>,
+
+    /// The forecast for each tag with a budget, keyed by tag. Empty unless
+    /// the composition includes a [crate::BudgetAllocator] with tag limits,
+    /// see [crate::BudgetAllocator::forecast].
+    pub tag_forecasts: BTreeMap<&'static str, BudgetForecast>,
+
+    /// Shared allocators which have already added their counters.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    visited: HashSet<usize>,
+}
+
+impl AllocatorStats {
+    /// Get the counters for a memory type, adding it if needed.
+    ///
+    /// # Params
+    ///
+    /// * memory_type_index: the memory type to update.
+    pub fn memory_type_mut(
+        &mut self,
+        memory_type_index: usize,
+    ) -> &mut MemoryUsage {
+        if self.memory_types.len() <= memory_type_index {
+            self.memory_types
+                .resize(memory_type_index + 1, MemoryUsage::default());
+        }
+        &mut self.memory_types[memory_type_index]
+    }
+
+    /// Record that a shared allocator is being visited. Allocators can be
+    /// reachable through more than one path, so this keeps them from adding
+    /// their counters twice.
+    ///
+    /// # Returns
+    ///
+    /// True the first time an address is visited.
+    pub(crate) fn visit(&mut self, address: usize) -> bool {
+        self.visited.insert(address)
+    }
+
+    /// Add up the per-type counters into the per-heap and total counters.
+    ///
+    /// # Params
+    ///
+    /// * memory_properties: the device's memory types and heaps.
+    pub(crate) fn finish(
+        mut self,
+        memory_properties: &MemoryProperties,
+    ) -> Self {
+        let memory_type_count = memory_properties.types().len();
+        if self.memory_types.len() < memory_type_count {
+            self.memory_types
+                .resize(memory_type_count, MemoryUsage::default());
+        }
+        self.memory_heaps =
+            vec![MemoryUsage::default(); memory_properties.heaps().len()];
+        self.total = MemoryUsage::default();
+        for (memory_type, usage) in
+            memory_properties.types().iter().zip(&self.memory_types)
+        {
+            if let Some(heap) =
+                self.memory_heaps.get_mut(memory_type.heap_index as usize)
+            {
+                heap.accumulate(usage);
+            }
+            self.total.accumulate(usage);
+        }
+        self.visited.clear();
+        self
+    }
+}
+
+/// An allocation which has been given to the application.
+struct LiveAllocation {
+    report: AllocationReport,
+    allocated_at: Instant,
+}
+
+/// Tracks the allocations which are given to the application so
+/// [crate::MemoryAllocator::stats] can report the used bytes and
+/// [crate::MemoryAllocator::live_allocations] can list them.
+pub(crate) struct UsageTracker<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    used: Vec<MemoryUsage>,
+    live: HashMap<AllocationId, LiveAllocation>,
+    frames: BTreeMap<u64, FrameUsage>,
+}
+
+impl<T: ComposableAllocator> UsageTracker<T> {
+    pub fn new(wrapped_allocator: T) -> Self {
+        Self {
+            wrapped_allocator,
+            used: vec![],
+            live: HashMap::new(),
+            frames: BTreeMap::new(),
+        }
+    }
+
+    /// Count an allocation in the frame it was made, forgetting the oldest
+    /// frame when there are too many.
+    fn record_frame(&mut self, frame: Option<u64>, size_in_bytes: u64) {
+        let frame = match frame {
+            Some(frame) => frame,
+            None => return,
+        };
+        let usage = self.frames.entry(frame).or_default();
+        usage.allocation_count += 1;
+        usage.used_bytes += size_in_bytes;
+        if self.frames.len() > FRAME_HISTORY {
+            self.frames.pop_first();
+        }
+    }
+
+    fn usage_mut(&mut self, memory_type_index: usize) -> &mut MemoryUsage {
+        if self.used.len() <= memory_type_index {
+            self.used
+                .resize(memory_type_index + 1, MemoryUsage::default());
+        }
+        &mut self.used[memory_type_index]
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for UsageTracker<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let mut allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        // Pools hand out suballocations, which don't carry the request's
+        // metadata.
+        allocation.set_metadata(&allocation_requirements);
+        let usage = self.usage_mut(allocation.memory_type_index());
+        usage.used_bytes += allocation.size_in_bytes();
+        usage.allocation_count += 1;
+        self.record_frame(
+            allocation_requirements.frame,
+            allocation.size_in_bytes(),
+        );
+        self.live.insert(
+            allocation.id(),
+            LiveAllocation {
+                report: AllocationReport {
+                    path: allocation.path().to_owned(),
+                    name: allocation.name().map(str::to_owned),
+                    tag: allocation_requirements.tag,
+                    user_data: allocation_requirements.user_data,
+                    memory_id: allocation.memory_id(),
+                    memory_type_index: allocation.memory_type_index(),
+                    offset_in_bytes: allocation.offset_in_bytes(),
+                    size_in_bytes: allocation.size_in_bytes(),
+                    age: Default::default(),
+                    frame: allocation_requirements.frame,
+                },
+                allocated_at: Instant::now(),
+            },
+        );
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        let usage = self.usage_mut(allocation.memory_type_index());
+        usage.used_bytes =
+            usage.used_bytes.saturating_sub(allocation.size_in_bytes());
+        usage.allocation_count = usage.allocation_count.saturating_sub(1);
+        self.live.remove(&allocation.id());
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        // SAFE because the id is only used to find the live allocation.
+        let id = unsafe { allocation.id() };
+        if let Some(live) = self.live.get_mut(&id) {
+            live.report.name = allocation.name().map(str::to_owned);
+        }
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats);
+        for (memory_type_index, usage) in self.used.iter().enumerate() {
+            let memory_type = stats.memory_type_mut(memory_type_index);
+            memory_type.used_bytes += usage.used_bytes;
+            memory_type.allocation_count += usage.allocation_count;
+        }
+        for (&frame, usage) in &self.frames {
+            let frame_usage = stats.frames.entry(frame).or_default();
+            frame_usage.allocation_count += usage.allocation_count;
+            frame_usage.used_bytes += usage.used_bytes;
+        }
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        for live in self.live.values() {
+            report.add_allocation(AllocationReport {
+                age: live.allocated_at.elapsed(),
+                ..live.report.clone()
+            });
+        }
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::FakeAllocator};
+
+    #[test]
+    fn test_usage_tracker_counts_live_allocations() {
+        let mut allocator = UsageTracker::new(FakeAllocator::default());
+        let requirements = AllocationRequirements {
+            size_in_bytes: 256,
+            memory_type_index: 1,
+            ..Default::default()
+        };
+        let first = unsafe { allocator.allocate(requirements).unwrap() };
+        let second = unsafe {
+            allocator
+                .allocate(AllocationRequirements {
+                    frame: Some(3),
+                    ..requirements
+                })
+                .unwrap()
+        };
+        unsafe { allocator.free(first) };
+
+        let mut stats = AllocatorStats::default();
+        allocator.stats(&mut stats);
+        assert_eq!(stats.memory_types.len(), 2);
+        assert_eq!(stats.memory_types[1].used_bytes, 256);
+        assert_eq!(stats.memory_types[1].allocation_count, 1);
+        assert_eq!(stats.frames.len(), 1);
+        assert_eq!(
+            stats.frames[&3],
+            FrameUsage {
+                allocation_count: 1,
+                used_bytes: 256,
+            }
+        );
+
+        unsafe { allocator.free(second) };
+    }
+
+    #[test]
+    fn test_usage_tracker_reports_live_allocations() {
+        let mut allocator = UsageTracker::new(FakeAllocator::default());
+        let requirements = AllocationRequirements {
+            size_in_bytes: 256,
+            memory_type_index: 1,
+            tag: Some("textures"),
+            ..Default::default()
+        };
+        let first = unsafe { allocator.allocate(requirements).unwrap() };
+        let mut second = unsafe {
+            allocator
+                .allocate(AllocationRequirements {
+                    user_data: 42,
+                    ..requirements
+                })
+                .unwrap()
+        };
+        assert_eq!(second.user_data(), 42);
+        second.set_name("Second");
+        allocator.name_allocation(&second);
+        unsafe { allocator.free(first) };
+
+        let mut report = MemoryReport::default();
+        allocator.report(&mut report);
+        assert_eq!(report.allocations.len(), 1);
+        let live = &report.allocations[0];
+        assert_eq!(live.name.as_deref(), Some("Second"));
+        assert_eq!(live.tag, Some("textures"));
+        assert_eq!(live.user_data, 42);
+        assert_eq!(live.memory_type_index, 1);
+        assert_eq!(live.size_in_bytes, 256);
+        assert_eq!(live.offset_in_bytes, second.offset_in_bytes());
+
+        unsafe { allocator.free(second) };
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+    ComposableAllocator, MemoryAnnotations, MemoryReport, PoolConfigurator,
+};
+
+/// An allocator decorator which records every live allocation in a shared
+/// [MemoryAnnotations] instance.
+///
+/// Combined with an [crate::AllocatorDebugMessenger], validation messages
+/// which mention device memory handles are annotated with the allocations
+/// that use the memory.
+pub struct AnnotatingAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    annotations: MemoryAnnotations,
+}
+
+impl<T: ComposableAllocator> AnnotatingAllocator<T> {
+    /// Create a new annotating allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator which does the actual allocation.
+    /// * annotations: the shared record of live allocations.
+    pub fn new(wrapped_allocator: T, annotations: MemoryAnnotations) -> Self {
+        Self {
+            wrapped_allocator,
+            annotations,
+        }
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for AnnotatingAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        self.annotations.insert(
+            allocation.memory(),
+            allocation.offset_in_bytes(),
+            allocation.size_in_bytes(),
+            allocation.memory_type_index(),
+            allocation_requirements,
+        );
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.annotations
+            .remove(allocation.memory(), allocation.offset_in_bytes());
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
@@ -0,0 +1,347 @@
+use {
+    crate::{
+        Allocation, AllocationId, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, MemoryProperties, MemoryReport,
+        PoolConfigurator,
+    },
+    std::collections::{HashMap, VecDeque},
+};
+
+/// Identifies the memory which a budget applies to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BudgetTarget {
+    /// A memory heap, identified by its index.
+    MemoryHeap(usize),
+
+    /// A memory type, identified by its index.
+    MemoryType(usize),
+
+    /// Every allocation with the tag, see [AllocationRequirements::tag].
+    Tag(&'static str),
+}
+
+/// A projection of when a budget will run out, based on how usage changed
+/// over recent frames.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BudgetForecast {
+    /// The memory which the forecast applies to.
+    pub target: BudgetTarget,
+
+    /// The number of bytes currently allocated from the target.
+    pub used_bytes: u64,
+
+    /// The target's budget.
+    pub limit_bytes: u64,
+
+    /// The average change in usage per frame. Negative when usage is
+    /// shrinking.
+    pub bytes_per_frame: f64,
+
+    /// The number of frames until the budget is exhausted at the current
+    /// rate. None when usage is not growing.
+    pub frames_until_exhausted: Option<u64>,
+}
+
+/// An allocator decorator which enforces a hard byte cap per memory heap, per
+/// memory type, or per tag.
+///
+/// Requests which would exceed a cap fail with
+/// [AllocatorError::BudgetExceeded] instead of being forwarded to the wrapped
+/// allocator. This is useful for reserving headroom for the OS and compositor.
+///
+/// Budgets can also apply to tagged allocations, e.g. "textures" or "meshes",
+/// so each asset category can be kept in check. Soft limits log a warning
+/// instead of failing.
+///
+/// Call [Self::end_frame] once per frame to track how usage changes over
+/// time. Streaming systems can use [Self::forecast] to start evicting before
+/// a budget runs out rather than reacting to failures. Forecasts for tag
+/// budgets are also added to [AllocatorStats::tag_forecasts].
+pub struct BudgetAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    memory_properties: MemoryProperties,
+    limits: HashMap<BudgetTarget, u64>,
+    soft_limits: HashMap<BudgetTarget, u64>,
+    usage: HashMap<BudgetTarget, u64>,
+    tags: HashMap<AllocationId, &'static str>,
+    forecast_window: usize,
+    history: HashMap<BudgetTarget, VecDeque<u64>>,
+}
+
+impl<T: ComposableAllocator> BudgetAllocator<T> {
+    /// Create a new budget allocator without any limits.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator which does the actual allocation.
+    /// * memory_properties: used to find the heap for each memory type.
+    pub fn new(
+        wrapped_allocator: T,
+        memory_properties: MemoryProperties,
+    ) -> Self {
+        Self {
+            wrapped_allocator,
+            memory_properties,
+            limits: HashMap::new(),
+            soft_limits: HashMap::new(),
+            usage: HashMap::new(),
+            tags: HashMap::new(),
+            forecast_window: 60,
+            history: HashMap::new(),
+        }
+    }
+
+    /// Set how many recent frames are used to forecast usage. Defaults to 60.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the window is zero frames.
+    pub fn with_forecast_window(self, frames: usize) -> Self {
+        assert!(frames > 0, "The forecast window must be at least one frame");
+        Self {
+            forecast_window: frames,
+            ..self
+        }
+    }
+
+    /// Limit the total number of bytes allocated from a memory heap.
+    pub fn with_heap_limit(self, heap_index: usize, max_bytes: u64) -> Self {
+        self.with_limit(BudgetTarget::MemoryHeap(heap_index), max_bytes)
+    }
+
+    /// Limit the total number of bytes allocated from a memory type.
+    pub fn with_memory_type_limit(
+        self,
+        memory_type_index: usize,
+        max_bytes: u64,
+    ) -> Self {
+        self.with_limit(BudgetTarget::MemoryType(memory_type_index), max_bytes)
+    }
+
+    /// Limit the total number of bytes in allocations with a tag.
+    pub fn with_tag_limit(self, tag: &'static str, max_bytes: u64) -> Self {
+        self.with_limit(BudgetTarget::Tag(tag), max_bytes)
+    }
+
+    /// Log a warning when an allocation takes the target over the soft
+    /// limit. The allocation still succeeds.
+    pub fn with_soft_limit(
+        mut self,
+        target: BudgetTarget,
+        max_bytes: u64,
+    ) -> Self {
+        self.soft_limits.insert(target, max_bytes);
+        self
+    }
+
+    /// The number of bytes currently allocated from the target.
+    pub fn usage(&self, target: BudgetTarget) -> u64 {
+        self.usage.get(&target).copied().unwrap_or(0)
+    }
+
+    /// The configured limit for the target, if any.
+    pub fn limit(&self, target: BudgetTarget) -> Option<u64> {
+        self.limits.get(&target).copied()
+    }
+
+    /// Record the usage for every limited target at the end of a frame.
+    pub fn end_frame(&mut self) {
+        for &target in self.limits.keys() {
+            let used = self.usage.get(&target).copied().unwrap_or(0);
+            let samples = self.history.entry(target).or_default();
+            samples.push_back(used);
+            while samples.len() > self.forecast_window + 1 {
+                samples.pop_front();
+            }
+        }
+    }
+
+    /// Project when the target's budget will run out.
+    ///
+    /// # Returns
+    ///
+    /// None when the target has no limit, or when fewer than two frames have
+    /// been recorded with [Self::end_frame].
+    pub fn forecast(&self, target: BudgetTarget) -> Option<BudgetForecast> {
+        let limit_bytes = self.limit(target)?;
+        let samples = self.history.get(&target)?;
+        if samples.len() < 2 {
+            return None;
+        }
+
+        let first = *samples.front().unwrap() as f64;
+        let last = *samples.back().unwrap() as f64;
+        let bytes_per_frame = (last - first) / (samples.len() - 1) as f64;
+
+        let used_bytes = self.usage(target);
+        let frames_until_exhausted = if used_bytes >= limit_bytes {
+            Some(0)
+        } else if bytes_per_frame > 0.0 {
+            let remaining = (limit_bytes - used_bytes) as f64;
+            Some((remaining / bytes_per_frame).ceil() as u64)
+        } else {
+            None
+        };
+
+        Some(BudgetForecast {
+            target,
+            used_bytes,
+            limit_bytes,
+            bytes_per_frame,
+            frames_until_exhausted,
+        })
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for BudgetAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let size_in_bytes = allocation_requirements.size_in_bytes;
+        let targets = self.targets(
+            allocation_requirements.memory_type_index,
+            allocation_requirements.tag,
+        );
+        for &target in &targets {
+            let limit = match self.limit(target) {
+                Some(limit) => limit,
+                None => continue,
+            };
+            let used = self.usage(target);
+            if used + size_in_bytes > limit {
+                return Err(AllocatorError::BudgetExceeded {
+                    target,
+                    size_in_bytes,
+                    used_bytes: used,
+                    limit_bytes: limit,
+                });
+            }
+        }
+        for &target in &targets {
+            let limit = match self.soft_limits.get(&target) {
+                Some(&limit) => limit,
+                None => continue,
+            };
+            let used = self.usage(target);
+            if used <= limit && used + size_in_bytes > limit {
+                log::warn!(
+                    "Allocating {} bytes takes {} over its soft limit of {} \
+                     bytes",
+                    size_in_bytes,
+                    target,
+                    limit
+                );
+            }
+        }
+
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        if let Some(tag) = allocation_requirements.tag {
+            self.tags.insert(allocation.id(), tag);
+        }
+        for target in targets {
+            *self.usage.entry(target).or_insert(0) +=
+                allocation.size_in_bytes();
+        }
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        let tag = self.tags.remove(&allocation.id());
+        for target in self.targets(allocation.memory_type_index(), tag) {
+            if let Some(used) = self.usage.get_mut(&target) {
+                *used -= allocation.size_in_bytes();
+            }
+        }
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats);
+        for &target in self.limits.keys() {
+            if let (BudgetTarget::Tag(tag), Some(forecast)) =
+                (target, self.forecast(target))
+            {
+                stats.tag_forecasts.insert(tag, forecast);
+            }
+        }
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+// Private API
+// -----------
+
+impl<T: ComposableAllocator> BudgetAllocator<T> {
+    fn with_limit(mut self, target: BudgetTarget, max_bytes: u64) -> Self {
+        self.limits.insert(target, max_bytes);
+        self
+    }
+
+    /// The budget targets which are affected by allocating from a memory
+    /// type with an optional tag.
+    fn targets(
+        &self,
+        memory_type_index: usize,
+        tag: Option<&'static str>,
+    ) -> Vec<BudgetTarget> {
+        let mut targets = vec![BudgetTarget::MemoryType(memory_type_index)];
+        if let Some(memory_type) =
+            self.memory_properties.types().get(memory_type_index)
+        {
+            targets.push(BudgetTarget::MemoryHeap(
+                memory_type.heap_index as usize,
+            ));
+        }
+        if let Some(tag) = tag {
+            targets.push(BudgetTarget::Tag(tag));
+        }
+        targets
+    }
+}
+
+impl std::fmt::Display for BudgetTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetTarget::MemoryHeap(index) => {
+                f.write_fmt(format_args!("memory heap {}", index))
+            }
+            BudgetTarget::MemoryType(index) => {
+                f.write_fmt(format_args!("memory type {}", index))
+            }
+            BudgetTarget::Tag(tag) => f.write_fmt(format_args!("tag {}", tag)),
+        }
+    }
+}
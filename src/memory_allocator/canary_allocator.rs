@@ -0,0 +1,365 @@
+use {
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+        ComposableAllocator, MemoryReport, PoolConfigurator,
+    },
+    anyhow::anyhow,
+    ash::vk,
+    indoc::indoc,
+    std::collections::HashMap,
+};
+
+/// The byte written to every guard region.
+const CANARY_PATTERN: u8 = 0xFD;
+
+/// Allocations are identified by their device memory id and offset. Vulkan
+/// handles can be reused after the memory is freed, ids are not.
+type AllocationKey = (u64, vk::DeviceSize);
+
+/// Bookkeeping for an allocation surrounded by guard bytes.
+struct GuardedAllocation {
+    /// The full allocation from the wrapped allocator, including guards.
+    outer: Allocation,
+
+    /// The size of the guard region before the user's allocation.
+    front_guard_size: u64,
+
+    /// The size of the user's allocation.
+    size_in_bytes: u64,
+
+    /// The size of the guard region after the user's allocation.
+    back_guard_size: u64,
+}
+
+/// An allocator decorator which surrounds host-visible allocations with guard
+/// bytes.
+///
+/// The guard bytes are filled with a known pattern when memory is allocated
+/// and verified when memory is freed, or on demand with check_canaries(). This
+/// catches CPU buffer overruns at the moment they can be attributed to an
+/// allocation, rather than when the GPU eventually reads garbage.
+///
+/// Allocations which are not HOST_VISIBLE, or which need a dedicated
+/// allocation, are passed through unchanged.
+pub struct CanaryAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    device: ash::Device,
+    guard_size_in_bytes: u64,
+    guarded: HashMap<AllocationKey, GuardedAllocation>,
+}
+
+impl<T: ComposableAllocator> CanaryAllocator<T> {
+    /// Create a new canary allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator which provides the guarded memory.
+    /// * device: used to map memory when writing and checking guard bytes.
+    /// * guard_size_in_bytes: the minimum number of guard bytes placed before
+    ///   and after each allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the device must not be destroyed while this allocator still exists
+    /// - guard bytes are read and written by the host, the application must
+    ///   synchronize access to host-visible memory
+    pub unsafe fn new(
+        wrapped_allocator: T,
+        device: ash::Device,
+        guard_size_in_bytes: u64,
+    ) -> Self {
+        Self {
+            wrapped_allocator,
+            device,
+            guard_size_in_bytes: guard_size_in_bytes.max(1),
+            guarded: HashMap::new(),
+        }
+    }
+
+    /// Verify the guard bytes around every live allocation.
+    ///
+    /// # Returns
+    ///
+    /// An error which describes every allocation with corrupted guard bytes.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the application must synchronize access to host-visible memory
+    pub unsafe fn check_canaries(&self) -> Result<(), AllocatorError> {
+        let mut corrupted = vec![];
+        for guarded in self.guarded.values() {
+            if let Some(offset) = self.find_stomp(guarded)? {
+                corrupted.push(describe_stomp(guarded, offset));
+            }
+        }
+        if corrupted.is_empty() {
+            Ok(())
+        } else {
+            Err(AllocatorError::RuntimeError(anyhow!(
+                "Corrupted canaries detected!\n\n{}",
+                corrupted.join("\n\n")
+            )))
+        }
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for CanaryAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let needs_guards = allocation_requirements
+            .memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+            && !allocation_requirements.prefers_dedicated_allocation
+            && !allocation_requirements.requires_dedicated_allocation;
+        if !needs_guards {
+            return self.wrapped_allocator.allocate(allocation_requirements);
+        }
+
+        // The front guard is padded so the user's allocation keeps its
+        // requested alignment.
+        let alignment = allocation_requirements.alignment.max(1);
+        let front_guard_size = self.guard_size_in_bytes
+            + (alignment - self.guard_size_in_bytes % alignment) % alignment;
+        let back_guard_size = self.guard_size_in_bytes;
+        let size_in_bytes = allocation_requirements.size_in_bytes;
+
+        let outer =
+            self.wrapped_allocator.allocate(AllocationRequirements {
+                size_in_bytes: front_guard_size
+                    + size_in_bytes
+                    + back_guard_size,
+                ..allocation_requirements
+            })?;
+        let guarded = GuardedAllocation {
+            outer,
+            front_guard_size,
+            size_in_bytes,
+            back_guard_size,
+        };
+
+        if let Err(err) = self.write_guards(&guarded) {
+            self.wrapped_allocator.free(guarded.outer);
+            return Err(err);
+        }
+
+        let allocation = Allocation::suballocate(
+            &guarded.outer,
+            front_guard_size,
+            size_in_bytes,
+            alignment,
+        );
+        self.guarded.insert(
+            (allocation.memory_id(), allocation.offset_in_bytes()),
+            guarded,
+        );
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        let key = (allocation.memory_id(), allocation.offset_in_bytes());
+        let guarded = match self.guarded.remove(&key) {
+            Some(guarded) => guarded,
+            None => {
+                // This allocation was passed through without guards.
+                self.wrapped_allocator.free(allocation);
+                return;
+            }
+        };
+
+        match self.find_stomp(&guarded) {
+            Ok(None) => (),
+            Ok(Some(offset)) => {
+                panic!("{}", describe_stomp(&guarded, offset));
+            }
+            Err(err) => {
+                log::warn!("Unable to check canaries on free: {}", err);
+            }
+        }
+
+        self.wrapped_allocator.free(guarded.outer);
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+// Private API
+// -----------
+
+impl<T: ComposableAllocator> CanaryAllocator<T> {
+    /// Fill the guard regions with the canary pattern.
+    unsafe fn write_guards(
+        &self,
+        guarded: &GuardedAllocation,
+    ) -> Result<(), AllocatorError> {
+        let ptr = guarded.outer.map(&self.device)? as *mut u8;
+        for (start, len) in guarded.guard_regions() {
+            std::ptr::write_bytes(
+                ptr.add(start as usize),
+                CANARY_PATTERN,
+                len as usize,
+            );
+        }
+        guarded.outer.unmap(&self.device)
+    }
+
+    /// Find the first corrupted guard byte, if any.
+    ///
+    /// # Returns
+    ///
+    /// The offset of the corrupted byte relative to the start of the outer
+    /// allocation.
+    unsafe fn find_stomp(
+        &self,
+        guarded: &GuardedAllocation,
+    ) -> Result<Option<u64>, AllocatorError> {
+        let ptr = guarded.outer.map(&self.device)? as *const u8;
+        let stomp = find_stomp_in(ptr, guarded);
+        guarded.outer.unmap(&self.device)?;
+        Ok(stomp)
+    }
+}
+
+impl GuardedAllocation {
+    /// The (offset, size) of both guard regions relative to the start of the
+    /// outer allocation.
+    fn guard_regions(&self) -> [(u64, u64); 2] {
+        [
+            (0, self.front_guard_size),
+            (
+                self.front_guard_size + self.size_in_bytes,
+                self.back_guard_size,
+            ),
+        ]
+    }
+}
+
+/// Find the first guard byte which does not match the canary pattern.
+///
+/// # Safety
+///
+/// Unsafe because ptr must point to the start of the outer allocation's host
+/// mapping.
+unsafe fn find_stomp_in(
+    ptr: *const u8,
+    guarded: &GuardedAllocation,
+) -> Option<u64> {
+    for (start, len) in guarded.guard_regions() {
+        let guard =
+            std::slice::from_raw_parts(ptr.add(start as usize), len as usize);
+        if let Some(index) = guard.iter().position(|&b| b != CANARY_PATTERN) {
+            return Some(start + index as u64);
+        }
+    }
+    None
+}
+
+fn describe_stomp(guarded: &GuardedAllocation, offset: u64) -> String {
+    let region = if offset < guarded.front_guard_size {
+        "before the start"
+    } else {
+        "after the end"
+    };
+    format!(
+        indoc!(
+            "
+            Memory stomp detected {} of an allocation!
+
+            corrupted byte offset: {}
+            allocation size: {}
+            allocation: {}
+            "
+        ),
+        region,
+        offset as i64 - guarded.front_guard_size as i64,
+        guarded.size_in_bytes,
+        guarded.outer,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::DeviceMemory};
+
+    fn guarded() -> GuardedAllocation {
+        GuardedAllocation {
+            outer: Allocation::new(
+                DeviceMemory::new(vk::DeviceMemory::null(), 12),
+                0,
+                0,
+                12,
+                AllocationRequirements::default(),
+            ),
+            front_guard_size: 4,
+            size_in_bytes: 4,
+            back_guard_size: 4,
+        }
+    }
+
+    fn guarded_bytes() -> Vec<u8> {
+        let mut bytes = vec![CANARY_PATTERN; 12];
+        bytes[4..8].copy_from_slice(&[1, 2, 3, 4]);
+        bytes
+    }
+
+    #[test]
+    fn test_intact_guards() {
+        let bytes = guarded_bytes();
+        assert_eq!(unsafe { find_stomp_in(bytes.as_ptr(), &guarded()) }, None);
+    }
+
+    #[test]
+    fn test_overrun_is_detected() {
+        let mut bytes = guarded_bytes();
+        bytes[9] = 0;
+        assert_eq!(
+            unsafe { find_stomp_in(bytes.as_ptr(), &guarded()) },
+            Some(9)
+        );
+    }
+
+    #[test]
+    fn test_underrun_is_detected() {
+        let mut bytes = guarded_bytes();
+        bytes[3] = 0;
+        assert_eq!(
+            unsafe { find_stomp_in(bytes.as_ptr(), &guarded()) },
+            Some(3)
+        );
+    }
+}
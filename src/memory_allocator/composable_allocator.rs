@@ -1,5 +1,8 @@
 use {
-    crate::{Allocation, AllocationRequirements, AllocatorError},
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+        MemoryReport, PoolConfigurator,
+    },
     std::sync::{Arc, Mutex},
 };
 
@@ -30,6 +33,85 @@ pub trait ComposableAllocator {
     ///    memory. It is an error to free memory while ongoing GPU operations
     ///    are still referencing it.
     unsafe fn free(&mut self, allocation: Allocation);
+
+    /// Check the allocator's internal bookkeeping for inconsistencies.
+    ///
+    /// Allocators which compose over other allocators should validate those
+    /// allocators too. The default implementation has nothing to check.
+    fn validate(&self) -> Result<(), AllocatorError> {
+        Ok(())
+    }
+
+    /// Record the name which was given to a live allocation, see
+    /// [Allocation::name].
+    ///
+    /// Allocators which compose over other allocators should pass the name
+    /// along to those allocators too. The default implementation ignores the
+    /// name.
+    fn name_allocation(&mut self, _allocation: &Allocation) {}
+
+    /// Add the allocator's usage counters to the stats.
+    ///
+    /// Allocators which compose over other allocators should add the stats
+    /// for those allocators too. The default implementation has nothing to
+    /// add.
+    fn stats(&self, _stats: &mut AllocatorStats) {}
+
+    /// Add the allocator's chunks to the report.
+    ///
+    /// Allocators which compose over other allocators should add the chunks
+    /// for those allocators too. The default implementation has nothing to
+    /// add.
+    fn report(&self, _report: &mut MemoryReport) {}
+
+    /// Return memory which the allocator is holding but not using, e.g. empty
+    /// chunks, to the allocators it composes over.
+    ///
+    /// Allocators which compose over other allocators should trim those
+    /// allocators too. The default implementation holds no unused memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because memory can be freed by the wrapped allocators. See
+    /// [ComposableAllocator::free].
+    unsafe fn trim(&mut self) {}
+
+    /// Adjust the settings of the allocator's pools. New settings only apply
+    /// to chunks which are created afterwards, existing chunks keep their
+    /// size and page size.
+    ///
+    /// Allocators which compose over other allocators should reconfigure
+    /// those allocators too. The default implementation has no pools.
+    fn reconfigure_pools(
+        &mut self,
+        _configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        Ok(())
+    }
+
+    /// Create memory ahead of time so later allocations from the memory type
+    /// don't need to allocate new chunks.
+    ///
+    /// Allocators which compose over other allocators should reserve memory
+    /// in the allocator which serves typical allocations. The default
+    /// implementation has nothing to reserve.
+    ///
+    /// # Params
+    ///
+    /// * allocation_requirements: the memory type and properties to reserve.
+    ///   The size is the number of bytes which should be available without
+    ///   allocating new chunks.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because reserved memory must be trimmed or freed before the
+    /// device is destroyed.
+    unsafe fn reserve(
+        &mut self,
+        _allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        Ok(())
+    }
 }
 
 impl ComposableAllocator for Box<dyn ComposableAllocator> {
@@ -43,6 +125,87 @@ impl ComposableAllocator for Box<dyn ComposableAllocator> {
     unsafe fn free(&mut self, allocation: Allocation) {
         self.as_mut().free(allocation)
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.as_ref().validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.as_mut().name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.as_ref().stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.as_ref().report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.as_mut().trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.as_mut().reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.as_mut().reserve(allocation_requirements)
+    }
+}
+
+impl ComposableAllocator for Box<dyn ComposableAllocator + Send> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.as_mut().allocate(allocation_requirements)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.as_mut().free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.as_ref().validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.as_mut().name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.as_ref().stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.as_ref().report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.as_mut().trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.as_mut().reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.as_mut().reserve(allocation_requirements)
+    }
 }
 
 impl<T> ComposableAllocator for Box<T>
@@ -59,6 +222,40 @@ where
     unsafe fn free(&mut self, allocation: Allocation) {
         self.as_mut().free(allocation)
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.as_ref().validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.as_mut().name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.as_ref().stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.as_ref().report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.as_mut().trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.as_mut().reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.as_mut().reserve(allocation_requirements)
+    }
 }
 
 impl<T> ComposableAllocator for Arc<Mutex<T>>
@@ -75,4 +272,48 @@ where
     unsafe fn free(&mut self, allocation: Allocation) {
         self.lock().unwrap().free(allocation)
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.lock().unwrap().validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.lock().unwrap().name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        // Shared allocators are often reachable through several decorators,
+        // only count them once.
+        if stats.visit(Arc::as_ptr(self) as *const () as usize) {
+            self.lock().unwrap().stats(stats)
+        }
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        if report.visit(Arc::as_ptr(self) as *const () as usize) {
+            self.lock().unwrap().report(report)
+        }
+    }
+
+    unsafe fn trim(&mut self) {
+        self.lock().unwrap().trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        if configurator.visit(Arc::as_ptr(self) as *const () as usize) {
+            self.lock().unwrap().reconfigure_pools(configurator)
+        } else {
+            Ok(())
+        }
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.lock().unwrap().reserve(allocation_requirements)
+    }
 }
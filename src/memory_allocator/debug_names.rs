@@ -0,0 +1,85 @@
+use {
+    ash::vk::{self, Handle},
+    std::ffi::CString,
+};
+
+/// Names device memory with VK_EXT_debug_utils so tools like RenderDoc and
+/// the validation layers can show where the memory came from.
+pub(crate) struct DebugNames {
+    device: ash::Device,
+    debug_utils: vk::ExtDebugUtilsFn,
+}
+
+impl DebugNames {
+    /// Load the entrypoint used to name objects.
+    ///
+    /// # Returns
+    ///
+    /// None when VK_EXT_debug_utils isn't enabled on the instance.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the device must not be destroyed while this object still exists
+    pub(crate) unsafe fn new(
+        instance: &ash::Instance,
+        device: ash::Device,
+    ) -> Option<Self> {
+        // Only the object naming entrypoint is used, the other debug utils
+        // entrypoints are instance-level and aren't returned for the device.
+        let mut enabled = false;
+        let debug_utils = vk::ExtDebugUtilsFn::load(|name| {
+            let function =
+                instance.get_device_proc_addr(device.handle(), name.as_ptr());
+            if name.to_bytes() == b"vkSetDebugUtilsObjectNameEXT" {
+                enabled = function.is_some();
+            }
+            std::mem::transmute(function)
+        });
+        enabled.then_some(Self {
+            device,
+            debug_utils,
+        })
+    }
+
+    /// Set the name of a device memory object.
+    ///
+    /// Naming is best-effort. Failures are logged rather than returned
+    /// because a missing name should never fail an allocation.
+    ///
+    /// # Params
+    ///
+    /// * memory: the device memory to name.
+    /// * name: the name shown by debugging tools.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the memory must not have been freed
+    pub(crate) unsafe fn set_name(&self, memory: vk::DeviceMemory, name: &str) {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => {
+                log::debug!("Unable to name device memory {:?}", name);
+                return;
+            }
+        };
+        let name_info = vk::DebugUtilsObjectNameInfoEXT {
+            object_type: vk::ObjectType::DEVICE_MEMORY,
+            object_handle: memory.as_raw(),
+            p_object_name: name.as_ptr(),
+            ..Default::default()
+        };
+        let result = (self.debug_utils.set_debug_utils_object_name_ext)(
+            self.device.handle(),
+            &name_info,
+        );
+        if result != vk::Result::SUCCESS {
+            log::debug!(
+                "Unable to name device memory {:?}: {:?}",
+                name,
+                result
+            );
+        }
+    }
+}
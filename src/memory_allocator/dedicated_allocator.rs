@@ -1,5 +1,6 @@
 use crate::{
-    Allocation, AllocationRequirements, AllocatorError, ComposableAllocator,
+    Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+    ComposableAllocator, MemoryReport, PoolConfigurator,
 };
 
 /// An allocator which correctly handles allocations which prefer or require
@@ -7,6 +8,9 @@ use crate::{
 pub struct DedicatedAllocator<A: ComposableAllocator, B: ComposableAllocator> {
     allocator: A,
     device_allocator: B,
+
+    /// Allocations at least this large are always dedicated.
+    size_threshold: Option<u64>,
 }
 
 impl<A, B> DedicatedAllocator<A, B>
@@ -26,8 +30,35 @@ where
         Self {
             allocator,
             device_allocator,
+            size_threshold: None,
+        }
+    }
+
+    /// Give allocations a dedicated device allocation when they are at least
+    /// this large, even if the resource doesn't prefer one.
+    ///
+    /// # Param
+    ///
+    /// - size_threshold: the smallest allocation size which is dedicated.
+    pub fn with_size_threshold(self, size_threshold: u64) -> Self {
+        Self {
+            size_threshold: Some(size_threshold),
+            ..self
         }
     }
+
+    /// Returns true when the allocation should come from the device
+    /// allocator.
+    fn is_dedicated(
+        &self,
+        allocation_requirements: &AllocationRequirements,
+    ) -> bool {
+        allocation_requirements.prefers_dedicated_allocation
+            || allocation_requirements.requires_dedicated_allocation
+            || self.size_threshold.is_some_and(|threshold| {
+                allocation_requirements.size_in_bytes >= threshold
+            })
+    }
 }
 
 impl<A, B> ComposableAllocator for DedicatedAllocator<A, B>
@@ -39,9 +70,7 @@ where
         &mut self,
         allocation_requirements: AllocationRequirements,
     ) -> Result<Allocation, AllocatorError> {
-        if allocation_requirements.prefers_dedicated_allocation
-            || allocation_requirements.requires_dedicated_allocation
-        {
+        if self.is_dedicated(&allocation_requirements) {
             self.device_allocator.allocate(allocation_requirements)
         } else {
             self.allocator.allocate(allocation_requirements)
@@ -49,13 +78,50 @@ where
     }
 
     unsafe fn free(&mut self, allocation: Allocation) {
-        let allocation_requirements = allocation.allocation_requirements();
-        if allocation_requirements.prefers_dedicated_allocation
-            || allocation_requirements.requires_dedicated_allocation
-        {
+        if self.is_dedicated(allocation.allocation_requirements()) {
             self.device_allocator.free(allocation)
         } else {
             self.allocator.free(allocation)
         }
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.allocator.validate()?;
+        self.device_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.allocator.name_allocation(allocation);
+        self.device_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.allocator.stats(stats);
+        self.device_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.allocator.report(report);
+        self.device_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.allocator.trim();
+        self.device_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.allocator.reconfigure_pools(configurator)?;
+        self.device_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.allocator.reserve(allocation_requirements)
+    }
 }
@@ -0,0 +1,99 @@
+use {crate::Allocation, ash::vk};
+
+/// Signals that the GPU is done with a resource which was freed with
+/// [crate::MemoryAllocator::free_buffer_deferred] or a similar method.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GpuCompletion {
+    /// The GPU is done once the fence is signaled. The fence still belongs to
+    /// the application, which must not destroy it until the resource has
+    /// been collected.
+    Fence(vk::Fence),
+
+    /// The GPU is done once the timeline semaphore reaches the value.
+    Timeline {
+        semaphore: vk::Semaphore,
+        value: u64,
+    },
+}
+
+impl GpuCompletion {
+    /// Returns true when the GPU has signaled completion.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the fence or semaphore must not have been destroyed
+    pub(crate) unsafe fn is_signaled(
+        &self,
+        device: &ash::Device,
+    ) -> Result<bool, vk::Result> {
+        match *self {
+            Self::Fence(fence) => device.get_fence_status(fence),
+            Self::Timeline { semaphore, value } => device
+                .get_semaphore_counter_value(semaphore)
+                .map(|counter| counter >= value),
+        }
+    }
+}
+
+/// The resource which is bound to a deferred allocation.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum PendingResource {
+    Buffer(vk::Buffer),
+    Image(vk::Image),
+
+    /// Memory without a resource, e.g. from
+    /// [crate::MemoryAllocator::allocate].
+    None,
+}
+
+/// A resource which can be freed once the GPU signals completion.
+pub(crate) struct PendingFree {
+    pub resource: PendingResource,
+    pub allocation: Allocation,
+    pub completion: GpuCompletion,
+
+    /// The frame when the resource was freed.
+    pub frame: u64,
+
+    /// True when the fence was created by the allocator and should be
+    /// destroyed along with the resource.
+    pub owns_fence: bool,
+
+    /// The command buffer which references the resource, and the pool it was
+    /// allocated from.
+    pub command_buffer: Option<(vk::CommandPool, vk::CommandBuffer)>,
+}
+
+impl PendingFree {
+    /// Returns true when the GPU is done with the resource.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the fence or semaphore must not have been destroyed
+    pub unsafe fn is_complete(
+        &self,
+        device: &ash::Device,
+    ) -> Result<bool, vk::Result> {
+        self.completion.is_signaled(device)
+    }
+
+    /// Destroy the fence and command buffer which track the GPU work, if they
+    /// belong to the allocator.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must be done with the command buffer
+    pub unsafe fn destroy_sync_objects(&self, device: &ash::Device) {
+        if let (true, GpuCompletion::Fence(fence)) =
+            (self.owns_fence, self.completion)
+        {
+            device.destroy_fence(fence, None);
+        }
+        if let Some((command_pool, command_buffer)) = self.command_buffer {
+            device.free_command_buffers(command_pool, &[command_buffer]);
+        }
+    }
+}
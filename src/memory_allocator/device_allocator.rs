@@ -1,16 +1,47 @@
 use {
+    super::debug_names::DebugNames,
     crate::{
-        Allocation, AllocationRequirements, AllocatorError,
-        ComposableAllocator, DeviceMemory,
+        pretty_wrappers::PrettySize, Allocation, AllocationRequirements,
+        AllocatorError, AllocatorStats, ComposableAllocator,
+        DedicatedResourceHandle, DeviceMemory, IdGenerator, ImportedHandle,
+        MemoryUsage,
     },
-    anyhow::Context,
     ash::vk,
+    std::{collections::HashMap, ffi::c_void},
 };
 
 /// A GPU memory allocator which always allocates memory directly from the
 /// device.
+///
+/// With [Self::with_debug_names], every VkDeviceMemory is named with
+/// VK_EXT_debug_utils, so RenderDoc and validation messages can be traced
+/// back to the allocator. Pools name their chunks, and dedicated allocations
+/// take the name given to the allocation, see
+/// [crate::MemoryAllocator::name_allocation].
 pub struct DeviceAllocator {
     device: ash::Device,
+
+    /// The live device memory for each memory type.
+    usage: HashMap<usize, MemoryUsage>,
+
+    /// The number of live device memory allocations.
+    allocation_count: u32,
+
+    /// VkPhysicalDeviceLimits::maxMemoryAllocationCount, if known.
+    max_allocation_count: Option<u32>,
+
+    /// VkPhysicalDeviceLimits::nonCoherentAtomSize, if known.
+    non_coherent_atom_size: Option<u64>,
+
+    /// Names device memory when VK_EXT_debug_utils is enabled.
+    debug_names: Option<DebugNames>,
+
+    /// The number of device memory allocations made so far, used to number
+    /// the debug names.
+    total_allocation_count: u64,
+
+    /// Generates the id for each device memory allocation.
+    memory_ids: IdGenerator,
 }
 
 impl DeviceAllocator {
@@ -23,7 +54,86 @@ impl DeviceAllocator {
     ///  - all memory allocated by this allocator must be freed before
     ///    destroying the device
     pub unsafe fn new(device: ash::Device) -> Self {
-        Self { device }
+        Self {
+            device,
+            usage: HashMap::new(),
+            allocation_count: 0,
+            max_allocation_count: None,
+            non_coherent_atom_size: None,
+            debug_names: None,
+            total_allocation_count: 0,
+            memory_ids: IdGenerator::sequential(),
+        }
+    }
+
+    /// Fail with [AllocatorError::TooManyDeviceAllocations] instead of
+    /// calling vkAllocateMemory once the limit is reached.
+    ///
+    /// # Params
+    ///
+    /// * max_allocation_count: usually
+    ///   VkPhysicalDeviceLimits::maxMemoryAllocationCount.
+    pub fn with_max_allocation_count(self, max_allocation_count: u32) -> Self {
+        Self {
+            max_allocation_count: Some(max_allocation_count),
+            ..self
+        }
+    }
+
+    /// Round flush and invalidate ranges for allocated memory to the device's
+    /// atom size, see [crate::Allocation::flush]. Otherwise ranges are
+    /// rounded to the largest atom size allowed by the spec.
+    ///
+    /// # Params
+    ///
+    /// * non_coherent_atom_size: usually
+    ///   VkPhysicalDeviceLimits::nonCoherentAtomSize.
+    pub fn with_non_coherent_atom_size(
+        self,
+        non_coherent_atom_size: u64,
+    ) -> Self {
+        Self {
+            non_coherent_atom_size: Some(non_coherent_atom_size),
+            ..self
+        }
+    }
+
+    /// Name every device memory allocation with VK_EXT_debug_utils. This
+    /// does nothing when the extension isn't enabled on the instance.
+    ///
+    /// # Params
+    ///
+    /// * instance: the instance the device was created with.
+    pub fn with_debug_names(self, instance: &ash::Instance) -> Self {
+        Self {
+            // SAFE because the device must outlive this allocator, see
+            // Self::new.
+            debug_names: unsafe {
+                DebugNames::new(instance, self.device.clone())
+            },
+            ..self
+        }
+    }
+
+    /// True when device memory is named with VK_EXT_debug_utils, see
+    /// [Self::with_debug_names].
+    pub fn has_debug_names(&self) -> bool {
+        self.debug_names.is_some()
+    }
+
+    /// Use a different generator for the id of each device memory
+    /// allocation. Ids are sequential by default.
+    ///
+    /// # Params
+    ///
+    /// * memory_ids: generates the id for each device memory allocation.
+    pub fn with_memory_ids(self, memory_ids: IdGenerator) -> Self {
+        Self { memory_ids, ..self }
+    }
+
+    /// The number of live device memory allocations.
+    pub fn allocation_count(&self) -> u32 {
+        self.allocation_count
     }
 }
 
@@ -32,27 +142,141 @@ impl ComposableAllocator for DeviceAllocator {
         &mut self,
         allocation_requirements: AllocationRequirements,
     ) -> Result<Allocation, AllocatorError> {
-        let dedicated_info = allocation_requirements
+        if let Some(max_allocation_count) = self.max_allocation_count {
+            if self.allocation_count >= max_allocation_count {
+                return Err(AllocatorError::TooManyDeviceAllocations {
+                    allocation_count: self.allocation_count,
+                    max_allocation_count,
+                });
+            }
+        }
+
+        let extensions = &allocation_requirements.extensions;
+
+        // Build the pNext chain from the end, so each struct points at the
+        // structs already in the chain.
+        let mut p_next: *const c_void = std::ptr::null();
+        let priority_info = vk::MemoryPriorityAllocateInfoEXT {
+            p_next,
+            priority: extensions.priority().unwrap_or(0.5),
+            ..Default::default()
+        };
+        if extensions.priority().is_some() {
+            p_next = &priority_info as *const vk::MemoryPriorityAllocateInfoEXT
+                as *const c_void;
+        }
+        let export_info = vk::ExportMemoryAllocateInfo {
+            p_next,
+            handle_types: extensions.export_handle_types(),
+            ..Default::default()
+        };
+        if !extensions.export_handle_types().is_empty() {
+            p_next = &export_info as *const vk::ExportMemoryAllocateInfo
+                as *const c_void;
+        }
+        let import_fd_info = match extensions.import() {
+            Some(ImportedHandle::Fd(handle_type, fd)) => {
+                vk::ImportMemoryFdInfoKHR {
+                    p_next,
+                    handle_type,
+                    fd,
+                    ..Default::default()
+                }
+            }
+            _ => vk::ImportMemoryFdInfoKHR::default(),
+        };
+        let import_win32_info = match extensions.import() {
+            Some(ImportedHandle::Win32(handle_type, handle)) => {
+                vk::ImportMemoryWin32HandleInfoKHR {
+                    p_next,
+                    handle_type,
+                    handle: handle as vk::HANDLE,
+                    ..Default::default()
+                }
+            }
+            _ => vk::ImportMemoryWin32HandleInfoKHR::default(),
+        };
+        match extensions.import() {
+            Some(ImportedHandle::Fd(..)) => {
+                p_next = &import_fd_info as *const vk::ImportMemoryFdInfoKHR
+                    as *const c_void;
+            }
+            Some(ImportedHandle::Win32(..)) => {
+                p_next = &import_win32_info
+                    as *const vk::ImportMemoryWin32HandleInfoKHR
+                    as *const c_void;
+            }
+            None => (),
+        }
+        let allocate_flags_info = vk::MemoryAllocateFlagsInfo {
+            p_next,
+            flags: extensions.memory_allocate_flags(),
+            device_mask: extensions.device_mask().unwrap_or_default(),
+            ..Default::default()
+        };
+        if !extensions.memory_allocate_flags().is_empty() {
+            p_next = &allocate_flags_info as *const vk::MemoryAllocateFlagsInfo
+                as *const c_void;
+        }
+        let mut dedicated_info = allocation_requirements
             .dedicated_resource_handle
             .as_dedicated_allocation_info();
+        dedicated_info.p_next = p_next;
+
         let create_info = vk::MemoryAllocateInfo {
             p_next: &dedicated_info as *const vk::MemoryDedicatedAllocateInfo
-                as *const std::ffi::c_void,
+                as *const c_void,
             allocation_size: allocation_requirements.size_in_bytes,
             memory_type_index: allocation_requirements.memory_type_index as u32,
             ..Default::default()
         };
-        let memory = self
-            .device
-            .allocate_memory(&create_info, None)
-            .with_context(|| {
-                format!(
-                    "Error allocating memory with requirements {}",
-                    allocation_requirements,
-                )
-            })?;
+        let memory =
+            self.device
+                .allocate_memory(&create_info, None)
+                .map_err(|err| {
+                    AllocatorError::from_vk_result(
+                        err,
+                        format!(
+                            "Error allocating memory with requirements {}",
+                            allocation_requirements,
+                        ),
+                    )
+                })?;
+        let usage = self
+            .usage
+            .entry(allocation_requirements.memory_type_index)
+            .or_default();
+        usage.allocated_bytes += allocation_requirements.size_in_bytes;
+        usage.device_allocation_count += 1;
+        self.allocation_count += 1;
+        self.total_allocation_count += 1;
+
+        if let Some(debug_names) = &self.debug_names {
+            let kind = match allocation_requirements.dedicated_resource_handle {
+                DedicatedResourceHandle::None => "device memory",
+                _ => "dedicated memory",
+            };
+            debug_names.set_name(
+                memory,
+                &format!(
+                    "{} {} #{} type {}",
+                    kind,
+                    PrettySize(allocation_requirements.size_in_bytes),
+                    self.total_allocation_count,
+                    allocation_requirements.memory_type_index
+                ),
+            );
+        }
+
+        let mut device_memory =
+            DeviceMemory::new(memory, allocation_requirements.size_in_bytes)
+                .with_id(self.memory_ids.next_id());
+        if let Some(non_coherent_atom_size) = self.non_coherent_atom_size {
+            device_memory = device_memory
+                .with_non_coherent_atom_size(non_coherent_atom_size);
+        }
         let allocation = Allocation::new(
-            DeviceMemory::new(memory),
+            device_memory,
             allocation_requirements.memory_type_index,
             0,
             allocation_requirements.size_in_bytes,
@@ -62,6 +286,48 @@ impl ComposableAllocator for DeviceAllocator {
     }
 
     unsafe fn free(&mut self, allocation: Allocation) {
+        if let Some(usage) = self.usage.get_mut(&allocation.memory_type_index())
+        {
+            usage.allocated_bytes -= allocation.size_in_bytes();
+            usage.device_allocation_count -= 1;
+        }
+        self.allocation_count -= 1;
         self.device.free_memory(allocation.memory(), None)
     }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        let debug_names = match &self.debug_names {
+            Some(debug_names) => debug_names,
+            None => return,
+        };
+        // Suballocations share their chunk's memory, so only allocations
+        // which own their memory are named.
+        //
+        // SAFE because the memory is live until the allocation is freed.
+        unsafe {
+            if let (None, Some(name)) =
+                (allocation.parent_id(), allocation.name())
+            {
+                debug_names.set_name(allocation.memory(), name);
+            }
+        }
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        for (&memory_type_index, usage) in &self.usage {
+            let memory_type = stats.memory_type_mut(memory_type_index);
+            memory_type.allocated_bytes += usage.allocated_bytes;
+            memory_type.device_allocation_count +=
+                usage.device_allocation_count;
+        }
+        if let Some(max_allocation_count) = self.max_allocation_count {
+            stats.max_device_allocation_count = Some(
+                stats
+                    .max_device_allocation_count
+                    .map_or(max_allocation_count, |count| {
+                        count.min(max_allocation_count)
+                    }),
+            );
+        }
+    }
 }
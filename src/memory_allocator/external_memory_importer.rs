@@ -0,0 +1,372 @@
+use {
+    super::allocator_stats::UsageTracker,
+    crate::{
+        Allocation, AllocationId, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, DeviceAllocator, ImportedHandle,
+        MemoryReport,
+    },
+    anyhow::{anyhow, Context},
+    ash::vk,
+    std::{collections::HashSet, sync::Mutex},
+};
+
+/// Imports memory which is owned by another API or process, using
+/// VK_KHR_external_memory_fd or VK_KHR_external_memory_win32.
+pub(crate) struct ExternalMemoryImporter {
+    device: vk::Device,
+    external_memory_fd: vk::KhrExternalMemoryFdFn,
+    external_memory_win32: vk::KhrExternalMemoryWin32Fn,
+    imported: Mutex<ImportedMemory<UsageTracker<DeviceAllocator>>>,
+}
+
+impl ExternalMemoryImporter {
+    /// Load the extension entrypoints.
+    ///
+    /// The device does not need to support either extension. In that case
+    /// every import of the matching handle type fails.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the device must not be destroyed while the importer still exists
+    pub(crate) unsafe fn new(
+        instance: &ash::Instance,
+        device: ash::Device,
+    ) -> Self {
+        let load = |name: &std::ffi::CStr| {
+            std::mem::transmute(
+                instance.get_device_proc_addr(device.handle(), name.as_ptr()),
+            )
+        };
+        Self {
+            device: device.handle(),
+            external_memory_fd: vk::KhrExternalMemoryFdFn::load(load),
+            external_memory_win32: vk::KhrExternalMemoryWin32Fn::load(load),
+            imported: Mutex::new(ImportedMemory::new(UsageTracker::new(
+                DeviceAllocator::new(device).with_debug_names(instance),
+            ))),
+        }
+    }
+
+    /// Import memory from a POSIX file descriptor.
+    ///
+    /// # Params
+    ///
+    /// * memory_types: the memory types available on the physical device.
+    /// * handle_type: the type of the file descriptor, e.g. OPAQUE_FD or
+    ///   DMA_BUF_EXT.
+    /// * fd: the file descriptor. Vulkan takes ownership of it when the import
+    ///   succeeds.
+    /// * requirements: the size, memory type bits, and memory properties of the
+    ///   imported memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - VK_KHR_external_memory_fd must be enabled on the device
+    pub(crate) unsafe fn import_fd(
+        &self,
+        memory_types: &[vk::MemoryType],
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        fd: std::os::raw::c_int,
+        requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        // Opaque handles come from another Vulkan device, so their memory
+        // types are already described by the resource's requirements. The
+        // spec doesn't allow them to be queried.
+        let handle_memory_type_bits = if is_opaque(handle_type) {
+            None
+        } else {
+            let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+            (self.external_memory_fd.get_memory_fd_properties_khr)(
+                self.device,
+                handle_type,
+                fd,
+                &mut fd_properties,
+            )
+            .result()
+            .context("Unable to get the file descriptor's memory properties")?;
+            Some(fd_properties.memory_type_bits)
+        };
+        self.imported.lock().unwrap().import(
+            memory_types,
+            requirements,
+            ImportedHandle::Fd(handle_type, fd),
+            handle_memory_type_bits,
+        )
+    }
+
+    /// Import memory from a Win32 handle.
+    ///
+    /// # Params
+    ///
+    /// * memory_types: the memory types available on the physical device.
+    /// * handle_type: the type of the handle, e.g. OPAQUE_WIN32 or
+    ///   D3D12_RESOURCE.
+    /// * handle: the NT handle. Importing does not transfer ownership, the
+    ///   application must close it.
+    /// * requirements: the size, memory type bits, and memory properties of the
+    ///   imported memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - VK_KHR_external_memory_win32 must be enabled on the device
+    pub(crate) unsafe fn import_win32_handle(
+        &self,
+        memory_types: &[vk::MemoryType],
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        handle: vk::HANDLE,
+        requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let handle_memory_type_bits = if is_opaque(handle_type) {
+            None
+        } else {
+            let mut handle_properties =
+                vk::MemoryWin32HandlePropertiesKHR::default();
+            (self
+                .external_memory_win32
+                .get_memory_win32_handle_properties_khr)(
+                self.device,
+                handle_type,
+                handle,
+                &mut handle_properties,
+            )
+            .result()
+            .context("Unable to get the Win32 handle's memory properties")?;
+            Some(handle_properties.memory_type_bits)
+        };
+        self.imported.lock().unwrap().import(
+            memory_types,
+            requirements,
+            ImportedHandle::Win32(handle_type, handle as usize),
+            handle_memory_type_bits,
+        )
+    }
+
+    /// Free imported memory. See [ImportedMemory::free].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the memory must not be in use by the GPU
+    pub(crate) unsafe fn free(&self, allocation: Allocation) {
+        self.imported.lock().unwrap().free(allocation)
+    }
+
+    /// Add the imported memory to the allocator's stats.
+    pub(crate) fn stats(&self, stats: &mut AllocatorStats) {
+        self.imported.lock().unwrap().allocator.stats(stats)
+    }
+
+    /// Add the imported memory to the allocator's report.
+    pub(crate) fn report(&self, report: &mut MemoryReport) {
+        self.imported.lock().unwrap().allocator.report(report)
+    }
+}
+
+/// Keeps track of the live imports, so only imported memory is freed as
+/// imported memory.
+///
+/// The memory is allocated directly by the wrapped allocator, usually a
+/// [DeviceAllocator], so it's never suballocated.
+pub(crate) struct ImportedMemory<A: ComposableAllocator> {
+    allocator: A,
+    live: HashSet<AllocationId>,
+}
+
+impl<A: ComposableAllocator> ImportedMemory<A> {
+    pub(crate) fn new(allocator: A) -> Self {
+        Self {
+            allocator,
+            live: HashSet::new(),
+        }
+    }
+
+    /// Import memory with the wrapped allocator.
+    ///
+    /// The memory type must be allowed by both the requirements and the
+    /// handle. A dedicated allocation is used when the requirements name a
+    /// resource.
+    ///
+    /// # Params
+    ///
+    /// * memory_types: the memory types available on the physical device.
+    /// * requirements: the size, memory type bits, and memory properties of the
+    ///   imported memory.
+    /// * handle: the handle to import.
+    /// * handle_memory_type_bits: the memory types allowed by the handle, or
+    ///   None when the handle doesn't restrict them.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the handle must be valid for the wrapped allocator's device
+    pub(crate) unsafe fn import(
+        &mut self,
+        memory_types: &[vk::MemoryType],
+        requirements: AllocationRequirements,
+        handle: ImportedHandle,
+        handle_memory_type_bits: Option<u32>,
+    ) -> Result<Allocation, AllocatorError> {
+        if requirements.size_in_bytes == 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to import an empty allocation"
+            )));
+        }
+
+        let memory_requirements = vk::MemoryRequirements {
+            size: requirements.size_in_bytes,
+            alignment: requirements.alignment,
+            memory_type_bits: requirements.memory_type_bits
+                & handle_memory_type_bits.unwrap_or(u32::MAX),
+        };
+        let memory_type_index = AllocationRequirements::pick_memory_type_index(
+            memory_types,
+            &memory_requirements,
+            requirements.memory_properties,
+        )?;
+
+        let allocation = self.allocator.allocate(AllocationRequirements {
+            memory_type_bits: memory_requirements.memory_type_bits,
+            memory_type_index,
+            extensions: requirements.extensions.with_import(handle),
+            ..requirements
+        })?;
+        self.live.insert(allocation.id());
+        Ok(allocation)
+    }
+
+    /// Free memory which was imported with [Self::import].
+    ///
+    /// Any other allocation is logged and left alone, because freeing it
+    /// here would free memory which belongs to the internal allocator.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the memory must not be in use by the GPU
+    pub(crate) unsafe fn free(&mut self, allocation: Allocation) {
+        if !self.live.remove(&allocation.id()) {
+            log::error!(
+                "Attempted to free memory which was not imported!\n{:#?}",
+                allocation
+            );
+            return;
+        }
+        self.allocator.free(allocation)
+    }
+}
+
+/// Opaque handles can't be queried for their memory properties, see
+/// VUID-vkGetMemoryFdPropertiesKHR-handleType-00674 and
+/// VUID-vkGetMemoryWin32HandlePropertiesKHR-handleType-00666.
+fn is_opaque(handle_type: vk::ExternalMemoryHandleTypeFlags) -> bool {
+    handle_type.intersects(
+        vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD
+            | vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32
+            | vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32_KMT,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::FakeAllocator};
+
+    fn memory_types() -> Vec<vk::MemoryType> {
+        vec![
+            vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+            vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+        ]
+    }
+
+    fn requirements() -> AllocationRequirements {
+        AllocationRequirements {
+            size_in_bytes: 1024,
+            alignment: 256,
+            memory_type_bits: 0b11,
+            memory_properties: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_opaque_handles_are_not_queried() {
+        assert!(is_opaque(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD));
+        assert!(is_opaque(vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32));
+        assert!(!is_opaque(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT));
+    }
+
+    #[test]
+    fn test_imports_are_dedicated_allocations() -> Result<(), AllocatorError> {
+        let mut imported = ImportedMemory::new(FakeAllocator::default());
+        let handle = ImportedHandle::Fd(
+            vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+            7,
+        );
+
+        let allocation = unsafe {
+            imported.import(&memory_types(), requirements(), handle, Some(0b10))
+        }?;
+
+        assert_eq!(allocation.memory_type_index(), 1);
+        let requested = imported.allocator.allocations[0];
+        assert_eq!(requested.memory_type_bits, 0b10);
+        assert_eq!(requested.extensions.import(), Some(handle));
+        assert_eq!(imported.allocator.active_allocations, 1);
+
+        unsafe { imported.free(allocation) };
+        assert_eq!(imported.allocator.active_allocations, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_imported_memory_is_freed() -> Result<(), AllocatorError> {
+        let mut imported = ImportedMemory::new(FakeAllocator::default());
+        let handle =
+            ImportedHandle::Fd(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD, 7);
+        let allocation = unsafe {
+            imported.import(&memory_types(), requirements(), handle, None)
+        }?;
+        assert_eq!(imported.allocator.allocations[0].memory_type_bits, 0b11);
+
+        // Skip the first allocation so the ids don't collide.
+        let mut other = FakeAllocator::default();
+        unsafe { other.allocate(requirements()) }?;
+        let not_imported = unsafe { other.allocate(requirements()) }?;
+        unsafe { imported.free(not_imported) };
+        assert_eq!(imported.allocator.active_allocations, 1);
+
+        unsafe { imported.free(allocation.clone()) };
+        unsafe { imported.free(allocation) };
+        assert_eq!(imported.allocator.active_allocations, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_imports_are_rejected() {
+        let mut imported = ImportedMemory::new(FakeAllocator::default());
+        let result = unsafe {
+            imported.import(
+                &memory_types(),
+                AllocationRequirements {
+                    size_in_bytes: 0,
+                    ..requirements()
+                },
+                ImportedHandle::Fd(
+                    vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+                    7,
+                ),
+                None,
+            )
+        };
+        assert!(result.is_err());
+        assert_eq!(imported.allocator.allocation_count, 0);
+    }
+}
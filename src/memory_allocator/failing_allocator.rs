@@ -0,0 +1,135 @@
+use {
+    super::XorShiftRng,
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+        ComposableAllocator, MemoryReport, PoolConfigurator,
+    },
+    ash::vk,
+};
+
+/// Controls which allocations a [FailingAllocator] rejects.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FailureMode {
+    /// Fail only the Nth allocation request, counting from 1.
+    NthAllocation(u64),
+
+    /// Fail every allocation request with a size larger than this many bytes.
+    LargerThan(u64),
+
+    /// Fail allocation requests at random with the given probability in the
+    /// range [0, 1]. The seed makes the sequence of failures reproducible.
+    Probability { probability: f64, seed: u64 },
+}
+
+/// An allocator decorator which injects allocation failures.
+///
+/// This makes it possible to test an application's out-of-memory recovery
+/// paths without a device which is actually out of memory. Injected failures
+/// are [AllocatorError::RuntimeError]s which wrap
+/// vk::Result::ERROR_OUT_OF_DEVICE_MEMORY, just like real failures.
+pub struct FailingAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    mode: FailureMode,
+    request_count: u64,
+    failure_count: u64,
+    rng: XorShiftRng,
+}
+
+impl<T: ComposableAllocator> FailingAllocator<T> {
+    /// Create a new allocator which fails requests according to the given
+    /// mode and forwards everything else to the wrapped allocator.
+    pub fn new(wrapped_allocator: T, mode: FailureMode) -> Self {
+        let seed = match mode {
+            FailureMode::Probability { seed, .. } => seed,
+            _ => 0,
+        };
+        Self {
+            wrapped_allocator,
+            mode,
+            request_count: 0,
+            failure_count: 0,
+            rng: XorShiftRng::new(seed),
+        }
+    }
+
+    /// The number of allocation requests which were deliberately failed.
+    pub fn failure_count(&self) -> u64 {
+        self.failure_count
+    }
+
+    /// Decide whether the current request should fail.
+    fn should_fail(
+        &mut self,
+        allocation_requirements: &AllocationRequirements,
+    ) -> bool {
+        match self.mode {
+            FailureMode::NthAllocation(n) => self.request_count == n,
+            FailureMode::LargerThan(size) => {
+                allocation_requirements.size_in_bytes > size
+            }
+            FailureMode::Probability { probability, .. } => {
+                self.rng.next_f64() < probability
+            }
+        }
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for FailingAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.request_count += 1;
+        if self.should_fail(&allocation_requirements) {
+            self.failure_count += 1;
+            // Fail the same way as vkAllocateMemory so recovery code which
+            // checks the Vulkan result sees the injected failures.
+            return Err(AllocatorError::from_vk_result(
+                vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+                format!(
+                    "Injected allocation failure for request {} ({:?})",
+                    self.request_count, self.mode
+                ),
+            ));
+        }
+        self.wrapped_allocator.allocate(allocation_requirements)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
@@ -1,7 +1,7 @@
 use {
     crate::{
         device_memory::DeviceMemory, Allocation, AllocationRequirements,
-        AllocatorError, ComposableAllocator,
+        AllocatorError, ComposableAllocator, IdGenerator,
     },
     ash::vk,
 };
@@ -20,6 +20,7 @@ pub struct FakeAllocator {
     pub allocation_count: u64,
 
     offset: u64,
+    memory_ids: IdGenerator,
 }
 
 impl ComposableAllocator for FakeAllocator {
@@ -32,7 +33,11 @@ impl ComposableAllocator for FakeAllocator {
         self.allocations.push(allocation_requirements);
 
         let allocation = Allocation::new(
-            DeviceMemory::new(vk::DeviceMemory::null()),
+            DeviceMemory::new(
+                vk::DeviceMemory::null(),
+                self.offset + allocation_requirements.size_in_bytes,
+            )
+            .with_id(self.memory_ids.next_id()),
             allocation_requirements.memory_type_index,
             self.offset,
             allocation_requirements.size_in_bytes,
@@ -0,0 +1,114 @@
+use {
+    crate::{
+        Allocation, AllocationId, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, MemoryReport, PoolConfigurator,
+    },
+    std::collections::HashSet,
+};
+
+/// An allocator which composes over two other allocators. Requests are sent to
+/// the primary allocator first and retried with the fallback allocator if the
+/// primary allocator fails.
+///
+/// For example, this can express "allocate from a pool, but allocate directly
+/// from the device when the pool can't satisfy the request".
+pub struct FallbackAllocator<A: ComposableAllocator, B: ComposableAllocator> {
+    primary_allocator: A,
+    fallback_allocator: B,
+    fallback_allocations: HashSet<AllocationId>,
+}
+
+impl<A, B> FallbackAllocator<A, B>
+where
+    A: ComposableAllocator,
+    B: ComposableAllocator,
+{
+    /// Create a new fallback allocator.
+    ///
+    /// # Params
+    ///
+    /// * primary_allocator: every request is sent to this allocator first.
+    /// * fallback_allocator: requests which the primary allocator fails to
+    ///   satisfy are sent to this allocator.
+    pub fn new(primary_allocator: A, fallback_allocator: B) -> Self {
+        Self {
+            primary_allocator,
+            fallback_allocator,
+            fallback_allocations: HashSet::new(),
+        }
+    }
+}
+
+impl<A, B> ComposableAllocator for FallbackAllocator<A, B>
+where
+    A: ComposableAllocator,
+    B: ComposableAllocator,
+{
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let err = match self.primary_allocator.allocate(allocation_requirements)
+        {
+            Ok(allocation) => return Ok(allocation),
+            Err(err) => err,
+        };
+        log::debug!(
+            "Primary allocator failed, using the fallback allocator: {}",
+            err
+        );
+
+        let allocation =
+            self.fallback_allocator.allocate(allocation_requirements)?;
+        self.fallback_allocations.insert(allocation.id());
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        if self.fallback_allocations.remove(&allocation.id()) {
+            self.fallback_allocator.free(allocation)
+        } else {
+            self.primary_allocator.free(allocation)
+        }
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.primary_allocator.validate()?;
+        self.fallback_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.primary_allocator.name_allocation(allocation);
+        self.fallback_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.primary_allocator.stats(stats);
+        self.fallback_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.primary_allocator.report(report);
+        self.fallback_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.primary_allocator.trim();
+        self.fallback_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.primary_allocator.reconfigure_pools(configurator)?;
+        self.fallback_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.primary_allocator.reserve(allocation_requirements)
+    }
+}
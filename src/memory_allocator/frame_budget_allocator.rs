@@ -0,0 +1,212 @@
+use {
+    super::PolicyClock,
+    crate::{
+        pretty_wrappers::PrettySize, Allocation, AllocationRequirements,
+        AllocatorError, AllocatorStats, ComposableAllocator, FrameClock,
+        MemoryReport, PoolConfigurator,
+    },
+    indoc::indoc,
+};
+
+/// Soft limits on how much a wrapped allocator is asked to allocate in a
+/// single frame.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FrameBudget {
+    /// The maximum number of allocations per frame. None means unlimited.
+    pub max_allocations: Option<u32>,
+
+    /// The maximum number of bytes allocated per frame. None means unlimited.
+    pub max_bytes: Option<u64>,
+}
+
+/// An allocator decorator which emits a warning for each frame that exceeds a
+/// per-frame budget.
+///
+/// The warning is logged once, by [Self::end_frame], and lists every request
+/// made during the frame. The budget is soft: over-budget requests are still
+/// forwarded to the wrapped allocator. Wrapping the allocator which backs a
+/// pool (e.g. the device allocator) turns this into an alarm for pool growth,
+/// so allocation regressions show up in the log during development instead of
+/// as frame-time spikes.
+pub struct FrameBudgetAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    name: String,
+    budget: FrameBudget,
+    clock: PolicyClock,
+    frame_allocations: u32,
+    frame_bytes: u64,
+    frame_requests: Vec<AllocationRequirements>,
+}
+
+impl<T: ComposableAllocator> FrameBudgetAllocator<T> {
+    /// Create a new frame budget allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator which does the actual allocation.
+    /// * budget: the soft per-frame limits.
+    /// * name: used to identify this allocator in warnings.
+    pub fn new(
+        wrapped_allocator: T,
+        budget: FrameBudget,
+        name: impl Into<String>,
+    ) -> Self {
+        Self {
+            wrapped_allocator,
+            name: name.into(),
+            budget,
+            clock: PolicyClock::default(),
+            frame_allocations: 0,
+            frame_bytes: 0,
+            frame_requests: vec![],
+        }
+    }
+
+    /// Count frames with a clock which is advanced by the application,
+    /// rather than by [Self::end_frame]. See [FrameClock].
+    pub fn with_clock(self, clock: FrameClock) -> Self {
+        Self {
+            clock: PolicyClock::shared(clock),
+            ..self
+        }
+    }
+
+    /// Mark the end of the current frame, warn if the frame was over budget,
+    /// and reset the per-frame counters.
+    ///
+    /// The clock is only advanced when it isn't shared, see
+    /// [Self::with_clock].
+    pub fn end_frame(&mut self) {
+        if self.is_over_budget() {
+            self.warn();
+        }
+        self.clock.end_frame();
+        self.frame_allocations = 0;
+        self.frame_bytes = 0;
+        self.frame_requests.clear();
+    }
+
+    /// The number of allocations made since the last call to end_frame().
+    pub fn frame_allocations(&self) -> u32 {
+        self.frame_allocations
+    }
+
+    /// The number of bytes allocated since the last call to end_frame().
+    pub fn frame_bytes(&self) -> u64 {
+        self.frame_bytes
+    }
+
+    /// The requests made since the last call to end_frame().
+    pub fn frame_requests(&self) -> &[AllocationRequirements] {
+        &self.frame_requests
+    }
+
+    /// Returns true when the current frame has exceeded either budget.
+    pub fn is_over_budget(&self) -> bool {
+        let too_many_allocations = self
+            .budget
+            .max_allocations
+            .is_some_and(|max| self.frame_allocations > max);
+        let too_many_bytes = self
+            .budget
+            .max_bytes
+            .is_some_and(|max| self.frame_bytes > max);
+        too_many_allocations || too_many_bytes
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for FrameBudgetAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+
+        self.frame_allocations += 1;
+        self.frame_bytes += allocation_requirements.size_in_bytes;
+        self.frame_requests.push(allocation_requirements);
+
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+// Private API
+// -----------
+
+impl<T: ComposableAllocator> FrameBudgetAllocator<T> {
+    /// Log a single warning which lists every request made this frame.
+    fn warn(&self) {
+        let mut requests = vec![];
+        for request in &self.frame_requests {
+            let location = match request.location {
+                Some(location) => location.to_string(),
+                None => "an unknown location".to_owned(),
+            };
+            requests.push(format!(
+                "  - {} from memory type {}, tag {:?}, at {}",
+                PrettySize(request.size_in_bytes),
+                request.memory_type_index,
+                request.tag,
+                location,
+            ));
+        }
+        log::warn!(
+            indoc!(
+                "
+                {} exceeded its frame budget in frame {}
+
+                allocations this frame: {} (max {:?})
+                bytes this frame: {} (max {:?})
+                requests this frame:
+                {}
+                "
+            ),
+            self.name,
+            self.clock.frame(),
+            self.frame_allocations,
+            self.budget.max_allocations,
+            PrettySize(self.frame_bytes),
+            self.budget.max_bytes.map(PrettySize),
+            requests.join("\n"),
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// A frame counter which drives every age-based policy, e.g. how long freed
+/// allocations stay in quarantine.
+///
+/// Policies never read wall time, they only count frames. By default each
+/// policy owns its own clock which is advanced by its `end_frame` method.
+/// Sharing one clock between policies (with their `with_clock` methods) keeps
+/// them in lockstep, and lets tests and replays control time directly.
+///
+/// Clones share the same counter.
+#[derive(Debug, Clone, Default)]
+pub struct FrameClock {
+    frame: Arc<AtomicU64>,
+}
+
+impl FrameClock {
+    /// Create a new clock which starts at frame zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current frame.
+    pub fn frame(&self) -> u64 {
+        self.frame.load(Ordering::SeqCst)
+    }
+
+    /// Advance to the next frame.
+    ///
+    /// # Returns
+    ///
+    /// The new frame.
+    pub fn advance(&self) -> u64 {
+        self.frame.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Jump to a specific frame, e.g. when replaying a recorded trace.
+    pub fn set_frame(&self, frame: u64) {
+        self.frame.store(frame, Ordering::SeqCst);
+    }
+}
+
+/// The clock used by a single policy.
+///
+/// An owned clock is advanced by the policy's `end_frame` method. A shared
+/// clock is advanced by the application, so `end_frame` leaves it alone.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PolicyClock {
+    clock: FrameClock,
+    is_shared: bool,
+}
+
+impl PolicyClock {
+    /// Use a clock which is advanced by the application.
+    pub fn shared(clock: FrameClock) -> Self {
+        Self {
+            clock,
+            is_shared: true,
+        }
+    }
+
+    /// The current frame.
+    pub fn frame(&self) -> u64 {
+        self.clock.frame()
+    }
+
+    /// Jump to the application's frame if the policy owns the clock. A shared
+    /// clock is left alone because other policies count with it too.
+    pub fn begin_frame(&self, frame: u64) {
+        if !self.is_shared {
+            self.clock.set_frame(frame);
+        }
+    }
+
+    /// Advance the clock if the policy owns it.
+    pub fn end_frame(&self) {
+        if !self.is_shared {
+            self.clock.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_owned_clocks_follow_the_application() {
+        let clock = PolicyClock::default();
+
+        clock.begin_frame(5);
+        assert_eq!(clock.frame(), 5);
+        clock.end_frame();
+        assert_eq!(clock.frame(), 6);
+    }
+
+    #[test]
+    fn test_shared_clocks_are_left_alone() {
+        let shared = FrameClock::new();
+        shared.set_frame(3);
+        let clock = PolicyClock::shared(shared.clone());
+
+        clock.begin_frame(5);
+        clock.end_frame();
+        assert_eq!(shared.frame(), 3);
+
+        shared.advance();
+        assert_eq!(clock.frame(), 4);
+    }
+}
@@ -0,0 +1,102 @@
+use {crate::AllocationRequirements, std::panic::Location};
+
+/// The options for a handle to the memory allocator, see
+/// [crate::MemoryAllocator::tagged] and
+/// [crate::MemoryAllocator::for_devices].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct HandleOptions {
+    /// The tag for allocations made with the handle.
+    pub tag: Option<&'static str>,
+
+    /// The physical devices which get a copy of each allocation.
+    pub device_mask: Option<u32>,
+}
+
+impl HandleOptions {
+    /// Add the tag and device mask, the location of the application code
+    /// which made the allocation, and the current frame, to requirements which
+    /// don't have them.
+    ///
+    /// # Params
+    ///
+    /// * allocation_requirements: the requirements for the allocation.
+    /// * frame: the current frame.
+    #[track_caller]
+    pub fn apply(
+        &self,
+        allocation_requirements: AllocationRequirements,
+        frame: u64,
+    ) -> AllocationRequirements {
+        let caller = Location::caller();
+        let extensions = allocation_requirements.extensions;
+        AllocationRequirements {
+            tag: allocation_requirements.tag.or(self.tag),
+            location: allocation_requirements.location.or(Some(caller)),
+            frame: allocation_requirements.frame.or(Some(frame)),
+            extensions: match (extensions.device_mask(), self.device_mask) {
+                (None, Some(device_mask)) => {
+                    extensions.with_device_mask(device_mask)
+                }
+                _ => extensions,
+            },
+            ..allocation_requirements
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::AllocationExtensions, ash::vk};
+
+    #[test]
+    fn test_handle_options_are_added() {
+        let options = HandleOptions {
+            tag: Some("textures"),
+            device_mask: Some(0b10),
+        };
+
+        let requirements = options.apply(AllocationRequirements::default(), 3);
+        let line = line!() - 1;
+
+        assert_eq!(requirements.tag, Some("textures"));
+        assert_eq!(requirements.frame, Some(3));
+        let location = requirements.location.unwrap();
+        assert_eq!((location.file(), location.line()), (file!(), line));
+        assert_eq!(requirements.extensions.device_mask(), Some(0b10));
+        assert!(requirements
+            .extensions
+            .memory_allocate_flags()
+            .contains(vk::MemoryAllocateFlags::DEVICE_MASK));
+    }
+
+    #[test]
+    fn test_requirements_keep_their_own_options() {
+        let options = HandleOptions {
+            tag: Some("textures"),
+            device_mask: Some(0b10),
+        };
+
+        let requirements = options.apply(
+            AllocationRequirements {
+                tag: Some("meshes"),
+                frame: Some(1),
+                extensions: AllocationExtensions::default()
+                    .with_device_mask(0b01),
+                ..AllocationRequirements::default()
+            },
+            3,
+        );
+
+        assert_eq!(requirements.tag, Some("meshes"));
+        assert_eq!(requirements.frame, Some(1));
+        assert_eq!(requirements.extensions.device_mask(), Some(0b01));
+    }
+
+    #[test]
+    fn test_default_options_leave_the_extensions_alone() {
+        let requirements = HandleOptions::default()
+            .apply(AllocationRequirements::default(), 0);
+        assert_eq!(requirements.tag, None);
+        assert!(requirements.extensions.is_empty());
+    }
+}
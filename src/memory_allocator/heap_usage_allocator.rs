@@ -0,0 +1,257 @@
+use {
+    crate::{
+        pretty_wrappers::PrettySize, Allocation, AllocationRequirements,
+        AllocatorError, AllocatorStats, ComposableAllocator, MemoryProperties,
+        MemoryReport, PoolConfigurator,
+    },
+    ash::vk,
+};
+
+/// Describes a heap whose usage crossed the warning threshold.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeapUsageWarning {
+    /// The heap which crossed the threshold.
+    pub heap_index: usize,
+
+    /// The number of bytes allocated from the heap.
+    pub used_bytes: u64,
+
+    /// The heap's budget.
+    pub budget_bytes: u64,
+
+    /// The fraction of the budget which triggers a warning.
+    pub threshold: f64,
+}
+
+/// A callback which is invoked when a heap's usage crosses the warning
+/// threshold.
+pub type HeapUsageHook = Box<dyn FnMut(&HeapUsageWarning) + Send>;
+
+/// An allocator decorator which tracks the bytes allocated from each memory
+/// heap and warns when usage crosses a fraction of the heap's budget.
+///
+/// This gives applications a chance to evict or stream out resources before
+/// the device runs out of memory. Warnings are logged by default, use a hook
+/// to handle them in the application instead. Each heap warns once each time
+/// it crosses the threshold.
+///
+/// Budgets default to the heap sizes. The OS changes the real budgets at
+/// runtime, so use [Self::with_budget_query] to refresh them from
+/// VK_EXT_memory_budget before each allocation is checked. Otherwise update
+/// them with [Self::set_heap_budget], e.g. from
+/// [crate::MemoryAllocator::heap_budgets], before the allocator is moved into
+/// a [crate::MemoryAllocator].
+pub struct HeapUsageAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    memory_properties: MemoryProperties,
+    budget_query: Option<(ash::Instance, vk::PhysicalDevice)>,
+    threshold: f64,
+    budgets: Vec<u64>,
+    usage: Vec<u64>,
+    on_warning: HeapUsageHook,
+}
+
+impl<T: ComposableAllocator> HeapUsageAllocator<T> {
+    /// Create a new heap usage allocator which warns at 85% of each heap.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator which does the actual allocation.
+    /// * memory_properties: used to find the heap for each memory type.
+    pub fn new(
+        wrapped_allocator: T,
+        memory_properties: MemoryProperties,
+    ) -> Self {
+        let budgets = memory_properties
+            .heaps()
+            .iter()
+            .map(|heap| heap.size)
+            .collect::<Vec<_>>();
+        Self {
+            wrapped_allocator,
+            usage: vec![0; budgets.len()],
+            budgets,
+            memory_properties,
+            budget_query: None,
+            threshold: 0.85,
+            on_warning: Box::new(|warning| {
+                log::warn!(
+                    "Memory heap {} is using {} of its {} budget",
+                    warning.heap_index,
+                    PrettySize(warning.used_bytes),
+                    PrettySize(warning.budget_bytes),
+                )
+            }),
+        }
+    }
+
+    /// Warn when a heap's usage crosses this fraction of its budget.
+    ///
+    /// # Params
+    ///
+    /// * threshold: a fraction of the budget, e.g. 0.85 for 85%.
+    pub fn with_warning_threshold(self, threshold: f64) -> Self {
+        Self { threshold, ..self }
+    }
+
+    /// Invoke a custom callback instead of logging when a heap crosses the
+    /// threshold.
+    pub fn with_warning_hook(
+        self,
+        on_warning: impl FnMut(&HeapUsageWarning) + Send + 'static,
+    ) -> Self {
+        Self {
+            on_warning: Box::new(on_warning),
+            ..self
+        }
+    }
+
+    /// Query the heap budgets with VK_EXT_memory_budget before each
+    /// allocation is checked, so warnings use what the OS currently allows.
+    /// Budgets keep their current values when the extension isn't supported.
+    ///
+    /// # Params
+    ///
+    /// * instance: the instance used to create the physical device.
+    /// * physical_device: the physical device the memory properties came from.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the physical device must be the one the memory properties were
+    ///     queried from
+    ///   - the instance must not be destroyed while this allocator still exists
+    pub unsafe fn with_budget_query(
+        self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        let mut allocator = Self {
+            budget_query: Some((instance.clone(), physical_device)),
+            ..self
+        };
+        allocator.refresh_budgets();
+        allocator
+    }
+
+    /// Change a heap's budget. Unknown heaps are ignored.
+    ///
+    /// # Params
+    ///
+    /// * heap_index: the heap to update.
+    /// * budget_bytes: the number of bytes the application can use.
+    pub fn set_heap_budget(&mut self, heap_index: usize, budget_bytes: u64) {
+        if let Some(budget) = self.budgets.get_mut(heap_index) {
+            *budget = budget_bytes;
+        }
+    }
+
+    /// The number of bytes allocated from a heap through this allocator.
+    pub fn heap_usage(&self, heap_index: usize) -> u64 {
+        self.usage.get(heap_index).copied().unwrap_or(0)
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for HeapUsageAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        if let Some(heap_index) = self.heap_index(&allocation) {
+            let before = self.usage[heap_index];
+            let after = before + allocation.size_in_bytes();
+            self.usage[heap_index] = after;
+
+            self.refresh_budgets();
+            let limit =
+                (self.budgets[heap_index] as f64 * self.threshold) as u64;
+            if before <= limit && after > limit {
+                (self.on_warning)(&HeapUsageWarning {
+                    heap_index,
+                    used_bytes: after,
+                    budget_bytes: self.budgets[heap_index],
+                    threshold: self.threshold,
+                });
+            }
+        }
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        if let Some(heap_index) = self.heap_index(&allocation) {
+            self.usage[heap_index] = self.usage[heap_index]
+                .saturating_sub(allocation.size_in_bytes());
+        }
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+// Private API
+// -----------
+
+impl<T: ComposableAllocator> HeapUsageAllocator<T> {
+    /// Query the current heap budgets, see [Self::with_budget_query].
+    fn refresh_budgets(&mut self) {
+        let (instance, physical_device) = match &self.budget_query {
+            Some(budget_query) => budget_query,
+            None => return,
+        };
+        // SAFE because the physical device is the one the memory properties
+        // were queried from, see Self::with_budget_query.
+        let heap_budgets = unsafe {
+            self.memory_properties
+                .query_heap_budgets(instance, *physical_device)
+        };
+        for (budget, heap_budget) in
+            self.budgets.iter_mut().zip(heap_budgets.iter().flatten())
+        {
+            *budget = heap_budget.budget_bytes;
+        }
+    }
+
+    /// The heap which an allocation's memory type belongs to.
+    fn heap_index(&self, allocation: &Allocation) -> Option<usize> {
+        let memory_type = self
+            .memory_properties
+            .types()
+            .get(allocation.memory_type_index())?;
+        let heap_index = memory_type.heap_index as usize;
+        (heap_index < self.usage.len()).then_some(heap_index)
+    }
+}
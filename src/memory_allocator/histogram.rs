@@ -0,0 +1,154 @@
+/// A histogram with power-of-two buckets.
+///
+/// Bucket `i` counts values in `[2^i, 2^(i+1))`, except bucket 0 which also
+/// counts zero. Percentiles are approximate, they report the largest value in
+/// the bucket which contains the percentile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; 64],
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; 64],
+            count: 0,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Histogram {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        // Serde only derives Serialize for arrays of up to 32 elements.
+        let mut state = serializer.serialize_struct("Histogram", 2)?;
+        state.serialize_field("buckets", &self.buckets[..])?;
+        state.serialize_field("count", &self.count)?;
+        state.end()
+    }
+}
+
+impl Histogram {
+    /// Add a value to the histogram.
+    pub fn record(&mut self, value: u64) {
+        self.buckets[Self::bucket_index(value)] += 1;
+        self.count += 1;
+    }
+
+    /// The number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The number of values in each bucket.
+    pub fn buckets(&self) -> &[u64; 64] {
+        &self.buckets
+    }
+
+    /// The smallest and largest value which can be counted in a bucket.
+    pub fn bucket_range(bucket_index: usize) -> (u64, u64) {
+        let start = if bucket_index == 0 {
+            0
+        } else {
+            1 << bucket_index
+        };
+        let end = if bucket_index >= 63 {
+            u64::MAX
+        } else {
+            (1 << (bucket_index + 1)) - 1
+        };
+        (start, end)
+    }
+
+    /// Estimate a percentile.
+    ///
+    /// # Params
+    ///
+    /// * percentile: a fraction between 0.0 and 1.0, e.g. 0.95 for the 95th
+    ///   percentile.
+    ///
+    /// # Returns
+    ///
+    /// The largest value in the bucket which contains the percentile, or None
+    /// when the histogram is empty.
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 1.0) * self.count as f64).ceil()
+            as u64)
+            .max(1);
+        let mut seen = 0;
+        for (bucket_index, &bucket) in self.buckets.iter().enumerate() {
+            seen += bucket;
+            if seen >= rank {
+                return Some(Self::bucket_range(bucket_index).1);
+            }
+        }
+        None
+    }
+}
+
+// Private API
+// -----------
+
+impl Histogram {
+    /// The bucket which counts a value.
+    fn bucket_index(value: u64) -> usize {
+        if value == 0 {
+            0
+        } else {
+            63 - value.leading_zeros() as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_values_are_counted_in_power_of_two_buckets() {
+        let mut histogram = Histogram::default();
+        for value in [0, 1, 2, 3, 4, 1023, 1024, u64::MAX] {
+            histogram.record(value);
+        }
+
+        assert_eq!(histogram.count(), 8);
+        assert_eq!(histogram.buckets()[0], 2);
+        assert_eq!(histogram.buckets()[1], 2);
+        assert_eq!(histogram.buckets()[2], 1);
+        assert_eq!(histogram.buckets()[9], 1);
+        assert_eq!(histogram.buckets()[10], 1);
+        assert_eq!(histogram.buckets()[63], 1);
+        assert_eq!(Histogram::bucket_range(0), (0, 1));
+        assert_eq!(Histogram::bucket_range(10), (1024, 2047));
+        assert_eq!(Histogram::bucket_range(63), (1 << 63, u64::MAX));
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(0.5), None);
+
+        // 90 small values and 10 large values.
+        for _ in 0..90 {
+            histogram.record(100);
+        }
+        for _ in 0..10 {
+            histogram.record(5000);
+        }
+
+        assert_eq!(histogram.percentile(0.0), Some(127));
+        assert_eq!(histogram.percentile(0.5), Some(127));
+        assert_eq!(histogram.percentile(0.9), Some(127));
+        assert_eq!(histogram.percentile(0.91), Some(8191));
+        assert_eq!(histogram.percentile(1.0), Some(8191));
+    }
+}
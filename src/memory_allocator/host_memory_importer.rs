@@ -0,0 +1,220 @@
+use {
+    crate::{Allocation, AllocationRequirements, AllocatorError, DeviceMemory},
+    anyhow::{anyhow, Context},
+    ash::vk,
+    std::ffi::{c_void, CStr},
+};
+
+/// Imports application-provided host allocations as device memory with
+/// VK_EXT_external_memory_host.
+#[derive(Clone)]
+pub(crate) struct HostMemoryImporter {
+    device: ash::Device,
+    external_memory_host: vk::ExtExternalMemoryHostFn,
+    min_imported_host_pointer_alignment: u64,
+}
+
+impl HostMemoryImporter {
+    /// Load the extension entrypoints and query the required alignment.
+    ///
+    /// The physical device does not need to support the extension. In that
+    /// case every import fails with an error.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the device must not be destroyed while the importer still exists
+    pub(crate) unsafe fn new(
+        instance: &ash::Instance,
+        device: ash::Device,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        let external_memory_host_supported = instance
+            .enumerate_device_extension_properties(physical_device)
+            .map(|extensions| {
+                extensions.iter().any(|extension| {
+                    // SAFE because Vulkan extension names are
+                    // null-terminated.
+                    CStr::from_ptr(extension.extension_name.as_ptr())
+                        == vk::ExtExternalMemoryHostFn::name()
+                })
+            })
+            .unwrap_or(false);
+
+        // The properties struct can only be chained when the extension is
+        // supported. Otherwise the alignment stays 0, which fails every
+        // import.
+        let mut host_properties =
+            vk::PhysicalDeviceExternalMemoryHostPropertiesEXT::default();
+        if external_memory_host_supported {
+            let mut properties2 = vk::PhysicalDeviceProperties2 {
+                p_next: &mut host_properties
+                    as *mut vk::PhysicalDeviceExternalMemoryHostPropertiesEXT
+                    as *mut c_void,
+                ..Default::default()
+            };
+            instance.get_physical_device_properties2(
+                physical_device,
+                &mut properties2,
+            );
+        }
+
+        let external_memory_host = vk::ExtExternalMemoryHostFn::load(|name| {
+            std::mem::transmute(
+                instance.get_device_proc_addr(device.handle(), name.as_ptr()),
+            )
+        });
+
+        Self {
+            device,
+            external_memory_host,
+            min_imported_host_pointer_alignment: host_properties
+                .min_imported_host_pointer_alignment,
+        }
+    }
+
+    /// Import a host allocation as device memory.
+    ///
+    /// # Params
+    ///
+    /// * memory_types: the memory types available on the physical device.
+    /// * host_pointer: the start of the host allocation.
+    /// * size_in_bytes: the size of the host allocation.
+    /// * memory_property_flags: the required memory properties.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the host allocation must outlive the imported device memory
+    ///  - VK_EXT_external_memory_host must be enabled on the device
+    pub(crate) unsafe fn import(
+        &self,
+        memory_types: &[vk::MemoryType],
+        host_pointer: *mut c_void,
+        size_in_bytes: u64,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, AllocatorError> {
+        check_host_allocation(
+            host_pointer as usize as u64,
+            size_in_bytes,
+            self.min_imported_host_pointer_alignment,
+        )?;
+
+        let mut host_pointer_properties =
+            vk::MemoryHostPointerPropertiesEXT::default();
+        (self
+            .external_memory_host
+            .get_memory_host_pointer_properties_ext)(
+            self.device.handle(),
+            vk::ExternalMemoryHandleTypeFlags::HOST_ALLOCATION_EXT,
+            host_pointer,
+            &mut host_pointer_properties,
+        )
+        .result()
+        .context("Unable to get the host pointer's memory properties")?;
+
+        let memory_requirements = vk::MemoryRequirements {
+            size: size_in_bytes,
+            alignment: self.min_imported_host_pointer_alignment,
+            memory_type_bits: host_pointer_properties.memory_type_bits,
+        };
+        let memory_type_index = AllocationRequirements::pick_memory_type_index(
+            memory_types,
+            &memory_requirements,
+            memory_property_flags,
+        )?;
+
+        let import_info = vk::ImportMemoryHostPointerInfoEXT {
+            handle_type: vk::ExternalMemoryHandleTypeFlags::HOST_ALLOCATION_EXT,
+            p_host_pointer: host_pointer,
+            ..Default::default()
+        };
+        let allocate_info = vk::MemoryAllocateInfo {
+            p_next: &import_info as *const vk::ImportMemoryHostPointerInfoEXT
+                as *const c_void,
+            allocation_size: size_in_bytes,
+            memory_type_index: memory_type_index as u32,
+            ..Default::default()
+        };
+        let memory = self
+            .device
+            .allocate_memory(&allocate_info, None)
+            .map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    format!(
+                        "Error importing {} bytes of host memory at {:?}",
+                        size_in_bytes, host_pointer
+                    ),
+                )
+            })?;
+
+        Ok(Allocation::new(
+            DeviceMemory::new(memory, size_in_bytes),
+            memory_type_index,
+            0,
+            size_in_bytes,
+            AllocationRequirements {
+                size_in_bytes,
+                alignment: memory_requirements.alignment,
+                memory_type_bits: memory_requirements.memory_type_bits,
+                memory_type_index,
+                memory_properties: memory_property_flags,
+                ..Default::default()
+            },
+        ))
+    }
+}
+
+/// Check that a host allocation can be imported.
+///
+/// Both the address and size must be multiples of the device's
+/// minImportedHostPointerAlignment. An alignment of 0 means the physical
+/// device does not support the extension.
+fn check_host_allocation(
+    address: u64,
+    size_in_bytes: u64,
+    min_alignment: u64,
+) -> Result<(), AllocatorError> {
+    if min_alignment == 0 {
+        return Err(AllocatorError::RuntimeError(anyhow!(
+            "The device does not support VK_EXT_external_memory_host"
+        )));
+    }
+    if size_in_bytes == 0 {
+        return Err(AllocatorError::RuntimeError(anyhow!(
+            "Unable to import an empty host allocation"
+        )));
+    }
+    if address % min_alignment != 0 || size_in_bytes % min_alignment != 0 {
+        return Err(AllocatorError::RuntimeError(anyhow!(
+            "Host allocation at {:#x} with {} bytes is not aligned to {} bytes",
+            address,
+            size_in_bytes,
+            min_alignment
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_aligned_host_allocation() {
+        assert!(check_host_allocation(4096, 8192, 4096).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_device() {
+        assert!(check_host_allocation(4096, 4096, 0).is_err());
+    }
+
+    #[test]
+    fn test_misaligned_host_allocation() {
+        assert!(check_host_allocation(4097, 4096, 4096).is_err());
+        assert!(check_host_allocation(4096, 4000, 4096).is_err());
+        assert!(check_host_allocation(4096, 0, 4096).is_err());
+    }
+}
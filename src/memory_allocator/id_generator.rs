@@ -0,0 +1,49 @@
+use super::XorShiftRng;
+
+/// Generates the ids which identify device memory, see
+/// [crate::DeviceAllocator::with_memory_ids], and chunks in allocation paths,
+/// see [crate::Allocation::path].
+///
+/// Allocators keep their live allocations ordered by these ids rather than by
+/// Vulkan handles, which vary from run to run.
+///
+/// Sequential ids are the default. Seeded ids are spread out, which shakes out
+/// code that accidentally relies on ids being small or contiguous. Either way,
+/// the same sequence of allocations always produces the same ids so recorded
+/// traces and test assertions are reproducible.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct IdGenerator {
+    next: u64,
+    rng: Option<XorShiftRng>,
+}
+
+impl IdGenerator {
+    /// Generate ids counting up from zero.
+    pub fn sequential() -> Self {
+        Self::default()
+    }
+
+    /// Generate pseudo-random ids from a seed.
+    ///
+    /// # Params
+    ///
+    /// * seed: the same seed always produces the same sequence of ids.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            next: 0,
+            rng: Some(XorShiftRng::new(seed)),
+        }
+    }
+
+    /// Generate the next id.
+    pub fn next_id(&mut self) -> u64 {
+        match self.rng.as_mut() {
+            Some(rng) => rng.next_u64(),
+            None => {
+                let id = self.next;
+                self.next += 1;
+                id
+            }
+        }
+    }
+}
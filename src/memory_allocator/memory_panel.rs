@@ -0,0 +1,179 @@
+use {
+    crate::{
+        pretty_wrappers::PrettySize, AllocatorStats, MemoryAllocator,
+        MemoryReport,
+    },
+    std::collections::BTreeMap,
+};
+
+/// A ready-made egui widget which draws the allocator's memory usage as bars,
+/// one for each heap and one for each pool.
+///
+/// The panel is a snapshot, so create a new one every frame to keep it
+/// current, e.g. `ui.add(MemoryPanel::new(&allocator))`.
+pub struct MemoryPanel {
+    stats: AllocatorStats,
+    report: MemoryReport,
+    heap_sizes: Vec<u64>,
+}
+
+// Public API
+// ----------
+
+impl MemoryPanel {
+    /// Take a snapshot of the allocator's stats and report.
+    ///
+    /// # Params
+    ///
+    /// * allocator: the allocator to display.
+    pub fn new(allocator: &MemoryAllocator) -> Self {
+        Self {
+            stats: allocator.stats(),
+            report: allocator.generate_report(),
+            heap_sizes: allocator
+                .memory_properties()
+                .heaps()
+                .iter()
+                .map(|heap| heap.size)
+                .collect(),
+        }
+    }
+}
+
+impl egui::Widget for MemoryPanel {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        ui.vertical(|ui| {
+            ui.heading("Memory Heaps");
+            for (heap_index, usage) in
+                self.stats.memory_heaps.iter().enumerate()
+            {
+                // The budget is a better limit than the heap size when the
+                // device reports it.
+                let capacity = match &self.stats.heap_budgets {
+                    Some(budgets) if heap_index < budgets.len() => {
+                        budgets[heap_index].budget_bytes
+                    }
+                    _ => self.heap_sizes.get(heap_index).copied().unwrap_or(0),
+                };
+                usage_bar(
+                    ui,
+                    usage.allocated_bytes,
+                    capacity,
+                    format!(
+                        "Heap {}: {} used, {} allocated of {}",
+                        heap_index,
+                        PrettySize(usage.used_bytes),
+                        PrettySize(usage.allocated_bytes),
+                        PrettySize(capacity),
+                    ),
+                );
+            }
+
+            ui.heading("Pools");
+            let pools = pool_usage(&self.report);
+            if pools.is_empty() {
+                ui.label("No pool chunks");
+            }
+            for (pool, (used_bytes, size_in_bytes)) in pools {
+                usage_bar(
+                    ui,
+                    used_bytes,
+                    size_in_bytes,
+                    format!(
+                        "{}: {} used of {}",
+                        pool,
+                        PrettySize(used_bytes),
+                        PrettySize(size_in_bytes),
+                    ),
+                );
+            }
+        })
+        .response
+    }
+}
+
+// Private API
+// -----------
+
+/// Draw a single bar which is filled to `value / capacity`.
+fn usage_bar(ui: &mut egui::Ui, value: u64, capacity: u64, text: String) {
+    let fraction = if capacity == 0 {
+        0.0
+    } else {
+        (value as f64 / capacity as f64).min(1.0) as f32
+    };
+    ui.add(egui::ProgressBar::new(fraction).text(text));
+}
+
+/// Add up the used and total bytes in each pool's chunks.
+///
+/// # Returns
+///
+/// The `(used_bytes, size_in_bytes)` for each pool, keyed by the pool's
+/// label. Pools are identified by their owner and memory type.
+fn pool_usage(report: &MemoryReport) -> BTreeMap<String, (u64, u64)> {
+    let mut pools = BTreeMap::new();
+    for chunk in &report.chunks {
+        let label = if chunk.owner.is_empty() {
+            format!("Memory type {}", chunk.memory_type_index)
+        } else {
+            format!("{} (memory type {})", chunk.owner, chunk.memory_type_index)
+        };
+        let used_bytes: u64 = chunk
+            .runs
+            .iter()
+            .filter(|run| run.used)
+            .map(|run| run.size_in_bytes)
+            .sum();
+        let (pool_used_bytes, pool_size_in_bytes) =
+            pools.entry(label).or_insert((0, 0));
+        *pool_used_bytes += used_bytes;
+        *pool_size_in_bytes += chunk.size_in_bytes;
+    }
+    pools
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{ChunkReport, MemoryRun},
+    };
+
+    fn chunk(memory_type_index: usize, used_bytes: u64) -> ChunkReport {
+        ChunkReport {
+            owner: String::new(),
+            name: "chunk".to_owned(),
+            memory_id: 0,
+            memory_type_index,
+            offset_in_bytes: 0,
+            size_in_bytes: 1024,
+            runs: vec![
+                MemoryRun {
+                    offset_in_bytes: 0,
+                    size_in_bytes: used_bytes,
+                    used: true,
+                },
+                MemoryRun {
+                    offset_in_bytes: used_bytes,
+                    size_in_bytes: 1024 - used_bytes,
+                    used: false,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_pool_usage_adds_up_chunks() {
+        let mut report = MemoryReport::default();
+        report.add_chunk(chunk(0, 256));
+        report.add_chunk(chunk(0, 512));
+        report
+            .with_owner("Textures", |report| report.add_chunk(chunk(1, 1024)));
+
+        let pools = pool_usage(&report);
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools["Memory type 0"], (768, 2048));
+        assert_eq!(pools["Textures (memory type 1)"], (1024, 1024));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{collections::HashSet, time::Duration};
+
+/// A contiguous range of a chunk which is either entirely used or entirely
+/// free.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryRun {
+    /// The offset of the run from the start of the chunk.
+    pub offset_in_bytes: u64,
+
+    /// The size of the run.
+    pub size_in_bytes: u64,
+
+    /// True when the run is a live suballocation.
+    pub used: bool,
+}
+
+/// The layout of a single chunk of memory owned by a pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChunkReport {
+    /// The names of the named allocators which own the chunk, joined with
+    /// `/`, e.g. `Application Allocator`. Empty when no named allocator
+    /// encloses the pool.
+    pub owner: String,
+
+    /// The chunk's path segment, the same one which appears in
+    /// [crate::Allocation::path].
+    pub name: String,
+
+    /// Identifies the device memory which contains the chunk. Allocations in
+    /// the chunk have the same id, see [AllocationReport::memory_id].
+    pub memory_id: u64,
+
+    /// The memory type the chunk was allocated from.
+    pub memory_type_index: usize,
+
+    /// The offset of the chunk within its device memory.
+    pub offset_in_bytes: u64,
+
+    /// The size of the chunk.
+    pub size_in_bytes: u64,
+
+    /// Every used and free run in the chunk, ordered by offset.
+    pub runs: Vec<MemoryRun>,
+}
+
+/// An allocation which has been given to the application and not freed yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AllocationReport {
+    /// The allocators and chunks the allocation came from, see
+    /// [crate::Allocation::path].
+    pub path: String,
+
+    /// The allocation's name, see [crate::Allocation::name].
+    pub name: Option<String>,
+
+    /// The tag from the allocation's requirements, if any.
+    pub tag: Option<&'static str>,
+
+    /// The allocation's user data, see [crate::Allocation::user_data].
+    pub user_data: u64,
+
+    /// Identifies the device memory which contains the allocation, see
+    /// [ChunkReport::memory_id].
+    pub memory_id: u64,
+
+    /// The memory type the allocation came from.
+    pub memory_type_index: usize,
+
+    /// The offset of the allocation within its device memory.
+    pub offset_in_bytes: u64,
+
+    /// The size of the allocation.
+    pub size_in_bytes: u64,
+
+    /// How long ago the allocation was made.
+    pub age: Duration,
+
+    /// The frame when the allocation was made, see
+    /// [crate::MemoryAllocator::begin_frame].
+    pub frame: Option<u64>,
+}
+
+/// A structured description of every chunk in an allocator composition, see
+/// [crate::MemoryAllocator::generate_report].
+///
+/// Composable allocators add their own chunks in
+/// [crate::ComposableAllocator::report].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryReport {
+    /// Every chunk which was found while walking the composition.
+    pub chunks: Vec<ChunkReport>,
+
+    /// Every allocation which is live in the application, see
+    /// [crate::MemoryAllocator::live_allocations].
+    pub allocations: Vec<AllocationReport>,
+
+    /// The names of the enclosing named allocators.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    owners: Vec<String>,
+
+    /// Shared allocators which have already added their chunks.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    visited: HashSet<usize>,
+}
+
+impl MemoryReport {
+    /// Add the chunks reported by `f` with an extra owner name.
+    ///
+    /// # Params
+    ///
+    /// * name: the name of the allocator which owns the chunks.
+    /// * f: reports the chunks.
+    pub fn with_owner(&mut self, name: &str, f: impl FnOnce(&mut Self)) {
+        self.owners.push(name.to_owned());
+        f(self);
+        self.owners.pop();
+    }
+
+    /// Add a chunk to the report. The chunk's owner is replaced by the
+    /// current owner.
+    pub fn add_chunk(&mut self, chunk: ChunkReport) {
+        self.chunks.push(ChunkReport {
+            owner: self.owners.join("/"),
+            ..chunk
+        });
+    }
+
+    /// Add a live allocation to the report.
+    pub fn add_allocation(&mut self, allocation: AllocationReport) {
+        self.allocations.push(allocation);
+    }
+
+    /// Record that a shared allocator is being visited.
+    ///
+    /// # Returns
+    ///
+    /// True the first time an address is visited.
+    pub(crate) fn visit(&mut self, address: usize) -> bool {
+        self.visited.insert(address)
+    }
+
+    /// Forget the walk state once the report is complete.
+    pub(crate) fn finish(mut self) -> Self {
+        self.owners.clear();
+        self.visited.clear();
+        self
+    }
+}
@@ -1,18 +1,53 @@
 use {
+    super::{IdGenerator, XorShiftRng},
     crate::{
-        Allocation, AllocationId, AllocationRequirements, AllocatorError,
-        ComposableAllocator, PageSuballocator,
+        pretty_wrappers::PrettySize, Allocation, AllocationExtensions,
+        AllocationId, AllocationRequirements, AllocatorError, AllocatorStats,
+        ChunkReport, ComposableAllocator, MemoryReport, PageSuballocator,
+        PoolConfigurator, PoolSettings, ResourceKind,
     },
-    anyhow::anyhow,
-    std::collections::HashMap,
+    anyhow::{anyhow, Context},
+    std::collections::BTreeMap,
 };
 
 pub struct MemoryTypePoolAllocator<Allocator: ComposableAllocator> {
     memory_type_index: usize,
     allocator: Allocator,
     chunk_size: u64,
+
+    /// When set, each new chunk is twice as large as the one before it, up to
+    /// this size.
+    max_chunk_size: Option<u64>,
+
+    /// The most memory the pool's chunks can hold in total.
+    max_pool_bytes: Option<u64>,
+
+    /// The number of empty chunks which are kept for reuse instead of being
+    /// freed right away.
+    max_empty_chunks: usize,
     page_size: u64,
-    pool: HashMap<AllocationId, PageSuballocator>,
+    buffer_image_granularity: u64,
+    /// Ordered so existing chunks are always searched in the same order,
+    /// which keeps placement reproducible run-to-run.
+    pool: BTreeMap<AllocationId, PoolChunk>,
+    chunk_ids: IdGenerator,
+    random_placement: Option<XorShiftRng>,
+}
+
+/// A chunk of memory which is divided into pages for suballocation.
+struct PoolChunk {
+    /// A number which identifies the chunk in allocation paths.
+    index: u64,
+    suballocator: PageSuballocator,
+
+    /// True when the chunk was kept for reuse by
+    /// [MemoryTypePoolAllocator::reset] and nothing has been allocated
+    /// from it since.
+    retained: bool,
+
+    /// The extensions the chunk's memory was allocated with. Only
+    /// compatible allocations can use the chunk.
+    extensions: AllocationExtensions,
 }
 
 impl<Allocator: ComposableAllocator> MemoryTypePoolAllocator<Allocator> {
@@ -40,8 +75,193 @@ impl<Allocator: ComposableAllocator> MemoryTypePoolAllocator<Allocator> {
             memory_type_index,
             allocator,
             chunk_size,
+            max_chunk_size: None,
+            max_pool_bytes: None,
+            max_empty_chunks: 0,
             page_size,
-            pool: HashMap::new(),
+            buffer_image_granularity: 1,
+            pool: BTreeMap::new(),
+            chunk_ids: IdGenerator::sequential(),
+            random_placement: None,
+        }
+    }
+
+    /// Place allocations at random suitable offsets within each chunk. This
+    /// is a testing mode which shakes out code that relies on deterministic
+    /// offsets.
+    ///
+    /// # Params
+    ///
+    /// * seed: the seed used to generate the placement for every chunk.
+    pub fn with_random_placement(self, seed: u64) -> Self {
+        Self {
+            random_placement: Some(XorShiftRng::new(seed)),
+            ..self
+        }
+    }
+
+    /// Keep linear and optimal resources in the same chunk apart by the
+    /// device's bufferImageGranularity. The granularity is 1, e.g. not
+    /// enforced, by default.
+    ///
+    /// # Params
+    ///
+    /// * buffer_image_granularity: from VkPhysicalDeviceLimits.
+    pub fn with_buffer_image_granularity(
+        self,
+        buffer_image_granularity: u64,
+    ) -> Self {
+        Self {
+            buffer_image_granularity,
+            ..self
+        }
+    }
+
+    /// Use a different chunk size for chunks allocated from now on. The page
+    /// size is reduced to the chunk size if it's larger.
+    ///
+    /// # Params
+    ///
+    /// * chunk_size: the size of each new chunk.
+    pub fn with_chunk_size(self, chunk_size: u64) -> Self {
+        let page_size = self.page_size.min(chunk_size);
+        debug_assert!(
+            chunk_size % page_size == 0,
+            "Chunks must be evenly divisible into pages."
+        );
+        Self {
+            chunk_size,
+            page_size,
+            ..self
+        }
+    }
+
+    /// Grow the pool with larger chunks as it fills up. Each new chunk is
+    /// twice as large as the last, up to the maximum, so workloads which
+    /// outgrow the initial chunk size spread across fewer chunks.
+    ///
+    /// Allocations must still be smaller than the initial chunk size.
+    ///
+    /// # Params
+    ///
+    /// * max_chunk_size: the largest chunk size. It's rounded down to a whole
+    ///   number of pages.
+    pub fn with_chunk_growth(self, max_chunk_size: u64) -> Self {
+        Self {
+            max_chunk_size: Some(max_chunk_size),
+            ..self
+        }
+    }
+
+    /// Limit the total size of the pool's chunks. New chunks which would
+    /// exceed the limit fail with [AllocatorError::PoolLimitExceeded], so a
+    /// runaway leak is caught before it consumes the whole heap.
+    ///
+    /// # Params
+    ///
+    /// * max_pool_bytes: the most memory the pool's chunks can hold.
+    pub fn with_max_pool_size(self, max_pool_bytes: u64) -> Self {
+        Self {
+            max_pool_bytes: Some(max_pool_bytes),
+            ..self
+        }
+    }
+
+    /// Keep up to this many empty chunks instead of freeing them right away.
+    /// This avoids freeing and reallocating a chunk over and over when a
+    /// workload oscillates around a chunk boundary. Use
+    /// [ComposableAllocator::trim] to free the empty chunks. No empty chunks
+    /// are kept by default.
+    ///
+    /// # Params
+    ///
+    /// * max_empty_chunks: the most empty chunks to keep.
+    pub fn with_empty_chunk_retention(self, max_empty_chunks: usize) -> Self {
+        Self {
+            max_empty_chunks,
+            ..self
+        }
+    }
+
+    /// The total size of the pool's chunks.
+    pub fn pool_bytes(&self) -> u64 {
+        self.pool
+            .values()
+            .map(|chunk| chunk.suballocator.allocation().size_in_bytes())
+            .sum()
+    }
+
+    /// The size of each new chunk.
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size
+    }
+
+    /// The settings which control how new chunks are created.
+    pub fn settings(&self) -> PoolSettings {
+        PoolSettings {
+            chunk_size: self.chunk_size,
+            page_size: self.page_size,
+            max_chunk_size: self.max_chunk_size,
+            max_pool_bytes: self.max_pool_bytes,
+            max_empty_chunks: self.max_empty_chunks,
+        }
+    }
+
+    /// Change the settings of a live pool. Chunks which already exist keep
+    /// their size and page size, the new settings apply to chunks created
+    /// from now on.
+    ///
+    /// # Params
+    ///
+    /// * settings: the new settings. The chunk size must be a multiple of the
+    ///   page size.
+    pub fn set_settings(
+        &mut self,
+        settings: PoolSettings,
+    ) -> Result<(), AllocatorError> {
+        settings.validate()?;
+        self.chunk_size = settings.chunk_size;
+        self.page_size = settings.page_size;
+        self.max_chunk_size = settings.max_chunk_size;
+        self.max_pool_bytes = settings.max_pool_bytes;
+        self.max_empty_chunks = settings.max_empty_chunks;
+        Ok(())
+    }
+
+    /// Returns true when the allocation was suballocated from one of the
+    /// pool's chunks.
+    pub(crate) fn owns(&self, allocation: &Allocation) -> bool {
+        // SAFE because the id is only compared with the pool's live chunks.
+        let parent_id = unsafe { allocation.parent_id() };
+        parent_id.is_some_and(|parent_id| self.pool.contains_key(&parent_id))
+    }
+
+    /// Use a custom generator for the chunk ids in allocation paths. Chunk ids
+    /// are sequential by default.
+    ///
+    /// # Params
+    ///
+    /// * chunk_ids: generates the id for each new chunk.
+    pub fn with_chunk_ids(self, chunk_ids: IdGenerator) -> Self {
+        Self { chunk_ids, ..self }
+    }
+
+    /// Invalidate every allocation from this pool at once.
+    ///
+    /// Chunks are kept and reused by later allocations rather than being
+    /// returned to the backing allocator. This is much faster than freeing
+    /// each allocation individually, e.g. when unloading a level.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must ensure that no allocations from this pool are
+    ///     in use by the GPU
+    ///   - allocations from this pool must not be freed after the reset
+    pub unsafe fn reset(&mut self) {
+        for chunk in self.pool.values_mut() {
+            chunk.suballocator.reset();
+            chunk.retained = true;
         }
     }
 }
@@ -67,33 +287,38 @@ impl<Allocator: ComposableAllocator> ComposableAllocator
         }
 
         // Attempt to allocate from an existing chunk
-        for suballocator in self.pool.values_mut() {
-            if let Ok(allocation) = suballocator.allocate(
+        for chunk in self.pool.values_mut() {
+            if !allocation_requirements
+                .extensions
+                .can_use_memory_from(&chunk.extensions)
+            {
+                continue;
+            }
+            if let Ok(mut allocation) = chunk.suballocator.allocate_resource(
                 allocation_requirements.size_in_bytes,
                 allocation_requirements.alignment,
+                allocation_requirements.resource_kind,
             ) {
+                allocation.prepend_path_segment(&chunk_path_segment(
+                    self.memory_type_index,
+                    chunk.index,
+                ));
+                chunk.retained = false;
                 return Ok(allocation);
             }
         }
 
         // Unable to allocate from an existing chunk, so create a new chunk
         // and allocate from it.
-        let chunk_requirements = AllocationRequirements {
-            alignment: 1,
-            size_in_bytes: self.chunk_size,
-            memory_type_index: self.memory_type_index,
-            ..allocation_requirements
-        };
-        let chunk_allocation = self.allocator.allocate(chunk_requirements)?;
-        let chunk_allocation_id = chunk_allocation.id();
-        let mut suballocator =
-            PageSuballocator::for_allocation(chunk_allocation, self.page_size);
+        let mut suballocator = self.allocate_chunk(allocation_requirements)?;
+        let chunk_allocation_id = suballocator.allocation().id();
 
         // Allocate using the newly created suballocator. Remember to
         // free the chunk if something goes wrong at this point.
-        let allocation = match suballocator.allocate(
+        let mut allocation = match suballocator.allocate_resource(
             allocation_requirements.size_in_bytes,
             allocation_requirements.alignment,
+            allocation_requirements.resource_kind,
         ) {
             Ok(allocation) => allocation,
             Err(err) => {
@@ -103,8 +328,15 @@ impl<Allocator: ComposableAllocator> ComposableAllocator
         };
 
         debug_assert!(allocation.parent_id().unwrap() == chunk_allocation_id);
-        debug_assert!(!self.pool.contains_key(&chunk_allocation_id));
-        self.pool.insert(chunk_allocation_id, suballocator);
+        let index = self.insert_chunk(
+            suballocator,
+            false,
+            allocation_requirements.extensions,
+        );
+        allocation.prepend_path_segment(&chunk_path_segment(
+            self.memory_type_index,
+            index,
+        ));
 
         Ok(allocation)
     }
@@ -120,13 +352,227 @@ impl<Allocator: ComposableAllocator> ComposableAllocator
         );
 
         let key = allocation.parent_id().unwrap();
-        let suballocator = self.pool.get_mut(&key).unwrap();
+        let suballocator = &mut self.pool.get_mut(&key).unwrap().suballocator;
         suballocator.free(allocation);
+        if !suballocator.is_empty() {
+            return;
+        }
 
-        if suballocator.is_empty() {
-            let chunk_mem =
-                self.pool.remove(&key).unwrap().release_allocation();
+        let empty_chunks = self
+            .pool
+            .values()
+            .filter(|chunk| chunk.suballocator.is_empty())
+            .count();
+        if empty_chunks <= self.max_empty_chunks {
+            self.pool.get_mut(&key).unwrap().retained = true;
+        } else {
+            let chunk_mem = self
+                .pool
+                .remove(&key)
+                .unwrap()
+                .suballocator
+                .release_allocation();
             self.allocator.free(chunk_mem);
         }
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        for chunk in self.pool.values() {
+            if chunk.suballocator.is_empty() && !chunk.retained {
+                return Err(AllocatorError::RuntimeError(anyhow!(
+                    "{} is holding an empty chunk",
+                    chunk_path_segment(self.memory_type_index, chunk.index)
+                )));
+            }
+            chunk.suballocator.validate().with_context(|| {
+                format!(
+                    "Invalid chunk {}",
+                    chunk_path_segment(self.memory_type_index, chunk.index)
+                )
+            })?;
+        }
+        self.allocator.validate()
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        let usage = stats.memory_type_mut(self.memory_type_index);
+        usage.chunk_count += self.pool.len();
+        for chunk in self.pool.values() {
+            usage.largest_free_block = usage
+                .largest_free_block
+                .max(chunk.suballocator.largest_free_block());
+        }
+        self.allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        for chunk in self.pool.values() {
+            let allocation = chunk.suballocator.allocation();
+            report.add_chunk(ChunkReport {
+                owner: String::new(),
+                name: chunk_path_segment(self.memory_type_index, chunk.index),
+                memory_id: allocation.memory_id(),
+                memory_type_index: self.memory_type_index,
+                offset_in_bytes: allocation.offset_in_bytes(),
+                size_in_bytes: allocation.size_in_bytes(),
+                runs: chunk.suballocator.runs(),
+            });
+        }
+        self.allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        let empty_chunks = self
+            .pool
+            .iter()
+            .filter(|(_, chunk)| chunk.suballocator.is_empty())
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        for key in empty_chunks {
+            let chunk = self.pool.remove(&key).unwrap();
+            self.allocator.free(chunk.suballocator.release_allocation());
+        }
+        self.allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        let settings = configurator.apply(self.settings())?;
+        self.set_settings(settings)?;
+        self.allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        if self.memory_type_index != allocation_requirements.memory_type_index {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Memory type index mismatch"
+            )));
+        }
+
+        let extensions = allocation_requirements.extensions;
+        let mut free_bytes: u64 = self
+            .pool
+            .values()
+            .filter(|chunk| extensions.can_use_memory_from(&chunk.extensions))
+            .map(|chunk| chunk.suballocator.free_bytes())
+            .sum();
+        while free_bytes < allocation_requirements.size_in_bytes {
+            let suballocator = self.allocate_chunk(allocation_requirements)?;
+            free_bytes += suballocator.free_bytes();
+            self.insert_chunk(suballocator, true, extensions);
+        }
+        Ok(())
+    }
+}
+
+// Private API
+// -----------
+
+impl<Allocator: ComposableAllocator> MemoryTypePoolAllocator<Allocator> {
+    /// Allocate a new chunk from the backing allocator.
+    ///
+    /// # Params
+    ///
+    /// * allocation_requirements: the requirements of the allocation which
+    ///   needs the chunk. The chunk uses the same memory properties and
+    ///   extensions.
+    unsafe fn allocate_chunk(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<PageSuballocator, AllocatorError> {
+        let chunk_size = self.next_chunk_size();
+        if let Some(max_pool_bytes) = self.max_pool_bytes {
+            let pool_bytes = self.pool_bytes();
+            if pool_bytes + chunk_size > max_pool_bytes {
+                return Err(AllocatorError::PoolLimitExceeded {
+                    memory_type_index: self.memory_type_index,
+                    chunk_size,
+                    pool_bytes,
+                    max_pool_bytes,
+                });
+            }
+        }
+
+        // Chunks are shared by every tag and resource kind. Aligning chunks
+        // to the granularity keeps their edges apart from neighbors when the
+        // chunk is itself suballocated.
+        let chunk_requirements = AllocationRequirements {
+            alignment: self.buffer_image_granularity.max(1),
+            size_in_bytes: chunk_size,
+            memory_type_index: self.memory_type_index,
+            tag: None,
+            resource_kind: ResourceKind::Unknown,
+            ..allocation_requirements
+        };
+        let chunk_allocation = self.allocator.allocate(chunk_requirements)?;
+        let mut suballocator =
+            PageSuballocator::for_allocation(chunk_allocation, self.page_size)
+                .with_buffer_image_granularity(self.buffer_image_granularity);
+        if let Some(rng) = self.random_placement.as_mut() {
+            suballocator = suballocator.with_random_placement(rng.next_u64());
+        }
+        Ok(suballocator)
+    }
+
+    /// The size of the next chunk. Without a growth policy this is always
+    /// the chunk size, otherwise it doubles for each chunk in the pool.
+    fn next_chunk_size(&self) -> u64 {
+        let max_chunk_size = match self.max_chunk_size {
+            Some(max_chunk_size) => max_chunk_size,
+            None => return self.chunk_size,
+        };
+        let max_chunk_size = max_chunk_size - max_chunk_size % self.page_size;
+        let doublings = self.pool.len().min(32) as u32;
+        self.chunk_size
+            .saturating_mul(1 << doublings)
+            .min(max_chunk_size)
+            .max(self.chunk_size)
+    }
+
+    /// Add a chunk to the pool.
+    ///
+    /// # Returns
+    ///
+    /// The chunk's index for allocation paths.
+    unsafe fn insert_chunk(
+        &mut self,
+        suballocator: PageSuballocator,
+        retained: bool,
+        extensions: AllocationExtensions,
+    ) -> u64 {
+        let chunk_allocation_id = suballocator.allocation().id();
+        debug_assert!(!self.pool.contains_key(&chunk_allocation_id));
+        let index = self.chunk_ids.next_id();
+
+        // Name the chunk's memory for debugging tools, see
+        // DeviceAllocator::with_debug_names.
+        let mut named_chunk = suballocator.allocation().clone();
+        named_chunk.set_name(&format!(
+            "pool chunk {} #{} type {}",
+            PrettySize(named_chunk.size_in_bytes()),
+            index,
+            self.memory_type_index
+        ));
+        self.allocator.name_allocation(&named_chunk);
+        self.pool.insert(
+            chunk_allocation_id,
+            PoolChunk {
+                index,
+                suballocator,
+                retained,
+                extensions,
+            },
+        );
+        index
+    }
+}
+
+/// The path segment for allocations from a chunk in a pool.
+fn chunk_path_segment(memory_type_index: usize, chunk_index: u64) -> String {
+    format!("Pool[type {}]/chunk {}", memory_type_index, chunk_index)
 }
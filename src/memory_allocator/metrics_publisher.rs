@@ -0,0 +1,63 @@
+use crate::{AllocatorStats, MemoryUsage};
+
+/// Publish a snapshot of the allocator's stats as gauges through the
+/// `metrics` facade, so whichever exporter the application installed, e.g.
+/// Prometheus or StatsD, picks them up.
+///
+/// Every gauge is named `ccthw_ash_allocator_<counter>`. Per-heap gauges
+/// have a `heap` label, the totals have none.
+///
+/// # Params
+///
+/// * stats: the allocator's stats, see [crate::MemoryAllocator::stats].
+pub(crate) fn publish_stats(stats: &AllocatorStats) {
+    for (heap_index, usage) in stats.memory_heaps.iter().enumerate() {
+        let heap = heap_index.to_string();
+        publish_usage(usage, Some(&heap));
+        let budget = stats
+            .heap_budgets
+            .as_ref()
+            .and_then(|budgets| budgets.get(heap_index));
+        if let Some(budget) = budget {
+            metrics::gauge!(
+                "ccthw_ash_allocator_heap_budget_bytes",
+                "heap" => heap.clone()
+            )
+            .set(budget.budget_bytes as f64);
+            metrics::gauge!(
+                "ccthw_ash_allocator_heap_usage_bytes",
+                "heap" => heap.clone()
+            )
+            .set(budget.usage_bytes as f64);
+        }
+    }
+    publish_usage(&stats.total, None);
+}
+
+// Private API
+// -----------
+
+/// Publish the counters for a single heap, or the totals when there's no
+/// heap.
+fn publish_usage(usage: &MemoryUsage, heap: Option<&str>) {
+    let gauges = [
+        ("ccthw_ash_allocator_allocated_bytes", usage.allocated_bytes),
+        ("ccthw_ash_allocator_used_bytes", usage.used_bytes),
+        (
+            "ccthw_ash_allocator_live_allocations",
+            usage.allocation_count as u64,
+        ),
+        (
+            "ccthw_ash_allocator_device_allocations",
+            usage.device_allocation_count as u64,
+        ),
+        ("ccthw_ash_allocator_chunks", usage.chunk_count as u64),
+    ];
+    for (name, value) in gauges {
+        match heap {
+            Some(heap) => metrics::gauge!(name, "heap" => heap.to_owned())
+                .set(value as f64),
+            None => metrics::gauge!(name).set(value as f64),
+        }
+    }
+}
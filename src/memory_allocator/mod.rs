@@ -1,33 +1,125 @@
+mod alignment_audit_allocator;
+mod allocator_stats;
+mod annotating_allocator;
+mod budget_allocator;
+mod canary_allocator;
 mod composable_allocator;
+mod debug_names;
 mod dedicated_allocator;
+mod deferred_free;
 mod device_allocator;
+mod external_memory_importer;
+mod failing_allocator;
 mod fake_allocator;
+mod fallback_allocator;
+mod frame_budget_allocator;
+mod frame_clock;
+mod handle_options;
+mod heap_usage_allocator;
+mod histogram;
+mod host_memory_importer;
+mod id_generator;
+#[cfg(feature = "egui")]
+mod memory_panel;
+mod memory_report;
 mod memory_type_pool_allocator;
+#[cfg(feature = "metrics")]
+mod metrics_publisher;
+mod named_allocator;
 mod page_suballocator;
+mod pageable_memory;
+mod persistent_mapping;
 mod pool_allocator;
+mod pool_handle;
+mod pool_settings;
+mod quarantine_allocator;
+mod resource_cache;
+mod retire_queue;
 mod sized_allocator;
+mod soak_test_allocator;
+mod tiling_allocator;
 mod trace_allocator;
+mod upload_path;
+mod usage_sampler;
+mod validation_allocator;
+mod virtual_block;
+mod visualization;
+mod vma_json;
+mod xorshift;
 
 use {
+    self::{
+        allocator_stats::UsageTracker,
+        deferred_free::{PendingFree, PendingResource},
+        external_memory_importer::ExternalMemoryImporter,
+        frame_clock::PolicyClock,
+        handle_options::HandleOptions,
+        host_memory_importer::HostMemoryImporter,
+        pageable_memory::PageableMemory,
+        persistent_mapping::PersistentMapping,
+        resource_cache::{BufferKey, ImageKey, ResourceCache},
+        retire_queue::RetireQueue,
+        xorshift::XorShiftRng,
+    },
     crate::{
         allocation::Allocation, AllocationRequirements, AllocatorError,
-        MemoryProperties,
+        HeapBudget, MappedMemory, MemoryProperties, ResourceKind,
+        SystemAllocatorBuilder, SystemAllocatorConfig, WriteOnlyMemory,
     },
-    anyhow::Context,
+    anyhow::{anyhow, Context},
     ash::vk,
-    std::sync::{Arc, Mutex},
+    indoc::indoc,
+    std::{
+        collections::BTreeMap,
+        panic::Location,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    },
 };
 
+#[cfg(feature = "egui")]
+pub use self::memory_panel::MemoryPanel;
 pub use self::{
+    alignment_audit_allocator::{
+        AlignmentAuditAllocator, AlignmentLimits, AlignmentViolation,
+    },
+    allocator_stats::{AllocatorStats, FrameUsage, MemoryUsage},
+    annotating_allocator::AnnotatingAllocator,
+    budget_allocator::{BudgetAllocator, BudgetForecast, BudgetTarget},
+    canary_allocator::CanaryAllocator,
     composable_allocator::{into_shared, ComposableAllocator},
     dedicated_allocator::DedicatedAllocator,
+    deferred_free::GpuCompletion,
     device_allocator::DeviceAllocator,
+    failing_allocator::{FailingAllocator, FailureMode},
     fake_allocator::FakeAllocator,
+    fallback_allocator::FallbackAllocator,
+    frame_budget_allocator::{FrameBudget, FrameBudgetAllocator},
+    frame_clock::FrameClock,
+    heap_usage_allocator::{
+        HeapUsageAllocator, HeapUsageHook, HeapUsageWarning,
+    },
+    histogram::Histogram,
+    id_generator::IdGenerator,
+    memory_report::{AllocationReport, ChunkReport, MemoryReport, MemoryRun},
     memory_type_pool_allocator::MemoryTypePoolAllocator,
+    named_allocator::NamedAllocator,
     page_suballocator::PageSuballocator,
     pool_allocator::PoolAllocator,
+    pool_handle::PoolHandle,
+    pool_settings::{PoolConfigurator, PoolSettings},
+    quarantine_allocator::{QuarantineAllocator, QuarantinePolicy},
     sized_allocator::SizedAllocator,
-    trace_allocator::TraceAllocator,
+    soak_test_allocator::{SoakTestAllocator, SoakTestFailureHook},
+    tiling_allocator::TilingAllocator,
+    trace_allocator::{TraceAllocator, TraceMetrics, TraceReport},
+    upload_path::UploadPath,
+    usage_sampler::{UsageSample, UsageSampler},
+    validation_allocator::ValidationAllocator,
+    virtual_block::{VirtualAllocation, VirtualBlock},
+    visualization::VisualizationFormat,
 };
 
 /// The top-level interface for allocating GPU memory.
@@ -35,12 +127,49 @@ pub use self::{
 /// The memory allocator owns a composable allocator instance which actually
 /// does the work of memory allocation. This allows the behavior to be
 /// customized by composing allocators.
+///
+/// Every method takes `&self`, so the allocator can be shared between threads
+/// without an external lock.
+///
+/// Cloning is cheap because all state is reference counted. Clones are handles
+/// to the same allocator, so subsystems can each hold their own.
+///
+/// # Frames
+///
+/// The allocator keeps a current frame which labels allocations, deferred
+/// frees, and retired resources, and which ages cached resources. Either:
+///
+/// - call [Self::begin_frame] with the application's frame number at the start
+///   of each frame and [Self::end_frame] at the end, or
+/// - count with a [FrameClock] which the application advances, see
+///   [Self::with_clock]. The allocator never changes a shared clock, so
+///   [Self::begin_frame] is ignored and [Self::end_frame] only does the
+///   per-frame work.
+///
+/// [Self::advance_frame] doesn't change the current frame. It takes the most
+/// recent frame which the GPU has completed, which trails the current frame
+/// by the number of frames in flight, and frees the resources which were
+/// retired in or before it.
 #[derive(Clone)]
 pub struct MemoryAllocator {
     internal_allocator:
         Arc<Mutex<Box<dyn ComposableAllocator + 'static + Send>>>,
-    memory_properties: MemoryProperties,
-    device: ash::Device,
+    memory_properties: Arc<MemoryProperties>,
+    instance: Arc<ash::Instance>,
+    physical_device: vk::PhysicalDevice,
+    device: Arc<ash::Device>,
+    frozen: Arc<AtomicBool>,
+    device_lost: Arc<AtomicBool>,
+    host_memory_importer: Arc<HostMemoryImporter>,
+    external_memory_importer: Arc<ExternalMemoryImporter>,
+    pageable_memory: Arc<PageableMemory>,
+    pending_frees: Arc<Mutex<Vec<PendingFree>>>,
+    retire_queue: Arc<Mutex<RetireQueue>>,
+    resource_cache: Option<Arc<Mutex<ResourceCache>>>,
+    usage_sampler: Option<Arc<Mutex<UsageSampler>>>,
+    clock: PolicyClock,
+    options: HandleOptions,
+    upload_path: UploadPath,
 }
 
 impl MemoryAllocator {
@@ -76,209 +205,3153 @@ impl MemoryAllocator {
         );
         Self {
             internal_allocator: Arc::new(Mutex::new(Box::new(
-                internal_allocator,
+                UsageTracker::new(PersistentMapping::new(
+                    device.clone(),
+                    internal_allocator,
+                )),
             ))),
-            memory_properties,
-            device,
+            memory_properties: Arc::new(memory_properties),
+            host_memory_importer: Arc::new(HostMemoryImporter::new(
+                instance,
+                device.clone(),
+                physical_device,
+            )),
+            external_memory_importer: Arc::new(ExternalMemoryImporter::new(
+                instance,
+                device.clone(),
+            )),
+            pageable_memory: Arc::new(PageableMemory::new(
+                instance,
+                device.clone(),
+            )),
+            instance: Arc::new(instance.clone()),
+            physical_device,
+            device: Arc::new(device),
+            frozen: Arc::new(AtomicBool::new(false)),
+            device_lost: Arc::new(AtomicBool::new(false)),
+            pending_frees: Arc::new(Mutex::new(vec![])),
+            retire_queue: Arc::new(Mutex::new(RetireQueue::default())),
+            resource_cache: None,
+            usage_sampler: None,
+            clock: PolicyClock::default(),
+            options: HandleOptions::default(),
+            upload_path: UploadPath::default(),
         }
     }
 
-    /// Allocate a buffer and memory.
+    /// Create a system allocator from a config file, see
+    /// [crate::SystemAllocatorConfig::parse] for the format.
+    ///
+    /// This makes it possible to tune the pool hierarchy without recompiling.
+    /// The config tunes the fixed hierarchy built by
+    /// [crate::SystemAllocatorBuilder]: tier sizes, the dedicated threshold,
+    /// host-visible chunk sizes, and tracing. It can't describe other
+    /// compositions, build those in code with [Self::new].
     ///
     /// # Params
     ///
-    /// - `buffer_create_info` - used to create the Buffer and determine what
-    ///   memory it needs
-    /// - `memory_property_flags` - used to pick the correct memory type for the
-    ///   buffer's memory
+    /// * `config` - the text of the config file
+    /// * `instance` - used to query the physical device's memory properties
+    /// * `device` - the logical device used to allocate memory
+    /// * `physical_device` - the physical device backing the logical device
     ///
     /// # Returns
     ///
-    /// A tuple of `(vk::buffer, Allocation)` which contains the raw vulkan
-    /// buffer and the backing memory Allocation.
-    ///
-    /// The buffer is already bound to the memory in the allocation so the
-    /// buffer is ready to use immediately.
+    /// An error when the config can't be parsed or its sizes can't be used to
+    /// build the allocator, see [crate::SystemAllocatorConfig::validate].
     ///
     /// # Safety
     ///
     /// Unsafe because:
-    ///   - the buffer and memory must be freed before the device is destroyed
-    pub unsafe fn allocate_buffer(
-        &mut self,
-        buffer_create_info: &vk::BufferCreateInfo,
-        memory_property_flags: vk::MemoryPropertyFlags,
-    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
-        let buffer = unsafe {
-            self.device
-                .create_buffer(buffer_create_info, None)
-                .with_context(|| {
-                    format!(
-                        "Error creating a buffer with {:#?}",
-                        buffer_create_info
-                    )
-                })?
-        };
+    ///  - the logical device must not be destroyed while the MemoryAllocator is
+    ///    still in use
+    ///  - the application must free any memory it allocates prior to dropping
+    ///    the memory allocator or device
+    pub unsafe fn from_config(
+        config: &str,
+        instance: &ash::Instance,
+        device: ash::Device,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<Self, AllocatorError> {
+        let config = SystemAllocatorConfig::parse(config)?;
+        Ok(SystemAllocatorBuilder::from_config(config).build(
+            instance,
+            device,
+            physical_device,
+        ))
+    }
 
-        let requirements = {
-            let result = AllocationRequirements::for_buffer(
-                &self.device,
-                self.memory_properties.types(),
-                memory_property_flags,
-                buffer,
-            );
-            if result.is_err() {
-                self.device.destroy_buffer(buffer, None);
-            }
-            result?
-        };
+    /// Get a handle to this allocator which tags every allocation it makes.
+    ///
+    /// The handle shares all state with this allocator, the same as a clone.
+    /// Tags group allocations for accounting, e.g. with
+    /// [crate::BudgetTarget::Tag]. Requirements which already have a tag keep
+    /// it.
+    ///
+    /// # Params
+    ///
+    /// - `tag` - the tag for allocations made with the handle, e.g.
+    ///   `"textures"`
+    pub fn tagged(&self, tag: &'static str) -> Self {
+        Self {
+            options: HandleOptions {
+                tag: Some(tag),
+                ..self.options
+            },
+            ..self.clone()
+        }
+    }
 
-        let allocation = {
-            let result = unsafe {
-                self.internal_allocator
-                    .lock()
-                    .unwrap()
-                    .allocate(requirements)
-            };
-            if result.is_err() {
-                self.device.destroy_buffer(buffer, None);
-            }
-            result?
-        };
+    /// Get a handle to this allocator which allocates memory on a subset of
+    /// the physical devices in a device group.
+    ///
+    /// The handle shares all state with this allocator, the same as a clone.
+    /// Memory is allocated with VkMemoryAllocateFlagsInfo and the mask, and
+    /// pools keep a separate set of chunks for each mask. Requirements which
+    /// already have a device mask keep it.
+    ///
+    /// # Params
+    ///
+    /// - `device_mask` - the physical devices which get a copy of each
+    ///   allocation, e.g. `0b01` for the first device in the group
+    pub fn for_devices(&self, device_mask: u32) -> Self {
+        Self {
+            options: HandleOptions {
+                device_mask: Some(device_mask),
+                ..self.options
+            },
+            ..self.clone()
+        }
+    }
 
-        unsafe {
-            let result = self
-                .device
-                .bind_buffer_memory(
-                    buffer,
-                    allocation.memory(),
-                    allocation.offset_in_bytes(),
-                )
-                .context("Error binding buffer memory");
-            if result.is_err() {
-                self.device.destroy_buffer(buffer, None);
-            }
-            result?;
+    /// Count frames with a clock which is advanced by the application,
+    /// rather than by [Self::begin_frame] and [Self::end_frame]. See
+    /// [Frames](Self#frames).
+    pub fn with_clock(self, clock: FrameClock) -> Self {
+        Self {
+            clock: PolicyClock::shared(clock),
+            ..self
         }
+    }
 
-        Ok((buffer, allocation))
+    /// Choose how buffers with initial data are uploaded. The default,
+    /// [UploadPath::Auto], writes straight into device-local memory on
+    /// devices with resizable BAR or unified memory and stages otherwise, so
+    /// the same application code is optimal on integrated and discrete GPUs.
+    ///
+    /// # Params
+    ///
+    /// - `upload_path` - used by [Self::allocate_buffer_with_data]
+    pub fn with_upload_path(self, upload_path: UploadPath) -> Self {
+        Self {
+            upload_path,
+            ..self
+        }
     }
 
-    /// Allocate an Image and memory.
+    /// Reject allocations larger than this size and split buffers at this
+    /// size, even if the device's maxMemoryAllocationSize is larger. This is
+    /// primarily used to test [Self::allocate_split_buffer] without
+    /// allocating gigabytes of memory.
     ///
     /// # Params
     ///
-    /// - `image_create_info` - used to create the Buffer and determine what
-    ///   memory it needs
-    /// - `memory_property_flags` - used to pick the correct memory type for the
-    ///   buffer's memory
+    /// - `max_memory_allocation_size` - the largest size for a single
+    ///   allocation
+    pub fn with_max_memory_allocation_size(
+        self,
+        max_memory_allocation_size: u64,
+    ) -> Self {
+        let memory_properties = (*self.memory_properties)
+            .clone()
+            .with_max_memory_allocation_size(max_memory_allocation_size);
+        Self {
+            memory_properties: Arc::new(memory_properties),
+            ..self
+        }
+    }
+
+    /// Keep freed buffers and images alive so they can be reused.
     ///
-    /// # Returns
+    /// Freed resources are retained along with their memory. Later calls to
+    /// [Self::allocate_buffer] or [Self::allocate_image] with identical
+    /// create infos and memory properties reuse them instead of creating new
+    /// ones. This eliminates create/destroy churn for per-frame resources,
+    /// e.g. shadow maps with dynamic resolution.
     ///
-    /// A tuple of `(vk::Image, Allocation)` which contains the raw Vulkan
-    /// image and the backing memory Allocation.
+    /// Create infos with extension structs in their p_next chain are never
+    /// cached. Resources are only reused by handles with the same tag and
+    /// device mask, see [Self::tagged] and [Self::for_devices].
     ///
-    /// The image is already bound to the memory in the allocation so the
-    /// image is ready to use immediately.
+    /// # Params
+    ///
+    /// - `retained_frames` - how many calls to [Self::end_frame] a freed
+    ///   resource survives before it is really destroyed
+    pub fn with_resource_cache(self, retained_frames: u64) -> Self {
+        Self {
+            resource_cache: Some(Arc::new(Mutex::new(ResourceCache::new(
+                retained_frames,
+            )))),
+            ..self
+        }
+    }
+
+    /// Record memory usage at the end of every frame so it can be exported
+    /// as a time series, see [Self::usage_csv].
+    ///
+    /// # Params
+    ///
+    /// - `max_samples` - the number of frames to keep. Older samples are
+    ///   dropped.
+    pub fn with_usage_sampler(self, max_samples: usize) -> Self {
+        Self {
+            usage_sampler: Some(Arc::new(Mutex::new(UsageSampler::new(
+                max_samples,
+            )))),
+            ..self
+        }
+    }
+
+    /// Mark the start of a frame.
+    ///
+    /// Allocations made after this are labeled with the frame, so stats,
+    /// live allocation lists, and trace output can be grouped by frame, see
+    /// [AllocatorStats::frames]. Deferred frees and retired resources are
+    /// labeled with the frame they were freed in, see
+    /// [Self::pending_frees_by_frame]. Does nothing when the clock is
+    /// shared, see [Self::with_clock] and [Frames](Self#frames).
+    ///
+    /// # Params
+    ///
+    /// - `index` - the application's frame number
+    pub fn begin_frame(&self, index: u64) {
+        self.clock.begin_frame(index);
+    }
+
+    /// Mark the end of a frame.
+    ///
+    /// Resources which have been in the resource cache for too long are
+    /// destroyed. See [Self::with_resource_cache]. Usage is sampled when the
+    /// usage sampler is enabled, see [Self::with_usage_sampler]. The clock is
+    /// only advanced when it isn't shared, see [Frames](Self#frames).
     ///
     /// # Safety
     ///
     /// Unsafe because:
-    ///   - the image and memory must be freed before the device is destroyed
-    pub unsafe fn allocate_image(
-        &mut self,
-        image_create_info: &vk::ImageCreateInfo,
-        memory_property_flags: vk::MemoryPropertyFlags,
-    ) -> Result<(vk::Image, Allocation), AllocatorError> {
-        let image = unsafe {
-            self.device
-                .create_image(image_create_info, None)
-                .with_context(|| {
-                    format!(
-                        "Error creating a image with {:#?}",
-                        image_create_info
-                    )
-                })?
+    ///   - the application must synchronize access to the cached resources
+    pub unsafe fn end_frame(&self) {
+        self.sample_usage();
+        self.clock.end_frame();
+        let cache = match &self.resource_cache {
+            Some(cache) => cache,
+            None => return,
+        };
+        let frame = self.clock.frame();
+        let (buffers, images) = {
+            let mut cache = cache.lock().unwrap();
+            (cache.buffers.expire(frame), cache.images.expire(frame))
         };
+        self.destroy_cached_resources(buffers, images);
+    }
 
-        let requirements = {
-            let result = AllocationRequirements::for_image(
-                &self.device,
-                self.memory_properties.types(),
-                memory_property_flags,
-                image,
-            );
-            if result.is_err() {
-                self.device.destroy_image(image, None);
-            }
-            result?
+    /// Destroy every resource in the resource cache.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - this must be called before the device is destroyed when the resource
+    ///     cache is enabled
+    pub unsafe fn clear_resource_cache(&self) {
+        let cache = match &self.resource_cache {
+            Some(cache) => cache,
+            None => return,
         };
+        let (buffers, images) = {
+            let mut cache = cache.lock().unwrap();
+            (cache.buffers.drain(), cache.images.drain())
+        };
+        self.destroy_cached_resources(buffers, images);
+    }
 
-        let allocation = {
-            let result = unsafe {
-                self.internal_allocator
-                    .lock()
-                    .unwrap()
-                    .allocate(requirements)
-            };
-            if result.is_err() {
-                self.device.destroy_image(image, None);
-            }
-            result?
+    /// Block all new allocations until [Self::thaw] is called.
+    ///
+    /// While frozen, every method which allocates memory returns
+    /// [AllocatorError::Frozen]. Frees, mapping, and reports still work. This
+    /// is useful while recovering from a lost device or recreating the
+    /// swapchain, where an accidental allocation indicates a bug.
+    ///
+    /// The frozen state is shared by every clone of this allocator.
+    pub fn freeze(&self) {
+        self.frozen.store(true, Ordering::SeqCst);
+    }
+
+    /// Allow new allocations again after a call to [Self::freeze].
+    pub fn thaw(&self) {
+        self.frozen.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns true when the allocator is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::SeqCst)
+    }
+
+    /// Returns true once any allocator call has observed a lost device.
+    ///
+    /// After that point every method which allocates memory fails fast with
+    /// [AllocatorError::DeviceLost]. Frees and [Self::emergency_dump] still
+    /// work so the application can tear everything down and recover.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::SeqCst)
+    }
+
+    /// Describe the allocator's state without touching the device.
+    ///
+    /// This is safe to call after the device is lost, or after a panic while
+    /// the internal allocator was locked.
+    pub fn emergency_dump(&self) -> String {
+        let internal_allocator = match self.internal_allocator.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
         };
+        let validation = match internal_allocator.validate() {
+            Ok(()) => "ok".to_owned(),
+            Err(err) => format!("{:?}", err),
+        };
+        format!(
+            indoc!(
+                "
+                # Memory Allocator Emergency Dump
 
-        unsafe {
-            let result = self
-                .device
-                .bind_image_memory(
-                    image,
-                    allocation.memory(),
-                    allocation.offset_in_bytes(),
-                )
-                .context("Error image buffer memory");
-            if result.is_err() {
-                self.device.destroy_image(image, None);
-            }
-            result?;
-        }
+                device lost: {}
+                frozen: {}
+                pending deferred frees: {}
+                validation: {}
 
-        Ok((image, allocation))
+                {}
+                "
+            ),
+            self.is_device_lost(),
+            self.is_frozen(),
+            self.pending_frees
+                .lock()
+                .map(|pending| pending.len())
+                .unwrap_or_default(),
+            validation,
+            self.memory_properties,
+        )
     }
 
-    /// Free a buffer and the associated allocated memory.
+    /// Allocate memory for a resource created elsewhere.
+    ///
+    /// Use [AllocationRequirements::for_buffer] or
+    /// [AllocationRequirements::for_image] to get the requirements, or build
+    /// them by hand for resources like acceleration structures. Binding the
+    /// memory is up to the application.
+    ///
+    /// # Params
+    ///
+    /// - `allocation_requirements` - the size, alignment, and memory type
+    ///   needed by the resource
     ///
     /// # Safety
     ///
     /// Unsafe because:
-    ///   - the application must synchronize access to the buffer and its memory
-    ///   - it is an error to free a buffer while ongoing GPU operations still
+    ///   - the memory must be freed with [Self::free] before the device is
+    ///     destroyed
+    #[track_caller]
+    pub unsafe fn allocate(
+        &self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.check_can_allocate()?;
+        self.check_allocation_size(&allocation_requirements)?;
+        let result = self
+            .internal_allocator
+            .lock()
+            .unwrap()
+            .allocate(self.apply_handle_options(allocation_requirements));
+        self.record_device_loss(result)
+    }
+
+    /// Give an allocation a name which identifies it in reports and leak
+    /// messages, e.g. from a [crate::TraceAllocator].
+    ///
+    /// # Params
+    ///
+    /// - `allocation` - the live allocation to name
+    /// - `name` - the allocation's name, see [Allocation::name]
+    pub fn name_allocation(&self, allocation: &mut Allocation, name: &str) {
+        allocation.set_name(name);
+        self.internal_allocator
+            .lock()
+            .unwrap()
+            .name_allocation(allocation);
+    }
+
+    /// Free memory which was allocated with [Self::allocate].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - any resources bound to the memory must be destroyed first, or never
+    ///     used again
+    ///   - it is an error to free memory while ongoing GPU operations still
     ///     reference it
-    ///   - it is an error to use the buffer handle after calling this method
-    pub unsafe fn free_buffer(
-        &mut self,
-        buffer: vk::Buffer,
-        allocation: Allocation,
-    ) {
-        self.device.destroy_buffer(buffer, None);
+    pub unsafe fn free(&self, allocation: Allocation) {
         self.internal_allocator.lock().unwrap().free(allocation);
     }
 
-    /// Free an image and the associated allocated memory.
+    /// Get the memory requirements for a buffer without allocating memory.
+    ///
+    /// A temporary buffer is created to query the requirements, then
+    /// destroyed. This lets streaming systems budget for a load before
+    /// committing to it.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - describes the buffer which would be allocated
+    /// - `memory_property_flags` - used to pick the memory type, the same as
+    ///   [Self::allocate_buffer]
+    ///
+    /// # Returns
+    ///
+    /// The size, alignment, and memory type the buffer would use.
     ///
     /// # Safety
     ///
     /// Unsafe because:
-    ///   - the application must synchronize access to the image and its memory
-    ///   - it is an error to free an image while ongoing GPU operations still
-    ///     reference it
-    ///   - it is an error to use the image handle after calling this method
-    pub unsafe fn free_image(
-        &mut self,
-        image: vk::Image,
-        allocation: Allocation,
-    ) {
+    ///   - the create info is passed to vkCreateBuffer, so its p_next chain and
+    ///     queue family indices must be valid
+    pub unsafe fn query_buffer_requirements(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<AllocationRequirements, AllocatorError> {
+        let buffer = self
+            .device
+            .create_buffer(buffer_create_info, None)
+            .with_context(|| {
+                format!(
+                    "Error creating a buffer with {:#?}",
+                    buffer_create_info
+                )
+            })?;
+        let result = AllocationRequirements::for_buffer(
+            &self.device,
+            self.memory_properties.types(),
+            memory_property_flags,
+            buffer,
+        );
+        self.device.destroy_buffer(buffer, None);
+        result
+    }
+
+    /// Get the memory requirements for an image without allocating memory.
+    /// See [Self::query_buffer_requirements].
+    ///
+    /// # Params
+    ///
+    /// - `image_create_info` - describes the image which would be allocated
+    /// - `memory_property_flags` - used to pick the memory type. Transient
+    ///   attachments are not routed to lazily-allocated memory, so the result
+    ///   is the fallback used by [Self::allocate_image].
+    ///
+    /// # Returns
+    ///
+    /// The size, alignment, and memory type the image would use.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the create info is passed to vkCreateImage, so its p_next chain and
+    ///     queue family indices must be valid
+    pub unsafe fn query_image_requirements(
+        &self,
+        image_create_info: &vk::ImageCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<AllocationRequirements, AllocatorError> {
+        let image = self
+            .device
+            .create_image(image_create_info, None)
+            .with_context(|| {
+                format!("Error creating an image with {:#?}", image_create_info)
+            })?;
+        let result = AllocationRequirements::for_image(
+            &self.device,
+            self.memory_properties.types(),
+            memory_property_flags,
+            image,
+        )
+        .map(|requirements| AllocationRequirements {
+            resource_kind: ResourceKind::for_image_tiling(
+                image_create_info.tiling,
+            ),
+            ..requirements
+        });
         self.device.destroy_image(image, None);
-        self.internal_allocator.lock().unwrap().free(allocation);
+        result
+    }
+
+    /// Allocate a buffer and memory.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - used to create the Buffer and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   buffer's memory
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(vk::buffer, Allocation)` which contains the raw vulkan
+    /// buffer and the backing memory Allocation.
+    ///
+    /// The buffer is already bound to the memory in the allocation so the
+    /// buffer is ready to use immediately. Buffers created with the
+    /// SHADER_DEVICE_ADDRESS usage get memory allocated with the
+    /// DEVICE_ADDRESS flag, and have their address cached in the allocation,
+    /// see [Allocation::device_address].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_buffer(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        self.allocate_buffer_aligned(
+            buffer_create_info,
+            memory_property_flags,
+            1,
+        )
+    }
+
+    /// Allocate a buffer and memory, then map the memory.
+    ///
+    /// This is the common flow for staging buffers which are written as soon
+    /// as they're allocated. The memory stays mapped until the buffer is
+    /// freed, see [Allocation::mapped_ptr].
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - used to create the Buffer and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   buffer's memory. Must include HOST_VISIBLE.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(vk::Buffer, Allocation, *mut c_void)` where the pointer is
+    /// the host address of the start of the allocation. See
+    /// [Self::allocate_buffer].
+    ///
+    /// [AllocatorError::NotHostVisible] is returned, before anything is
+    /// allocated, when the memory properties don't include HOST_VISIBLE.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and memory must be freed before the device is destroyed
+    ///   - the pointer must not be used after the buffer is freed
+    ///   - the application must synchronize access to the mapped memory
+    #[track_caller]
+    pub unsafe fn allocate_mapped_buffer(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<(vk::Buffer, Allocation, *mut std::ffi::c_void), AllocatorError>
+    {
+        if !memory_property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+        {
+            return Err(AllocatorError::NotHostVisible(memory_property_flags));
+        }
+        let (buffer, mut allocation) =
+            self.allocate_buffer(buffer_create_info, memory_property_flags)?;
+        if allocation.mapped_ptr().is_none() {
+            if let Err(err) = allocation.map(&self.device) {
+                self.free_buffer(buffer, allocation);
+                return Err(err);
+            }
+            allocation.set_persistently_mapped(true);
+        }
+        let ptr = allocation.mapped_ptr().unwrap();
+        Ok((buffer, allocation, ptr))
+    }
+
+    /// Allocate a buffer and memory with a name which appears in reports, see
+    /// [Self::allocate_buffer] and [Self::name_allocation].
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - used to create the Buffer and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   buffer's memory
+    /// - `name` - the allocation's name, e.g. `Terrain Vertices`
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_named_buffer(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+        name: &str,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        let (buffer, mut allocation) =
+            self.allocate_buffer(buffer_create_info, memory_property_flags)?;
+        self.name_allocation(&mut allocation, name);
+        Ok((buffer, allocation))
+    }
+
+    /// Allocate a buffer and memory with an alignment which is stricter than
+    /// the buffer's memory requirements.
+    ///
+    /// This is useful when the application computes offsets relative to the
+    /// allocation, e.g. dynamic uniform buffer offsets must be a multiple of
+    /// minUniformBufferOffsetAlignment, which is often 256 bytes.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - used to create the Buffer and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   buffer's memory
+    /// - `min_alignment` - the minimum alignment for the allocation's offset.
+    ///   Must be a power of two. The buffer's own alignment is used when it is
+    ///   stricter.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(vk::buffer, Allocation)`, the same as
+    /// [Self::allocate_buffer].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_buffer_aligned(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+        min_alignment: u64,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        if !min_alignment.is_power_of_two() {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "The minimum alignment must be a power of two, got {}",
+                min_alignment
+            )));
+        }
+        self.create_buffer(
+            buffer_create_info,
+            memory_property_flags,
+            vk::MemoryPropertyFlags::empty(),
+            min_alignment,
+        )
+    }
+
+    /// Allocate a buffer and memory, preferring a memory type with additional
+    /// properties when one is available.
+    ///
+    /// For example, requiring HOST_VISIBLE and preferring DEVICE_LOCAL puts
+    /// the buffer in resizable-BAR memory when the device has it, and in
+    /// plain host-visible memory otherwise. The memory properties in
+    /// [Allocation::allocation_requirements] show which properties were used.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - used to create the Buffer and determine what
+    ///   memory it needs
+    /// - `required_memory_property_flags` - properties the buffer's memory must
+    ///   have
+    /// - `preferred_memory_property_flags` - additional properties which are
+    ///   used when a memory type supports them
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(vk::buffer, Allocation)`, the same as
+    /// [Self::allocate_buffer].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_buffer_preferring(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        self.create_buffer(
+            buffer_create_info,
+            required_memory_property_flags,
+            preferred_memory_property_flags,
+            1,
+        )
+    }
+
+    /// Allocate and bind memory for a buffer created by the application.
+    ///
+    /// # Params
+    ///
+    /// - `buffer` - the buffer which needs memory. It must not already be bound
+    ///   to memory.
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   buffer's memory
+    ///
+    /// # Returns
+    ///
+    /// The allocation which is bound to the buffer. The buffer's device
+    /// address is not cached because the buffer's usage is unknown, see
+    /// [Allocation::device_address]. For the same reason, the memory is not
+    /// allocated with the DEVICE_ADDRESS flag. Buffers with
+    /// SHADER_DEVICE_ADDRESS usage should use [Self::allocate] with
+    /// [AllocationExtensions::with_memory_allocate_flags] instead.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must be destroyed by the application, then the memory
+    ///     freed with [Self::free], before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_for_buffer(
+        &self,
+        buffer: vk::Buffer,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, AllocatorError> {
+        self.allocate_buffer_memory(
+            buffer,
+            memory_property_flags,
+            vk::MemoryPropertyFlags::empty(),
+            1,
+            vk::MemoryAllocateFlags::empty(),
+        )
+    }
+
+    /// Allocate many buffers at once.
+    ///
+    /// Requests are sorted by memory type and size, then allocated in a single
+    /// pass while holding the internal allocator's lock. This packs
+    /// allocations into shared chunks much more tightly than allocating the
+    /// buffers one at a time, e.g. when loading hundreds of mesh buffers.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_infos` - used to create each buffer and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for
+    ///   every buffer's memory
+    ///
+    /// # Returns
+    ///
+    /// A `(vk::Buffer, Allocation)` tuple for each create info, in the same
+    /// order as the create infos. See [Self::allocate_buffer].
+    ///
+    /// Either every buffer is allocated or none are. Nothing needs to be freed
+    /// if an error is returned.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffers and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_buffers(
+        &self,
+        buffer_create_infos: &[vk::BufferCreateInfo],
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Vec<(vk::Buffer, Allocation)>, AllocatorError> {
+        self.check_can_allocate()?;
+
+        let destroy_buffers = |device: &ash::Device, buffers: &[vk::Buffer]| {
+            for &buffer in buffers {
+                device.destroy_buffer(buffer, None);
+            }
+        };
+
+        let mut buffers = Vec::with_capacity(buffer_create_infos.len());
+        let mut requirements = Vec::with_capacity(buffer_create_infos.len());
+        for buffer_create_info in buffer_create_infos {
+            let buffer = match self
+                .device
+                .create_buffer(buffer_create_info, None)
+                .with_context(|| {
+                    format!(
+                        "Error creating a buffer with {:#?}",
+                        buffer_create_info
+                    )
+                }) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    destroy_buffers(&self.device, &buffers);
+                    return Err(err.into());
+                }
+            };
+            buffers.push(buffer);
+            match AllocationRequirements::for_buffer(
+                &self.device,
+                self.memory_properties.types(),
+                memory_property_flags,
+                buffer,
+            ) {
+                Ok(mut buffer_requirements) => {
+                    buffer_requirements.extensions = buffer_requirements
+                        .extensions
+                        .with_memory_allocate_flags(
+                            Self::buffer_allocate_flags(
+                                buffer_create_info.usage,
+                            ),
+                        );
+                    requirements.push(buffer_requirements)
+                }
+                Err(err) => {
+                    destroy_buffers(&self.device, &buffers);
+                    return Err(err);
+                }
+            }
+        }
+
+        let mut allocations = match self.allocate_batch(&requirements) {
+            Ok(allocations) => allocations,
+            Err(err) => {
+                destroy_buffers(&self.device, &buffers);
+                return Err(err);
+            }
+        };
+
+        let bind_infos: Vec<vk::BindBufferMemoryInfo> = buffers
+            .iter()
+            .zip(allocations.iter())
+            .map(|(&buffer, allocation)| vk::BindBufferMemoryInfo {
+                buffer,
+                memory: allocation.memory(),
+                memory_offset: allocation.offset_in_bytes(),
+                ..Default::default()
+            })
+            .collect();
+        let result =
+            self.device.bind_buffer_memory2(&bind_infos).map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Error binding buffer memory",
+                )
+            });
+        if let Err(err) = self.record_device_loss(result) {
+            destroy_buffers(&self.device, &buffers);
+            let mut allocator = self.internal_allocator.lock().unwrap();
+            for allocation in allocations {
+                allocator.free(allocation);
+            }
+            return Err(err);
+        }
+
+        for ((buffer, allocation), buffer_create_info) in buffers
+            .iter()
+            .zip(allocations.iter_mut())
+            .zip(buffer_create_infos)
+        {
+            if buffer_create_info
+                .usage
+                .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+            {
+                let address_info = vk::BufferDeviceAddressInfo {
+                    buffer: *buffer,
+                    ..Default::default()
+                };
+                allocation.set_device_address(
+                    self.device.get_buffer_device_address(&address_info),
+                );
+            }
+        }
+
+        Ok(buffers.into_iter().zip(allocations).collect())
+    }
+
+    /// Allocate a buffer which may be larger than the largest possible device
+    /// memory allocation by splitting it into several buffers.
+    ///
+    /// Each buffer covers a consecutive range of the requested size. Every
+    /// buffer except the last is maxMemoryAllocationSize rounded down to a
+    /// whole MiB. When the device doesn't report a limit, a single buffer is
+    /// allocated.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - describes the whole buffer, the size is divided
+    ///   between the parts
+    /// - `memory_property_flags` - used to pick the correct memory type for
+    ///   every part's memory
+    ///
+    /// # Returns
+    ///
+    /// A `(vk::Buffer, Allocation)` tuple for each part, in order. See
+    /// [Self::allocate_buffers].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffers and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_split_buffer(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Vec<(vk::Buffer, Allocation)>, AllocatorError> {
+        let mib = 1024 * 1024;
+        let part_size = self
+            .memory_properties
+            .max_memory_allocation_size()
+            .map(|max_size| (max_size / mib * mib).max(mib))
+            .unwrap_or(buffer_create_info.size)
+            .max(1);
+
+        let mut create_infos = vec![];
+        let mut offset = 0;
+        while offset < buffer_create_info.size {
+            let size = part_size.min(buffer_create_info.size - offset);
+            create_infos.push(vk::BufferCreateInfo {
+                size,
+                ..*buffer_create_info
+            });
+            offset += size;
+        }
+        self.allocate_buffers(&create_infos, memory_property_flags)
+    }
+
+    /// Allocate an image and memory with a name which appears in reports, see
+    /// [Self::allocate_image] and [Self::name_allocation].
+    ///
+    /// # Params
+    ///
+    /// - `image_create_info` - used to create the Image and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   image's memory
+    /// - `name` - the allocation's name, e.g. `Shadow Map`
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_named_image(
+        &self,
+        image_create_info: &vk::ImageCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+        name: &str,
+    ) -> Result<(vk::Image, Allocation), AllocatorError> {
+        let (image, mut allocation) =
+            self.allocate_image(image_create_info, memory_property_flags)?;
+        self.name_allocation(&mut allocation, name);
+        Ok((image, allocation))
+    }
+
+    /// Allocate an Image and memory.
+    ///
+    /// # Params
+    ///
+    /// - `image_create_info` - used to create the Buffer and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   buffer's memory
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(vk::Image, Allocation)` which contains the raw Vulkan
+    /// image and the backing memory Allocation.
+    ///
+    /// The image is already bound to the memory in the allocation so the
+    /// image is ready to use immediately.
+    ///
+    /// Images with TRANSIENT_ATTACHMENT usage are placed in LAZILY_ALLOCATED
+    /// memory when the device has it. Tile-based GPUs can keep these
+    /// attachments in on-chip memory without ever committing physical pages.
+    /// If there's no suitable lazily-allocated type, or it's exhausted, the
+    /// image falls back to the requested memory properties.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_image(
+        &self,
+        image_create_info: &vk::ImageCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<(vk::Image, Allocation), AllocatorError> {
+        self.check_can_allocate()?;
+
+        let cache_key = self.resource_cache.as_ref().and_then(|_| {
+            ImageKey::new(
+                image_create_info,
+                memory_property_flags,
+                self.options,
+            )
+        });
+        if let (Some(cache), Some(key)) = (&self.resource_cache, &cache_key) {
+            if let Some(cached) = cache.lock().unwrap().images.take(key) {
+                return Ok(cached);
+            }
+        }
+
+        let image = unsafe {
+            self.device
+                .create_image(image_create_info, None)
+                .with_context(|| {
+                    format!(
+                        "Error creating a image with {:#?}",
+                        image_create_info
+                    )
+                })?
+        };
+
+        let allocation = {
+            let result = self
+                .allocate_image_memory(
+                    image,
+                    image_create_info,
+                    memory_property_flags,
+                )
+                .and_then(|allocation| self.bind_image(image, allocation));
+            if result.is_err() {
+                self.device.destroy_image(image, None);
+            }
+            result?
+        };
+
+        if let (Some(cache), Some(key)) = (&self.resource_cache, cache_key) {
+            cache.lock().unwrap().images.track(image, key);
+        }
+
+        Ok((image, allocation))
+    }
+
+    /// Allocate and bind memory for an image created by the application.
+    ///
+    /// Unlike [Self::allocate_image], transient attachments are not routed
+    /// to lazily-allocated memory automatically because the image's usage is
+    /// unknown. Include LAZILY_ALLOCATED in the memory properties to request
+    /// it explicitly.
+    ///
+    /// # Params
+    ///
+    /// - `image` - the image which needs memory. It must not already be bound
+    ///   to memory.
+    /// - `memory_property_flags` - used to pick the correct memory type for the
+    ///   image's memory
+    ///
+    /// # Returns
+    ///
+    /// The allocation which is bound to the image.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must be destroyed by the application, then the memory
+    ///     freed with [Self::free], before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_for_image(
+        &self,
+        image: vk::Image,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, AllocatorError> {
+        let requirements = AllocationRequirements::for_image(
+            &self.device,
+            self.memory_properties.types(),
+            memory_property_flags,
+            image,
+        )?;
+        let allocation = self.allocate(requirements)?;
+        self.bind_image(image, allocation)
+    }
+
+    /// Allocate memory from an explicit memory type, bypassing memory type
+    /// selection.
+    ///
+    /// This is for applications with their own memory type selection policy
+    /// which only need the allocator for suballocation.
+    ///
+    /// # Params
+    ///
+    /// - `allocation_requirements` - the size and alignment needed by the
+    ///   resource. The memory type index and properties are replaced.
+    /// - `memory_type_index` - the memory type to allocate from. It must be
+    ///   allowed by the requirements' memory type bits.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the memory must be freed with [Self::free] before the device is
+    ///     destroyed
+    #[track_caller]
+    pub unsafe fn allocate_in_memory_type(
+        &self,
+        allocation_requirements: AllocationRequirements,
+        memory_type_index: usize,
+    ) -> Result<Allocation, AllocatorError> {
+        let requirements = allocation_requirements.with_memory_type_index(
+            self.memory_properties.types(),
+            memory_type_index,
+        )?;
+        self.allocate(requirements)
+    }
+
+    /// Allocate and bind memory from an explicit memory type for a buffer
+    /// created by the application. See [Self::allocate_in_memory_type].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must be destroyed by the application, then the memory
+    ///     freed with [Self::free], before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_for_buffer_in_memory_type(
+        &self,
+        buffer: vk::Buffer,
+        memory_type_index: usize,
+    ) -> Result<Allocation, AllocatorError> {
+        let requirements = AllocationRequirements::for_buffer(
+            &self.device,
+            self.memory_properties.types(),
+            vk::MemoryPropertyFlags::empty(),
+            buffer,
+        )?;
+        let allocation =
+            self.allocate_in_memory_type(requirements, memory_type_index)?;
+        self.bind_buffer(buffer, allocation)
+    }
+
+    /// Allocate and bind memory from an explicit memory type for an image
+    /// created by the application. See [Self::allocate_in_memory_type].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must be destroyed by the application, then the memory
+    ///     freed with [Self::free], before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_for_image_in_memory_type(
+        &self,
+        image: vk::Image,
+        memory_type_index: usize,
+    ) -> Result<Allocation, AllocatorError> {
+        let requirements = AllocationRequirements::for_image(
+            &self.device,
+            self.memory_properties.types(),
+            vk::MemoryPropertyFlags::empty(),
+            image,
+        )?;
+        let allocation =
+            self.allocate_in_memory_type(requirements, memory_type_index)?;
+        self.bind_image(image, allocation)
+    }
+
+    /// Allocate pages of memory for sparsely bound buffers or opaque image
+    /// ranges. Bind the pages with vkQueueBindSparse, using
+    /// [crate::sparse_memory_binds] to build the binds.
+    ///
+    /// # Params
+    ///
+    /// - `count` - the number of pages to allocate
+    /// - `page_size` - the size of each page. This is usually the alignment
+    ///   from the sparse resource's memory requirements, which is its sparse
+    ///   block size.
+    /// - `memory_type_index` - the memory type for the pages. It must be
+    ///   allowed by the sparse resource's memory type bits.
+    ///
+    /// # Returns
+    ///
+    /// One allocation per page, each aligned to the page size. If any page
+    /// can't be allocated then none are.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the pages must be unbound, then freed with [Self::free], before the
+    ///     device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_sparse_pages(
+        &self,
+        count: usize,
+        page_size: u64,
+        memory_type_index: usize,
+    ) -> Result<Vec<Allocation>, AllocatorError> {
+        if page_size == 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Sparse pages must not be empty"
+            )));
+        }
+        let page_requirements = AllocationRequirements {
+            size_in_bytes: page_size,
+            alignment: page_size,
+            memory_type_bits: 1u32
+                .checked_shl(memory_type_index as u32)
+                .unwrap_or(0),
+            ..Default::default()
+        }
+        .with_memory_type_index(
+            self.memory_properties.types(),
+            memory_type_index,
+        )?;
+        self.allocate_batch(&vec![page_requirements; count])
+    }
+
+    /// Allocate many images at once. See [Self::allocate_buffers].
+    ///
+    /// Unlike [Self::allocate_image], transient attachments are not routed
+    /// to lazily-allocated memory automatically. Allocate them individually,
+    /// or include LAZILY_ALLOCATED in the memory properties.
+    ///
+    /// # Params
+    ///
+    /// - `image_create_infos` - used to create each image and determine what
+    ///   memory it needs
+    /// - `memory_property_flags` - used to pick the correct memory type for
+    ///   every image's memory
+    ///
+    /// # Returns
+    ///
+    /// A `(vk::Image, Allocation)` tuple for each create info, in the same
+    /// order as the create infos.
+    ///
+    /// Either every image is allocated or none are. Nothing needs to be freed
+    /// if an error is returned.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the images and memory must be freed before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_images(
+        &self,
+        image_create_infos: &[vk::ImageCreateInfo],
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Vec<(vk::Image, Allocation)>, AllocatorError> {
+        self.check_can_allocate()?;
+
+        let destroy_images = |device: &ash::Device, images: &[vk::Image]| {
+            for &image in images {
+                device.destroy_image(image, None);
+            }
+        };
+
+        let mut images = Vec::with_capacity(image_create_infos.len());
+        let mut requirements = Vec::with_capacity(image_create_infos.len());
+        for image_create_info in image_create_infos {
+            let image = match self
+                .device
+                .create_image(image_create_info, None)
+                .with_context(|| {
+                    format!(
+                        "Error creating an image with {:#?}",
+                        image_create_info
+                    )
+                }) {
+                Ok(image) => image,
+                Err(err) => {
+                    destroy_images(&self.device, &images);
+                    return Err(err.into());
+                }
+            };
+            images.push(image);
+            match AllocationRequirements::for_image(
+                &self.device,
+                self.memory_properties.types(),
+                memory_property_flags,
+                image,
+            ) {
+                Ok(image_requirements) => {
+                    requirements.push(AllocationRequirements {
+                        resource_kind: ResourceKind::for_image_tiling(
+                            image_create_info.tiling,
+                        ),
+                        ..image_requirements
+                    })
+                }
+                Err(err) => {
+                    destroy_images(&self.device, &images);
+                    return Err(err);
+                }
+            }
+        }
+
+        let allocations = match self.allocate_batch(&requirements) {
+            Ok(allocations) => allocations,
+            Err(err) => {
+                destroy_images(&self.device, &images);
+                return Err(err);
+            }
+        };
+
+        let bind_infos: Vec<vk::BindImageMemoryInfo> = images
+            .iter()
+            .zip(allocations.iter())
+            .map(|(&image, allocation)| vk::BindImageMemoryInfo {
+                image,
+                memory: allocation.memory(),
+                memory_offset: allocation.offset_in_bytes(),
+                ..Default::default()
+            })
+            .collect();
+        let result =
+            self.device.bind_image_memory2(&bind_infos).map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Error binding image memory",
+                )
+            });
+        if let Err(err) = self.record_device_loss(result) {
+            destroy_images(&self.device, &images);
+            let mut allocator = self.internal_allocator.lock().unwrap();
+            for allocation in allocations {
+                allocator.free(allocation);
+            }
+            return Err(err);
+        }
+
+        Ok(images.into_iter().zip(allocations).collect())
+    }
+
+    /// Import an application-provided host allocation as device memory.
+    ///
+    /// This enables zero-copy uploads on unified memory systems and interop
+    /// with existing CPU-side arenas. The imported memory does not come from
+    /// the internal allocator, so it must be freed with
+    /// [Self::free_host_memory].
+    ///
+    /// # Params
+    ///
+    /// - `host_pointer` - the start of the host allocation. Must be aligned to
+    ///   minImportedHostPointerAlignment.
+    /// - `size_in_bytes` - the size of the host allocation. Must be a multiple
+    ///   of minImportedHostPointerAlignment.
+    /// - `memory_property_flags` - used to pick the memory type for the
+    ///   imported memory
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - VK_EXT_external_memory_host must be enabled on the device
+    ///   - the host allocation must outlive the returned allocation
+    ///   - the memory must be freed before the device is destroyed
+    pub unsafe fn import_host_memory(
+        &self,
+        host_pointer: *mut std::ffi::c_void,
+        size_in_bytes: u64,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, AllocatorError> {
+        self.check_can_allocate()?;
+        let result = self.host_memory_importer.import(
+            self.memory_properties.types(),
+            host_pointer,
+            size_in_bytes,
+            memory_property_flags,
+        );
+        self.record_device_loss(result)
+    }
+
+    /// Free device memory which was imported with
+    /// [Self::import_host_memory]. The host allocation can be released once
+    /// this returns.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the memory must not be in use by the GPU
+    ///   - any resources bound to the memory must be destroyed first
+    pub unsafe fn free_host_memory(&self, allocation: Allocation) {
+        self.device.free_memory(allocation.memory(), None);
+    }
+
+    /// Import memory which is owned by another API or process from a POSIX
+    /// file descriptor.
+    ///
+    /// The imported memory is never suballocated and does not come from the
+    /// internal allocator. Otherwise the allocation can be bound, mapped, and
+    /// inspected like any other, and it's included in [Self::stats] and
+    /// [Self::generate_report]. Free it with [Self::free_imported_memory].
+    ///
+    /// # Params
+    ///
+    /// - `handle_type` - the type of the file descriptor, e.g. OPAQUE_FD for
+    ///   memory exported by another Vulkan device or DMA_BUF_EXT for a dma-buf.
+    ///   Only non-opaque handles restrict the memory types.
+    /// - `fd` - the file descriptor exported by the owner of the memory. Vulkan
+    ///   takes ownership of the file descriptor when the import succeeds.
+    /// - `requirements` - the size, memory type bits, and memory properties of
+    ///   the imported memory. Use [AllocationRequirements::for_buffer] or
+    ///   [AllocationRequirements::for_image] when the memory backs a single
+    ///   resource, which also makes it a dedicated allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - VK_KHR_external_memory_fd must be enabled on the device
+    ///   - the memory must be freed before the device is destroyed
+    pub unsafe fn import_memory_fd(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        fd: std::os::raw::c_int,
+        requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.check_can_allocate()?;
+        let result = self.external_memory_importer.import_fd(
+            self.memory_properties.types(),
+            handle_type,
+            fd,
+            requirements,
+        );
+        self.record_device_loss(result)
+    }
+
+    /// Import memory which is owned by another API or process from a Win32
+    /// handle. See [Self::import_memory_fd].
+    ///
+    /// # Params
+    ///
+    /// - `handle_type` - the type of the handle, e.g. OPAQUE_WIN32 for memory
+    ///   exported by another Vulkan device or D3D12_RESOURCE for a Direct3D
+    ///   resource. Only non-opaque handles restrict the memory types.
+    /// - `handle` - the NT handle exported by the owner of the memory.
+    ///   Importing does not transfer ownership, so the application must still
+    ///   close the handle.
+    /// - `requirements` - the size, memory type bits, and memory properties of
+    ///   the imported memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - VK_KHR_external_memory_win32 must be enabled on the device
+    ///   - the memory must be freed before the device is destroyed
+    pub unsafe fn import_memory_win32_handle(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        handle: vk::HANDLE,
+        requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.check_can_allocate()?;
+        let result = self.external_memory_importer.import_win32_handle(
+            self.memory_properties.types(),
+            handle_type,
+            handle,
+            requirements,
+        );
+        self.record_device_loss(result)
+    }
+
+    /// Free memory which was imported with [Self::import_memory_fd] or
+    /// [Self::import_memory_win32_handle].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the memory must not be in use by the GPU
+    ///   - any resources bound to the memory must be destroyed first
+    pub unsafe fn free_imported_memory(&self, allocation: Allocation) {
+        self.external_memory_importer.free(allocation);
+    }
+
+    /// Create a device-local buffer which is initialized with data.
+    ///
+    /// The data is written to a temporary staging buffer, then copied into
+    /// the new buffer on the provided queue. This method blocks until the
+    /// copy completes, so the data is resident when it returns. When
+    /// [Self::uses_direct_uploads] is true, the buffer is placed in
+    /// host-visible device-local memory and written directly instead.
+    ///
+    /// # Params
+    ///
+    /// - `buffer_create_info` - used to create the buffer. The size is replaced
+    ///   with the size of the data and TRANSFER_DST usage is added.
+    /// - `data` - the buffer's initial contents. It must not be empty.
+    /// - `queue` - the queue used to submit the copy.
+    /// - `command_pool` - the pool for the copy's command buffer. It must
+    ///   belong to the queue's family.
+    ///
+    /// # Returns
+    ///
+    /// The new buffer and its allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and the command
+    ///     pool
+    ///   - the buffer must be freed with [Self::free_buffer] before the device
+    ///     is destroyed
+    #[track_caller]
+    pub unsafe fn allocate_buffer_with_data<T: Copy>(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        data: &[T],
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        let size_in_bytes = std::mem::size_of_val(data) as u64;
+        if size_in_bytes == 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to create a buffer with no initial data"
+            )));
+        }
+
+        let (buffer, allocation) = self.allocate_buffer_preferring(
+            &vk::BufferCreateInfo {
+                size: size_in_bytes,
+                usage: buffer_create_info.usage
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+                ..*buffer_create_info
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            self.direct_upload_properties(),
+        )?;
+
+        // Device-local memory which the host can write doesn't need a staging
+        // copy.
+        let direct_upload_properties = self.direct_upload_properties();
+        let result = if !direct_upload_properties.is_empty()
+            && allocation
+                .allocation_requirements()
+                .memory_properties
+                .contains(direct_upload_properties)
+        {
+            self.write_to_allocation(&allocation, data)
+        } else {
+            self.upload_to_buffer(buffer, data, queue, command_pool)
+        };
+        if let Err(err) = result {
+            self.free_buffer(buffer, allocation);
+            return Err(err);
+        }
+
+        Ok((buffer, allocation))
+    }
+
+    /// Returns true when [Self::allocate_buffer_with_data] writes straight
+    /// into device-local memory instead of copying through a staging buffer.
+    ///
+    /// With [UploadPath::Auto] this is the case on devices with resizable
+    /// BAR, where most of the device-local heap is also HOST_VISIBLE, and on
+    /// integrated GPUs where device-local memory is host-visible anyway. See
+    /// [MemoryProperties::has_rebar] and
+    /// [MemoryProperties::is_unified_memory].
+    pub fn uses_direct_uploads(&self) -> bool {
+        let properties = &self.memory_properties;
+        let has_host_visible_device_memory = properties
+            .find_type(
+                vk::MemoryPropertyFlags::DEVICE_LOCAL
+                    | vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+                vk::MemoryPropertyFlags::empty(),
+                u32::MAX,
+            )
+            .is_some();
+        match self.upload_path {
+            UploadPath::Auto => {
+                properties.has_rebar()
+                    || (properties.is_unified_memory()
+                        && has_host_visible_device_memory)
+            }
+            UploadPath::Staged => false,
+            UploadPath::Direct => has_host_visible_device_memory,
+        }
+    }
+
+    /// Create a device-local image which is initialized with data.
+    ///
+    /// The data is written to a temporary staging buffer and copied into the
+    /// first mip level of the new image on the provided queue. The image is
+    /// transitioned from UNDEFINED to TRANSFER_DST_OPTIMAL for the copy, then
+    /// to SHADER_READ_ONLY_OPTIMAL. This method blocks until the copy
+    /// completes, so the image is ready to sample when it returns.
+    ///
+    /// # Params
+    ///
+    /// - `image_create_info` - used to create the image. TRANSFER_DST usage is
+    ///   added and the initial layout is replaced with UNDEFINED. The format
+    ///   must be a color format.
+    /// - `data` - tightly packed texels for every array layer of the first mip
+    ///   level. It must not be empty.
+    /// - `queue` - the queue used to submit the copy.
+    /// - `command_pool` - the pool for the copy's command buffer. It must
+    ///   belong to the queue's family.
+    ///
+    /// # Returns
+    ///
+    /// The new image and its allocation. Other mip levels are in
+    /// SHADER_READ_ONLY_OPTIMAL with undefined contents.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and the command
+    ///     pool
+    ///   - the data must be large enough to fill the first mip level
+    ///   - the image must be freed with [Self::free_image] before the device is
+    ///     destroyed
+    #[track_caller]
+    pub unsafe fn upload_image<T: Copy>(
+        &self,
+        image_create_info: &vk::ImageCreateInfo,
+        data: &[T],
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<(vk::Image, Allocation), AllocatorError> {
+        if data.is_empty() {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to create an image with no initial data"
+            )));
+        }
+
+        let image_create_info = vk::ImageCreateInfo {
+            usage: image_create_info.usage | vk::ImageUsageFlags::TRANSFER_DST,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            ..*image_create_info
+        };
+        let (image, allocation) = self.allocate_image(
+            &image_create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        if let Err(err) = self.upload_to_image(
+            image,
+            &image_create_info,
+            data,
+            queue,
+            command_pool,
+        ) {
+            self.free_image(image, allocation);
+            return Err(err);
+        }
+
+        Ok((image, allocation))
+    }
+
+    /// Replace a buffer with a resized copy.
+    ///
+    /// A new buffer is allocated and a copy of the old contents is submitted
+    /// to the queue. The old buffer and allocation are freed by
+    /// [Self::collect] once the copy completes. This is the building block
+    /// for growable GPU vectors.
+    ///
+    /// # Params
+    ///
+    /// - `buffer` - the buffer to resize. It must have TRANSFER_SRC usage.
+    /// - `allocation` - the buffer's memory.
+    /// - `buffer_create_info` - the create info originally used for the buffer.
+    ///   The new buffer uses the same parameters with the new size.
+    /// - `new_size` - the size of the new buffer. Contents are truncated if the
+    ///   new buffer is smaller.
+    /// - `queue` - the queue used to submit the copy.
+    /// - `command_pool` - the pool for the copy's command buffer. It must
+    ///   belong to the queue's family.
+    ///
+    /// # Returns
+    ///
+    /// The new buffer and allocation. Memory properties are the same as the
+    /// old allocation's.
+    ///
+    /// On success the old buffer and allocation belong to the allocator and
+    /// must not be used or freed by the application. On failure the old
+    /// buffer is untouched and its allocation is returned with the error, so
+    /// both still belong to the application.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and the command
+    ///     pool
+    ///   - writes to the old buffer must be complete or submitted to the queue
+    ///     before calling this method. Writes submitted to other queues must be
+    ///     synchronized by the application.
+    // The old allocation is returned on failure so the application doesn't
+    // lose it.
+    #[allow(clippy::result_large_err)]
+    #[track_caller]
+    pub unsafe fn resize_buffer(
+        &self,
+        buffer: vk::Buffer,
+        allocation: Allocation,
+        buffer_create_info: &vk::BufferCreateInfo,
+        new_size: u64,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<(vk::Buffer, Allocation), (AllocatorError, Allocation)> {
+        let allocated = self.allocate_buffer(
+            &vk::BufferCreateInfo {
+                size: new_size,
+                usage: buffer_create_info.usage
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+                ..*buffer_create_info
+            },
+            allocation.allocation_requirements().memory_properties,
+        );
+        let (new_buffer, new_allocation) = match allocated {
+            Ok(allocated) => allocated,
+            Err(err) => return Err((err, allocation)),
+        };
+
+        let (command_buffer, fence) = match self.submit_copy(
+            buffer,
+            new_buffer,
+            buffer_create_info.size.min(new_size),
+            queue,
+            command_pool,
+        ) {
+            Ok(submitted) => submitted,
+            Err(err) => {
+                self.free_buffer(new_buffer, new_allocation);
+                return Err((err, allocation));
+            }
+        };
+
+        self.pending_frees.lock().unwrap().push(PendingFree {
+            resource: PendingResource::Buffer(buffer),
+            allocation,
+            completion: GpuCompletion::Fence(fence),
+            frame: self.clock.frame(),
+            owns_fence: true,
+            command_buffer: Some((command_pool, command_buffer)),
+        });
+
+        Ok((new_buffer, new_allocation))
+    }
+
+    /// Free a buffer and its memory once the GPU is done with them.
+    ///
+    /// Nothing is freed until [Self::collect] sees that the GPU has signaled
+    /// completion, so the buffer can be released as soon as the application
+    /// is done recording commands which use it.
+    ///
+    /// # Params
+    ///
+    /// - `buffer` - the buffer to free
+    /// - `allocation` - the buffer's memory
+    /// - `completion` - signaled when the GPU is done with the buffer
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be used by commands submitted after the work
+    ///     which signals completion
+    ///   - the fence or semaphore must not be destroyed until the buffer is
+    ///     collected
+    pub unsafe fn free_buffer_deferred(
+        &self,
+        buffer: vk::Buffer,
+        allocation: Allocation,
+        completion: GpuCompletion,
+    ) {
+        self.defer_free(
+            PendingResource::Buffer(buffer),
+            allocation,
+            completion,
+        );
+    }
+
+    /// Free an image and its memory once the GPU is done with them. See
+    /// [Self::free_buffer_deferred].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not be used by commands submitted after the work
+    ///     which signals completion
+    ///   - the fence or semaphore must not be destroyed until the image is
+    ///     collected
+    pub unsafe fn free_image_deferred(
+        &self,
+        image: vk::Image,
+        allocation: Allocation,
+        completion: GpuCompletion,
+    ) {
+        self.defer_free(PendingResource::Image(image), allocation, completion);
+    }
+
+    /// Free memory from [Self::allocate] once the GPU is done with it. See
+    /// [Self::free_buffer_deferred].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - resources bound to the memory must be destroyed by the application
+    ///     after the GPU is done with them
+    ///   - the fence or semaphore must not be destroyed until the memory is
+    ///     collected
+    pub unsafe fn free_deferred(
+        &self,
+        allocation: Allocation,
+        completion: GpuCompletion,
+    ) {
+        self.defer_free(PendingResource::None, allocation, completion);
+    }
+
+    /// Free a buffer and its memory once the current frame has completed on
+    /// the GPU.
+    ///
+    /// This is a simpler alternative to [Self::free_buffer_deferred] for
+    /// applications which already track frames in flight. The current frame
+    /// comes from the allocator's clock, so it must be kept in step with the
+    /// application's frames, see [Frames](Self#frames).
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be used by commands submitted after the current
+    ///     frame
+    pub unsafe fn retire(&self, buffer: vk::Buffer, allocation: Allocation) {
+        self.retire_queue.lock().unwrap().retire(
+            self.clock.frame(),
+            PendingResource::Buffer(buffer),
+            allocation,
+        );
+    }
+
+    /// Free an image and its memory once the current frame has completed on
+    /// the GPU. See [Self::retire].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not be used by commands submitted after the current
+    ///     frame
+    pub unsafe fn retire_image(
+        &self,
+        image: vk::Image,
+        allocation: Allocation,
+    ) {
+        self.retire_queue.lock().unwrap().retire(
+            self.clock.frame(),
+            PendingResource::Image(image),
+            allocation,
+        );
+    }
+
+    /// Free every resource which was retired in or before a completed frame.
+    /// The current frame is unchanged, see [Frames](Self#frames).
+    ///
+    /// # Params
+    ///
+    /// - `completed_frame` - the most recent frame which the GPU has finished,
+    ///   e.g. after waiting on that frame's fence.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must really be done with the completed frame
+    pub unsafe fn advance_frame(&self, completed_frame: u64) {
+        let completed = self
+            .retire_queue
+            .lock()
+            .unwrap()
+            .take_completed(completed_frame);
+        for (resource, allocation) in completed {
+            self.free_pending(resource, allocation);
+        }
+    }
+
+    /// The number of retired resources which are waiting for their frame to
+    /// complete.
+    pub fn retired_count(&self) -> usize {
+        self.retire_queue.lock().unwrap().len()
+    }
+
+    /// The bytes waiting to be freed, grouped by the frame they were freed
+    /// in.
+    ///
+    /// This covers deferred frees which haven't been collected, see
+    /// [Self::collect], and retired resources whose frame hasn't completed,
+    /// see [Self::advance_frame].
+    pub fn pending_frees_by_frame(&self) -> BTreeMap<u64, u64> {
+        let mut frames = BTreeMap::new();
+        for pending_free in self.pending_frees.lock().unwrap().iter() {
+            *frames.entry(pending_free.frame).or_default() +=
+                pending_free.allocation.size_in_bytes();
+        }
+        for (frame, allocation) in self.retire_queue.lock().unwrap().iter() {
+            *frames.entry(frame).or_default() += allocation.size_in_bytes();
+        }
+        frames
+    }
+
+    /// The number of deferred frees which have not been collected yet.
+    pub fn pending_free_count(&self) -> usize {
+        self.pending_frees.lock().unwrap().len()
+    }
+
+    /// Free every deferred resource whose GPU work has completed.
+    ///
+    /// Call this regularly, e.g. once per frame. Deferred resources which are
+    /// still in use are left for a later call.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the command pools used
+    ///     for deferred work
+    pub unsafe fn collect(&self) -> Result<(), AllocatorError> {
+        let pending = std::mem::take(&mut *self.pending_frees.lock().unwrap());
+        let mut still_pending = vec![];
+        let mut result = Ok(());
+        for pending_free in pending {
+            match pending_free.is_complete(&self.device).map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Unable to check if a deferred free is complete",
+                )
+            }) {
+                Ok(true) => {
+                    pending_free.destroy_sync_objects(&self.device);
+                    self.free_pending(
+                        pending_free.resource,
+                        pending_free.allocation,
+                    );
+                }
+                Ok(false) => still_pending.push(pending_free),
+                Err(err) => {
+                    still_pending.push(pending_free);
+                    result = self.record_device_loss(Err(err));
+                }
+            }
+        }
+        self.pending_frees.lock().unwrap().extend(still_pending);
+        result
+    }
+
+    /// Free a buffer and the associated allocated memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the buffer and its memory
+    ///   - it is an error to free a buffer while ongoing GPU operations still
+    ///     reference it
+    ///   - it is an error to use the buffer handle after calling this method
+    pub unsafe fn free_buffer(
+        &self,
+        buffer: vk::Buffer,
+        allocation: Allocation,
+    ) {
+        let allocation = match &self.resource_cache {
+            Some(cache) => {
+                match cache.lock().unwrap().buffers.retain(
+                    buffer,
+                    allocation,
+                    self.clock.frame(),
+                ) {
+                    Some(allocation) => allocation,
+                    None => return,
+                }
+            }
+            None => allocation,
+        };
+        self.device.destroy_buffer(buffer, None);
+        self.internal_allocator.lock().unwrap().free(allocation);
+    }
+
+    /// Free an image and the associated allocated memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the image and its memory
+    ///   - it is an error to free an image while ongoing GPU operations still
+    ///     reference it
+    ///   - it is an error to use the image handle after calling this method
+    pub unsafe fn free_image(&self, image: vk::Image, allocation: Allocation) {
+        let allocation = match &self.resource_cache {
+            Some(cache) => {
+                match cache.lock().unwrap().images.retain(
+                    image,
+                    allocation,
+                    self.clock.frame(),
+                ) {
+                    Some(allocation) => allocation,
+                    None => return,
+                }
+            }
+            None => allocation,
+        };
+        self.device.destroy_image(image, None);
+        self.internal_allocator.lock().unwrap().free(allocation);
+    }
+
+    /// Check the internal allocator's bookkeeping for inconsistencies. See
+    /// [ComposableAllocator::validate].
+    pub fn validate(&self) -> Result<(), AllocatorError> {
+        self.internal_allocator.lock().unwrap().validate()
+    }
+
+    /// Get a snapshot of the allocator's memory usage for each memory type
+    /// and heap.
+    ///
+    /// Used bytes and allocation counts cover everything allocated through
+    /// this allocator. The other counters come from the composed allocators,
+    /// see [ComposableAllocator::stats].
+    pub fn stats(&self) -> AllocatorStats {
+        let mut stats = AllocatorStats::default();
+        self.internal_allocator.lock().unwrap().stats(&mut stats);
+        self.external_memory_importer.stats(&mut stats);
+        stats.heap_budgets = self.heap_budgets();
+        stats.finish(&self.memory_properties)
+    }
+
+    /// Publish the allocator's stats as gauges through the `metrics` facade.
+    ///
+    /// Gauges include the bytes allocated and used, the live allocations,
+    /// and the chunk count for each heap, along with the heap budgets when
+    /// VK_EXT_memory_budget is supported. Call this periodically, e.g. once
+    /// a second, so exporters see current values. See [Self::stats].
+    #[cfg(feature = "metrics")]
+    pub fn publish_metrics(&self) {
+        metrics_publisher::publish_stats(&self.stats());
+    }
+
+    /// Record the current memory usage with the usage sampler. This does
+    /// nothing unless the sampler is enabled, see [Self::with_usage_sampler].
+    ///
+    /// [Self::end_frame] calls this automatically. Call it directly to sample
+    /// at other times, e.g. after loading a level.
+    pub fn sample_usage(&self) {
+        if let Some(sampler) = &self.usage_sampler {
+            let stats = self.stats();
+            sampler.lock().unwrap().sample(self.clock.frame(), &stats);
+        }
+    }
+
+    /// Write the sampled memory usage as CSV, see [UsageSampler::to_csv].
+    ///
+    /// # Returns
+    ///
+    /// None when the usage sampler isn't enabled, see
+    /// [Self::with_usage_sampler].
+    pub fn usage_csv(&self) -> Option<String> {
+        self.usage_sampler
+            .as_ref()
+            .map(|sampler| sampler.lock().unwrap().to_csv())
+    }
+
+    /// Query how much of each heap the OS currently lets the application use.
+    /// Use this rather than raw heap sizes to decide when to evict or stream
+    /// out resources.
+    ///
+    /// # Returns
+    ///
+    /// The budget for each heap, indexed by heap index, or None when
+    /// VK_EXT_memory_budget isn't supported. See
+    /// [MemoryProperties::query_heap_budgets].
+    pub fn heap_budgets(&self) -> Option<Vec<HeapBudget>> {
+        // SAFE because the physical device is the one the memory properties
+        // were queried from.
+        unsafe {
+            self.memory_properties
+                .query_heap_budgets(&self.instance, self.physical_device)
+        }
+    }
+
+    /// True when VK_EXT_pageable_device_local_memory is enabled on the
+    /// device, so [Self::set_priority] can change the priority of live
+    /// allocations.
+    pub fn supports_pageable_memory(&self) -> bool {
+        self.pageable_memory.is_supported()
+    }
+
+    /// Change the priority of an allocation's memory with
+    /// vkSetDeviceMemoryPriorityEXT.
+    ///
+    /// When the device is under pressure, the OS pages out low-priority
+    /// memory instead of failing new allocations. Lower the priority of
+    /// resources which aren't needed soon, e.g. distant streaming content.
+    ///
+    /// The priority applies to the whole VkDeviceMemory, so every allocation
+    /// which shares a chunk with this one is affected. Use a dedicated
+    /// allocation for resources which need their own priority.
+    ///
+    /// # Params
+    ///
+    /// - `allocation` - the allocation to update
+    /// - `priority` - the new priority, between 0.0 and 1.0
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the allocation must not have been freed
+    pub unsafe fn set_priority(
+        &self,
+        allocation: &Allocation,
+        priority: f32,
+    ) -> Result<(), AllocatorError> {
+        if self.is_device_lost() {
+            return Err(AllocatorError::DeviceLost);
+        }
+        self.pageable_memory.set_priority(allocation, priority)
+    }
+
+    /// Walk the allocator composition and describe the layout of every chunk.
+    ///
+    /// This is useful for drawing a live memory map in a debug UI. See
+    /// [ComposableAllocator::report].
+    pub fn generate_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        self.internal_allocator.lock().unwrap().report(&mut report);
+        self.external_memory_importer.report(&mut report);
+        report.finish()
+    }
+
+    /// List every allocation which has been given to the application and not
+    /// freed yet, largest first.
+    ///
+    /// This shows what is currently resident, e.g. in a debug UI.
+    pub fn live_allocations(&self) -> Vec<AllocationReport> {
+        let mut allocations = self.generate_report().allocations;
+        allocations.sort_by(|a, b| {
+            b.size_in_bytes
+                .cmp(&a.size_in_bytes)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        allocations
+    }
+
+    /// Draw a memory map of every chunk, see [VisualizationFormat].
+    ///
+    /// Each chunk is drawn as a bar with its used and free runs. Used runs
+    /// are colored by the tag or name of the allocation in them, see
+    /// [Self::tagged] and [Self::name_allocation]. Fragmentation is much
+    /// easier to spot in the picture than in counters.
+    pub fn visualize(&self, format: VisualizationFormat) -> String {
+        visualization::visualize(&self.generate_report(), format)
+    }
+
+    /// Describe every chunk in the JSON format written by VMA's
+    /// vmaBuildStatsString.
+    ///
+    /// The dump can be loaded by VmaDumpVis and other tools built for VMA.
+    /// Live suballocations are written with the UNKNOWN type because pools
+    /// don't keep the resource type.
+    pub fn dump_json(&self) -> String {
+        // SAFE because the physical device is the one the allocator was
+        // created for.
+        let device_properties = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+        };
+        vma_json::vma_json(
+            &device_properties,
+            &self.memory_properties,
+            &self.stats(),
+            &self.generate_report(),
+        )
+    }
+
+    /// Create pool chunks ahead of time so allocations from the memory type
+    /// don't stall while new chunks are allocated, e.g. at the start of a
+    /// level.
+    ///
+    /// The memory is reserved in the allocator which serves typical
+    /// allocations, see [ComposableAllocator::reserve]. Reserved chunks are
+    /// released by [Self::trim].
+    ///
+    /// # Params
+    ///
+    /// - `memory_type_index` - the memory type to reserve
+    /// - `bytes` - how many bytes should be available without allocating new
+    ///   chunks
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - reserved memory must be released before the device is destroyed
+    pub unsafe fn reserve(
+        &self,
+        memory_type_index: usize,
+        bytes: u64,
+    ) -> Result<(), AllocatorError> {
+        self.check_can_allocate()?;
+        let memory_type = self
+            .memory_properties
+            .types()
+            .get(memory_type_index)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unable to reserve memory type {}, there are only {} types",
+                    memory_type_index,
+                    self.memory_properties.types().len()
+                )
+            })?;
+        let allocation_requirements = AllocationRequirements {
+            alignment: 1,
+            size_in_bytes: bytes,
+            memory_type_bits: 1 << memory_type_index,
+            memory_type_index,
+            memory_properties: memory_type.property_flags,
+            ..AllocationRequirements::default()
+        };
+        let result = self
+            .internal_allocator
+            .lock()
+            .unwrap()
+            .reserve(allocation_requirements);
+        self.record_device_loss(result)
+    }
+
+    /// Return memory which is held but unused back to the device.
+    ///
+    /// The resource cache is cleared and every allocator in the composition
+    /// releases its unused memory, see [ComposableAllocator::trim]. This is
+    /// useful when unloading a level or when the OS signals memory pressure.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must be done with every resource and allocation which has
+    ///     been freed
+    pub unsafe fn trim(&self) {
+        self.clear_resource_cache();
+        self.internal_allocator.lock().unwrap().trim();
+    }
+
+    /// Create a pool which belongs to the application, e.g. for the
+    /// resources of a single level. Every allocation from the pool can be
+    /// invalidated at once with [PoolHandle::reset].
+    ///
+    /// # Params
+    ///
+    /// - `chunk_size` - the size of each chunk, which is allocated from this
+    ///   allocator
+    /// - `page_size` - the size of each page, it must divide the chunk size
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the pool's chunks must be released with [PoolHandle::reset] and
+    ///     [PoolHandle::trim] before the device is destroyed
+    pub unsafe fn create_pool(
+        &self,
+        chunk_size: u64,
+        page_size: u64,
+    ) -> Result<PoolHandle, AllocatorError> {
+        PoolSettings {
+            chunk_size,
+            page_size,
+            max_chunk_size: None,
+            max_pool_bytes: None,
+            max_empty_chunks: 0,
+        }
+        .validate()?;
+        Ok(PoolHandle::new(
+            self.clone(),
+            self.internal_allocator.clone(),
+            chunk_size,
+            page_size,
+        ))
+    }
+
+    /// Adjust the settings of every pool in the allocator composition, e.g.
+    /// to use larger chunks once a level has loaded. New settings only apply
+    /// to chunks which are created afterwards, existing chunks keep their
+    /// size until they are freed. See [ComposableAllocator::reconfigure_pools].
+    ///
+    /// # Params
+    ///
+    /// - `reconfigure` - called once for each pool with its current settings.
+    ///   Reconfiguration stops at the first pool whose new settings are
+    ///   invalid, that pool keeps its current settings.
+    pub fn reconfigure_pools(
+        &self,
+        mut reconfigure: impl FnMut(&mut PoolSettings),
+    ) -> Result<(), AllocatorError> {
+        let mut configurator = PoolConfigurator::new(&mut reconfigure);
+        self.internal_allocator
+            .lock()
+            .unwrap()
+            .reconfigure_pools(&mut configurator)
+    }
+
+    /// Map an allocation into application address space.
+    ///
+    /// Allocations in write-combined memory (HOST_VISIBLE but not
+    /// HOST_CACHED) are returned as [MappedMemory::WriteOnly], which has no
+    /// way to read, so accidental host reads don't compile rather than
+    /// showing up as a mysterious slowdown. Other memory is returned as
+    /// [MappedMemory::ReadWrite]. The variant is the same in debug and
+    /// release builds.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the application must synchronize access to the underlying device
+    ///   memory. See [Allocation::map] for details.
+    /// - the allocation must be unmapped with a call to
+    ///   [MemoryAllocator::unmap] or [Allocation::unmap].
+    pub unsafe fn map(
+        &self,
+        allocation: &Allocation,
+    ) -> Result<MappedMemory, AllocatorError> {
+        let ptr = allocation.map(&self.device)?;
+        let is_write_combined = self
+            .memory_properties
+            .is_write_combined(allocation.memory_type_index());
+        if is_write_combined {
+            Ok(MappedMemory::WriteOnly(WriteOnlyMemory::new(
+                ptr,
+                allocation.size_in_bytes(),
+            )))
+        } else {
+            Ok(MappedMemory::ReadWrite(ptr))
+        }
+    }
+
+    /// Unmap an allocation which was mapped with [MemoryAllocator::map].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the mapped memory must not be used after the call to unmap()
+    pub unsafe fn unmap(
+        &self,
+        allocation: &Allocation,
+    ) -> Result<(), AllocatorError> {
+        allocation.unmap(&self.device)
+    }
+}
+
+// Private API
+// -----------
+
+impl MemoryAllocator {
+    /// The device used to create and bind resources.
+    pub(crate) fn device(&self) -> &ash::Device {
+        &self.device
+    }
+
+    /// The device's memory properties.
+    pub(crate) fn memory_properties(&self) -> &MemoryProperties {
+        &self.memory_properties
+    }
+
+    /// Add this handle's tag and device mask, the location of the
+    /// application code which made the allocation, and the current frame, to
+    /// requirements which don't have them.
+    #[track_caller]
+    fn apply_handle_options(
+        &self,
+        allocation_requirements: AllocationRequirements,
+    ) -> AllocationRequirements {
+        self.options
+            .apply(allocation_requirements, self.clock.frame())
+    }
+
+    /// Fail with a descriptive error, rather than a driver error, when the
+    /// allocation is larger than the device allows.
+    fn check_allocation_size(
+        &self,
+        allocation_requirements: &AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        match self.memory_properties.max_memory_allocation_size() {
+            Some(max_allocation_size)
+                if allocation_requirements.size_in_bytes
+                    > max_allocation_size =>
+            {
+                Err(AllocatorError::AllocationTooLarge {
+                    size_in_bytes: allocation_requirements.size_in_bytes,
+                    max_allocation_size,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail fast when new allocations are not allowed.
+    fn check_can_allocate(&self) -> Result<(), AllocatorError> {
+        if self.is_device_lost() {
+            return Err(AllocatorError::DeviceLost);
+        }
+        if self.is_frozen() {
+            return Err(AllocatorError::Frozen);
+        }
+        Ok(())
+    }
+
+    /// Poison the allocator if the result indicates that the device was
+    /// lost.
+    pub(crate) fn record_device_loss<T>(
+        &self,
+        result: Result<T, AllocatorError>,
+    ) -> Result<T, AllocatorError> {
+        if let Err(AllocatorError::DeviceLost) = result {
+            log::error!("Device lost! No new allocations are possible.");
+            self.device_lost.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Allocate memory for many requests while holding the internal
+    /// allocator's lock.
+    ///
+    /// Requests are grouped by memory type and allocated from largest to
+    /// smallest, which packs them into shared chunks more tightly than the
+    /// original order would.
+    ///
+    /// # Returns
+    ///
+    /// One allocation per request, in the same order as the requests. If any
+    /// request fails then every allocation made so far is freed.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the allocations must be freed before the device is destroyed
+    #[track_caller]
+    unsafe fn allocate_batch(
+        &self,
+        requirements: &[AllocationRequirements],
+    ) -> Result<Vec<Allocation>, AllocatorError> {
+        self.check_can_allocate()?;
+        for requirements in requirements {
+            self.check_allocation_size(requirements)?;
+        }
+
+        let mut order: Vec<usize> = (0..requirements.len()).collect();
+        order.sort_by_key(|&index| {
+            let request = &requirements[index];
+            (
+                request.memory_type_index,
+                std::cmp::Reverse(request.alignment),
+                std::cmp::Reverse(request.size_in_bytes),
+            )
+        });
+
+        let mut allocations: Vec<Option<Allocation>> =
+            vec![None; requirements.len()];
+        let result = {
+            let mut allocator = self.internal_allocator.lock().unwrap();
+            let mut result = Ok(());
+            for index in order {
+                match allocator
+                    .allocate(self.apply_handle_options(requirements[index]))
+                {
+                    Ok(allocation) => allocations[index] = Some(allocation),
+                    Err(err) => {
+                        for allocation in allocations.iter_mut() {
+                            if let Some(allocation) = allocation.take() {
+                                allocator.free(allocation);
+                            }
+                        }
+                        result = Err(err);
+                        break;
+                    }
+                }
+            }
+            result
+        };
+        self.record_device_loss(result)?;
+
+        Ok(allocations.into_iter().flatten().collect())
+    }
+
+    /// Record and submit a copy from one buffer to another.
+    ///
+    /// Writes to the source which were submitted to the queue earlier are
+    /// finished and visible before the copy starts.
+    ///
+    /// # Returns
+    ///
+    /// The command buffer and a fence which is signaled when the copy
+    /// completes.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    unsafe fn submit_copy(
+        &self,
+        src: vk::Buffer,
+        dst: vk::Buffer,
+        size_in_bytes: u64,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<(vk::CommandBuffer, vk::Fence), AllocatorError> {
+        self.submit_commands(queue, command_pool, |device, command_buffer| {
+            if size_in_bytes > 0 {
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::MEMORY_WRITE,
+                        dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+                        ..Default::default()
+                    }],
+                    &[],
+                    &[],
+                );
+                device.cmd_copy_buffer(
+                    command_buffer,
+                    src,
+                    dst,
+                    &[vk::BufferCopy {
+                        src_offset: 0,
+                        dst_offset: 0,
+                        size: size_in_bytes,
+                    }],
+                );
+            }
+        })
+    }
+
+    /// Record commands into a new command buffer and submit them.
+    ///
+    /// # Params
+    ///
+    /// - `queue` - the queue to submit to
+    /// - `command_pool` - the pool for the command buffer
+    /// - `record` - records commands into the command buffer
+    ///
+    /// # Returns
+    ///
+    /// The command buffer and a fence which is signaled when the commands
+    /// complete. Both belong to the caller.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    pub(crate) unsafe fn submit_commands(
+        &self,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer),
+    ) -> Result<(vk::CommandBuffer, vk::Fence), AllocatorError> {
+        let command_buffer = self
+            .device
+            .allocate_command_buffers(&vk::CommandBufferAllocateInfo {
+                command_pool,
+                level: vk::CommandBufferLevel::PRIMARY,
+                command_buffer_count: 1,
+                ..Default::default()
+            })
+            .context("Unable to allocate a command buffer")?[0];
+
+        let fence = match self.record_and_submit(command_buffer, queue, record)
+        {
+            Ok(fence) => fence,
+            Err(err) => {
+                self.device
+                    .free_command_buffers(command_pool, &[command_buffer]);
+                return Err(err);
+            }
+        };
+
+        Ok((command_buffer, fence))
+    }
+
+    /// Record commands and submit them with a new fence.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue
+    unsafe fn record_and_submit(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        queue: vk::Queue,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer),
+    ) -> Result<vk::Fence, AllocatorError> {
+        self.device
+            .begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo {
+                    flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                    ..Default::default()
+                },
+            )
+            .context("Unable to begin the command buffer")?;
+        record(&self.device, command_buffer);
+        self.device
+            .end_command_buffer(command_buffer)
+            .context("Unable to end the command buffer")?;
+
+        let fence = self
+            .device
+            .create_fence(&vk::FenceCreateInfo::default(), None)
+            .context("Unable to create a fence for the commands")?;
+        let submit_info = vk::SubmitInfo {
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            ..Default::default()
+        };
+        if let Err(err) = self.device.queue_submit(queue, &[submit_info], fence)
+        {
+            self.device.destroy_fence(fence, None);
+            return self.record_device_loss(Err(
+                AllocatorError::from_vk_result(
+                    err,
+                    "Unable to submit the commands",
+                ),
+            ));
+        }
+
+        Ok(fence)
+    }
+
+    /// Copy data into the start of a buffer using a temporary staging
+    /// buffer. Blocks until the copy completes.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    ///   - the buffer must have TRANSFER_DST usage and be large enough to hold
+    ///     the data
+    unsafe fn upload_to_buffer<T: Copy>(
+        &self,
+        dst: vk::Buffer,
+        data: &[T],
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<(), AllocatorError> {
+        let size_in_bytes = std::mem::size_of_val(data) as u64;
+        self.upload_with_staging(
+            data,
+            queue,
+            command_pool,
+            |device, command_buffer, staging_buffer| {
+                device.cmd_copy_buffer(
+                    command_buffer,
+                    staging_buffer,
+                    dst,
+                    &[vk::BufferCopy {
+                        src_offset: 0,
+                        dst_offset: 0,
+                        size: size_in_bytes,
+                    }],
+                );
+            },
+        )
+    }
+
+    /// Copy data into the first mip level of an image using a temporary
+    /// staging buffer. The image is moved from UNDEFINED to
+    /// TRANSFER_DST_OPTIMAL for the copy, then to SHADER_READ_ONLY_OPTIMAL.
+    /// Blocks until the copy completes.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    ///   - the image must have TRANSFER_DST usage, a color format, and the data
+    ///     must be tightly packed texels for the whole first mip level
+    unsafe fn upload_to_image<T: Copy>(
+        &self,
+        dst: vk::Image,
+        image_create_info: &vk::ImageCreateInfo,
+        data: &[T],
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<(), AllocatorError> {
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: image_create_info.mip_levels,
+            base_array_layer: 0,
+            layer_count: image_create_info.array_layers,
+        };
+        let to_transfer_dst = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image: dst,
+            subresource_range,
+            ..Default::default()
+        };
+        let to_shader_read = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::empty(),
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..to_transfer_dst
+        };
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: image_create_info.array_layers,
+            },
+            image_offset: vk::Offset3D::default(),
+            image_extent: image_create_info.extent,
+        };
+        self.upload_with_staging(
+            data,
+            queue,
+            command_pool,
+            |device, command_buffer, staging_buffer| {
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[to_transfer_dst],
+                );
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    staging_buffer,
+                    dst,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region],
+                );
+                // The upload waits for the fence, so later work on any queue
+                // is ordered after the transition without a destination
+                // stage. BOTTOM_OF_PIPE is valid on transfer-only queues.
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[to_shader_read],
+                );
+            },
+        )
+    }
+
+    /// Write data to a temporary host-visible staging buffer, record commands
+    /// which read from it, and wait for them to complete.
+    ///
+    /// # Params
+    ///
+    /// - `data` - the staging buffer's contents
+    /// - `queue` - the queue to submit to
+    /// - `command_pool` - the pool for the command buffer
+    /// - `record` - records commands which read from the staging buffer
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    unsafe fn upload_with_staging<T: Copy>(
+        &self,
+        data: &[T],
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer, vk::Buffer),
+    ) -> Result<(), AllocatorError> {
+        let size_in_bytes = std::mem::size_of_val(data) as u64;
+        let (staging_buffer, staging_allocation) = self.allocate_buffer(
+            &vk::BufferCreateInfo {
+                size: size_in_bytes,
+                usage: vk::BufferUsageFlags::TRANSFER_SRC,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let result = self.write_and_submit(
+            staging_buffer,
+            &staging_allocation,
+            data,
+            queue,
+            command_pool,
+            record,
+        );
+
+        // The commands are either complete or were never submitted, so the
+        // staging buffer can be freed right away.
+        self.free_buffer(staging_buffer, staging_allocation);
+        result
+    }
+
+    /// Write data to a host-visible staging buffer, then submit commands which
+    /// read from it and wait for them to complete.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    unsafe fn write_and_submit<T: Copy>(
+        &self,
+        staging_buffer: vk::Buffer,
+        staging_allocation: &Allocation,
+        data: &[T],
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer, vk::Buffer),
+    ) -> Result<(), AllocatorError> {
+        self.write_to_allocation(staging_allocation, data)?;
+
+        let (command_buffer, fence) =
+            self.submit_commands(queue, command_pool, |device, cb| {
+                record(device, cb, staging_buffer)
+            })?;
+        let result = self
+            .device
+            .wait_for_fences(&[fence], true, u64::MAX)
+            .map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Error while waiting for an upload to complete",
+                )
+            });
+        self.device.destroy_fence(fence, None);
+        self.device
+            .free_command_buffers(command_pool, &[command_buffer]);
+        self.record_device_loss(result)
+    }
+
+    /// Copy data into the start of a HOST_VISIBLE | HOST_COHERENT allocation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the allocation must be large enough to hold the data
+    ///   - the GPU must not be using the memory
+    unsafe fn write_to_allocation<T: Copy>(
+        &self,
+        allocation: &Allocation,
+        data: &[T],
+    ) -> Result<(), AllocatorError> {
+        let ptr = allocation.map(&self.device)?;
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr() as *const u8,
+            ptr as *mut u8,
+            std::mem::size_of_val(data),
+        );
+        allocation.unmap(&self.device)
+    }
+
+    /// The extra memory properties which let the host write device-local
+    /// memory directly, skipping the staging copy. Empty when uploads must
+    /// be staged.
+    fn direct_upload_properties(&self) -> vk::MemoryPropertyFlags {
+        if self.uses_direct_uploads() {
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT
+        } else {
+            vk::MemoryPropertyFlags::empty()
+        }
+    }
+
+    /// Destroy resources which were removed from the resource cache.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the resources must not be in use
+    unsafe fn destroy_cached_resources(
+        &self,
+        buffers: Vec<(vk::Buffer, Allocation)>,
+        images: Vec<(vk::Image, Allocation)>,
+    ) {
+        let mut allocator = self.internal_allocator.lock().unwrap();
+        for (buffer, allocation) in buffers {
+            self.device.destroy_buffer(buffer, None);
+            allocator.free(allocation);
+        }
+        for (image, allocation) in images {
+            self.device.destroy_image(image, None);
+            allocator.free(allocation);
+        }
+    }
+
+    /// Create a buffer and allocate memory for it. Used by every variant of
+    /// [Self::allocate_buffer].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and memory must be freed before the device is destroyed
+    #[track_caller]
+    unsafe fn create_buffer(
+        &self,
+        buffer_create_info: &vk::BufferCreateInfo,
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+        min_alignment: u64,
+    ) -> Result<(vk::Buffer, Allocation), AllocatorError> {
+        self.check_can_allocate()?;
+
+        let cache_key = self.resource_cache.as_ref().and_then(|_| {
+            BufferKey::new(
+                buffer_create_info,
+                required_memory_property_flags,
+                preferred_memory_property_flags,
+                min_alignment,
+                self.options,
+            )
+        });
+        if let (Some(cache), Some(key)) = (&self.resource_cache, &cache_key) {
+            if let Some(cached) = cache.lock().unwrap().buffers.take(key) {
+                return Ok(cached);
+            }
+        }
+
+        let buffer = unsafe {
+            self.device
+                .create_buffer(buffer_create_info, None)
+                .with_context(|| {
+                    format!(
+                        "Error creating a buffer with {:#?}",
+                        buffer_create_info
+                    )
+                })?
+        };
+
+        let mut allocation = {
+            let result = self.allocate_buffer_memory(
+                buffer,
+                required_memory_property_flags,
+                preferred_memory_property_flags,
+                min_alignment,
+                Self::buffer_allocate_flags(buffer_create_info.usage),
+            );
+            if result.is_err() {
+                self.device.destroy_buffer(buffer, None);
+            }
+            result?
+        };
+
+        if buffer_create_info
+            .usage
+            .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+        {
+            let address_info = vk::BufferDeviceAddressInfo {
+                buffer,
+                ..Default::default()
+            };
+            allocation.set_device_address(
+                self.device.get_buffer_device_address(&address_info),
+            );
+        }
+
+        if let (Some(cache), Some(key)) = (&self.resource_cache, cache_key) {
+            cache.lock().unwrap().buffers.track(buffer, key);
+        }
+
+        Ok((buffer, allocation))
+    }
+
+    /// Free a resource which was deferred or retired.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must be done with the resource
+    unsafe fn free_pending(
+        &self,
+        resource: PendingResource,
+        allocation: Allocation,
+    ) {
+        match resource {
+            PendingResource::Buffer(buffer) => {
+                self.free_buffer(buffer, allocation)
+            }
+            PendingResource::Image(image) => self.free_image(image, allocation),
+            PendingResource::None => self.free(allocation),
+        }
+    }
+
+    /// Queue a resource to be freed by [Self::collect].
+    fn defer_free(
+        &self,
+        resource: PendingResource,
+        allocation: Allocation,
+        completion: GpuCompletion,
+    ) {
+        self.pending_frees.lock().unwrap().push(PendingFree {
+            resource,
+            allocation,
+            completion,
+            frame: self.clock.frame(),
+            owns_fence: false,
+            command_buffer: None,
+        });
+    }
+
+    /// The memory allocate flags needed by a buffer with the given usage.
+    ///
+    /// Memory bound to a buffer with SHADER_DEVICE_ADDRESS usage must be
+    /// allocated with the DEVICE_ADDRESS flag.
+    fn buffer_allocate_flags(
+        usage: vk::BufferUsageFlags,
+    ) -> vk::MemoryAllocateFlags {
+        if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        } else {
+            vk::MemoryAllocateFlags::empty()
+        }
+    }
+
+    /// Allocate memory for a buffer and bind it.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not already be bound to memory
+    #[track_caller]
+    unsafe fn allocate_buffer_memory(
+        &self,
+        buffer: vk::Buffer,
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+        min_alignment: u64,
+        memory_allocate_flags: vk::MemoryAllocateFlags,
+    ) -> Result<Allocation, AllocatorError> {
+        let mut requirements = AllocationRequirements::for_buffer_preferring(
+            &self.device,
+            self.memory_properties.types(),
+            required_memory_property_flags,
+            preferred_memory_property_flags,
+            buffer,
+        )?;
+        requirements.alignment = requirements.alignment.max(min_alignment);
+        requirements.extensions = requirements
+            .extensions
+            .with_memory_allocate_flags(memory_allocate_flags);
+        let allocation = self.allocate(requirements)?;
+        self.bind_buffer(buffer, allocation)
+    }
+
+    /// Bind a buffer to its memory. The allocation is freed if binding
+    /// fails.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not already be bound to memory
+    unsafe fn bind_buffer(
+        &self,
+        buffer: vk::Buffer,
+        allocation: Allocation,
+    ) -> Result<Allocation, AllocatorError> {
+        let result = self
+            .device
+            .bind_buffer_memory(
+                buffer,
+                allocation.memory(),
+                allocation.offset_in_bytes(),
+            )
+            .map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Error binding buffer memory",
+                )
+            });
+        if let Err(err) = self.record_device_loss(result) {
+            self.free(allocation);
+            return Err(err);
+        }
+        Ok(allocation)
+    }
+
+    /// Bind an image to its memory. The allocation is freed if binding
+    /// fails.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not already be bound to memory
+    unsafe fn bind_image(
+        &self,
+        image: vk::Image,
+        allocation: Allocation,
+    ) -> Result<Allocation, AllocatorError> {
+        let result = self
+            .device
+            .bind_image_memory(
+                image,
+                allocation.memory(),
+                allocation.offset_in_bytes(),
+            )
+            .map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Error binding image memory",
+                )
+            });
+        if let Err(err) = self.record_device_loss(result) {
+            self.free(allocation);
+            return Err(err);
+        }
+        Ok(allocation)
+    }
+
+    /// Allocate memory for an image, preferring lazily-allocated memory for
+    /// transient attachments.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must be a valid image created by this allocator's device
+    #[track_caller]
+    unsafe fn allocate_image_memory(
+        &self,
+        image: vk::Image,
+        image_create_info: &vk::ImageCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, AllocatorError> {
+        let resource_kind =
+            ResourceKind::for_image_tiling(image_create_info.tiling);
+        let wants_lazy_memory = image_create_info
+            .usage
+            .contains(vk::ImageUsageFlags::TRANSIENT_ATTACHMENT)
+            && !memory_property_flags
+                .contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED)
+            && self.memory_properties.has_lazily_allocated_memory();
+        if wants_lazy_memory {
+            // The closure would otherwise be recorded as the caller.
+            let location = Location::caller();
+            let lazy_allocation = AllocationRequirements::for_image(
+                &self.device,
+                self.memory_properties.types(),
+                memory_property_flags
+                    | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+                image,
+            )
+            .and_then(|requirements| {
+                self.allocate(AllocationRequirements {
+                    resource_kind,
+                    location: Some(location),
+                    ..requirements
+                })
+            });
+            match lazy_allocation {
+                Ok(allocation) => return Ok(allocation),
+                Err(err) => log::debug!(
+                    "Falling back to {:#?} for a transient attachment: {}",
+                    memory_property_flags,
+                    err
+                ),
+            }
+        }
+
+        let requirements = AllocationRequirements::for_image(
+            &self.device,
+            self.memory_properties.types(),
+            memory_property_flags,
+            image,
+        )?;
+        self.allocate(AllocationRequirements {
+            resource_kind,
+            ..requirements
+        })
     }
 }
 
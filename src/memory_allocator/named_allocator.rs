@@ -0,0 +1,86 @@
+use crate::{
+    Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+    ComposableAllocator, MemoryReport, PoolConfigurator,
+};
+
+/// An allocator decorator which names a node in the allocator tree.
+///
+/// The name is prepended to the path of every allocation which passes through
+/// this node, see [Allocation::path]. Wrap each interesting node in the tree
+/// to make multi-tier reports attributable.
+pub struct NamedAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    name: String,
+}
+
+impl<T: ComposableAllocator> NamedAllocator<T> {
+    /// Create a new named allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator being named.
+    /// * name: the path segment for this node, e.g. `Sized<64kb>`.
+    pub fn new(wrapped_allocator: T, name: impl Into<String>) -> Self {
+        Self {
+            wrapped_allocator,
+            name: name.into(),
+        }
+    }
+
+    /// The name of this node.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for NamedAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let mut allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        allocation.prepend_path_segment(&self.name);
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        report.with_owner(&self.name, |report| {
+            self.wrapped_allocator.report(report)
+        })
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
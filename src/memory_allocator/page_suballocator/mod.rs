@@ -1,15 +1,16 @@
 //! An allocator which allocates chunks from an existing allocation.
 
-mod page_arena;
+pub(crate) mod page_arena;
 
 use {
-    crate::{Allocation, AllocatorError},
-    anyhow::Context,
+    crate::{Allocation, AllocatorError, MemoryRun, ResourceKind},
+    anyhow::{anyhow, Context},
 };
 
 pub struct PageSuballocator {
     allocation: Allocation,
     page_size_in_bytes: u64,
+    buffer_image_granularity: u64,
     arena: page_arena::PageArena,
 }
 
@@ -39,10 +40,33 @@ impl PageSuballocator {
         Self {
             allocation,
             page_size_in_bytes,
+            buffer_image_granularity: 1,
             arena: page_arena::PageArena::new(page_count as usize),
         }
     }
 
+    /// Keep linear and optimal suballocations apart by the device's
+    /// bufferImageGranularity, see [Self::allocate_resource].
+    pub fn with_buffer_image_granularity(
+        self,
+        buffer_image_granularity: u64,
+    ) -> Self {
+        Self {
+            buffer_image_granularity: buffer_image_granularity.max(1),
+            ..self
+        }
+    }
+
+    /// Place suballocations at random suitable offsets instead of the first
+    /// available offset. This is a testing mode, see
+    /// [page_arena::PageArena::with_random_placement].
+    pub fn with_random_placement(self, seed: u64) -> Self {
+        Self {
+            arena: self.arena.with_random_placement(seed),
+            ..self
+        }
+    }
+
     /// Releases ownership of the underlying allocation.
     ///
     /// # Safety
@@ -60,6 +84,52 @@ impl PageSuballocator {
         self.arena.is_empty()
     }
 
+    /// The total size of every free page, in bytes.
+    pub fn free_bytes(&self) -> u64 {
+        self.arena.free_page_count() as u64 * self.page_size_in_bytes
+    }
+
+    /// The size of the largest contiguous range of free pages, in bytes.
+    pub fn largest_free_block(&self) -> u64 {
+        self.arena.largest_free_run() as u64 * self.page_size_in_bytes
+    }
+
+    /// Every used and free run of pages, ordered by offset. Offsets are
+    /// relative to the start of the suballocator's allocation.
+    pub fn runs(&self) -> Vec<MemoryRun> {
+        self.arena
+            .runs()
+            .into_iter()
+            .map(|(first_page, page_count, used)| MemoryRun {
+                offset_in_bytes: first_page as u64 * self.page_size_in_bytes,
+                size_in_bytes: page_count as u64 * self.page_size_in_bytes,
+                used,
+            })
+            .collect()
+    }
+
+    /// The allocation which is divided into pages.
+    pub fn allocation(&self) -> &Allocation {
+        &self.allocation
+    }
+
+    /// Check that the page bookkeeping is consistent.
+    pub fn validate(&self) -> Result<(), AllocatorError> {
+        self.arena.validate().map_err(|err| anyhow!(err).into())
+    }
+
+    /// Free every suballocation at once.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// * the application must ensure that no suballocations are in-use after
+    ///   this call.
+    /// * existing suballocations must not be freed after this call.
+    pub unsafe fn reset(&mut self) {
+        self.arena.reset();
+    }
+
     /// Suballocate a region of memory.
     ///
     /// # Params
@@ -80,18 +150,20 @@ impl PageSuballocator {
         size_in_bytes: u64,
         alignment: u64,
     ) -> Result<Allocation, AllocatorError> {
-        if (self.allocation.offset_in_bytes() + self.page_size_in_bytes)
-            % alignment
-            == 0
+        // Treat an alignment of 0 (e.g. from default requirements) as no
+        // alignment requirement at all.
+        let alignment = alignment.max(1);
+        if self.allocation.offset_in_bytes() % alignment == 0
+            && self.page_size_in_bytes % alignment == 0
         {
-            // The page boundaries are already aligned for this request, so
+            // Every page boundary is already aligned for this request, so
             // no extra work is needed.
-            return self.allocate_unaligned(size_in_bytes);
+            return self.allocate_unaligned(size_in_bytes, alignment);
         }
 
         // Add enough additional size that the offset can be aligned.
         let aligned_size = size_in_bytes + (alignment - 1);
-        let unaligned = self.allocate_unaligned(aligned_size)?;
+        let unaligned = self.allocate_unaligned(aligned_size, 1)?;
 
         // How many bytes must the offset be advanced to reach the next aligned
         // value?
@@ -107,9 +179,64 @@ impl PageSuballocator {
             }
         };
 
+        // Suballocate from the original allocation rather than the unaligned
+        // chunk so the result's parent is always this suballocator's
+        // allocation.
+        let relative_offset =
+            unaligned.offset_in_bytes() - self.allocation.offset_in_bytes();
         Ok(Allocation::suballocate(
-            &unaligned,
-            alignment_correction,
+            &self.allocation,
+            relative_offset + alignment_correction,
+            size_in_bytes,
+            alignment,
+        ))
+    }
+
+    /// Suballocate a region of memory for a resource of a known kind.
+    ///
+    /// Linear and optimal resources must not share a bufferImageGranularity
+    /// sized page of memory. When the page size doesn't already keep
+    /// resources apart, the resource is given whole granularity pages of its
+    /// own.
+    ///
+    /// # Params
+    ///
+    /// * size_in_bytes: the required size of the allocation.
+    /// * alignment: the required alignment of the allocation.
+    /// * resource_kind: the kind of resource which will be bound to the memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because
+    /// * The caller must free the returned allocation
+    /// * The caller is responsible for synchronizing access (CPU and GPU) to
+    ///   the underlying memory
+    pub unsafe fn allocate_resource(
+        &mut self,
+        size_in_bytes: u64,
+        alignment: u64,
+        resource_kind: ResourceKind,
+    ) -> Result<Allocation, AllocatorError> {
+        let granularity = self.buffer_image_granularity;
+        let pages_are_separated = self.page_size_in_bytes % granularity == 0
+            && self.allocation.offset_in_bytes() % granularity == 0;
+        if resource_kind == ResourceKind::Unknown || pages_are_separated {
+            return self.allocate(size_in_bytes, alignment);
+        }
+
+        let alignment = alignment.max(granularity);
+        let padded = self.allocate(
+            div_ceil(size_in_bytes, granularity) * granularity,
+            alignment,
+        )?;
+
+        // Only the pages matter for freeing, so the padding is dropped from
+        // the allocation's size.
+        let relative_offset =
+            padded.offset_in_bytes() - self.allocation.offset_in_bytes();
+        Ok(Allocation::suballocate(
+            &self.allocation,
+            relative_offset,
             size_in_bytes,
             alignment,
         ))
@@ -121,6 +248,8 @@ impl PageSuballocator {
     /// # Params
     ///
     /// * size_in_bytes: the required size of the allocation.
+    /// * offset_alignment: the alignment recorded on the allocation. The caller
+    ///   must ensure that every page boundary satisfies it.
     ///
     /// # Safety
     ///
@@ -133,6 +262,7 @@ impl PageSuballocator {
     unsafe fn allocate_unaligned(
         &mut self,
         size_in_bytes: u64,
+        offset_alignment: u64,
     ) -> Result<Allocation, AllocatorError> {
         let page_count =
             div_ceil(size_in_bytes, self.page_size_in_bytes) as usize;
@@ -144,7 +274,7 @@ impl PageSuballocator {
             &self.allocation,
             starting_index as u64 * self.page_size_in_bytes,
             size_in_bytes,
-            1,
+            offset_alignment,
         ))
     }
 
@@ -173,7 +303,7 @@ impl PageSuballocator {
 }
 
 /// Divide top/bottom, rounding towards positive infinity.
-fn div_ceil(top: u64, bottom: u64) -> u64 {
+pub(crate) fn div_ceil(top: u64, bottom: u64) -> u64 {
     (top / bottom) + u64::from(top % bottom != 0)
 }
 
@@ -9,6 +9,8 @@
 //! * Arena: A collection of contiguous pages.
 //! * Chunk: A contiguous subset of pages which can be allocated from the arena.
 
+use crate::memory_allocator::XorShiftRng;
+
 /// A representation of a single unit of memory with a fixed size.
 /// Pages can either be free or allocated. Pages are allocated in contiguous
 /// chunks and they each keep track of where their current chunk begins.
@@ -27,6 +29,7 @@ enum Page {
 pub struct PageArena {
     pages: Vec<Page>,
     allocation_count: usize,
+    random_placement: Option<XorShiftRng>,
 }
 
 impl PageArena {
@@ -40,6 +43,23 @@ impl PageArena {
         Self {
             pages: vec![Page::Free; page_count],
             allocation_count: 0,
+            random_placement: None,
+        }
+    }
+
+    /// Place chunks at a random suitable location instead of the first one.
+    ///
+    /// This is a testing mode. It shakes out code which accidentally relies on
+    /// deterministic offsets and helps fuzz alignment handling. The same seed
+    /// always produces the same sequence of placements.
+    ///
+    /// # Params
+    ///
+    /// * seed - the seed for the random number generator.
+    pub fn with_random_placement(self, seed: u64) -> Self {
+        Self {
+            random_placement: Some(XorShiftRng::new(seed)),
+            ..self
         }
     }
 
@@ -48,6 +68,81 @@ impl PageArena {
         self.allocation_count == 0
     }
 
+    /// The number of free pages.
+    pub fn free_page_count(&self) -> usize {
+        self.pages
+            .iter()
+            .filter(|&&page| page == Page::Free)
+            .count()
+    }
+
+    /// The length of the longest run of contiguous free pages.
+    pub fn largest_free_run(&self) -> usize {
+        let mut largest = 0;
+        let mut free_run = 0;
+        for &page in &self.pages {
+            if page == Page::Free {
+                free_run += 1;
+                largest = largest.max(free_run);
+            } else {
+                free_run = 0;
+            }
+        }
+        largest
+    }
+
+    /// Every run of free pages and every allocated chunk, ordered by index.
+    ///
+    /// # Returns
+    ///
+    /// A list of (first page, page count, allocated) tuples.
+    pub fn runs(&self) -> Vec<(usize, usize, bool)> {
+        let mut runs: Vec<(usize, usize, bool)> = vec![];
+        let mut previous = None;
+        for (index, &page) in self.pages.iter().enumerate() {
+            match runs.last_mut() {
+                Some((_, page_count, _)) if previous == Some(page) => {
+                    *page_count += 1;
+                }
+                _ => runs.push((index, 1, page != Page::Free)),
+            }
+            previous = Some(page);
+        }
+        runs
+    }
+
+    /// Check that every allocated page belongs to a contiguous chunk and that
+    /// the number of chunks matches the allocation count.
+    ///
+    /// # Returns
+    ///
+    /// A description of the first inconsistency, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut chunk_count = 0;
+        let mut previous = Page::Free;
+        for (index, &page) in self.pages.iter().enumerate() {
+            if let Page::Allocated { first_in_chunk } = page {
+                if first_in_chunk == index {
+                    chunk_count += 1;
+                } else if previous != page {
+                    return Err(format!(
+                        "Page {} claims to be in the chunk starting at {} \
+                         but the chunk is not contiguous",
+                        index, first_in_chunk
+                    ));
+                }
+            }
+            previous = page;
+        }
+        if chunk_count != self.allocation_count {
+            return Err(format!(
+                "Found {} chunks but expected {} allocations",
+                chunk_count, self.allocation_count
+            ));
+        }
+        Ok(())
+    }
+
     /// Allocate a chunk of contiguous pages.
     ///
     /// # Params
@@ -59,7 +154,11 @@ impl PageArena {
     /// * Some(index) - the index of the first page in the allocated chunk.
     /// * None - when the chunk could not be allocated
     pub fn allocate_chunk(&mut self, page_count: usize) -> Option<usize> {
-        let first_in_chunk = self.find_first_free_chunk(page_count)?;
+        let first_in_chunk = if self.random_placement.is_some() {
+            self.find_random_free_chunk(page_count)?
+        } else {
+            self.find_first_free_chunk(page_count)?
+        };
 
         debug_assert!(first_in_chunk + page_count <= self.pages.len());
         for page in self.pages.iter_mut().skip(first_in_chunk).take(page_count)
@@ -76,6 +175,12 @@ impl PageArena {
         Some(first_in_chunk)
     }
 
+    /// Free every chunk at once.
+    pub fn reset(&mut self) {
+        self.pages.fill(Page::Free);
+        self.allocation_count = 0;
+    }
+
     /// Free a chunk of contiguous pages.
     ///
     /// # Params
@@ -135,6 +240,38 @@ impl PageArena {
         }
         None
     }
+
+    /// Find the index of a random contiguous free chunk that is large enough
+    /// to fit the requested size.
+    ///
+    /// # Params
+    ///
+    /// * page_count: The number of contiguous free pages being requested.
+    ///
+    /// # Returns
+    ///
+    /// * Some(index): The index of a free page which has at least page_count
+    ///   free pages after it. Every such index is equally likely.
+    /// * None: When there isn't enough space.
+    fn find_random_free_chunk(&mut self, page_count: usize) -> Option<usize> {
+        let mut candidates = vec![];
+        let mut free_run = 0;
+        for (index, &value) in self.pages.iter().enumerate() {
+            if value == Page::Free {
+                free_run += 1;
+            } else {
+                free_run = 0;
+            }
+            if free_run >= page_count {
+                candidates.push(index + 1 - page_count);
+            }
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+        let rng = self.random_placement.as_mut()?;
+        Some(candidates[rng.next_below(candidates.len())])
+    }
 }
 
 #[cfg(test)]
@@ -168,6 +305,7 @@ mod test {
         PageArena {
             pages: pages_from_str(pages),
             allocation_count,
+            random_placement: None,
         }
     }
 
@@ -210,6 +348,25 @@ mod test {
         assert_eq!(pages_to_str(&arena.pages), "0000055777");
     }
 
+    #[test]
+    fn test_largest_free_run() {
+        assert_eq!(PageArena::new(5).largest_free_run(), 5);
+        let arena = arena_with_pages("f|1|1|f|f|f|6|6|6|6|f|f", 2);
+        assert_eq!(arena.largest_free_run(), 3);
+        assert_eq!(arena_with_pages("0|0", 1).largest_free_run(), 0);
+        assert_eq!(arena.free_page_count(), 6);
+    }
+
+    #[test]
+    fn test_runs() {
+        let arena = arena_with_pages("f|1|1|3|f|f", 2);
+        assert_eq!(
+            arena.runs(),
+            vec![(0, 1, false), (1, 2, true), (3, 1, true), (4, 2, false)]
+        );
+        assert_eq!(PageArena::new(3).runs(), vec![(0, 3, false)]);
+    }
+
     #[test]
     fn test_page_arena_free() {
         let mut arena = arena_with_pages("f|f|2|2|2|2", 1);
@@ -243,6 +400,16 @@ mod test {
         assert!(arena.is_empty());
     }
 
+    #[test]
+    fn test_page_arena_reset() {
+        let mut arena = arena_with_pages("0|0|2|2|2|5|f|f", 3);
+        arena.reset();
+        assert!(arena.is_empty());
+        assert_eq!(pages_to_str(&arena.pages), "ffffffff");
+        assert!(arena.validate().is_ok());
+        assert_eq!(arena.allocate_chunk(8), Some(0));
+    }
+
     #[test]
     fn test_smoke_test() {
         let mut chunks = vec![];
@@ -261,4 +428,46 @@ mod test {
 
         assert!(arena.is_empty());
     }
+
+    #[test]
+    fn test_find_random_free_chunk() {
+        let mut arena = arena_with_pages("f|1|1|f|f|f|6|6|6|6|f|f", 2)
+            .with_random_placement(7);
+        for _ in 0..100 {
+            let index = arena.find_random_free_chunk(2).unwrap();
+            assert!([3, 4, 10].contains(&index), "{}", index);
+        }
+        assert_eq!(arena.find_random_free_chunk(3), Some(3));
+        assert_eq!(arena.find_random_free_chunk(4), None);
+    }
+
+    #[test]
+    fn test_random_placement_is_reproducible() {
+        let placements = |seed: u64| {
+            let mut arena = PageArena::new(100).with_random_placement(seed);
+            (0..10)
+                .map(|_| arena.allocate_chunk(3).unwrap())
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(placements(42), placements(42));
+        assert_ne!(placements(42), (0..10).map(|i| i * 3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_random_placement_fills_the_arena() {
+        let mut arena = PageArena::new(16).with_random_placement(3);
+        for _ in 0..16 {
+            assert!(arena.allocate_chunk(1).is_some());
+        }
+        assert_eq!(arena.allocate_chunk(1), None);
+        assert_eq!(pages_to_str(&arena.pages), "0123456789101112131415");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(arena_with_pages("f|1|1|f|4|5|5", 3).validate().is_ok());
+        assert!(arena_with_pages("f|1|1|f|4|5|5", 2).validate().is_err());
+        assert!(arena_with_pages("f|1|1|f|1|f", 1).validate().is_err());
+        assert!(arena_with_pages("f|0|f", 0).validate().is_err());
+    }
 }
@@ -0,0 +1,95 @@
+use {
+    crate::{Allocation, AllocatorError},
+    anyhow::anyhow,
+    ash::vk,
+};
+
+/// Changes the priority of live device memory with
+/// VK_EXT_pageable_device_local_memory.
+///
+/// With the extension, the OS pages out low-priority memory when the device
+/// is under pressure, rather than failing new allocations.
+pub(crate) struct PageableMemory {
+    device: ash::Device,
+    pageable_device_local_memory: Option<vk::ExtPageableDeviceLocalMemoryFn>,
+}
+
+impl PageableMemory {
+    /// Load the extension entrypoint.
+    ///
+    /// The extension does not need to be enabled. In that case
+    /// [Self::is_supported] is false and every priority change fails with an
+    /// error.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the device must not be destroyed while this object still exists
+    pub(crate) unsafe fn new(
+        instance: &ash::Instance,
+        device: ash::Device,
+    ) -> Self {
+        // The device only returns the entrypoint when the extension is
+        // enabled.
+        let mut enabled = true;
+        let pageable_device_local_memory =
+            vk::ExtPageableDeviceLocalMemoryFn::load(|name| {
+                let function = instance
+                    .get_device_proc_addr(device.handle(), name.as_ptr());
+                enabled &= function.is_some();
+                std::mem::transmute(function)
+            });
+        let pageable_device_local_memory =
+            enabled.then_some(pageable_device_local_memory);
+        Self {
+            device,
+            pageable_device_local_memory,
+        }
+    }
+
+    /// True when VK_EXT_pageable_device_local_memory is enabled on the
+    /// device.
+    pub(crate) fn is_supported(&self) -> bool {
+        self.pageable_device_local_memory.is_some()
+    }
+
+    /// Set the priority of the device memory which backs an allocation.
+    ///
+    /// # Params
+    ///
+    /// * allocation: the allocation to update.
+    /// * priority: the new priority, between 0.0 and 1.0.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///  - the allocation's memory must not have been freed
+    pub(crate) unsafe fn set_priority(
+        &self,
+        allocation: &Allocation,
+        priority: f32,
+    ) -> Result<(), AllocatorError> {
+        if !(0.0..=1.0).contains(&priority) {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Memory priority must be between 0.0 and 1.0, got {}",
+                priority
+            )));
+        }
+        let pageable_device_local_memory = match &self
+            .pageable_device_local_memory
+        {
+            Some(pageable_device_local_memory) => pageable_device_local_memory,
+            None => {
+                return Err(AllocatorError::RuntimeError(anyhow!(
+                    "VK_EXT_pageable_device_local_memory is not enabled"
+                )));
+            }
+        };
+        (pageable_device_local_memory.set_device_memory_priority_ext)(
+            self.device.handle(),
+            allocation.memory(),
+            priority,
+        );
+        Ok(())
+    }
+}
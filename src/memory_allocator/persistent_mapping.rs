@@ -0,0 +1,92 @@
+use crate::{
+    Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+    ComposableAllocator, MemoryReport, PoolConfigurator,
+};
+
+/// Maps allocations which request it once, when they're allocated, and keeps
+/// them mapped until they're freed. See
+/// [AllocationRequirements::persistently_mapped].
+pub(crate) struct PersistentMapping<T: ComposableAllocator> {
+    device: ash::Device,
+    wrapped_allocator: T,
+}
+
+impl<T: ComposableAllocator> PersistentMapping<T> {
+    pub fn new(device: ash::Device, wrapped_allocator: T) -> Self {
+        Self {
+            device,
+            wrapped_allocator,
+        }
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for PersistentMapping<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        if !allocation_requirements.persistently_mapped {
+            return self.wrapped_allocator.allocate(allocation_requirements);
+        }
+        if !allocation_requirements
+            .memory_properties
+            .contains(ash::vk::MemoryPropertyFlags::HOST_VISIBLE)
+        {
+            return Err(AllocatorError::NotHostVisible(
+                allocation_requirements.memory_properties,
+            ));
+        }
+
+        let mut allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        if let Err(err) = allocation.map(&self.device) {
+            self.wrapped_allocator.free(allocation);
+            return Err(err);
+        }
+        allocation.set_persistently_mapped(true);
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        if allocation.is_persistently_mapped() {
+            if let Err(err) = allocation.unmap(&self.device) {
+                log::error!("Unable to unmap a persistent mapping: {}", err);
+            }
+        }
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
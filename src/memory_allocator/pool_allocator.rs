@@ -1,8 +1,11 @@
 use {
     crate::{
-        Allocation, AllocationRequirements, AllocatorError,
-        ComposableAllocator, MemoryProperties, MemoryTypePoolAllocator,
+        Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+        ComposableAllocator, IdGenerator, MemoryProperties, MemoryReport,
+        MemoryTypePoolAllocator, PoolConfigurator, PoolSettings,
     },
+    anyhow::anyhow,
+    ash::vk,
     std::{
         collections::HashMap,
         sync::{Arc, Mutex},
@@ -11,8 +14,25 @@ use {
 
 type SharedAllocator<T> = Arc<Mutex<T>>;
 
+/// Suballocates from a separate [MemoryTypePoolAllocator] for each memory
+/// type. Pools are created the first time a memory type is used, so devices
+/// with many memory types only pay for the ones the application needs.
 pub struct PoolAllocator<A: ComposableAllocator> {
     typed_pools: HashMap<usize, MemoryTypePoolAllocator<SharedAllocator<A>>>,
+    memory_properties: MemoryProperties,
+    chunk_size: u64,
+    page_size: u64,
+    host_visible_chunk_size: Option<u64>,
+    heap_chunk_divisor: Option<u64>,
+    max_chunk_size: Option<u64>,
+    max_pool_bytes: Option<u64>,
+    max_empty_chunks: usize,
+    random_placement_seed: Option<u64>,
+    chunk_ids: Option<IdGenerator>,
+
+    /// Provides chunks for every pool. Allocations which are too large for
+    /// their memory type's smaller chunks come straight from here.
+    allocator: SharedAllocator<A>,
 }
 
 impl<A: ComposableAllocator> PoolAllocator<A> {
@@ -22,24 +42,264 @@ impl<A: ComposableAllocator> PoolAllocator<A> {
         page_size: u64,
         allocator: A,
     ) -> Self {
-        let allocator = SharedAllocator::new(Mutex::new(allocator));
-        let typed_pools = memory_properties
-            .types()
-            .iter()
-            .enumerate()
-            .map(|(memory_type_index, _memory_type)| {
-                (
-                    memory_type_index,
-                    MemoryTypePoolAllocator::new(
+        Self {
+            typed_pools: HashMap::new(),
+            memory_properties,
+            chunk_size,
+            page_size,
+            host_visible_chunk_size: None,
+            heap_chunk_divisor: None,
+            max_chunk_size: None,
+            max_pool_bytes: None,
+            max_empty_chunks: 0,
+            random_placement_seed: None,
+            chunk_ids: None,
+            allocator: SharedAllocator::new(Mutex::new(allocator)),
+        }
+    }
+
+    /// Use smaller chunks for HOST_VISIBLE memory types. Host-visible heaps
+    /// are often much smaller than device-local heaps, e.g. 256 MB of BAR
+    /// memory on a discrete GPU. Allocations which are too large for the
+    /// smaller chunks come straight from the backing allocator.
+    ///
+    /// # Params
+    ///
+    /// * chunk_size: the largest chunk size for host-visible memory types.
+    ///   Memory types with smaller chunks are unchanged.
+    pub fn with_host_visible_chunk_size(self, chunk_size: u64) -> Self {
+        Self {
+            host_visible_chunk_size: Some(chunk_size),
+            ..self
+        }
+    }
+
+    /// Clamp each memory type's chunk size to a fraction of its heap. Small
+    /// heaps, e.g. the 256 MB DEVICE_LOCAL | HOST_VISIBLE heap on many
+    /// discrete GPUs, get pools with appropriately small chunks rather than a
+    /// chunk which takes up most of the heap. Allocations which are too large
+    /// for the clamped chunks come straight from the backing allocator.
+    ///
+    /// # Params
+    ///
+    /// * divisor: chunks are at most `heap size / divisor`, rounded down to a
+    ///   whole number of pages.
+    pub fn with_heap_chunk_divisor(self, divisor: u64) -> Self {
+        Self {
+            heap_chunk_divisor: Some(divisor.max(1)),
+            ..self
+        }
+    }
+
+    /// Grow each memory type's pool with larger chunks as it fills up, see
+    /// [MemoryTypePoolAllocator::with_chunk_growth].
+    ///
+    /// # Params
+    ///
+    /// * max_chunk_size: the largest chunk size.
+    pub fn with_chunk_growth(self, max_chunk_size: u64) -> Self {
+        Self {
+            max_chunk_size: Some(max_chunk_size),
+            ..self
+        }
+    }
+
+    /// Limit the total size of each memory type's chunks, see
+    /// [MemoryTypePoolAllocator::with_max_pool_size].
+    ///
+    /// # Params
+    ///
+    /// * max_pool_bytes: the most memory each memory type's pool can hold.
+    pub fn with_max_pool_size(self, max_pool_bytes: u64) -> Self {
+        Self {
+            max_pool_bytes: Some(max_pool_bytes),
+            ..self
+        }
+    }
+
+    /// Keep empty chunks for reuse in each memory type's pool, see
+    /// [MemoryTypePoolAllocator::with_empty_chunk_retention].
+    ///
+    /// # Params
+    ///
+    /// * max_empty_chunks: the most empty chunks each pool keeps.
+    pub fn with_empty_chunk_retention(self, max_empty_chunks: usize) -> Self {
+        Self {
+            max_empty_chunks,
+            ..self
+        }
+    }
+
+    /// Place allocations at random suitable offsets within each chunk. This
+    /// is a testing mode which shakes out code that relies on deterministic
+    /// offsets.
+    ///
+    /// # Params
+    ///
+    /// * seed: the seed used to derive the placement for every memory type.
+    pub fn with_random_placement(self, seed: u64) -> Self {
+        Self {
+            random_placement_seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Use a custom generator for the chunk ids in allocation paths. Each
+    /// memory type gets its own copy of the generator.
+    ///
+    /// # Params
+    ///
+    /// * chunk_ids: generates the id for each new chunk.
+    pub fn with_chunk_ids(self, chunk_ids: IdGenerator) -> Self {
+        Self {
+            chunk_ids: Some(chunk_ids),
+            ..self
+        }
+    }
+
+    /// The settings used for memory type pools which are created from now
+    /// on. Host-visible pools may use smaller chunks, see
+    /// [Self::with_host_visible_chunk_size].
+    pub fn settings(&self) -> PoolSettings {
+        PoolSettings {
+            chunk_size: self.chunk_size,
+            page_size: self.page_size,
+            max_chunk_size: self.max_chunk_size,
+            max_pool_bytes: self.max_pool_bytes,
+            max_empty_chunks: self.max_empty_chunks,
+        }
+    }
+
+    /// The number of memory types which have a pool.
+    pub fn pool_count(&self) -> usize {
+        self.typed_pools.len()
+    }
+
+    /// Invalidate every allocation from this pool at once, see
+    /// [MemoryTypePoolAllocator::reset].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must ensure that no allocations from this pool are
+    ///     in use by the GPU
+    ///   - allocations from this pool must not be freed after the reset
+    pub unsafe fn reset(&mut self) {
+        for pool in self.typed_pools.values_mut() {
+            pool.reset();
+        }
+    }
+}
+
+// Private API
+// -----------
+
+impl<A: ComposableAllocator> PoolAllocator<A> {
+    /// Get the pool for a memory type, creating it if needed.
+    ///
+    /// # Params
+    ///
+    /// * memory_type_index: the memory type the pool allocates from.
+    fn typed_pool_mut(
+        &mut self,
+        memory_type_index: usize,
+    ) -> Result<&mut MemoryTypePoolAllocator<SharedAllocator<A>>, AllocatorError>
+    {
+        let memory_type =
+            match self.memory_properties.types().get(memory_type_index) {
+                Some(memory_type) => *memory_type,
+                None => {
+                    return Err(AllocatorError::RuntimeError(anyhow!(
+                        "No pool exists for memory type {}, there are {} \
+                         memory types",
                         memory_type_index,
-                        chunk_size,
-                        page_size,
-                        allocator.clone(),
-                    ),
-                )
-            })
-            .collect::<HashMap<_, _>>();
-        Self { typed_pools }
+                        self.memory_properties.types().len()
+                    )));
+                }
+            };
+        let chunk_size = self.chunk_size_for(&memory_type);
+        let pool =
+            self.typed_pools
+                .entry(memory_type_index)
+                .or_insert_with(|| {
+                    let mut pool = MemoryTypePoolAllocator::new(
+                        memory_type_index,
+                        self.chunk_size,
+                        self.page_size,
+                        self.allocator.clone(),
+                    )
+                    .with_buffer_image_granularity(
+                        self.memory_properties.buffer_image_granularity(),
+                    );
+                    if chunk_size < self.chunk_size {
+                        pool = pool.with_chunk_size(chunk_size);
+                    }
+                    // Pools with clamped chunks don't grow past the clamp.
+                    match self.max_chunk_size {
+                        Some(max_chunk_size)
+                            if chunk_size == self.chunk_size =>
+                        {
+                            pool = pool.with_chunk_growth(max_chunk_size);
+                        }
+                        _ => (),
+                    }
+                    if let Some(max_pool_bytes) = self.max_pool_bytes {
+                        pool = pool.with_max_pool_size(max_pool_bytes);
+                    }
+                    pool =
+                        pool.with_empty_chunk_retention(self.max_empty_chunks);
+                    if let Some(seed) = self.random_placement_seed {
+                        pool = pool.with_random_placement(
+                            seed.wrapping_add(memory_type_index as u64),
+                        );
+                    }
+                    if let Some(chunk_ids) = self.chunk_ids {
+                        pool = pool.with_chunk_ids(chunk_ids);
+                    }
+                    pool
+                });
+        Ok(pool)
+    }
+
+    /// The chunk size for a memory type's pool after applying the
+    /// host-visible and heap size limits.
+    ///
+    /// # Params
+    ///
+    /// * memory_type: the memory type the pool allocates from.
+    fn chunk_size_for(&self, memory_type: &vk::MemoryType) -> u64 {
+        let mut chunk_size = self.chunk_size;
+        let is_host_visible = memory_type
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+        if let (true, Some(host_visible_chunk_size)) =
+            (is_host_visible, self.host_visible_chunk_size)
+        {
+            chunk_size = chunk_size.min(host_visible_chunk_size);
+        }
+        let heap = self
+            .memory_properties
+            .heaps()
+            .get(memory_type.heap_index as usize);
+        if let (Some(heap), Some(divisor)) = (heap, self.heap_chunk_divisor) {
+            let limit = heap.size / divisor;
+            let limit = limit - limit % self.page_size;
+            if limit >= self.page_size {
+                chunk_size = chunk_size.min(limit);
+            }
+        }
+        chunk_size
+    }
+
+    /// Returns true when an allocation is too large for its memory type's
+    /// smaller chunks but would fit in the pool's usual chunks.
+    fn is_oversized(
+        pool: &MemoryTypePoolAllocator<SharedAllocator<A>>,
+        chunk_size: u64,
+        allocation_requirements: &AllocationRequirements,
+    ) -> bool {
+        let size = allocation_requirements.aligned_size();
+        size >= pool.chunk_size() && size < chunk_size
     }
 }
 
@@ -48,18 +308,79 @@ impl<A: ComposableAllocator> ComposableAllocator for PoolAllocator<A> {
         &mut self,
         allocation_requirements: AllocationRequirements,
     ) -> Result<Allocation, AllocatorError> {
-        let pool = self
-            .typed_pools
-            .get_mut(&allocation_requirements.memory_type_index)
-            .unwrap();
+        let chunk_size = self.chunk_size;
+        let pool =
+            self.typed_pool_mut(allocation_requirements.memory_type_index)?;
+        if Self::is_oversized(pool, chunk_size, &allocation_requirements) {
+            return self.allocator.allocate(allocation_requirements);
+        }
         pool.allocate(allocation_requirements)
     }
 
     unsafe fn free(&mut self, allocation: Allocation) {
-        let pool = self
-            .typed_pools
-            .get_mut(&allocation.memory_type_index())
-            .unwrap();
-        pool.free(allocation)
+        // Route by ownership rather than by size. Pools can be reconfigured
+        // after the allocation was made, so its size no longer says which
+        // allocator it came from.
+        match self.typed_pools.get_mut(&allocation.memory_type_index()) {
+            Some(pool) if pool.owns(&allocation) => pool.free(allocation),
+            Some(_) => self.allocator.free(allocation),
+            None => log::error!(
+                "Attempted to free an allocation with memory type {} which \
+                 does not belong to this pool!",
+                allocation.memory_type_index()
+            ),
+        }
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        for pool in self.typed_pools.values() {
+            pool.validate()?;
+        }
+        Ok(())
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        for pool in self.typed_pools.values() {
+            pool.stats(stats);
+        }
+        self.allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        for pool in self.typed_pools.values() {
+            pool.report(report);
+        }
+        self.allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        for pool in self.typed_pools.values_mut() {
+            pool.trim();
+        }
+        self.allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        let settings = configurator.apply(self.settings())?;
+        self.chunk_size = settings.chunk_size;
+        self.page_size = settings.page_size;
+        self.max_chunk_size = settings.max_chunk_size;
+        self.max_pool_bytes = settings.max_pool_bytes;
+        self.max_empty_chunks = settings.max_empty_chunks;
+        for pool in self.typed_pools.values_mut() {
+            pool.reconfigure_pools(configurator)?;
+        }
+        self.allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.typed_pool_mut(allocation_requirements.memory_type_index)?
+            .reserve(allocation_requirements)
     }
 }
@@ -0,0 +1,129 @@
+use {
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError,
+        ComposableAllocator, MemoryAllocator, PoolAllocator,
+    },
+    anyhow::anyhow,
+    std::sync::{Arc, Mutex},
+};
+
+type SharedAllocator = Arc<Mutex<Box<dyn ComposableAllocator + Send>>>;
+
+/// A pool which belongs to the application, see
+/// [MemoryAllocator::create_pool].
+///
+/// The pool's chunks come from the allocator which created it. Every
+/// allocation from the pool can be invalidated at once with [Self::reset],
+/// which is much faster than freeing each one, e.g. when unloading a level.
+///
+/// Cloning is cheap, clones are handles to the same pool.
+#[derive(Clone)]
+pub struct PoolHandle {
+    allocator: MemoryAllocator,
+    chunk_size: u64,
+    pool: Arc<Mutex<PoolAllocator<SharedAllocator>>>,
+}
+
+// Public API
+// ----------
+
+impl PoolHandle {
+    /// Allocate memory from the pool.
+    ///
+    /// # Params
+    ///
+    /// - `allocation_requirements` - the size, alignment, and memory type
+    ///   needed by the resource. It must fit in one of the pool's chunks.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the memory must be freed with [Self::free], or invalidated with
+    ///     [Self::reset], before the device is destroyed
+    #[track_caller]
+    pub unsafe fn allocate(
+        &self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.allocator.check_can_allocate()?;
+        if allocation_requirements.aligned_size() >= self.chunk_size {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to allocate {} bytes from a pool with {} byte chunks",
+                allocation_requirements.size_in_bytes,
+                self.chunk_size
+            )));
+        }
+        let allocation_requirements =
+            self.allocator.apply_handle_options(allocation_requirements);
+        self.pool.lock().unwrap().allocate(allocation_requirements)
+    }
+
+    /// Free memory which was allocated with [Self::allocate].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - any resources bound to the memory must be destroyed first, or never
+    ///     used again
+    ///   - the allocation must not have been invalidated by [Self::reset]
+    pub unsafe fn free(&self, allocation: Allocation) {
+        self.pool.lock().unwrap().free(allocation)
+    }
+
+    /// Invalidate every allocation from the pool at once. The chunks are kept
+    /// for later allocations, see [PoolAllocator::reset].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must be done with every allocation from the pool
+    ///   - allocations from the pool must not be used or freed after the reset
+    pub unsafe fn reset(&self) {
+        self.pool.lock().unwrap().reset()
+    }
+
+    /// Return the pool's empty chunks to the allocator which created it,
+    /// which then releases its own unused memory, see
+    /// [ComposableAllocator::trim].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must be done with every allocation which was freed or
+    ///     invalidated, whether it came from the pool or the allocator
+    pub unsafe fn trim(&self) {
+        self.pool.lock().unwrap().trim()
+    }
+}
+
+// Private API
+// -----------
+
+impl PoolHandle {
+    /// Create a pool which takes its chunks from the allocator.
+    ///
+    /// # Params
+    ///
+    /// * allocator: the allocator which provides the chunks.
+    /// * internal_allocator: the allocator's composition.
+    /// * chunk_size: the size of each chunk.
+    /// * page_size: the size of each page in a chunk.
+    pub(crate) fn new(
+        allocator: MemoryAllocator,
+        internal_allocator: SharedAllocator,
+        chunk_size: u64,
+        page_size: u64,
+    ) -> Self {
+        let pool = PoolAllocator::new(
+            allocator.memory_properties().clone(),
+            chunk_size,
+            page_size,
+            internal_allocator,
+        );
+        Self {
+            allocator,
+            chunk_size,
+            pool: Arc::new(Mutex::new(pool)),
+        }
+    }
+}
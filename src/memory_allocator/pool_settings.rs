@@ -0,0 +1,128 @@
+use {crate::AllocatorError, anyhow::anyhow, std::collections::HashSet};
+
+/// The parameters which control how a pool creates chunks, see
+/// [crate::MemoryTypePoolAllocator::settings].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PoolSettings {
+    /// The size of each new chunk.
+    pub chunk_size: u64,
+
+    /// New chunks are divided into pages with this size.
+    pub page_size: u64,
+
+    /// When set, each new chunk is twice as large as the one before it, up to
+    /// this size.
+    pub max_chunk_size: Option<u64>,
+
+    /// The most memory the pool's chunks can hold in total.
+    pub max_pool_bytes: Option<u64>,
+
+    /// The number of empty chunks which are kept for reuse.
+    pub max_empty_chunks: usize,
+}
+
+impl PoolSettings {
+    /// Check that chunks can be divided into pages with these settings.
+    pub fn validate(&self) -> Result<(), AllocatorError> {
+        if self.page_size == 0 || self.chunk_size == 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Pool chunk size {} and page size {} must be nonzero",
+                self.chunk_size,
+                self.page_size
+            )));
+        }
+        if self.chunk_size % self.page_size != 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Pool chunk size {} is not a multiple of the page size {}",
+                self.chunk_size,
+                self.page_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Adjusts the settings of every pool in an allocator composition, see
+/// [crate::MemoryAllocator::reconfigure_pools].
+///
+/// Composable allocators update their own pools in
+/// [crate::ComposableAllocator::reconfigure_pools].
+pub struct PoolConfigurator<'a> {
+    reconfigure: &'a mut dyn FnMut(&mut PoolSettings),
+
+    /// Shared allocators which have already been reconfigured.
+    visited: HashSet<usize>,
+}
+
+impl<'a> PoolConfigurator<'a> {
+    /// Create a configurator which applies a function to each pool's
+    /// settings.
+    ///
+    /// # Params
+    ///
+    /// * reconfigure: called once for each pool with its current settings.
+    pub fn new(reconfigure: &'a mut dyn FnMut(&mut PoolSettings)) -> Self {
+        Self {
+            reconfigure,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Compute a pool's new settings.
+    ///
+    /// # Params
+    ///
+    /// * settings: the pool's current settings.
+    ///
+    /// # Returns
+    ///
+    /// The new settings, or an error if they are invalid. The pool should be
+    /// left unchanged on error.
+    pub fn apply(
+        &mut self,
+        settings: PoolSettings,
+    ) -> Result<PoolSettings, AllocatorError> {
+        let mut updated = settings;
+        (self.reconfigure)(&mut updated);
+        updated.validate()?;
+        Ok(updated)
+    }
+
+    /// Record that a shared allocator is being visited.
+    ///
+    /// # Returns
+    ///
+    /// True the first time an address is visited.
+    pub(crate) fn visit(&mut self, address: usize) -> bool {
+        self.visited.insert(address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_rejects_invalid_settings() {
+        let settings = PoolSettings {
+            chunk_size: 1024,
+            page_size: 64,
+            max_chunk_size: None,
+            max_pool_bytes: None,
+            max_empty_chunks: 0,
+        };
+
+        let mut double = |settings: &mut PoolSettings| {
+            settings.chunk_size *= 2;
+        };
+        let updated =
+            PoolConfigurator::new(&mut double).apply(settings).unwrap();
+        assert_eq!(updated.chunk_size, 2048);
+        assert_eq!(updated.page_size, 64);
+
+        let mut uneven = |settings: &mut PoolSettings| {
+            settings.page_size = 100;
+        };
+        assert!(PoolConfigurator::new(&mut uneven).apply(settings).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use {
+    super::PolicyClock,
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+        ComposableAllocator, FrameClock, MemoryReport, PoolConfigurator,
+    },
+    ash::vk,
+    std::collections::VecDeque,
+};
+
+/// Controls how long freed allocations stay in quarantine.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuarantinePolicy {
+    /// Keep the N most recently freed allocations in quarantine.
+    Frees(usize),
+
+    /// Keep freed allocations in quarantine for N calls to end_frame().
+    Frames(u64),
+}
+
+/// An allocator decorator which delays returning freed allocations to the
+/// wrapped allocator.
+///
+/// Memory which is still referenced by the GPU after being freed would
+/// normally be handed out again almost immediately, making use-after-free
+/// bugs intermittent. Holding freed memory in quarantine (and optionally
+/// filling it with a poison pattern) makes those bugs reproduce
+/// deterministically in debug builds.
+pub struct QuarantineAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    policy: QuarantinePolicy,
+    poison: Option<(ash::Device, u8)>,
+    clock: PolicyClock,
+    quarantine: VecDeque<(u64, Allocation)>,
+}
+
+impl<T: ComposableAllocator> QuarantineAllocator<T> {
+    /// Create a new quarantine allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator which eventually receives freed
+    ///   allocations.
+    /// * policy: controls how long freed allocations remain in quarantine.
+    pub fn new(wrapped_allocator: T, policy: QuarantinePolicy) -> Self {
+        Self {
+            wrapped_allocator,
+            policy,
+            poison: None,
+            clock: PolicyClock::default(),
+            quarantine: VecDeque::new(),
+        }
+    }
+
+    /// Count frames with a clock which is advanced by the application,
+    /// rather than by [Self::end_frame]. See [FrameClock].
+    pub fn with_clock(mut self, clock: FrameClock) -> Self {
+        self.clock = PolicyClock::shared(clock);
+        self
+    }
+
+    /// Fill host-visible allocations with the given byte pattern when they
+    /// enter quarantine.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - the device must not be destroyed while this allocator still exists
+    /// - freed allocations are written by the host, the application must not
+    ///   free memory while it is still mapped elsewhere
+    pub unsafe fn poison_host_visible_memory(
+        &mut self,
+        device: ash::Device,
+        pattern: u8,
+    ) {
+        self.poison = Some((device, pattern));
+    }
+
+    /// The number of allocations currently held in quarantine.
+    pub fn quarantined_allocations(&self) -> usize {
+        self.quarantine.len()
+    }
+
+    /// Mark the end of a frame. Allocations which have been quarantined for
+    /// long enough are returned to the wrapped allocator.
+    ///
+    /// The clock is only advanced when it isn't shared, see
+    /// [Self::with_clock].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because allocations can be freed by the wrapped allocator. See
+    /// [ComposableAllocator::free].
+    pub unsafe fn end_frame(&mut self) {
+        self.clock.end_frame();
+        let now = self.clock.frame();
+        if let QuarantinePolicy::Frames(frames) = self.policy {
+            while self
+                .quarantine
+                .front()
+                .is_some_and(|(frame, _)| frame + frames <= now)
+            {
+                let (_, allocation) = self.quarantine.pop_front().unwrap();
+                self.wrapped_allocator.free(allocation);
+            }
+        }
+    }
+
+    /// Return every quarantined allocation to the wrapped allocator.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because allocations can be freed by the wrapped allocator. See
+    /// [ComposableAllocator::free].
+    pub unsafe fn release_all(&mut self) {
+        for (_, allocation) in self.quarantine.drain(..) {
+            self.wrapped_allocator.free(allocation);
+        }
+    }
+
+    /// Fill an allocation with the poison pattern, if enabled.
+    unsafe fn poison(&self, allocation: &Allocation) {
+        let (device, pattern) = match &self.poison {
+            Some(poison) => poison,
+            None => return,
+        };
+        let is_host_visible = allocation
+            .allocation_requirements()
+            .memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+        if !is_host_visible {
+            return;
+        }
+        match allocation.map(device) {
+            Ok(ptr) => {
+                std::ptr::write_bytes(
+                    ptr as *mut u8,
+                    *pattern,
+                    allocation.size_in_bytes() as usize,
+                );
+                if let Err(err) = allocation.unmap(device) {
+                    log::warn!("Unable to unmap poisoned memory: {}", err);
+                }
+            }
+            Err(err) => {
+                log::warn!("Unable to map memory for poisoning: {}", err);
+            }
+        }
+    }
+}
+
+impl<T: ComposableAllocator> Drop for QuarantineAllocator<T> {
+    fn drop(&mut self) {
+        // The application already freed everything in quarantine, so hand it
+        // back rather than leaking it.
+        unsafe { self.release_all() };
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for QuarantineAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.wrapped_allocator.allocate(allocation_requirements)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.poison(&allocation);
+        self.quarantine.push_back((self.clock.frame(), allocation));
+
+        if let QuarantinePolicy::Frees(frees) = self.policy {
+            while self.quarantine.len() > frees {
+                let (_, allocation) = self.quarantine.pop_front().unwrap();
+                self.wrapped_allocator.free(allocation);
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.release_all();
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
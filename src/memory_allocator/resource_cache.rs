@@ -0,0 +1,369 @@
+use {
+    super::handle_options::HandleOptions,
+    crate::Allocation,
+    ash::vk,
+    std::{collections::HashMap, hash::Hash},
+};
+
+/// Identifies buffers which can be used interchangeably.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct BufferKey {
+    flags: vk::BufferCreateFlags,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    sharing_mode: vk::SharingMode,
+    queue_family_indices: Vec<u32>,
+    required_memory_property_flags: vk::MemoryPropertyFlags,
+    preferred_memory_property_flags: vk::MemoryPropertyFlags,
+    min_alignment: u64,
+    options: HandleOptions,
+}
+
+impl BufferKey {
+    /// Get the key for a buffer.
+    ///
+    /// # Returns
+    ///
+    /// None when the create info has extension structs, because there's no
+    /// general way to compare them.
+    ///
+    /// Resources are only reused by handles with the same tag and device
+    /// mask, so they're bound to memory on the right physical devices and
+    /// counted against the right tag.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the queue family indices pointer must be valid for concurrent
+    ///     sharing
+    pub unsafe fn new(
+        create_info: &vk::BufferCreateInfo,
+        required_memory_property_flags: vk::MemoryPropertyFlags,
+        preferred_memory_property_flags: vk::MemoryPropertyFlags,
+        min_alignment: u64,
+        options: HandleOptions,
+    ) -> Option<Self> {
+        if !create_info.p_next.is_null() {
+            return None;
+        }
+        Some(Self {
+            flags: create_info.flags,
+            size: create_info.size,
+            usage: create_info.usage,
+            sharing_mode: create_info.sharing_mode,
+            queue_family_indices: queue_family_indices(
+                create_info.sharing_mode,
+                create_info.p_queue_family_indices,
+                create_info.queue_family_index_count,
+            ),
+            required_memory_property_flags,
+            preferred_memory_property_flags,
+            min_alignment,
+            options,
+        })
+    }
+}
+
+/// Identifies images which can be used interchangeably.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ImageKey {
+    flags: vk::ImageCreateFlags,
+    image_type: vk::ImageType,
+    format: vk::Format,
+    extent: (u32, u32, u32),
+    mip_levels: u32,
+    array_layers: u32,
+    samples: vk::SampleCountFlags,
+    tiling: vk::ImageTiling,
+    usage: vk::ImageUsageFlags,
+    sharing_mode: vk::SharingMode,
+    queue_family_indices: Vec<u32>,
+    initial_layout: vk::ImageLayout,
+    memory_property_flags: vk::MemoryPropertyFlags,
+    options: HandleOptions,
+}
+
+impl ImageKey {
+    /// Get the key for an image. See [BufferKey::new].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the queue family indices pointer must be valid for concurrent
+    ///     sharing
+    pub unsafe fn new(
+        create_info: &vk::ImageCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+        options: HandleOptions,
+    ) -> Option<Self> {
+        if !create_info.p_next.is_null() {
+            return None;
+        }
+        Some(Self {
+            flags: create_info.flags,
+            image_type: create_info.image_type,
+            format: create_info.format,
+            extent: (
+                create_info.extent.width,
+                create_info.extent.height,
+                create_info.extent.depth,
+            ),
+            mip_levels: create_info.mip_levels,
+            array_layers: create_info.array_layers,
+            samples: create_info.samples,
+            tiling: create_info.tiling,
+            usage: create_info.usage,
+            sharing_mode: create_info.sharing_mode,
+            queue_family_indices: queue_family_indices(
+                create_info.sharing_mode,
+                create_info.p_queue_family_indices,
+                create_info.queue_family_index_count,
+            ),
+            initial_layout: create_info.initial_layout,
+            memory_property_flags,
+            options,
+        })
+    }
+}
+
+/// Freed resources which are kept alive, along with their memory, so they
+/// can be handed out again by later allocations with the same parameters.
+pub(crate) struct HandleCache<H, K> {
+    retained_frames: u64,
+
+    /// The key for every live resource which can be retained when freed.
+    keys: HashMap<H, K>,
+
+    /// Freed resources along with the frame they were freed in.
+    retained: HashMap<K, Vec<(H, Allocation, u64)>>,
+}
+
+impl<H: Copy + Eq + Hash, K: Clone + Eq + Hash> HandleCache<H, K> {
+    /// Create an empty cache.
+    ///
+    /// # Params
+    ///
+    /// * retained_frames: how many frames a freed resource is kept before it is
+    ///   really destroyed.
+    pub fn new(retained_frames: u64) -> Self {
+        Self {
+            retained_frames,
+            keys: HashMap::new(),
+            retained: HashMap::new(),
+        }
+    }
+
+    /// Remember the key for a newly created resource so it can be retained
+    /// when freed.
+    pub fn track(&mut self, handle: H, key: K) {
+        self.keys.insert(handle, key);
+    }
+
+    /// Take a retained resource which matches the key, if any.
+    pub fn take(&mut self, key: &K) -> Option<(H, Allocation)> {
+        let resources = self.retained.get_mut(key)?;
+        let (handle, allocation, _) = resources.pop()?;
+        if resources.is_empty() {
+            self.retained.remove(key);
+        }
+        self.keys.insert(handle, key.clone());
+        Some((handle, allocation))
+    }
+
+    /// Retain a freed resource.
+    ///
+    /// # Params
+    ///
+    /// * handle: the freed resource.
+    /// * allocation: the resource's memory.
+    /// * frame: the current frame.
+    ///
+    /// # Returns
+    ///
+    /// The allocation is handed back when the resource isn't tracked by this
+    /// cache. The caller is responsible for destroying it as usual.
+    pub fn retain(
+        &mut self,
+        handle: H,
+        allocation: Allocation,
+        frame: u64,
+    ) -> Option<Allocation> {
+        match self.keys.remove(&handle) {
+            Some(key) => {
+                self.retained
+                    .entry(key)
+                    .or_default()
+                    .push((handle, allocation, frame));
+                None
+            }
+            None => Some(allocation),
+        }
+    }
+
+    /// Remove resources which have been retained for too long.
+    ///
+    /// # Params
+    ///
+    /// * frame: the current frame.
+    ///
+    /// # Returns
+    ///
+    /// The expired resources, which must be destroyed.
+    pub fn expire(&mut self, frame: u64) -> Vec<(H, Allocation)> {
+        let mut expired = vec![];
+        for resources in self.retained.values_mut() {
+            let (keep, expire) = std::mem::take(resources)
+                .into_iter()
+                .partition::<Vec<_>, _>(|&(_, _, freed_frame)| {
+                    // The clock can be moved backwards, see
+                    // MemoryAllocator::begin_frame.
+                    frame.saturating_sub(freed_frame) <= self.retained_frames
+                });
+            *resources = keep;
+            expired.extend(
+                expire
+                    .into_iter()
+                    .map(|(handle, allocation, _)| (handle, allocation)),
+            );
+        }
+        self.retained.retain(|_, resources| !resources.is_empty());
+        expired
+    }
+
+    /// Remove every retained resource.
+    ///
+    /// # Returns
+    ///
+    /// The retained resources, which must be destroyed.
+    pub fn drain(&mut self) -> Vec<(H, Allocation)> {
+        self.retained
+            .drain()
+            .flat_map(|(_, resources)| resources)
+            .map(|(handle, allocation, _)| (handle, allocation))
+            .collect()
+    }
+}
+
+/// Retained buffers and images.
+pub(crate) struct ResourceCache {
+    pub buffers: HandleCache<vk::Buffer, BufferKey>,
+    pub images: HandleCache<vk::Image, ImageKey>,
+}
+
+impl ResourceCache {
+    /// Create an empty cache. See [HandleCache::new].
+    pub fn new(retained_frames: u64) -> Self {
+        Self {
+            buffers: HandleCache::new(retained_frames),
+            images: HandleCache::new(retained_frames),
+        }
+    }
+}
+
+/// Copy the queue family indices which matter for the sharing mode.
+unsafe fn queue_family_indices(
+    sharing_mode: vk::SharingMode,
+    ptr: *const u32,
+    count: u32,
+) -> Vec<u32> {
+    if sharing_mode != vk::SharingMode::CONCURRENT || ptr.is_null() {
+        return vec![];
+    }
+    std::slice::from_raw_parts(ptr, count as usize).to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{AllocationRequirements, ComposableAllocator, FakeAllocator},
+    };
+
+    fn allocation() -> Allocation {
+        unsafe {
+            FakeAllocator::default()
+                .allocate(AllocationRequirements::default())
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_untracked_resources_are_not_retained() {
+        let mut cache = HandleCache::<u64, u32>::new(1);
+        assert!(cache.retain(1, allocation(), 0).is_some());
+    }
+
+    #[test]
+    fn test_retained_resources_are_reused() {
+        let mut cache = HandleCache::<u64, u32>::new(1);
+        cache.track(1, 7);
+        assert!(cache.retain(1, allocation(), 0).is_none());
+
+        assert!(cache.take(&8).is_none());
+        assert_eq!(cache.take(&7).map(|(handle, _)| handle), Some(1));
+        assert!(cache.take(&7).is_none());
+
+        // The reused resource is tracked again.
+        assert!(cache.retain(1, allocation(), 0).is_none());
+    }
+
+    #[test]
+    fn test_buffers_are_only_reused_by_the_same_handle() {
+        let create_info = vk::BufferCreateInfo {
+            size: 1024,
+            usage: vk::BufferUsageFlags::VERTEX_BUFFER,
+            ..Default::default()
+        };
+        let key = |options| unsafe {
+            BufferKey::new(
+                &create_info,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                vk::MemoryPropertyFlags::empty(),
+                1,
+                options,
+            )
+            .unwrap()
+        };
+        let plain = key(HandleOptions::default());
+        let masked = key(HandleOptions {
+            device_mask: Some(0b01),
+            ..HandleOptions::default()
+        });
+        let tagged = key(HandleOptions {
+            tag: Some("textures"),
+            ..HandleOptions::default()
+        });
+
+        let mut cache = HandleCache::<u64, BufferKey>::new(1);
+        cache.track(1, masked.clone());
+        assert!(cache.retain(1, allocation(), 0).is_none());
+
+        assert!(cache.take(&plain).is_none());
+        assert!(cache.take(&tagged).is_none());
+        assert_eq!(cache.take(&masked).map(|(handle, _)| handle), Some(1));
+    }
+
+    #[test]
+    fn test_retained_resources_expire() {
+        let mut cache = HandleCache::<u64, u32>::new(2);
+        cache.track(1, 7);
+        assert!(cache.retain(1, allocation(), 0).is_none());
+
+        assert!(cache.expire(1).is_empty());
+        assert!(cache.expire(2).is_empty());
+        let expired = cache.expire(3);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, 1);
+        assert!(cache.take(&7).is_none());
+    }
+
+    #[test]
+    fn test_clock_can_move_backwards() {
+        let mut cache = HandleCache::<u64, u32>::new(2);
+        cache.track(1, 7);
+        assert!(cache.retain(1, allocation(), 5).is_none());
+
+        assert!(cache.expire(0).is_empty());
+        assert_eq!(cache.take(&7).map(|(handle, _)| handle), Some(1));
+    }
+}
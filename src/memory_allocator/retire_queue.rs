@@ -0,0 +1,81 @@
+use {super::deferred_free::PendingResource, crate::Allocation};
+
+/// Resources which are freed once the frame they were retired in has
+/// completed on the GPU.
+#[derive(Default)]
+pub(crate) struct RetireQueue {
+    retired: Vec<(u64, PendingResource, Allocation)>,
+}
+
+impl RetireQueue {
+    /// Retire a resource during a frame.
+    pub fn retire(
+        &mut self,
+        frame: u64,
+        resource: PendingResource,
+        allocation: Allocation,
+    ) {
+        self.retired.push((frame, resource, allocation));
+    }
+
+    /// Remove every resource retired in or before the completed frame.
+    ///
+    /// # Returns
+    ///
+    /// The resources which must be freed.
+    pub fn take_completed(
+        &mut self,
+        completed_frame: u64,
+    ) -> Vec<(PendingResource, Allocation)> {
+        let (completed, retired) = std::mem::take(&mut self.retired)
+            .into_iter()
+            .partition::<Vec<_>, _>(|&(frame, _, _)| frame <= completed_frame);
+        self.retired = retired;
+        completed
+            .into_iter()
+            .map(|(_, resource, allocation)| (resource, allocation))
+            .collect()
+    }
+
+    /// The frame each resource was retired in, and its allocation.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Allocation)> {
+        self.retired
+            .iter()
+            .map(|(frame, _, allocation)| (*frame, allocation))
+    }
+
+    /// The number of resources waiting for their frame to complete.
+    pub fn len(&self) -> usize {
+        self.retired.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{AllocationRequirements, ComposableAllocator, FakeAllocator},
+    };
+
+    fn allocation() -> Allocation {
+        unsafe {
+            FakeAllocator::default()
+                .allocate(AllocationRequirements::default())
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_resources_wait_for_their_frame() {
+        let mut queue = RetireQueue::default();
+        queue.retire(3, PendingResource::None, allocation());
+        queue.retire(4, PendingResource::None, allocation());
+        queue.retire(4, PendingResource::None, allocation());
+
+        assert!(queue.take_completed(2).is_empty());
+        assert_eq!(queue.take_completed(3).len(), 1);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.take_completed(10).len(), 2);
+        assert_eq!(queue.len(), 0);
+    }
+}
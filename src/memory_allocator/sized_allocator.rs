@@ -1,5 +1,6 @@
 use crate::{
-    Allocation, AllocationRequirements, AllocatorError, ComposableAllocator,
+    Allocation, AllocationRequirements, AllocatorError, AllocatorStats,
+    ComposableAllocator, MemoryReport, PoolConfigurator,
 };
 
 /// An allocator which composes over two other allocators. When a request is
@@ -59,4 +60,44 @@ where
             self.large_allocator.free(allocation)
         }
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.small_allocator.validate()?;
+        self.large_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.small_allocator.name_allocation(allocation);
+        self.large_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.small_allocator.stats(stats);
+        self.large_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.small_allocator.report(report);
+        self.large_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.small_allocator.trim();
+        self.large_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.small_allocator.reconfigure_pools(configurator)?;
+        self.large_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.small_allocator.reserve(allocation_requirements)
+    }
 }
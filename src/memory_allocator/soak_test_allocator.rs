@@ -0,0 +1,171 @@
+use {
+    crate::{
+        pretty_wrappers::PrettySize, Allocation, AllocationRequirements,
+        AllocatorError, AllocatorStats, ComposableAllocator, MemoryReport,
+        PoolConfigurator,
+    },
+    indoc::indoc,
+};
+
+/// A callback which is invoked when validation fails during a soak test.
+pub type SoakTestFailureHook = Box<dyn FnMut(&AllocatorError) + Send>;
+
+/// An allocator decorator for long-running soak tests.
+///
+/// Every N operations (allocations and frees) the wrapped allocator is
+/// validated and a snapshot of the current usage is logged. Validation
+/// failures are logged by default, use a failure hook to abort on the first
+/// inconsistency instead.
+pub struct SoakTestAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    interval: u64,
+    operation_count: u64,
+    live_allocations: u64,
+    live_bytes: u64,
+    peak_bytes: u64,
+    failure_count: u64,
+    on_failure: SoakTestFailureHook,
+}
+
+impl<T: ComposableAllocator> SoakTestAllocator<T> {
+    /// Create a new soak test allocator.
+    ///
+    /// # Params
+    ///
+    /// * wrapped_allocator: the allocator being tested.
+    /// * interval: validate and log a snapshot every interval operations.
+    pub fn new(wrapped_allocator: T, interval: u64) -> Self {
+        Self {
+            wrapped_allocator,
+            interval: interval.max(1),
+            operation_count: 0,
+            live_allocations: 0,
+            live_bytes: 0,
+            peak_bytes: 0,
+            failure_count: 0,
+            on_failure: Box::new(|err| {
+                log::error!("Soak test validation failed!\n\n{}", err)
+            }),
+        }
+    }
+
+    /// Invoke a custom callback when validation fails.
+    pub fn with_failure_hook(
+        self,
+        on_failure: impl FnMut(&AllocatorError) + Send + 'static,
+    ) -> Self {
+        Self {
+            on_failure: Box::new(on_failure),
+            ..self
+        }
+    }
+
+    /// Panic on the first validation failure.
+    pub fn panic_on_failure(self) -> Self {
+        self.with_failure_hook(|err| {
+            panic!("Soak test validation failed!\n\n{}", err)
+        })
+    }
+
+    /// The number of validation failures observed so far.
+    pub fn failure_count(&self) -> u64 {
+        self.failure_count
+    }
+
+    /// The total number of allocations and frees so far.
+    pub fn operation_count(&self) -> u64 {
+        self.operation_count
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for SoakTestAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        self.live_allocations += 1;
+        self.live_bytes += allocation.size_in_bytes();
+        self.peak_bytes = self.peak_bytes.max(self.live_bytes);
+        self.record_operation();
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.live_allocations -= 1;
+        self.live_bytes -= allocation.size_in_bytes();
+        self.wrapped_allocator.free(allocation);
+        self.record_operation();
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+// Private API
+// -----------
+
+impl<T: ComposableAllocator> SoakTestAllocator<T> {
+    /// Count an operation and run the periodic checks when it's time.
+    fn record_operation(&mut self) {
+        self.operation_count += 1;
+        if self.operation_count % self.interval != 0 {
+            return;
+        }
+
+        log::info!(
+            indoc!(
+                "
+                Soak test snapshot after {} operations
+
+                live allocations: {}
+                live bytes: {}
+                peak bytes: {}
+                validation failures: {}
+                "
+            ),
+            self.operation_count,
+            self.live_allocations,
+            PrettySize(self.live_bytes),
+            PrettySize(self.peak_bytes),
+            self.failure_count,
+        );
+
+        if let Err(err) = self.wrapped_allocator.validate() {
+            self.failure_count += 1;
+            (self.on_failure)(&err);
+        }
+    }
+}
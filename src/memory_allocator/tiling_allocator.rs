@@ -0,0 +1,119 @@
+use {
+    crate::{
+        Allocation, AllocationId, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, MemoryReport, PoolConfigurator,
+        ResourceKind,
+    },
+    std::collections::HashSet,
+};
+
+/// An allocator which composes over two other allocators. Optimal-tiling
+/// images are sent to the optimal allocator and everything else, e.g.
+/// buffers, linear images, and raw memory, is sent to the linear allocator.
+///
+/// When each allocator is a separate pool, linear and optimal resources never
+/// share a chunk, so bufferImageGranularity never needs to be enforced
+/// between neighbors.
+pub struct TilingAllocator<
+    LinearAllocator: ComposableAllocator,
+    OptimalAllocator: ComposableAllocator,
+> {
+    linear_allocator: LinearAllocator,
+    optimal_allocator: OptimalAllocator,
+    optimal_allocations: HashSet<AllocationId>,
+}
+
+impl<L, O> TilingAllocator<L, O>
+where
+    L: ComposableAllocator,
+    O: ComposableAllocator,
+{
+    /// Create a new allocator which routes requests based on the resource
+    /// kind.
+    ///
+    /// # Params
+    ///
+    /// * linear_allocator: serves buffers, linear images, and requests with an
+    ///   unknown resource kind.
+    /// * optimal_allocator: serves optimal-tiling images.
+    pub fn new(linear_allocator: L, optimal_allocator: O) -> Self {
+        Self {
+            linear_allocator,
+            optimal_allocator,
+            optimal_allocations: HashSet::new(),
+        }
+    }
+}
+
+impl<L, O> ComposableAllocator for TilingAllocator<L, O>
+where
+    L: ComposableAllocator,
+    O: ComposableAllocator,
+{
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        if allocation_requirements.resource_kind != ResourceKind::Optimal {
+            return self.linear_allocator.allocate(allocation_requirements);
+        }
+        let allocation =
+            self.optimal_allocator.allocate(allocation_requirements)?;
+        self.optimal_allocations.insert(allocation.id());
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        // Pools don't keep the resource kind on their suballocations, so the
+        // allocator is found by id.
+        if self.optimal_allocations.remove(&allocation.id()) {
+            self.optimal_allocator.free(allocation)
+        } else {
+            self.linear_allocator.free(allocation)
+        }
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.linear_allocator.validate()?;
+        self.optimal_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.linear_allocator.name_allocation(allocation);
+        self.optimal_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.linear_allocator.stats(stats);
+        self.optimal_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.linear_allocator.report(report);
+        self.optimal_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.linear_allocator.trim();
+        self.optimal_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.linear_allocator.reconfigure_pools(configurator)?;
+        self.optimal_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        if allocation_requirements.resource_kind == ResourceKind::Optimal {
+            self.optimal_allocator.reserve(allocation_requirements)
+        } else {
+            self.linear_allocator.reserve(allocation_requirements)
+        }
+    }
+}
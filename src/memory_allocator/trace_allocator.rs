@@ -1,57 +1,238 @@
 use {
     crate::{
-        pretty_wrappers::PrettySize, Allocation, AllocationRequirements,
-        AllocatorError, ComposableAllocator, MemoryProperties,
+        pretty_wrappers::PrettySize, Allocation, AllocationId,
+        AllocationOrigin, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, Histogram, MemoryProperties,
+        MemoryReport, PoolConfigurator,
     },
     ash::vk,
     indoc::indoc,
-    std::collections::HashMap,
+    std::{
+        collections::{BTreeMap, HashMap},
+        panic::Location,
+        time::{Duration, Instant},
+    },
 };
 
-struct Metrics {
-    total_allocations: u32,
-    leaked_allocations: u32,
-    max_size: u64,
-    min_size: u64,
-    avg_size: u64,
+/// Allocation counters for a [TraceAllocator], either across every memory
+/// type or for a single memory type.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TraceMetrics {
+    /// The number of allocations made since the trace allocator was created.
+    pub total_allocations: u32,
+
+    /// The number of allocations which haven't been freed yet.
+    pub leaked_allocations: u32,
+
+    /// The number of bytes allocated since the trace allocator was created.
+    pub total_bytes: u64,
+
+    /// The number of bytes in allocations which haven't been freed yet.
+    pub live_bytes: u64,
+
+    /// The most bytes which were live at the same time.
+    pub peak_bytes: u64,
+
+    /// The size of the largest allocation.
+    pub max_size: u64,
+
+    /// The size of the smallest allocation, or 0 before the first
+    /// allocation.
+    pub min_size: u64,
+
+    /// The size of every allocation, in bytes.
+    pub size_histogram: Histogram,
+
+    /// How long each freed allocation was live, in microseconds.
+    pub lifetime_histogram: Histogram,
 }
 
-impl Default for Metrics {
-    fn default() -> Self {
-        Self {
-            total_allocations: 0,
-            leaked_allocations: 0,
-            max_size: 0,
-            min_size: std::u64::MAX,
-            avg_size: 0,
+impl TraceMetrics {
+    /// The average allocation size, or 0 before the first allocation.
+    pub fn avg_size(&self) -> u64 {
+        if self.total_allocations == 0 {
+            0
+        } else {
+            self.total_bytes / self.total_allocations as u64
         }
     }
+
+    /// Estimate how long freed allocations were live.
+    ///
+    /// # Params
+    ///
+    /// * percentile: a fraction between 0.0 and 1.0, e.g. 0.5 for the median.
+    ///
+    /// # Returns
+    ///
+    /// An upper bound for the percentile, see [Histogram::percentile], or
+    /// None when no allocations have been freed.
+    pub fn lifetime_percentile(&self, percentile: f64) -> Option<Duration> {
+        self.lifetime_histogram
+            .percentile(percentile)
+            .map(Duration::from_micros)
+    }
 }
 
-impl Metrics {
-    fn record_allocation(&mut self, size: u64) {
-        self.avg_size = (self.avg_size * self.total_allocations as u64 + size)
-            / (self.total_allocations as u64 + 1);
+// Private API
+// -----------
 
+impl TraceMetrics {
+    fn record_allocation(&mut self, size: u64) {
+        if self.total_allocations == 0 {
+            self.min_size = size;
+        }
         self.total_allocations += 1;
         self.leaked_allocations += 1;
+        self.total_bytes += size;
+        self.live_bytes += size;
+        self.peak_bytes = self.peak_bytes.max(self.live_bytes);
         self.max_size = self.max_size.max(size);
         self.min_size = self.min_size.min(size);
+        self.size_histogram.record(size);
     }
 
-    fn record_free(&mut self) {
+    fn record_free(&mut self, size: u64, lifetime: Duration) {
         self.leaked_allocations -= 1;
+        self.live_bytes -= size;
+        self.lifetime_histogram
+            .record(lifetime.as_micros().min(u64::MAX as u128) as u64);
+    }
+
+    /// Write the counters as markdown.
+    fn write(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lifetime = |percentile| match self.lifetime_percentile(percentile) {
+            Some(lifetime) => format!("<= {:?}", lifetime),
+            None => "n/a".to_owned(),
+        };
+        write!(
+            f,
+            indoc!(
+                "
+                total allocations: {}
+                leaked allocations: {}
+                live bytes: {}
+                peak bytes: {}
+                min_size: {}
+                max_size: {}
+                avg_size: {}
+                p50 lifetime: {}
+                p95 lifetime: {}
+                p99 lifetime: {}
+
+                "
+            ),
+            self.total_allocations,
+            self.leaked_allocations,
+            PrettySize(self.live_bytes),
+            PrettySize(self.peak_bytes),
+            PrettySize(self.min_size),
+            PrettySize(self.max_size),
+            PrettySize(self.avg_size()),
+            lifetime(0.5),
+            lifetime(0.95),
+            lifetime(0.99),
+        )
     }
 }
 
+/// A snapshot of a [TraceAllocator]'s metrics, see [TraceAllocator::metrics].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceReport {
+    /// The trace allocator's name.
+    pub name: String,
+
+    /// Metrics across every memory type.
+    pub total: TraceMetrics,
+
+    /// Metrics for each memory type which has been allocated from, indexed
+    /// by memory type index.
+    pub per_type: BTreeMap<usize, TraceMetrics>,
+
+    /// The property flags for each memory type in [Self::per_type].
+    pub property_flags: BTreeMap<usize, vk::MemoryPropertyFlags>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TraceReport {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        // Vulkan flags aren't serializable, so they're written as raw bits.
+        let property_flags: BTreeMap<usize, u32> = self
+            .property_flags
+            .iter()
+            .map(|(&memory_type_index, flags)| {
+                (memory_type_index, flags.as_raw())
+            })
+            .collect();
+        let mut state = serializer.serialize_struct("TraceReport", 4)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("total", &self.total)?;
+        state.serialize_field("per_type", &self.per_type)?;
+        state.serialize_field("property_flags", &property_flags)?;
+        state.end()
+    }
+}
+
+impl std::fmt::Display for TraceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# {} Allocation Trace\n", self.name)?;
+        writeln!(f, "## Total Allocations\n")?;
+        self.total.write(f)?;
+        writeln!(f, "## Allocations Per Memory Type\n")?;
+        for (memory_type_index, metrics) in &self.per_type {
+            writeln!(
+                f,
+                "### Memory Type {}\nProperties: {:#?}\n",
+                memory_type_index,
+                self.property_flags
+                    .get(memory_type_index)
+                    .copied()
+                    .unwrap_or_default(),
+            )?;
+            metrics.write(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// An allocation which hasn't been freed yet.
+struct LiveAllocation {
+    path: String,
+    name: Option<String>,
+    size_in_bytes: u64,
+    memory_type_index: usize,
+    allocated_at: Instant,
+    location: Option<&'static Location<'static>>,
+    frame: Option<u64>,
+    user_data: u64,
+    origin: AllocationOrigin,
+}
+
 /// An allocator decorator which tracks metrics and generates a report for
 /// all allocations made to the wrapped allocator.
+///
+/// The trace allocator's name is also a path segment for every allocation
+/// which passes through it, see [Allocation::path]. Allocations which are
+/// still live when the trace allocator is dropped are listed by path and
+/// name, see [Allocation::name], along with the code which allocated them,
+/// see [AllocationRequirements::location].
+///
+/// Use [Self::metrics] to query the counters at any time, e.g. to display
+/// them every frame.
 pub struct TraceAllocator<T: ComposableAllocator> {
     wrapped_allocator: T,
     name: String,
-    total: Metrics,
-    per_type: HashMap<usize, Metrics>,
+    total: TraceMetrics,
+    per_type: BTreeMap<usize, TraceMetrics>,
+    live: HashMap<AllocationId, LiveAllocation>,
     properties: MemoryProperties,
+    log_on_drop: bool,
 }
 
 impl<T: ComposableAllocator> TraceAllocator<T> {
@@ -65,66 +246,51 @@ impl<T: ComposableAllocator> TraceAllocator<T> {
         Self {
             wrapped_allocator,
             name: name.into(),
-            total: Metrics::default(),
-            per_type: HashMap::new(),
+            total: TraceMetrics::default(),
+            per_type: BTreeMap::new(),
+            live: HashMap::new(),
             properties,
+            log_on_drop: true,
+        }
+    }
+
+    /// Enable or disable the report which is logged when the trace allocator
+    /// is dropped. The report is logged by default.
+    pub fn with_drop_log(mut self, log_on_drop: bool) -> Self {
+        // Struct update syntax can't move out of a type which implements Drop.
+        self.log_on_drop = log_on_drop;
+        self
+    }
+
+    /// Get a snapshot of the metrics for every allocation made so far.
+    pub fn metrics(&self) -> TraceReport {
+        let property_flags = self
+            .per_type
+            .keys()
+            .filter_map(|&memory_type_index| {
+                let memory_type =
+                    self.properties.types().get(memory_type_index)?;
+                Some((memory_type_index, memory_type.property_flags))
+            })
+            .collect();
+        TraceReport {
+            name: self.name.clone(),
+            total: self.total,
+            per_type: self.per_type.clone(),
+            property_flags,
         }
     }
 }
 
 impl<T: ComposableAllocator> Drop for TraceAllocator<T> {
     fn drop(&mut self) {
-        let mut report = format!(
-            indoc!(
-                "
-                # {} Allocation Trace
-
-                ## Total Allocations
-
-                total allocations: {}
-                leaked allocations: {}
-                min_size: {}
-                max_size: {}
-                avg_size: {}
-
-                ## Allocations Per Memory Type
-
-                "
-            ),
-            self.name,
-            self.total.total_allocations,
-            self.total.leaked_allocations,
-            PrettySize(self.total.min_size),
-            PrettySize(self.total.max_size),
-            PrettySize(self.total.avg_size),
-        );
-
-        for (memory_type_index, metrics) in self.per_type.iter() {
-            report.push_str(&format!(
-                indoc!(
-                    "
-                    ### Memory Type {}
-                    Properties: {:#?}
-
-                    total allocations: {}
-                    leaked allocations: {}
-                    min_size: {}
-                    max_size: {}
-                    avg_size: {}
-
-                    "
-                ),
-                memory_type_index,
-                self.properties.types()[*memory_type_index].property_flags,
-                metrics.total_allocations,
-                metrics.leaked_allocations,
-                PrettySize(self.total.min_size),
-                PrettySize(self.total.max_size),
-                PrettySize(self.total.avg_size),
-            ));
+        if !self.log_on_drop {
+            return;
+        }
+        log::debug!("{}", self.metrics());
+        if let Some(leak_report) = self.leak_report() {
+            log::warn!("{}", leak_report);
         }
-
-        log::debug!("{}", report);
     }
 }
 
@@ -133,21 +299,232 @@ impl<T: ComposableAllocator> ComposableAllocator for TraceAllocator<T> {
         &mut self,
         allocation_requirements: AllocationRequirements,
     ) -> Result<Allocation, AllocatorError> {
-        self.total
-            .record_allocation(allocation_requirements.size_in_bytes);
+        let mut allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+        self.total.record_allocation(allocation.size_in_bytes());
         self.per_type
-            .entry(allocation_requirements.memory_type_index)
+            .entry(allocation.memory_type_index())
             .or_default()
-            .record_allocation(allocation_requirements.size_in_bytes);
-        self.wrapped_allocator.allocate(allocation_requirements)
+            .record_allocation(allocation.size_in_bytes());
+        allocation.prepend_path_segment(&self.name);
+        self.live.insert(
+            allocation.id(),
+            LiveAllocation {
+                path: allocation.path().to_owned(),
+                name: allocation.name().map(str::to_owned),
+                size_in_bytes: allocation.size_in_bytes(),
+                memory_type_index: allocation.memory_type_index(),
+                allocated_at: Instant::now(),
+                location: allocation_requirements.location,
+                frame: allocation_requirements.frame,
+                user_data: allocation_requirements.user_data,
+                origin: AllocationOrigin::capture(),
+            },
+        );
+        Ok(allocation)
     }
 
     unsafe fn free(&mut self, allocation: Allocation) {
-        self.total.record_free();
+        let lifetime = match self.live.remove(&allocation.id()) {
+            Some(live) => live.allocated_at.elapsed(),
+            None => Duration::ZERO,
+        };
+        self.total.record_free(allocation.size_in_bytes(), lifetime);
         self.per_type
             .entry(allocation.memory_type_index())
             .or_default()
-            .record_free();
+            .record_free(allocation.size_in_bytes(), lifetime);
         self.wrapped_allocator.free(allocation)
     }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        if let Some(live) = self.live.get_mut(unsafe { &allocation.id() }) {
+            live.name = allocation.name().map(str::to_owned);
+        }
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        report.with_owner(&self.name, |report| {
+            self.wrapped_allocator.report(report)
+        })
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
+
+impl<T: ComposableAllocator> TraceAllocator<T> {
+    /// Describe every allocation which hasn't been freed yet, so leaks can be
+    /// found by name.
+    ///
+    /// # Returns
+    ///
+    /// None when there are no leaked allocations.
+    fn leak_report(&self) -> Option<String> {
+        if self.live.is_empty() {
+            return None;
+        }
+        let mut report = format!(
+            "# {} Leaked Allocations\n\n{} allocation(s) were not freed\n\n",
+            self.name,
+            self.live.len()
+        );
+        let mut live: Vec<&LiveAllocation> = self.live.values().collect();
+        live.sort_by(|a, b| a.path.cmp(&b.path).then(a.name.cmp(&b.name)));
+        for allocation in live {
+            let name = match &allocation.name {
+                Some(name) => format!(" ({})", name),
+                None => String::new(),
+            };
+            let frame = match allocation.frame {
+                Some(frame) => format!(", frame {}", frame),
+                None => String::new(),
+            };
+            let user_data = match allocation.user_data {
+                0 => String::new(),
+                user_data => format!(", user data {}", user_data),
+            };
+            let location = match allocation.location {
+                Some(location) => format!(", allocated at {}", location),
+                None => String::new(),
+            };
+            report.push_str(&format!(
+                "- {}{}: {}, memory type {}{}{}, age {:.2?}{}{}\n",
+                allocation.path,
+                name,
+                PrettySize(allocation.size_in_bytes),
+                allocation.memory_type_index,
+                frame,
+                user_data,
+                allocation.allocated_at.elapsed(),
+                location,
+                allocation.origin.describe("Allocated"),
+            ));
+        }
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::FakeAllocator};
+
+    #[test]
+    fn test_metrics_math() {
+        let mut metrics = TraceMetrics::default();
+        assert_eq!(metrics.min_size, 0);
+        assert_eq!(metrics.avg_size(), 0);
+        assert_eq!(metrics.lifetime_percentile(0.5), None);
+
+        metrics.record_allocation(100);
+        metrics.record_allocation(50);
+        metrics.record_allocation(51);
+        metrics.record_free(100, Duration::from_micros(10));
+        metrics.record_allocation(10);
+
+        assert_eq!(metrics.min_size, 10);
+        assert_eq!(metrics.max_size, 100);
+        // (100 + 50 + 51 + 10) / 4 = 52.75
+        assert_eq!(metrics.avg_size(), 52);
+        assert_eq!(metrics.live_bytes, 111);
+        assert_eq!(metrics.peak_bytes, 201);
+        assert_eq!(metrics.size_histogram.count(), 4);
+        assert_eq!(metrics.size_histogram.buckets()[5], 2);
+        assert_eq!(
+            metrics.lifetime_percentile(0.5),
+            Some(Duration::from_micros(15))
+        );
+    }
+
+    #[test]
+    fn test_metrics_can_be_queried_while_allocating() {
+        let properties = unsafe {
+            // SAFE because the fake allocator never allocates real memory.
+            MemoryProperties::from_raw(
+                &[vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap_index: 0,
+                }],
+                &[vk::MemoryHeap {
+                    size: 1024,
+                    flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+                }],
+            )
+        };
+        let mut allocator = TraceAllocator {
+            wrapped_allocator: FakeAllocator::default(),
+            name: "Trace".to_owned(),
+            total: TraceMetrics::default(),
+            per_type: BTreeMap::new(),
+            live: HashMap::new(),
+            properties,
+            log_on_drop: true,
+        }
+        .with_drop_log(false);
+
+        let requirements = AllocationRequirements {
+            size_in_bytes: 64,
+            alignment: 1,
+            ..AllocationRequirements::default()
+        };
+        let a = unsafe { allocator.allocate(requirements).unwrap() };
+        let b = unsafe {
+            allocator
+                .allocate(AllocationRequirements {
+                    size_in_bytes: 128,
+                    location: Some(Location::caller()),
+                    frame: Some(7),
+                    user_data: 42,
+                    ..requirements
+                })
+                .unwrap()
+        };
+        unsafe { allocator.free(a) };
+
+        let report = allocator.metrics();
+        assert_eq!(report.total.total_allocations, 2);
+        assert_eq!(report.total.leaked_allocations, 1);
+        assert_eq!(report.total.total_bytes, 192);
+        assert_eq!(report.total.live_bytes, 128);
+        assert_eq!(report.per_type[&0], report.total);
+        assert_eq!(
+            report.property_flags[&0],
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+        );
+
+        let leak_report = allocator.leak_report().unwrap();
+        assert!(leak_report.contains("1 allocation(s) were not freed"));
+        assert!(leak_report.contains(
+            "- Trace: 128 b, memory type 0, frame 7, user data 42, age"
+        ));
+        assert!(leak_report.contains(&format!("allocated at {}:", file!())));
+
+        unsafe { allocator.free(b) };
+        assert_eq!(allocator.metrics().total.live_bytes, 0);
+        assert!(allocator.leak_report().is_none());
+    }
 }
@@ -0,0 +1,23 @@
+/// How [crate::MemoryAllocator::allocate_buffer_with_data] gets data into
+/// device-local memory, see [crate::MemoryAllocator::with_upload_path].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UploadPath {
+    /// Write directly when the device has host-visible device-local memory
+    /// which is large enough to be worth using, e.g. with resizable BAR or
+    /// on an integrated GPU with unified memory. Stage otherwise.
+    Auto,
+
+    /// Always copy through a staging buffer.
+    Staged,
+
+    /// Write directly whenever a host-visible device-local memory type
+    /// exists, even the small BAR heap on a discrete GPU without resizable
+    /// BAR. Falls back to staging when there is no such memory type.
+    Direct,
+}
+
+impl Default for UploadPath {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
@@ -0,0 +1,180 @@
+use {
+    crate::{AllocatorStats, MemoryUsage},
+    std::{
+        collections::VecDeque,
+        time::{Duration, Instant},
+    },
+};
+
+/// Memory usage at a single point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UsageSample {
+    /// The frame when the sample was recorded.
+    pub frame: u64,
+
+    /// The time since the sampler was created.
+    pub elapsed: Duration,
+
+    /// Usage across every memory type.
+    pub total: MemoryUsage,
+
+    /// Usage for each memory type, indexed by memory type index.
+    pub memory_types: Vec<MemoryUsage>,
+}
+
+/// Records memory usage over time so it can be plotted, e.g. to find slow
+/// leaks over a play session.
+///
+/// Only the most recent samples are kept so the sampler can run for the
+/// whole session. See [crate::MemoryAllocator::with_usage_sampler] to sample
+/// automatically at the end of each frame.
+#[derive(Debug, Clone)]
+pub struct UsageSampler {
+    samples: VecDeque<UsageSample>,
+    max_samples: usize,
+    started_at: Instant,
+}
+
+// Public API
+// ----------
+
+impl UsageSampler {
+    /// Create a new sampler.
+    ///
+    /// # Params
+    ///
+    /// * max_samples: the number of samples to keep. The oldest sample is
+    ///   dropped when a new sample would exceed the limit.
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(max_samples.min(1024)),
+            max_samples: max_samples.max(1),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Record the allocator's current usage.
+    ///
+    /// # Params
+    ///
+    /// * frame: the current frame, used to label the sample.
+    /// * stats: the allocator's stats, see [crate::MemoryAllocator::stats].
+    pub fn sample(&mut self, frame: u64, stats: &AllocatorStats) {
+        if self.samples.len() == self.max_samples {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(UsageSample {
+            frame,
+            elapsed: self.started_at.elapsed(),
+            total: stats.total,
+            memory_types: stats.memory_types.clone(),
+        });
+    }
+
+    /// The recorded samples, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = &UsageSample> {
+        self.samples.iter()
+    }
+
+    /// Forget every sample.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Write the samples as CSV with one row per sample.
+    ///
+    /// Each row has the frame, the elapsed seconds, the total allocated and
+    /// used bytes, then the allocated and used bytes for each memory type.
+    /// Memory types which didn't exist yet when a sample was recorded are
+    /// written as zero.
+    pub fn to_csv(&self) -> String {
+        let memory_type_count = self
+            .samples
+            .iter()
+            .map(|sample| sample.memory_types.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut csv = String::from(
+            "frame,elapsed_seconds,total_allocated_bytes,total_used_bytes",
+        );
+        for memory_type_index in 0..memory_type_count {
+            csv.push_str(&format!(
+                ",type_{0}_allocated_bytes,type_{0}_used_bytes",
+                memory_type_index
+            ));
+        }
+        csv.push('\n');
+
+        for sample in &self.samples {
+            csv.push_str(&format!(
+                "{},{:.3},{},{}",
+                sample.frame,
+                sample.elapsed.as_secs_f64(),
+                sample.total.allocated_bytes,
+                sample.total.used_bytes,
+            ));
+            for memory_type_index in 0..memory_type_count {
+                let usage = sample
+                    .memory_types
+                    .get(memory_type_index)
+                    .copied()
+                    .unwrap_or_default();
+                csv.push_str(&format!(
+                    ",{},{}",
+                    usage.allocated_bytes, usage.used_bytes
+                ));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stats(memory_type_bytes: &[u64]) -> AllocatorStats {
+        let mut stats = AllocatorStats::default();
+        for (memory_type_index, &bytes) in memory_type_bytes.iter().enumerate()
+        {
+            let usage = stats.memory_type_mut(memory_type_index);
+            usage.allocated_bytes = bytes * 2;
+            usage.used_bytes = bytes;
+            stats.total.allocated_bytes += bytes * 2;
+            stats.total.used_bytes += bytes;
+        }
+        stats
+    }
+
+    #[test]
+    fn test_samples_are_written_as_csv() {
+        let mut sampler = UsageSampler::new(2);
+        sampler.sample(1, &stats(&[100]));
+        sampler.sample(2, &stats(&[100, 50]));
+        sampler.sample(3, &stats(&[200]));
+
+        let frames: Vec<u64> =
+            sampler.samples().map(|sample| sample.frame).collect();
+        assert_eq!(frames, vec![2, 3]);
+
+        let csv = sampler.to_csv();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            rows[0],
+            "frame,elapsed_seconds,total_allocated_bytes,total_used_bytes,\
+             type_0_allocated_bytes,type_0_used_bytes,\
+             type_1_allocated_bytes,type_1_used_bytes"
+        );
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("2,"));
+        assert!(rows[1].ends_with(",300,150,200,100,100,50"));
+        assert!(rows[2].starts_with("3,"));
+        assert!(rows[2].ends_with(",400,200,400,200,0,0"));
+
+        sampler.clear();
+        assert_eq!(sampler.samples().count(), 0);
+    }
+}
@@ -0,0 +1,235 @@
+use {
+    crate::{
+        Allocation, AllocationOrigin, AllocationRequirements, AllocatorError,
+        AllocatorStats, ComposableAllocator, MemoryReport, PoolConfigurator,
+    },
+    ash::vk,
+    indoc::indoc,
+    std::collections::{BTreeMap, VecDeque},
+};
+
+/// Allocations are identified by their device memory id and offset. Vulkan
+/// handles can be reused after the memory is freed, ids are not.
+type AllocationKey = (u64, vk::DeviceSize);
+
+/// An allocator decorator which checks that the wrapped allocator and the
+/// application both respect the allocator contract.
+///
+/// The validation allocator panics when:
+/// - an allocation is freed twice
+/// - an allocation is freed which this allocator never produced
+/// - the wrapped allocator returns memory which is not aligned to the requested
+///   alignment
+/// - the wrapped allocator returns memory which overlaps an allocation that is
+///   still in use
+///
+/// Each of these bugs would otherwise silently corrupt memory, so this is
+/// intended to wrap allocators in debug builds and tests. With the
+/// `backtrace` feature, double-free reports include where the allocation was
+/// made and where it was first freed. Only the most recent frees are
+/// remembered, see [Self::with_free_history], so older double frees are
+/// reported as frees of allocations which this allocator never produced.
+pub struct ValidationAllocator<T: ComposableAllocator> {
+    wrapped_allocator: T,
+    outstanding: BTreeMap<AllocationKey, (vk::DeviceSize, AllocationOrigin)>,
+    freed: BTreeMap<AllocationKey, (u64, AllocationOrigin, AllocationOrigin)>,
+    freed_order: VecDeque<(u64, AllocationKey)>,
+    free_count: u64,
+    free_history: usize,
+}
+
+impl<T: ComposableAllocator> ValidationAllocator<T> {
+    /// Create a new validation allocator.
+    pub fn new(wrapped_allocator: T) -> Self {
+        Self {
+            wrapped_allocator,
+            outstanding: BTreeMap::new(),
+            freed: BTreeMap::new(),
+            freed_order: VecDeque::new(),
+            free_count: 0,
+            free_history: 4096,
+        }
+    }
+
+    /// Set how many recent frees are remembered to report double frees.
+    /// Defaults to 4096.
+    pub fn with_free_history(self, frees: usize) -> Self {
+        Self {
+            free_history: frees,
+            ..self
+        }
+    }
+
+    /// The number of allocations which have not been freed yet.
+    pub fn outstanding_allocations(&self) -> usize {
+        self.outstanding.len()
+    }
+
+    /// Remember a free so a later double free can be reported, forgetting
+    /// the oldest free when there are too many.
+    fn record_free(
+        &mut self,
+        key: AllocationKey,
+        allocated_at: AllocationOrigin,
+    ) {
+        self.free_count += 1;
+        self.freed.insert(
+            key,
+            (self.free_count, allocated_at, AllocationOrigin::capture()),
+        );
+        self.freed_order.push_back((self.free_count, key));
+        if self.freed_order.len() > self.free_history {
+            let (free_number, key) = self.freed_order.pop_front().unwrap();
+            // The key may have been allocated and freed again since.
+            if matches!(self.freed.get(&key), Some(&(n, ..)) if n == free_number)
+            {
+                self.freed.remove(&key);
+            }
+        }
+    }
+
+    /// Panic if the allocation is misaligned or overlaps any outstanding
+    /// allocation.
+    fn check_new_allocation(
+        &self,
+        key: AllocationKey,
+        allocation: &Allocation,
+        allocation_requirements: &AllocationRequirements,
+    ) {
+        let (memory, offset) = key;
+        let alignment = allocation_requirements.alignment.max(1);
+        assert!(
+            offset % alignment == 0,
+            indoc!(
+                "
+                Allocator returned a misaligned allocation!
+
+                requested alignment: {}
+                allocation: {}
+                "
+            ),
+            alignment,
+            allocation,
+        );
+
+        // Outstanding allocations never overlap each other, so only the
+        // allocation which starts closest to the end of the new allocation
+        // needs to be checked.
+        let end = offset + allocation.size_in_bytes();
+        let closest = self.outstanding.range((memory, 0)..(memory, end));
+        if let Some((&(_, existing_offset), &(existing_size, _))) =
+            closest.last()
+        {
+            assert!(
+                existing_offset + existing_size <= offset,
+                indoc!(
+                    "
+                    Allocator returned an allocation which overlaps memory
+                    that is still in use!
+
+                    existing allocation: offset {}, size {}
+                    new allocation: {}
+                    "
+                ),
+                existing_offset,
+                existing_size,
+                allocation,
+            );
+        }
+    }
+}
+
+impl<T: ComposableAllocator> ComposableAllocator for ValidationAllocator<T> {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        let allocation =
+            self.wrapped_allocator.allocate(allocation_requirements)?;
+
+        let key = (allocation.memory_id(), allocation.offset_in_bytes());
+        self.check_new_allocation(key, &allocation, &allocation_requirements);
+        self.freed.remove(&key);
+        self.outstanding.insert(
+            key,
+            (allocation.size_in_bytes(), AllocationOrigin::capture()),
+        );
+
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        let key = (allocation.memory_id(), allocation.offset_in_bytes());
+        let allocated_at = match self.outstanding.remove(&key) {
+            Some((size, allocated_at)) => {
+                assert!(
+                    size == allocation.size_in_bytes(),
+                    indoc!(
+                        "
+                        Attempted to free an allocation with a different size
+                        than the one which was allocated!
+
+                        allocated size: {}
+                        allocation: {}
+                        "
+                    ),
+                    size,
+                    allocation,
+                );
+                allocated_at
+            }
+            None => {
+                if let Some((_, allocated_at, freed_at)) = self.freed.get(&key)
+                {
+                    panic!(
+                        "Double free detected!\n\n{}{}{}",
+                        allocation,
+                        allocated_at.describe("Allocated"),
+                        freed_at.describe("First freed"),
+                    );
+                }
+                panic!(
+                    "Attempted to free an allocation which was not produced \
+                     by this allocator!\n\n{}",
+                    allocation
+                );
+            }
+        };
+        self.record_free(key, allocated_at);
+        self.wrapped_allocator.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.validate()
+    }
+
+    fn name_allocation(&mut self, allocation: &Allocation) {
+        self.wrapped_allocator.name_allocation(allocation)
+    }
+
+    fn stats(&self, stats: &mut AllocatorStats) {
+        self.wrapped_allocator.stats(stats)
+    }
+
+    fn report(&self, report: &mut MemoryReport) {
+        self.wrapped_allocator.report(report)
+    }
+
+    unsafe fn trim(&mut self) {
+        self.wrapped_allocator.trim()
+    }
+
+    fn reconfigure_pools(
+        &mut self,
+        configurator: &mut PoolConfigurator,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reconfigure_pools(configurator)
+    }
+
+    unsafe fn reserve(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<(), AllocatorError> {
+        self.wrapped_allocator.reserve(allocation_requirements)
+    }
+}
@@ -0,0 +1,169 @@
+use {
+    super::page_suballocator::{div_ceil, page_arena::PageArena},
+    crate::{pretty_wrappers::PrettySize, AllocatorError},
+    anyhow::{anyhow, Context},
+};
+
+/// A range of offsets allocated from a [VirtualBlock].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VirtualAllocation {
+    offset_in_bytes: u64,
+    size_in_bytes: u64,
+}
+
+/// A suballocator for an abstract range of offsets with no backing device
+/// memory.
+///
+/// This uses the same paged placement logic as the [crate::PageSuballocator]
+/// but only hands out offsets. It's useful for managing space inside a
+/// resource which the application creates itself, like one giant buffer
+/// shared by many meshes.
+pub struct VirtualBlock {
+    size_in_bytes: u64,
+    page_size_in_bytes: u64,
+    arena: PageArena,
+}
+
+// Public API
+// ----------
+
+impl VirtualAllocation {
+    /// The offset of the allocation from the start of the block.
+    pub fn offset_in_bytes(&self) -> u64 {
+        self.offset_in_bytes
+    }
+
+    /// The size of the allocation.
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size_in_bytes
+    }
+}
+
+impl VirtualBlock {
+    /// Create a new virtual block.
+    ///
+    /// # Params
+    ///
+    /// * size_in_bytes: the size of the range being managed.
+    /// * page_size_in_bytes: the size of each page in the block. Larger pages
+    ///   waste space for small allocations while small pages increase
+    ///   allocation time.
+    ///
+    /// # Panic
+    ///
+    /// Panics if size_in_bytes is not a multiple of page_size_in_bytes.
+    pub fn new(size_in_bytes: u64, page_size_in_bytes: u64) -> Self {
+        assert!(
+            page_size_in_bytes > 0 && size_in_bytes % page_size_in_bytes == 0,
+            "size_in_bytes must be a multiple of page_size_in_bytes"
+        );
+        let page_count = size_in_bytes / page_size_in_bytes;
+        Self {
+            size_in_bytes,
+            page_size_in_bytes,
+            arena: PageArena::new(page_count as usize),
+        }
+    }
+
+    /// Place allocations at random suitable offsets instead of the first
+    /// available offset. This is a testing mode, see
+    /// [crate::PageSuballocator::with_random_placement].
+    pub fn with_random_placement(self, seed: u64) -> Self {
+        Self {
+            arena: self.arena.with_random_placement(seed),
+            ..self
+        }
+    }
+
+    /// The total size of the range managed by this block.
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size_in_bytes
+    }
+
+    /// Returns true when every allocation has been freed.
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Check that the page bookkeeping is consistent.
+    pub fn validate(&self) -> Result<(), AllocatorError> {
+        self.arena.validate().map_err(|err| anyhow!(err).into())
+    }
+
+    /// Allocate a range of offsets.
+    ///
+    /// # Params
+    ///
+    /// * size_in_bytes: the required size of the allocation.
+    /// * alignment: the required alignment of the offset, relative to the start
+    ///   of the block.
+    pub fn allocate(
+        &mut self,
+        size_in_bytes: u64,
+        alignment: u64,
+    ) -> Result<VirtualAllocation, AllocatorError> {
+        let alignment = alignment.max(1);
+        if self.page_size_in_bytes % alignment == 0 {
+            // Every page starts at an aligned offset.
+            let offset_in_bytes = self.allocate_pages(size_in_bytes)?;
+            return Ok(VirtualAllocation {
+                offset_in_bytes,
+                size_in_bytes,
+            });
+        }
+
+        // Allocate enough extra space that the offset can be aligned.
+        let page_offset = self.allocate_pages(size_in_bytes + alignment - 1)?;
+        let alignment_correction =
+            (alignment - page_offset % alignment) % alignment;
+        Ok(VirtualAllocation {
+            offset_in_bytes: page_offset + alignment_correction,
+            size_in_bytes,
+        })
+    }
+
+    /// Free a previously allocated range.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the allocation is outside of this block.
+    pub fn free(&mut self, allocation: VirtualAllocation) {
+        assert!(
+            allocation.offset_in_bytes < self.size_in_bytes,
+            "The allocation at {} is outside of the block with size {}",
+            allocation.offset_in_bytes,
+            PrettySize(self.size_in_bytes),
+        );
+
+        // Any offset within the chunk frees the whole chunk, so it's safe to
+        // round down even when the offset was aligned past the first page.
+        let page_index = allocation.offset_in_bytes / self.page_size_in_bytes;
+        self.arena.free_chunk(page_index as usize);
+    }
+}
+
+// Private API
+// -----------
+
+impl VirtualBlock {
+    /// Allocate a contiguous chunk of pages.
+    ///
+    /// # Returns
+    ///
+    /// The offset of the first page in the chunk.
+    fn allocate_pages(
+        &mut self,
+        size_in_bytes: u64,
+    ) -> Result<u64, AllocatorError> {
+        let page_count =
+            div_ceil(size_in_bytes.max(1), self.page_size_in_bytes) as usize;
+        let first_page =
+            self.arena.allocate_chunk(page_count).with_context(|| {
+                format!(
+                    "Unable to find {} contiguous bytes in the virtual block",
+                    PrettySize(size_in_bytes),
+                )
+            })?;
+        Ok(first_page as u64 * self.page_size_in_bytes)
+    }
+}
@@ -0,0 +1,397 @@
+use {
+    crate::{
+        pretty_wrappers::PrettySize, AllocationReport, ChunkReport,
+        MemoryReport,
+    },
+    indoc::indoc,
+    std::collections::{BTreeSet, HashMap},
+};
+
+/// The output format for [crate::MemoryAllocator::visualize].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VisualizationFormat {
+    /// A single SVG image.
+    Svg,
+
+    /// A standalone HTML page with the SVG image and a legend.
+    Html,
+}
+
+impl Default for VisualizationFormat {
+    fn default() -> Self {
+        Self::Html
+    }
+}
+
+/// The width of the bar for the largest chunk.
+const BAR_WIDTH: f64 = 800.0;
+
+/// The height of each chunk's bar.
+const BAR_HEIGHT: u64 = 20;
+
+/// The height of the label above each bar.
+const LABEL_HEIGHT: u64 = 16;
+
+/// Free runs are drawn with this color.
+const FREE_COLOR: &str = "#e0e0e0";
+
+/// Used runs are colored by the tag or name of their allocation.
+const PALETTE: [&str; 8] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948",
+    "#b07aa1", "#ff9da7",
+];
+
+/// Render a memory report.
+///
+/// Each chunk is drawn as a bar with one rectangle for each used or free run.
+/// Bars are scaled to the largest chunk, so chunks of different sizes can be
+/// compared. Used runs are colored by the tag of the allocation in the run,
+/// or its name when it has no tag, see [AllocationReport]. Runs which hold
+/// another pool's chunk, or an allocation with neither, are colored by the
+/// chunk's owner, see [ChunkReport::owner].
+pub(crate) fn visualize(
+    report: &MemoryReport,
+    format: VisualizationFormat,
+) -> String {
+    let labels = run_labels(report);
+    let svg = render_svg(report, &labels);
+    match format {
+        VisualizationFormat::Svg => svg,
+        VisualizationFormat::Html => render_html(&labels, &svg),
+    }
+}
+
+// Private API
+// -----------
+
+/// The label for every run in every chunk, None for free runs.
+type RunLabels = Vec<Vec<Option<String>>>;
+
+/// Label each used run with the tag or name of the allocation in it.
+fn run_labels(report: &MemoryReport) -> RunLabels {
+    let allocations = run_allocations(report);
+    report
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(chunk_index, chunk)| {
+            chunk
+                .runs
+                .iter()
+                .map(|run| {
+                    if !run.used {
+                        return None;
+                    }
+                    let label = allocations
+                        .get(&(chunk_index, run.offset_in_bytes))
+                        .and_then(|allocation| {
+                            allocation
+                                .tag
+                                .map(str::to_owned)
+                                .or_else(|| allocation.name.clone())
+                        });
+                    Some(label.unwrap_or_else(|| {
+                        owner_label(&chunk.owner).to_owned()
+                    }))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Find the allocation in each used run, keyed by the chunk's index and the
+/// run's offset.
+///
+/// Pools can carve their chunks out of another pool's chunks, so an
+/// allocation can be inside several chunks. It belongs to the smallest one.
+fn run_allocations(
+    report: &MemoryReport,
+) -> HashMap<(usize, u64), &AllocationReport> {
+    let mut allocations = HashMap::new();
+    for allocation in &report.allocations {
+        let end = allocation.offset_in_bytes + allocation.size_in_bytes;
+        let chunk = report
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| {
+                chunk.memory_id == allocation.memory_id
+                    && chunk.memory_type_index == allocation.memory_type_index
+                    && chunk.offset_in_bytes <= allocation.offset_in_bytes
+                    && end <= chunk.offset_in_bytes + chunk.size_in_bytes
+            })
+            .min_by_key(|(_, chunk)| chunk.size_in_bytes);
+        let (chunk_index, chunk) = match chunk {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+        let offset_in_chunk =
+            allocation.offset_in_bytes - chunk.offset_in_bytes;
+        let run = chunk.runs.iter().find(|run| {
+            run.used
+                && run.offset_in_bytes <= offset_in_chunk
+                && offset_in_chunk < run.offset_in_bytes + run.size_in_bytes
+        });
+        if let Some(run) = run {
+            allocations.insert((chunk_index, run.offset_in_bytes), allocation);
+        }
+    }
+    allocations
+}
+
+/// Draw every chunk as a bar in a single SVG image.
+fn render_svg(report: &MemoryReport, labels: &RunLabels) -> String {
+    let largest_chunk = report
+        .chunks
+        .iter()
+        .map(|chunk| chunk.size_in_bytes)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let row_height = LABEL_HEIGHT + BAR_HEIGHT + 4;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+         height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        BAR_WIDTH,
+        (row_height * report.chunks.len() as u64).max(1)
+    );
+    for (row, (chunk, labels)) in report.chunks.iter().zip(labels).enumerate() {
+        let top = row as u64 * row_height;
+        let scale = BAR_WIDTH / largest_chunk as f64;
+        svg.push_str(&format!(
+            "<text x=\"0\" y=\"{}\">{} ({})</text>\n",
+            top + LABEL_HEIGHT - 4,
+            escape(&chunk_label(chunk)),
+            PrettySize(chunk.size_in_bytes),
+        ));
+        for (run, label) in chunk.runs.iter().zip(labels) {
+            svg.push_str(&format!(
+                "<rect x=\"{:.2}\" y=\"{}\" width=\"{:.2}\" height=\"{}\" \
+                 fill=\"{}\" stroke=\"#ffffff\" stroke-width=\"0.5\">\
+                 <title>{} at {}: {}</title></rect>\n",
+                run.offset_in_bytes as f64 * scale,
+                top + LABEL_HEIGHT,
+                run.size_in_bytes as f64 * scale,
+                BAR_HEIGHT,
+                match label {
+                    Some(label) => label_color(label),
+                    None => FREE_COLOR,
+                },
+                escape(label.as_deref().unwrap_or("free")),
+                run.offset_in_bytes,
+                PrettySize(run.size_in_bytes),
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Wrap the SVG image in a standalone page with a legend for each label.
+fn render_html(labels: &RunLabels, svg: &str) -> String {
+    let labels: BTreeSet<&str> = labels
+        .iter()
+        .flatten()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    let mut legend = format!(
+        "<li><span style=\"background:{}\"></span>free</li>\n",
+        FREE_COLOR
+    );
+    for label in labels {
+        legend.push_str(&format!(
+            "<li><span style=\"background:{}\"></span>{}</li>\n",
+            label_color(label),
+            escape(label),
+        ));
+    }
+    format!(
+        indoc!(
+            "
+            <!DOCTYPE html>
+            <html>
+            <head>
+            <meta charset=\"utf-8\">
+            <title>Memory Map</title>
+            <style>
+            body {{ font-family: monospace; }}
+            ul {{ list-style: none; padding: 0; }}
+            li span {{
+                display: inline-block; width: 12px; height: 12px;
+                margin-right: 6px;
+            }}
+            </style>
+            </head>
+            <body>
+            <h1>Memory Map</h1>
+            <ul>
+            {}</ul>
+            {}</body>
+            </html>
+            "
+        ),
+        legend, svg
+    )
+}
+
+/// The label for a chunk's bar.
+fn chunk_label(chunk: &ChunkReport) -> String {
+    if chunk.owner.is_empty() {
+        chunk.name.clone()
+    } else {
+        format!("{}/{}", chunk.owner, chunk.name)
+    }
+}
+
+/// The legend label for an owner.
+fn owner_label(owner: &str) -> &str {
+    if owner.is_empty() {
+        "(no owner)"
+    } else {
+        owner
+    }
+}
+
+/// Pick a stable color for a label so it's the same in every render.
+fn label_color(label: &str) -> &'static str {
+    // FNV-1a
+    let hash = label.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    PALETTE[(hash % PALETTE.len() as u64) as usize]
+}
+
+/// Escape text for XML and HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::MemoryRun};
+
+    fn chunk(owner: &str, size_in_bytes: u64) -> ChunkReport {
+        ChunkReport {
+            owner: owner.to_owned(),
+            name: "type-0/chunk-0".to_owned(),
+            memory_id: 0,
+            memory_type_index: 0,
+            offset_in_bytes: 0,
+            size_in_bytes,
+            runs: vec![
+                MemoryRun {
+                    offset_in_bytes: 0,
+                    size_in_bytes: size_in_bytes / 4,
+                    used: true,
+                },
+                MemoryRun {
+                    offset_in_bytes: size_in_bytes / 4,
+                    size_in_bytes: size_in_bytes - size_in_bytes / 4,
+                    used: false,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_runs_are_scaled_and_colored_by_owner() {
+        let mut report = MemoryReport::default();
+        report.add_chunk(chunk("", 1024));
+        report.with_owner("Textures & Meshes", |report| {
+            report.add_chunk(chunk("", 512))
+        });
+
+        let svg = visualize(&report, VisualizationFormat::Svg);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+        // The largest chunk fills the bar.
+        assert!(svg.contains("x=\"200.00\" y=\"16\" width=\"600.00\""));
+        // The smaller chunk is drawn at half the width.
+        assert!(svg.contains("x=\"0.00\" y=\"56\" width=\"100.00\""));
+        assert!(svg.contains(&format!(
+            "fill=\"{}\"",
+            label_color("Textures & Meshes")
+        )));
+        assert!(svg.contains("Textures &amp; Meshes/type-0/chunk-0"));
+
+        let html = visualize(&report, VisualizationFormat::Html);
+        assert!(html.trim_start().starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(&svg));
+        assert!(html.contains("(no owner)"));
+    }
+
+    fn allocation(
+        tag: Option<&'static str>,
+        name: Option<&str>,
+        offset_in_bytes: u64,
+    ) -> AllocationReport {
+        AllocationReport {
+            path: String::new(),
+            name: name.map(str::to_owned),
+            tag,
+            user_data: 0,
+            memory_id: 0,
+            memory_type_index: 0,
+            offset_in_bytes,
+            size_in_bytes: 64,
+            age: Default::default(),
+            frame: None,
+        }
+    }
+
+    #[test]
+    fn test_runs_are_colored_by_tag_or_name() {
+        let mut report = MemoryReport::default();
+        report.with_owner("App", |report| {
+            // The outer chunk's used run holds the inner chunk.
+            report.add_chunk(chunk("", 1024));
+            report.add_chunk(ChunkReport {
+                name: "type-0/chunk-1".to_owned(),
+                runs: vec![
+                    MemoryRun {
+                        offset_in_bytes: 0,
+                        size_in_bytes: 64,
+                        used: true,
+                    },
+                    MemoryRun {
+                        offset_in_bytes: 64,
+                        size_in_bytes: 64,
+                        used: true,
+                    },
+                    MemoryRun {
+                        offset_in_bytes: 128,
+                        size_in_bytes: 128,
+                        used: false,
+                    },
+                ],
+                ..chunk("", 256)
+            });
+        });
+        report.add_allocation(allocation(Some("textures"), Some("Grass"), 0));
+        report.add_allocation(allocation(None, Some("Terrain"), 64));
+
+        let labels = run_labels(&report);
+        assert_eq!(labels[0], vec![Some("App".to_owned()), None]);
+        assert_eq!(
+            labels[1],
+            vec![
+                Some("textures".to_owned()),
+                Some("Terrain".to_owned()),
+                None
+            ]
+        );
+
+        let svg = visualize(&report, VisualizationFormat::Svg);
+        assert!(svg.contains(&format!("fill=\"{}\"", label_color("textures"))));
+        assert!(svg.contains("<title>Terrain at 64: 64 b</title>"));
+
+        let html = visualize(&report, VisualizationFormat::Html);
+        assert!(html.contains("textures</li>"));
+        assert!(html.contains("Terrain</li>"));
+        assert!(html.contains("App</li>"));
+    }
+}
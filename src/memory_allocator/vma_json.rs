@@ -0,0 +1,338 @@
+use {
+    crate::{
+        AllocatorStats, ChunkReport, MemoryProperties, MemoryReport,
+        MemoryUsage,
+    },
+    ash::vk,
+    std::ffi::CStr,
+};
+
+/// Memory type property flags and their names in the VMA JSON format.
+const MEMORY_PROPERTY_NAMES: [(vk::MemoryPropertyFlags, &str); 6] = [
+    (vk::MemoryPropertyFlags::DEVICE_LOCAL, "DEVICE_LOCAL"),
+    (vk::MemoryPropertyFlags::HOST_VISIBLE, "HOST_VISIBLE"),
+    (vk::MemoryPropertyFlags::HOST_COHERENT, "HOST_COHERENT"),
+    (vk::MemoryPropertyFlags::HOST_CACHED, "HOST_CACHED"),
+    (
+        vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+        "LAZILY_ALLOCATED",
+    ),
+    (vk::MemoryPropertyFlags::PROTECTED, "PROTECTED"),
+];
+
+/// Describe the allocator's memory in the JSON format written by VMA's
+/// vmaBuildStatsString, so the dump can be read by VmaDumpVis and similar
+/// tools.
+///
+/// Every pool chunk is written as a block in the default pool for its memory
+/// type. Chunks from nested pools are written as separate blocks, so a block
+/// may overlap the block it was suballocated from.
+///
+/// # Params
+///
+/// * device_properties: used for the "General" section.
+/// * memory_properties: the device's memory types and heaps.
+/// * stats: usage for each memory type and heap.
+/// * report: the layout of every chunk.
+pub(crate) fn vma_json(
+    device_properties: &vk::PhysicalDeviceProperties,
+    memory_properties: &MemoryProperties,
+    stats: &AllocatorStats,
+    report: &MemoryReport,
+) -> String {
+    let sections = [
+        format!(
+            "\"General\": {}",
+            general_json(device_properties, memory_properties)
+        ),
+        format!("\"Total\": {}", stats_json(&stats.total)),
+        format!(
+            "\"MemoryInfo\": {}",
+            memory_info_json(memory_properties, stats)
+        ),
+        format!(
+            "\"DefaultPools\": {}",
+            default_pools_json(memory_properties, report)
+        ),
+    ];
+    format!("{{{}}}", sections.join(", "))
+}
+
+// Private API
+// -----------
+
+/// The device and its limits.
+fn general_json(
+    device_properties: &vk::PhysicalDeviceProperties,
+    memory_properties: &MemoryProperties,
+) -> String {
+    // SAFE because Vulkan device names are null-terminated.
+    let device_name =
+        unsafe { CStr::from_ptr(device_properties.device_name.as_ptr()) }
+            .to_string_lossy();
+    let api_version = device_properties.api_version;
+    format!(
+        "{{\"API\": \"Vulkan\", \"apiVersion\": \"{}.{}.{}\", \
+         \"GPU\": {}, \"deviceType\": {}, \
+         \"bufferImageGranularity\": {}, \"memoryHeapCount\": {}, \
+         \"memoryTypeCount\": {}}}",
+        vk::api_version_major(api_version),
+        vk::api_version_minor(api_version),
+        vk::api_version_patch(api_version),
+        json_string(&device_name),
+        device_properties.device_type.as_raw(),
+        memory_properties.buffer_image_granularity(),
+        memory_properties.heaps().len(),
+        memory_properties.types().len(),
+    )
+}
+
+/// The budget and usage for each heap and memory type.
+fn memory_info_json(
+    memory_properties: &MemoryProperties,
+    stats: &AllocatorStats,
+) -> String {
+    let heaps: Vec<String> = memory_properties
+        .heaps()
+        .iter()
+        .enumerate()
+        .map(|(heap_index, heap)| {
+            let mut flags = vec![];
+            if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
+                flags.push("DEVICE_LOCAL");
+            }
+            if heap.flags.contains(vk::MemoryHeapFlags::MULTI_INSTANCE) {
+                flags.push("MULTI_INSTANCE");
+            }
+            let budget = match stats
+                .heap_budgets
+                .as_ref()
+                .and_then(|budgets| budgets.get(heap_index))
+            {
+                Some(budget) => format!(
+                    "\"Budget\": {{\"BudgetBytes\": {}, \"UsageBytes\": {}}}, ",
+                    budget.budget_bytes, budget.usage_bytes
+                ),
+                None => String::new(),
+            };
+            let types: Vec<String> = memory_properties
+                .types()
+                .iter()
+                .enumerate()
+                .filter(|(_, memory_type)| {
+                    memory_type.heap_index as usize == heap_index
+                })
+                .map(|(memory_type_index, memory_type)| {
+                    format!(
+                        "\"Type {}\": {{\"Flags\": {}, \"Stats\": {}}}",
+                        memory_type_index,
+                        flags_json(memory_type.property_flags),
+                        stats_json(&usage(
+                            &stats.memory_types,
+                            memory_type_index
+                        )),
+                    )
+                })
+                .collect();
+            format!(
+                "\"Heap {}\": {{\"Flags\": [{}], \"Size\": {}, {}\"Stats\": \
+                 {}, \"MemoryPools\": {{{}}}}}",
+                heap_index,
+                flags
+                    .iter()
+                    .map(|flag| json_string(flag))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                heap.size,
+                budget,
+                stats_json(&usage(&stats.memory_heaps, heap_index)),
+                types.join(", "),
+            )
+        })
+        .collect();
+    format!("{{{}}}", heaps.join(", "))
+}
+
+/// The chunks for each memory type.
+fn default_pools_json(
+    memory_properties: &MemoryProperties,
+    report: &MemoryReport,
+) -> String {
+    let pools: Vec<String> = (0..memory_properties.types().len())
+        .map(|memory_type_index| {
+            let chunks: Vec<&ChunkReport> = report
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.memory_type_index == memory_type_index)
+                .collect();
+            let preferred_block_size = chunks
+                .iter()
+                .map(|chunk| chunk.size_in_bytes)
+                .max()
+                .unwrap_or(0);
+            let blocks: Vec<String> = chunks
+                .iter()
+                .enumerate()
+                .map(|(block_id, chunk)| {
+                    format!("\"{}\": {}", block_id, block_json(chunk))
+                })
+                .collect();
+            format!(
+                "\"Type {}\": {{\"PreferredBlockSize\": {}, \"Blocks\": {{{}}}, \
+                 \"DedicatedAllocations\": []}}",
+                memory_type_index,
+                preferred_block_size,
+                blocks.join(", "),
+            )
+        })
+        .collect();
+    format!("{{{}}}", pools.join(", "))
+}
+
+/// A chunk and the runs inside of it.
+fn block_json(chunk: &ChunkReport) -> String {
+    let free_runs = chunk.runs.iter().filter(|run| !run.used);
+    let unused_bytes: u64 =
+        free_runs.clone().map(|run| run.size_in_bytes).sum();
+    let suballocations: Vec<String> = chunk
+        .runs
+        .iter()
+        .map(|run| {
+            format!(
+                "{{\"Offset\": {}, \"Type\": \"{}\", \"Size\": {}}}",
+                run.offset_in_bytes,
+                if run.used { "UNKNOWN" } else { "FREE" },
+                run.size_in_bytes
+            )
+        })
+        .collect();
+    let name = if chunk.owner.is_empty() {
+        chunk.name.clone()
+    } else {
+        format!("{}/{}", chunk.owner, chunk.name)
+    };
+    format!(
+        "{{\"Name\": {}, \"MapRefCount\": 0, \"TotalBytes\": {}, \
+         \"UnusedBytes\": {}, \"Allocations\": {}, \"UnusedRanges\": {}, \
+         \"Suballocations\": [{}]}}",
+        json_string(&name),
+        chunk.size_in_bytes,
+        unused_bytes,
+        chunk.runs.iter().filter(|run| run.used).count(),
+        free_runs.count(),
+        suballocations.join(", "),
+    )
+}
+
+/// Usage counters in VMA's detailed statistics format.
+fn stats_json(usage: &MemoryUsage) -> String {
+    format!(
+        "{{\"BlockCount\": {}, \"BlockBytes\": {}, \"AllocationCount\": {}, \
+         \"AllocationBytes\": {}}}",
+        usage.device_allocation_count,
+        usage.allocated_bytes,
+        usage.allocation_count,
+        usage.used_bytes,
+    )
+}
+
+/// The usage at an index, or empty usage when nothing was recorded.
+fn usage(usages: &[MemoryUsage], index: usize) -> MemoryUsage {
+    usages.get(index).copied().unwrap_or_default()
+}
+
+/// The names of a memory type's property flags.
+fn flags_json(property_flags: vk::MemoryPropertyFlags) -> String {
+    let names: Vec<String> = MEMORY_PROPERTY_NAMES
+        .iter()
+        .filter(|(flag, _)| property_flags.contains(*flag))
+        .map(|(_, name)| json_string(name))
+        .collect();
+    format!("[{}]", names.join(", "))
+}
+
+/// Quote and escape a string for JSON.
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                escaped.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::MemoryRun};
+
+    #[test]
+    fn test_chunks_are_written_as_blocks() {
+        let memory_properties = unsafe {
+            // SAFE because no memory is allocated.
+            MemoryProperties::from_raw(
+                &[vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap_index: 0,
+                }],
+                &[vk::MemoryHeap {
+                    size: 4096,
+                    flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+                }],
+            )
+        };
+        let mut report = MemoryReport::default();
+        report.add_chunk(ChunkReport {
+            owner: String::new(),
+            name: "type-0/chunk-\"0\"".to_owned(),
+            memory_id: 0,
+            memory_type_index: 0,
+            offset_in_bytes: 0,
+            size_in_bytes: 1024,
+            runs: vec![
+                MemoryRun {
+                    offset_in_bytes: 0,
+                    size_in_bytes: 256,
+                    used: true,
+                },
+                MemoryRun {
+                    offset_in_bytes: 256,
+                    size_in_bytes: 768,
+                    used: false,
+                },
+            ],
+        });
+
+        let json = vma_json(
+            &vk::PhysicalDeviceProperties::default(),
+            &memory_properties,
+            &AllocatorStats::default(),
+            &report,
+        );
+
+        assert!(json.starts_with("{\"General\": {\"API\": \"Vulkan\""));
+        assert!(json.contains(
+            "\"Heap 0\": {\"Flags\": [\"DEVICE_LOCAL\"], \"Size\": 4096"
+        ));
+        assert!(json.contains(
+            "\"Type 0\": {\"PreferredBlockSize\": 1024, \"Blocks\": {\"0\": \
+             {\"Name\": \"type-0/chunk-\\\"0\\\"\", \"MapRefCount\": 0, \
+             \"TotalBytes\": 1024, \"UnusedBytes\": 768, \"Allocations\": 1, \
+             \"UnusedRanges\": 1"
+        ));
+        assert!(json.contains(
+            "\"Suballocations\": [{\"Offset\": 0, \"Type\": \"UNKNOWN\", \
+             \"Size\": 256}, {\"Offset\": 256, \"Type\": \"FREE\", \
+             \"Size\": 768}]"
+        ));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+    }
+}
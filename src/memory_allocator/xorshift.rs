@@ -0,0 +1,52 @@
+/// A tiny, seedable pseudo-random number generator.
+///
+/// This is only used for test and debug features (like randomized failures
+/// and placement) where reproducibility matters more than quality, so it's not
+/// worth pulling in another dependency.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    /// Create a new generator from a seed.
+    pub(crate) fn new(seed: u64) -> Self {
+        const MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+
+        // xorshift gets stuck at zero, so mix the seed first. The one seed
+        // which still mixes to zero gets a non-zero state instead.
+        let state = seed ^ MIX;
+        Self {
+            state: if state == 0 { MIX } else { state },
+        }
+    }
+
+    /// Generate the next random value (xorshift64*).
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Generate a uniformly distributed random value in the range [0, 1).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Generate a random value in the range [0, bound).
+    pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_no_seed_gets_stuck_at_zero() {
+        let mut rng = XorShiftRng::new(0x9E37_79B9_7F4A_7C15);
+        assert!((0..4).any(|_| rng.next_u64() != 0));
+    }
+}
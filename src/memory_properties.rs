@@ -1,9 +1,31 @@
-use {crate::PrettySize, ash::vk, indoc::indoc};
+use {
+    crate::PrettySize,
+    ash::vk,
+    indoc::indoc,
+    std::ffi::{c_void, CStr},
+};
+
+/// How much of a heap the OS lets the application use, from
+/// VkPhysicalDeviceMemoryBudgetPropertiesEXT.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeapBudget {
+    /// An estimate of how much memory the process can allocate from the heap
+    /// before allocations fail or performance degrades.
+    pub budget_bytes: u64,
+
+    /// An estimate of how much memory the process is currently using in the
+    /// heap, including memory which wasn't allocated by this allocator.
+    pub usage_bytes: u64,
+}
 
 #[derive(Debug, Clone)]
 pub struct MemoryProperties {
     types: Vec<vk::MemoryType>,
     heaps: Vec<vk::MemoryHeap>,
+    max_memory_allocation_size: Option<u64>,
+    buffer_image_granularity: u64,
+    memory_budget_supported: bool,
 }
 
 impl MemoryProperties {
@@ -25,7 +47,48 @@ impl MemoryProperties {
         heaps.extend_from_slice(
             &properties.memory_heaps[0..properties.memory_heap_count as usize],
         );
-        Self { types, heaps }
+
+        let mut maintenance3 =
+            vk::PhysicalDeviceMaintenance3Properties::default();
+        let mut properties2 = vk::PhysicalDeviceProperties2 {
+            p_next: &mut maintenance3
+                as *mut vk::PhysicalDeviceMaintenance3Properties
+                as *mut c_void,
+            ..Default::default()
+        };
+        unsafe {
+            instance.get_physical_device_properties2(
+                physical_device,
+                &mut properties2,
+            )
+        };
+
+        let memory_budget_supported = unsafe {
+            instance.enumerate_device_extension_properties(physical_device)
+        }
+        .map(|extensions| {
+            extensions.iter().any(|extension| {
+                // SAFE because Vulkan extension names are null-terminated.
+                let name = unsafe {
+                    CStr::from_ptr(extension.extension_name.as_ptr())
+                };
+                name == vk::ExtMemoryBudgetFn::name()
+            })
+        })
+        .unwrap_or(false);
+
+        Self {
+            types,
+            heaps,
+            max_memory_allocation_size: Some(
+                maintenance3.max_memory_allocation_size,
+            ),
+            buffer_image_granularity: properties2
+                .properties
+                .limits
+                .buffer_image_granularity,
+            memory_budget_supported,
+        }
     }
 
     /// Create memory properties directly from a slice of memory types and
@@ -46,6 +109,21 @@ impl MemoryProperties {
         Self {
             types: types.to_owned(),
             heaps: heaps.to_owned(),
+            max_memory_allocation_size: None,
+            buffer_image_granularity: 1,
+            memory_budget_supported: false,
+        }
+    }
+
+    /// Set the largest size for a single device memory allocation. This is
+    /// primarily used for testing, with [Self::from_raw].
+    pub fn with_max_memory_allocation_size(
+        self,
+        max_memory_allocation_size: u64,
+    ) -> Self {
+        Self {
+            max_memory_allocation_size: Some(max_memory_allocation_size),
+            ..self
         }
     }
 
@@ -58,6 +136,216 @@ impl MemoryProperties {
     pub fn types(&self) -> &[vk::MemoryType] {
         &self.types
     }
+
+    /// The largest size for a single device memory allocation, from
+    /// VkPhysicalDeviceMaintenance3Properties::maxMemoryAllocationSize. None
+    /// when unknown.
+    pub fn max_memory_allocation_size(&self) -> Option<u64> {
+        self.max_memory_allocation_size
+    }
+
+    /// The granularity at which linear and optimal resources must be kept
+    /// apart in the same memory, from
+    /// VkPhysicalDeviceLimits::bufferImageGranularity.
+    pub fn buffer_image_granularity(&self) -> u64 {
+        self.buffer_image_granularity
+    }
+
+    /// Set the granularity at which linear and optimal resources must be kept
+    /// apart. This is primarily used for testing, with [Self::from_raw].
+    pub fn with_buffer_image_granularity(
+        self,
+        buffer_image_granularity: u64,
+    ) -> Self {
+        Self {
+            buffer_image_granularity,
+            ..self
+        }
+    }
+
+    /// Returns true when the physical device supports VK_EXT_memory_budget, so
+    /// [Self::query_heap_budgets] can report what the OS actually allows.
+    pub fn supports_memory_budget(&self) -> bool {
+        self.memory_budget_supported
+    }
+
+    /// Query the current budget and usage for every heap. Budgets change over
+    /// time, e.g. when other processes allocate memory, so they are queried
+    /// from the device each time rather than stored.
+    ///
+    /// # Params
+    ///
+    /// * instance: the instance used to create the physical device.
+    /// * physical_device: the physical device these properties came from.
+    ///
+    /// # Returns
+    ///
+    /// The budget for each heap, indexed by heap index, or None when
+    /// VK_EXT_memory_budget isn't supported.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the physical device must be the one these properties were queried
+    ///     from
+    pub unsafe fn query_heap_budgets(
+        &self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Option<Vec<HeapBudget>> {
+        if !self.memory_budget_supported {
+            return None;
+        }
+        let mut budget_properties =
+            vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut properties2 = vk::PhysicalDeviceMemoryProperties2 {
+            p_next: &mut budget_properties
+                as *mut vk::PhysicalDeviceMemoryBudgetPropertiesEXT
+                as *mut c_void,
+            ..Default::default()
+        };
+        instance.get_physical_device_memory_properties2(
+            physical_device,
+            &mut properties2,
+        );
+        let budgets = (0..self.heaps.len())
+            .map(|heap_index| HeapBudget {
+                budget_bytes: budget_properties.heap_budget[heap_index],
+                usage_bytes: budget_properties.heap_usage[heap_index],
+            })
+            .collect();
+        Some(budgets)
+    }
+
+    /// Find a memory type which has both the required and preferred
+    /// properties, falling back to a type with only the required properties.
+    ///
+    /// Types with DEVICE_COHERENT_AMD or DEVICE_UNCACHED_AMD are only picked
+    /// when those properties are required or preferred. Allocating from them
+    /// needs VK_AMD_device_coherent_memory with the deviceCoherentMemory
+    /// feature enabled. They are useful for trace buffers which must survive
+    /// a GPU crash.
+    ///
+    /// # Params
+    ///
+    /// * required: properties the memory type must have.
+    /// * preferred: properties which are used when available.
+    /// * memory_type_bits: the memory types which can be used, e.g. from
+    ///   VkMemoryRequirements::memoryTypeBits.
+    ///
+    /// # Returns
+    ///
+    /// The index of the first suitable memory type, or None when no allowed
+    /// memory type has the required properties.
+    pub fn find_type(
+        &self,
+        required: vk::MemoryPropertyFlags,
+        preferred: vk::MemoryPropertyFlags,
+        memory_type_bits: u32,
+    ) -> Option<usize> {
+        find_memory_type(&self.types, memory_type_bits, required | preferred)
+            .or_else(|| {
+                find_memory_type(&self.types, memory_type_bits, required)
+            })
+    }
+
+    /// Returns true when every heap is DEVICE_LOCAL, e.g. on integrated GPUs
+    /// where the device and host share memory.
+    pub fn is_unified_memory(&self) -> bool {
+        !self.heaps.is_empty()
+            && self.heaps.iter().all(|heap| {
+                heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+            })
+    }
+
+    /// The size of the largest DEVICE_LOCAL heap, or 0 when there isn't one.
+    pub fn device_local_heap_size(&self) -> u64 {
+        self.heaps
+            .iter()
+            .filter(|heap| {
+                heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+            })
+            .map(|heap| heap.size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns true when a discrete GPU exposes more than the legacy 256 MB
+    /// window of its memory as HOST_VISIBLE, e.g. with resizable BAR.
+    pub fn has_rebar(&self) -> bool {
+        const LEGACY_BAR_SIZE: u64 = 256 * 1024 * 1024;
+        !self.is_unified_memory()
+            && self.types.iter().any(|memory_type| {
+                memory_type.property_flags.contains(
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL
+                        | vk::MemoryPropertyFlags::HOST_VISIBLE,
+                ) && self
+                    .heaps
+                    .get(memory_type.heap_index as usize)
+                    .is_some_and(|heap| heap.size > LEGACY_BAR_SIZE)
+            })
+    }
+
+    /// Returns true when any memory type is LAZILY_ALLOCATED. This is typical
+    /// for tile-based GPUs, where transient attachments may never need
+    /// physical memory.
+    pub fn has_lazily_allocated_memory(&self) -> bool {
+        self.types.iter().any(|memory_type| {
+            memory_type
+                .property_flags
+                .contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED)
+        })
+    }
+
+    /// Returns true when the memory type is write-combined. e.g. it is
+    /// HOST_VISIBLE but not HOST_CACHED, so host reads are extremely slow.
+    pub fn is_write_combined(&self, memory_type_index: usize) -> bool {
+        self.types
+            .get(memory_type_index)
+            .is_some_and(|memory_type| {
+                let flags = memory_type.property_flags;
+                flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+                    && !flags.contains(vk::MemoryPropertyFlags::HOST_CACHED)
+            })
+    }
+}
+
+/// Memory properties which are only picked when they are required, see
+/// [find_memory_type].
+const OPT_IN_MEMORY_PROPERTIES: vk::MemoryPropertyFlags =
+    vk::MemoryPropertyFlags::from_raw(
+        vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD.as_raw()
+            | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD.as_raw(),
+    );
+
+/// Find the first allowed memory type with the required properties.
+///
+/// Memory types with DEVICE_COHERENT_AMD or DEVICE_UNCACHED_AMD are much
+/// slower and need the deviceCoherentMemory feature, so they are skipped
+/// unless those properties are required.
+///
+/// # Params
+///
+/// * memory_types: every memory type on the device.
+/// * memory_type_bits: a bitmask of the memory types which can be used.
+/// * required: properties the memory type must have.
+pub(crate) fn find_memory_type(
+    memory_types: &[vk::MemoryType],
+    memory_type_bits: u32,
+    required: vk::MemoryPropertyFlags,
+) -> Option<usize> {
+    memory_types
+        .iter()
+        .enumerate()
+        .find(|(index, memory_type)| {
+            let is_allowed_type = (1 << index) & memory_type_bits != 0;
+            let opt_in_properties =
+                memory_type.property_flags & OPT_IN_MEMORY_PROPERTIES;
+            is_allowed_type
+                && memory_type.property_flags.contains(required)
+                && required.contains(opt_in_properties)
+        })
+        .map(|(index, _memory_type)| index)
 }
 
 impl std::fmt::Display for MemoryProperties {
@@ -0,0 +1,246 @@
+use {
+    crate::{Allocation, AllocatorError, GpuCompletion, MemoryAllocator},
+    ash::vk,
+};
+
+/// A buffer and its memory which are freed when dropped.
+///
+/// The buffer keeps a handle to the allocator which created it, so there is
+/// no need to remember to call [MemoryAllocator::free_buffer].
+pub struct OwnedBuffer {
+    buffer: vk::Buffer,
+
+    /// None once ownership has been given up, e.g. by [Self::into_raw].
+    allocation: Option<Allocation>,
+    allocator: MemoryAllocator,
+}
+
+/// An image and its memory which are freed when dropped. See [OwnedBuffer].
+pub struct OwnedImage {
+    image: vk::Image,
+
+    /// None once ownership has been given up, e.g. by [Self::into_raw].
+    allocation: Option<Allocation>,
+    allocator: MemoryAllocator,
+}
+
+// Public API
+// ----------
+
+impl OwnedBuffer {
+    /// Allocate a buffer and memory, see [MemoryAllocator::allocate_buffer].
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the buffer's memory and frees it on drop.
+    /// * buffer_create_info: used to create the buffer.
+    /// * memory_property_flags: used to pick the buffer's memory type.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be in use by the GPU when it is dropped
+    ///   - the buffer must be dropped before the device is destroyed
+    pub unsafe fn new(
+        allocator: &MemoryAllocator,
+        buffer_create_info: &vk::BufferCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Self, AllocatorError> {
+        let (buffer, allocation) = allocator
+            .allocate_buffer(buffer_create_info, memory_property_flags)?;
+        Ok(Self::from_raw(allocator.clone(), buffer, allocation))
+    }
+
+    /// Take ownership of a buffer and its memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer and allocation must have come from the allocator, and
+    ///     must not be freed by any other means
+    ///   - the buffer must not be in use by the GPU when it is dropped
+    pub unsafe fn from_raw(
+        allocator: MemoryAllocator,
+        buffer: vk::Buffer,
+        allocation: Allocation,
+    ) -> Self {
+        Self {
+            buffer,
+            allocation: Some(allocation),
+            allocator,
+        }
+    }
+
+    /// The raw Vulkan buffer handle.
+    pub fn raw(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// The buffer's memory.
+    pub fn allocation(&self) -> &Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+
+    /// The allocator which frees the buffer.
+    pub fn allocator(&self) -> &MemoryAllocator {
+        &self.allocator
+    }
+
+    /// Give up ownership of the buffer and its memory. The application is
+    /// responsible for freeing them with [MemoryAllocator::free_buffer].
+    pub fn into_raw(mut self) -> (vk::Buffer, Allocation) {
+        (self.buffer, self.allocation.take().unwrap())
+    }
+
+    /// Free the buffer once the GPU is done with it, see
+    /// [MemoryAllocator::free_buffer_deferred].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be used by commands submitted after the work
+    ///     which signals completion
+    pub unsafe fn free_deferred(mut self, completion: GpuCompletion) {
+        let allocation = self.allocation.take().unwrap();
+        self.allocator.free_buffer_deferred(
+            self.buffer,
+            allocation,
+            completion,
+        );
+    }
+
+    /// Free the buffer once the current frame has completed on the GPU, see
+    /// [MemoryAllocator::retire].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be used by commands in later frames
+    pub unsafe fn retire(mut self) {
+        let allocation = self.allocation.take().unwrap();
+        self.allocator.retire(self.buffer, allocation);
+    }
+}
+
+impl Drop for OwnedBuffer {
+    fn drop(&mut self) {
+        if let Some(allocation) = self.allocation.take() {
+            // SAFE because the creator promised that the buffer is not in use
+            unsafe { self.allocator.free_buffer(self.buffer, allocation) };
+        }
+    }
+}
+
+impl std::fmt::Debug for OwnedBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedBuffer")
+            .field("buffer", &self.buffer)
+            .field("allocation", &self.allocation)
+            .finish()
+    }
+}
+
+impl OwnedImage {
+    /// Allocate an image and memory, see [MemoryAllocator::allocate_image].
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the image's memory and frees it on drop.
+    /// * image_create_info: used to create the image.
+    /// * memory_property_flags: used to pick the image's memory type.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not be in use by the GPU when it is dropped
+    ///   - the image must be dropped before the device is destroyed
+    pub unsafe fn new(
+        allocator: &MemoryAllocator,
+        image_create_info: &vk::ImageCreateInfo,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Self, AllocatorError> {
+        let (image, allocation) = allocator
+            .allocate_image(image_create_info, memory_property_flags)?;
+        Ok(Self::from_raw(allocator.clone(), image, allocation))
+    }
+
+    /// Take ownership of an image and its memory.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image and allocation must have come from the allocator, and must
+    ///     not be freed by any other means
+    ///   - the image must not be in use by the GPU when it is dropped
+    pub unsafe fn from_raw(
+        allocator: MemoryAllocator,
+        image: vk::Image,
+        allocation: Allocation,
+    ) -> Self {
+        Self {
+            image,
+            allocation: Some(allocation),
+            allocator,
+        }
+    }
+
+    /// The raw Vulkan image handle.
+    pub fn raw(&self) -> vk::Image {
+        self.image
+    }
+
+    /// The image's memory.
+    pub fn allocation(&self) -> &Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+
+    /// Give up ownership of the image and its memory. The application is
+    /// responsible for freeing them with [MemoryAllocator::free_image].
+    pub fn into_raw(mut self) -> (vk::Image, Allocation) {
+        (self.image, self.allocation.take().unwrap())
+    }
+
+    /// Free the image once the GPU is done with it, see
+    /// [MemoryAllocator::free_image_deferred].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not be used by commands submitted after the work
+    ///     which signals completion
+    pub unsafe fn free_deferred(mut self, completion: GpuCompletion) {
+        let allocation = self.allocation.take().unwrap();
+        self.allocator
+            .free_image_deferred(self.image, allocation, completion);
+    }
+
+    /// Free the image once the current frame has completed on the GPU, see
+    /// [MemoryAllocator::retire_image].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not be used by commands in later frames
+    pub unsafe fn retire(mut self) {
+        let allocation = self.allocation.take().unwrap();
+        self.allocator.retire_image(self.image, allocation);
+    }
+}
+
+impl Drop for OwnedImage {
+    fn drop(&mut self) {
+        if let Some(allocation) = self.allocation.take() {
+            // SAFE because the creator promised that the image is not in use
+            unsafe { self.allocator.free_image(self.image, allocation) };
+        }
+    }
+}
+
+impl std::fmt::Debug for OwnedImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedImage")
+            .field("image", &self.image)
+            .field("allocation", &self.allocation)
+            .finish()
+    }
+}
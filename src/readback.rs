@@ -0,0 +1,275 @@
+use {
+    crate::{AllocatorError, MemoryAllocator, OwnedBuffer},
+    anyhow::anyhow,
+    ash::vk,
+};
+
+/// Copies data from GPU memory into host memory.
+///
+/// The data is copied into HOST_CACHED memory when the device has it, which
+/// is much faster for the host to read than write-combined memory. Cached
+/// memory is usually not HOST_COHERENT, so it's invalidated before the bytes
+/// are read.
+///
+/// The copy is submitted as soon as the readback is created. Use
+/// [Self::fence] or [Self::is_ready] to avoid blocking, or [Self::read] to
+/// wait for the bytes.
+pub struct Readback {
+    buffer: OwnedBuffer,
+    size_in_bytes: u64,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+}
+
+// Public API
+// ----------
+
+impl Readback {
+    /// Copy a range of a buffer into host memory.
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the host memory.
+    /// * src: the buffer to read. It must have TRANSFER_SRC usage.
+    /// * src_offset: the start of the range to read.
+    /// * size_in_bytes: the size of the range to read.
+    /// * queue: the copy is submitted to this queue.
+    /// * command_pool: the pool for the copy's command buffer.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    ///   - GPU writes to the buffer must complete before the copy starts
+    ///   - the readback must be dropped before the pool or device are destroyed
+    pub unsafe fn from_buffer(
+        allocator: &MemoryAllocator,
+        src: vk::Buffer,
+        src_offset: u64,
+        size_in_bytes: u64,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<Self, AllocatorError> {
+        Self::submit(
+            allocator,
+            size_in_bytes,
+            queue,
+            command_pool,
+            |device, command_buffer, dst| {
+                device.cmd_copy_buffer(
+                    command_buffer,
+                    src,
+                    dst,
+                    &[vk::BufferCopy {
+                        src_offset,
+                        dst_offset: 0,
+                        size: size_in_bytes,
+                    }],
+                );
+            },
+        )
+    }
+
+    /// Copy a region of an image into host memory. Texels are tightly
+    /// packed.
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the host memory.
+    /// * src: the image to read. It must have TRANSFER_SRC usage.
+    /// * src_layout: the image's layout, TRANSFER_SRC_OPTIMAL or GENERAL.
+    /// * subresource: the mip level, array layers, and aspect to read.
+    /// * extent: the size of the region to read, starting at the origin.
+    /// * bytes_per_texel: the size of a texel in the image's format.
+    /// * queue: the copy is submitted to this queue.
+    /// * command_pool: the pool for the copy's command buffer.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    ///   - GPU writes to the image must complete, and the image must be in
+    ///     `src_layout`, before the copy starts
+    ///   - the readback must be dropped before the pool or device are destroyed
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn from_image(
+        allocator: &MemoryAllocator,
+        src: vk::Image,
+        src_layout: vk::ImageLayout,
+        subresource: vk::ImageSubresourceLayers,
+        extent: vk::Extent3D,
+        bytes_per_texel: u64,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+    ) -> Result<Self, AllocatorError> {
+        let size_in_bytes = extent.width as u64
+            * extent.height as u64
+            * extent.depth as u64
+            * subresource.layer_count as u64
+            * bytes_per_texel;
+        Self::submit(
+            allocator,
+            size_in_bytes,
+            queue,
+            command_pool,
+            |device, command_buffer, dst| {
+                device.cmd_copy_image_to_buffer(
+                    command_buffer,
+                    src,
+                    src_layout,
+                    dst,
+                    &[vk::BufferImageCopy {
+                        buffer_offset: 0,
+                        buffer_row_length: 0,
+                        buffer_image_height: 0,
+                        image_subresource: subresource,
+                        image_offset: vk::Offset3D::default(),
+                        image_extent: extent,
+                    }],
+                );
+            },
+        )
+    }
+
+    /// The number of bytes being read.
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size_in_bytes
+    }
+
+    /// The fence which is signaled when the copy completes. The fence
+    /// belongs to the readback and must not be destroyed by the application.
+    pub fn fence(&self) -> vk::Fence {
+        self.fence
+    }
+
+    /// Returns true when the copy has completed.
+    pub fn is_ready(&self) -> Result<bool, AllocatorError> {
+        let device = self.buffer.allocator().device();
+        unsafe { device.get_fence_status(self.fence) }.map_err(|err| {
+            AllocatorError::from_vk_result(err, "Error checking a readback")
+        })
+    }
+
+    /// Block until the copy has completed.
+    pub fn wait(&self) -> Result<(), AllocatorError> {
+        let device = self.buffer.allocator().device();
+        unsafe { device.wait_for_fences(&[self.fence], true, u64::MAX) }
+            .map_err(|err| {
+                AllocatorError::from_vk_result(
+                    err,
+                    "Error waiting for a readback",
+                )
+            })
+    }
+
+    /// Wait for the copy to complete, then read the bytes. Memory which isn't
+    /// HOST_COHERENT is invalidated first.
+    pub fn read(&self) -> Result<Vec<u8>, AllocatorError> {
+        self.wait()?;
+        let mut bytes = vec![0u8; self.size_in_bytes as usize];
+        unsafe {
+            // SAFE because the copy is complete
+            self.buffer.allocation().read_bytes(
+                self.buffer.allocator().device(),
+                0,
+                &mut bytes,
+            )?;
+        }
+        Ok(bytes)
+    }
+}
+
+impl Drop for Readback {
+    fn drop(&mut self) {
+        // The GPU must be done with the buffer and command buffer before they
+        // are freed.
+        if let Err(err) = self.wait() {
+            log::error!("Unable to wait for a readback: {}", err);
+        }
+        let device = self.buffer.allocator().device();
+        unsafe {
+            device.destroy_fence(self.fence, None);
+            device.free_command_buffers(
+                self.command_pool,
+                &[self.command_buffer],
+            );
+        }
+    }
+}
+
+impl std::fmt::Debug for Readback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Readback")
+            .field("buffer", &self.buffer)
+            .field("size_in_bytes", &self.size_in_bytes)
+            .field("fence", &self.fence)
+            .finish()
+    }
+}
+
+// Private API
+// -----------
+
+impl Readback {
+    /// Allocate host memory and submit the commands which copy into it.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the application must synchronize access to the queue and pool
+    unsafe fn submit(
+        allocator: &MemoryAllocator,
+        size_in_bytes: u64,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer, vk::Buffer),
+    ) -> Result<Self, AllocatorError> {
+        if size_in_bytes == 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to read back 0 bytes"
+            )));
+        }
+        let (buffer, allocation) = allocator.allocate_buffer_preferring(
+            &vk::BufferCreateInfo {
+                size: size_in_bytes,
+                usage: vk::BufferUsageFlags::TRANSFER_DST,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::MemoryPropertyFlags::HOST_CACHED,
+        )?;
+        let buffer =
+            OwnedBuffer::from_raw(allocator.clone(), buffer, allocation);
+
+        let dst = buffer.raw();
+        let (command_buffer, fence) =
+            allocator.submit_commands(queue, command_pool, |device, cb| {
+                record(device, cb, dst);
+
+                // The fence doesn't make the copy visible to the host, the
+                // writes must be made available with a barrier first.
+                device.cmd_pipeline_barrier(
+                    cb,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::HOST,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier {
+                        src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                        dst_access_mask: vk::AccessFlags::HOST_READ,
+                        ..Default::default()
+                    }],
+                    &[],
+                    &[],
+                );
+            })?;
+        Ok(Self {
+            buffer,
+            size_in_bytes,
+            command_pool,
+            command_buffer,
+            fence,
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use {crate::Allocation, ash::vk};
+
+/// Build the binds which back consecutive pages of a sparse buffer or opaque
+/// image range with memory, see
+/// [crate::MemoryAllocator::allocate_sparse_pages].
+///
+/// # Params
+///
+/// * `resource_offset` - the offset within the resource where the first page is
+///   bound. Each page is bound right after the one before it.
+/// * `pages` - the memory for each page.
+///
+/// # Returns
+///
+/// One bind per page, ready for VkSparseBufferMemoryBindInfo or
+/// VkSparseImageOpaqueMemoryBindInfo.
+pub fn sparse_memory_binds(
+    resource_offset: vk::DeviceSize,
+    pages: &[Allocation],
+) -> Vec<vk::SparseMemoryBind> {
+    let mut offset = resource_offset;
+    pages
+        .iter()
+        .map(|page| {
+            let bind = page.sparse_memory_bind(offset);
+            offset += page.size_in_bytes();
+            bind
+        })
+        .collect()
+}
+
+/// Build binds which remove the memory from a range of a sparse resource.
+///
+/// # Params
+///
+/// * `resource_offset` - the offset of the first page to unbind.
+/// * `page_size` - the size of each page.
+/// * `page_count` - the number of consecutive pages to unbind.
+pub fn sparse_memory_unbinds(
+    resource_offset: vk::DeviceSize,
+    page_size: vk::DeviceSize,
+    page_count: usize,
+) -> Vec<vk::SparseMemoryBind> {
+    (0..page_count as u64)
+        .map(|index| vk::SparseMemoryBind {
+            resource_offset: resource_offset + index * page_size,
+            size: page_size,
+            memory: vk::DeviceMemory::null(),
+            memory_offset: 0,
+            flags: vk::SparseMemoryBindFlags::empty(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{AllocationRequirements, ComposableAllocator, FakeAllocator},
+    };
+
+    #[test]
+    fn test_binds_are_consecutive() {
+        let mut allocator = FakeAllocator::default();
+        let pages: Vec<Allocation> = (0..3)
+            .map(|_| unsafe {
+                allocator
+                    .allocate(AllocationRequirements {
+                        size_in_bytes: 65536,
+                        alignment: 65536,
+                        ..Default::default()
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        let binds = sparse_memory_binds(131072, &pages);
+        let offsets: Vec<u64> =
+            binds.iter().map(|bind| bind.resource_offset).collect();
+        assert_eq!(offsets, vec![131072, 196608, 262144]);
+        assert!(binds.iter().all(|bind| bind.size == 65536));
+
+        let unbinds = sparse_memory_unbinds(131072, 65536, 3);
+        assert_eq!(unbinds.len(), 3);
+        assert_eq!(unbinds[2].resource_offset, 262144);
+        assert!(unbinds
+            .iter()
+            .all(|bind| bind.memory == vk::DeviceMemory::null()));
+    }
+
+    #[test]
+    fn test_pages_bound_at_an_offset_keep_their_memory_offset() {
+        let mut allocator = FakeAllocator::default();
+        let pages: Vec<Allocation> = (0..2)
+            .map(|_| unsafe {
+                allocator
+                    .allocate(AllocationRequirements {
+                        size_in_bytes: 4096,
+                        alignment: 4096,
+                        ..Default::default()
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        let binds = sparse_memory_binds(8192, &pages);
+        assert_eq!(binds[0].memory_offset, 0);
+        assert_eq!(binds[1].memory_offset, 4096);
+        assert_eq!(binds[1].resource_offset, 12288);
+        assert!(sparse_memory_binds(0, &[]).is_empty());
+        assert!(sparse_memory_unbinds(0, 4096, 0).is_empty());
+    }
+}
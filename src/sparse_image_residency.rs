@@ -0,0 +1,394 @@
+use {
+    crate::{
+        Allocation, AllocationRequirements, AllocatorError, GpuCompletion,
+        MemoryAllocator,
+    },
+    anyhow::anyhow,
+    ash::vk,
+    std::collections::HashMap,
+};
+
+/// A tile of a sparse image, in units of the image's sparse block size.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SparseTile {
+    pub mip_level: u32,
+    pub array_layer: u32,
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+/// Tracks which tiles of a sparse image are resident and builds the binds
+/// which stream tiles in and out.
+///
+/// Each resident tile is backed by one page from
+/// [MemoryAllocator::allocate_sparse_pages]. Changes are collected until
+/// [Self::take_binds], which returns them for a
+/// VkSparseImageMemoryBindInfo.
+///
+/// Only the mip levels before the mip tail are tracked. The mip tail must be
+/// bound with opaque binds, see [crate::sparse_memory_binds].
+pub struct SparseImageResidency {
+    allocator: MemoryAllocator,
+    image: vk::Image,
+    extent: vk::Extent3D,
+    array_layers: u32,
+    aspect_mask: vk::ImageAspectFlags,
+    tile_extent: vk::Extent3D,
+    mip_tail_first_lod: u32,
+    page_size: u64,
+    memory_type_index: usize,
+    resident: HashMap<SparseTile, Allocation>,
+
+    /// Binds recorded since the last call to [Self::take_binds].
+    pending_binds: Vec<vk::SparseImageMemoryBind>,
+
+    /// Pages which were evicted since the last call to [Self::take_binds].
+    /// They stay bound until the unbinds execute.
+    evicted: Vec<Allocation>,
+}
+
+// Public API
+// ----------
+
+impl SparseImageResidency {
+    /// Start tracking residency for a sparse image. No tiles are resident.
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates and frees the pages which back tiles.
+    /// * image: the image, created with SPARSE_BINDING and SPARSE_RESIDENCY
+    ///   flags.
+    /// * image_create_info: the create info used for the image.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the image must not be in use by the GPU when the residency is
+    ///     dropped, resident pages are freed right away
+    ///   - the residency must be dropped before the device is destroyed
+    pub unsafe fn new(
+        allocator: &MemoryAllocator,
+        image: vk::Image,
+        image_create_info: &vk::ImageCreateInfo,
+    ) -> Result<Self, AllocatorError> {
+        let device = allocator.device();
+        let memory_requirements = device.get_image_memory_requirements(image);
+        let sparse_requirements =
+            device.get_image_sparse_memory_requirements(image);
+        let sparse_requirements = match sparse_requirements.first() {
+            Some(requirements) => *requirements,
+            None => {
+                return Err(AllocatorError::RuntimeError(anyhow!(
+                    "Image {:?} has no sparse memory requirements",
+                    image
+                )));
+            }
+        };
+        let memory_type_index = AllocationRequirements::pick_memory_type_index(
+            allocator.memory_properties().types(),
+            &memory_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        Ok(Self {
+            allocator: allocator.clone(),
+            image,
+            extent: image_create_info.extent,
+            array_layers: image_create_info.array_layers,
+            aspect_mask: sparse_requirements.format_properties.aspect_mask,
+            tile_extent: sparse_requirements
+                .format_properties
+                .image_granularity,
+            mip_tail_first_lod: sparse_requirements
+                .image_mip_tail_first_lod
+                .min(image_create_info.mip_levels),
+            page_size: memory_requirements.alignment,
+            memory_type_index,
+            resident: HashMap::new(),
+            pending_binds: vec![],
+            evicted: vec![],
+        })
+    }
+
+    /// The image whose residency is tracked.
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    /// The size of a tile in texels.
+    pub fn tile_extent(&self) -> vk::Extent3D {
+        self.tile_extent
+    }
+
+    /// The number of tiles in each dimension of a mip level.
+    ///
+    /// # Returns
+    ///
+    /// None when the mip level is part of the mip tail.
+    pub fn tile_count(&self, mip_level: u32) -> Option<vk::Extent3D> {
+        if mip_level >= self.mip_tail_first_lod {
+            return None;
+        }
+        let mip_extent = mip_extent(self.extent, mip_level);
+        Some(vk::Extent3D {
+            width: tiles_to_cover(mip_extent.width, self.tile_extent.width),
+            height: tiles_to_cover(mip_extent.height, self.tile_extent.height),
+            depth: tiles_to_cover(mip_extent.depth, self.tile_extent.depth),
+        })
+    }
+
+    /// The first mip level which is part of the mip tail.
+    pub fn mip_tail_first_lod(&self) -> u32 {
+        self.mip_tail_first_lod
+    }
+
+    /// Returns true when the tile is backed by memory.
+    pub fn is_resident(&self, tile: &SparseTile) -> bool {
+        self.resident.contains_key(tile)
+    }
+
+    /// The number of resident tiles.
+    pub fn resident_count(&self) -> usize {
+        self.resident.len()
+    }
+
+    /// Allocate a page for a tile and record the bind which makes it
+    /// resident.
+    ///
+    /// # Returns
+    ///
+    /// True when the tile was not already resident.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the page must be freed before the device is destroyed, which happens
+    ///     when the residency is dropped
+    pub unsafe fn make_resident(
+        &mut self,
+        tile: SparseTile,
+    ) -> Result<bool, AllocatorError> {
+        if self.resident.contains_key(&tile) {
+            return Ok(false);
+        }
+        let (offset, extent) = self.tile_region(&tile)?;
+        let page = self
+            .allocator
+            .allocate_sparse_pages(1, self.page_size, self.memory_type_index)?
+            .remove(0);
+        self.pending_binds.push(vk::SparseImageMemoryBind {
+            subresource: self.subresource(&tile),
+            offset,
+            extent,
+            memory: page.memory(),
+            memory_offset: page.offset_in_bytes(),
+            flags: vk::SparseMemoryBindFlags::empty(),
+        });
+        self.resident.insert(tile, page);
+        Ok(true)
+    }
+
+    /// Record the unbind which evicts a tile. The tile's page is freed once
+    /// the unbind has executed, see [Self::take_binds].
+    ///
+    /// # Returns
+    ///
+    /// True when the tile was resident.
+    pub fn evict(&mut self, tile: SparseTile) -> Result<bool, AllocatorError> {
+        let page = match self.resident.remove(&tile) {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+        let (offset, extent) = self.tile_region(&tile)?;
+        self.pending_binds.push(vk::SparseImageMemoryBind {
+            subresource: self.subresource(&tile),
+            offset,
+            extent,
+            memory: vk::DeviceMemory::null(),
+            memory_offset: 0,
+            flags: vk::SparseMemoryBindFlags::empty(),
+        });
+        self.evicted.push(page);
+        Ok(true)
+    }
+
+    /// Take the binds recorded since the last call. They must be submitted
+    /// with vkQueueBindSparse in the order they are returned.
+    ///
+    /// # Params
+    ///
+    /// * completion: signaled when the bind operation completes. Pages for
+    ///   evicted tiles are freed by [MemoryAllocator::collect] after that.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the binds must be submitted, and must signal the completion, or
+    ///     evicted pages are never freed
+    ///   - the fence or semaphore must not be destroyed until the pages are
+    ///     collected
+    pub unsafe fn take_binds(
+        &mut self,
+        completion: GpuCompletion,
+    ) -> Vec<vk::SparseImageMemoryBind> {
+        for page in self.evicted.drain(..) {
+            self.allocator.free_deferred(page, completion);
+        }
+        std::mem::take(&mut self.pending_binds)
+    }
+}
+
+impl Drop for SparseImageResidency {
+    fn drop(&mut self) {
+        unsafe {
+            // SAFE because the creator promised that the image is not in use
+            for (_, page) in self.resident.drain() {
+                self.allocator.free(page);
+            }
+            for page in self.evicted.drain(..) {
+                self.allocator.free(page);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for SparseImageResidency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SparseImageResidency")
+            .field("image", &self.image)
+            .field("tile_extent", &self.tile_extent)
+            .field("mip_tail_first_lod", &self.mip_tail_first_lod)
+            .field("page_size", &self.page_size)
+            .field("memory_type_index", &self.memory_type_index)
+            .field("resident_count", &self.resident.len())
+            .field("pending_binds", &self.pending_binds.len())
+            .finish()
+    }
+}
+
+// Private API
+// -----------
+
+impl SparseImageResidency {
+    fn subresource(&self, tile: &SparseTile) -> vk::ImageSubresource {
+        vk::ImageSubresource {
+            aspect_mask: self.aspect_mask,
+            mip_level: tile.mip_level,
+            array_layer: tile.array_layer,
+        }
+    }
+
+    /// The texel region covered by a tile.
+    fn tile_region(
+        &self,
+        tile: &SparseTile,
+    ) -> Result<(vk::Offset3D, vk::Extent3D), AllocatorError> {
+        let region = if tile.mip_level < self.mip_tail_first_lod
+            && tile.array_layer < self.array_layers
+        {
+            tile_region(
+                mip_extent(self.extent, tile.mip_level),
+                self.tile_extent,
+                tile,
+            )
+        } else {
+            None
+        };
+        region.ok_or_else(|| {
+            AllocatorError::RuntimeError(anyhow!(
+                "{:?} is outside of the image's sparse tiles",
+                tile
+            ))
+        })
+    }
+}
+
+/// The size of a mip level.
+fn mip_extent(extent: vk::Extent3D, mip_level: u32) -> vk::Extent3D {
+    vk::Extent3D {
+        width: (extent.width >> mip_level).max(1),
+        height: (extent.height >> mip_level).max(1),
+        depth: (extent.depth >> mip_level).max(1),
+    }
+}
+
+/// The number of tiles needed to cover a dimension.
+fn tiles_to_cover(texels: u32, tile_texels: u32) -> u32 {
+    let tile_texels = tile_texels.max(1);
+    texels / tile_texels + u32::from(texels % tile_texels != 0)
+}
+
+/// The texel region covered by a tile. Tiles on the edge of a mip level are
+/// clipped to the level's extent.
+///
+/// # Returns
+///
+/// None when the tile is outside of the mip level.
+fn tile_region(
+    mip_extent: vk::Extent3D,
+    tile_extent: vk::Extent3D,
+    tile: &SparseTile,
+) -> Option<(vk::Offset3D, vk::Extent3D)> {
+    let clip = |index: u32, tile_texels: u32, texels: u32| {
+        let start = index.checked_mul(tile_texels)?;
+        if start >= texels {
+            return None;
+        }
+        Some((start, tile_texels.min(texels - start)))
+    };
+    let (x, width) = clip(tile.x, tile_extent.width, mip_extent.width)?;
+    let (y, height) = clip(tile.y, tile_extent.height, mip_extent.height)?;
+    let (z, depth) = clip(tile.z, tile_extent.depth, mip_extent.depth)?;
+    Some((
+        vk::Offset3D {
+            x: x as i32,
+            y: y as i32,
+            z: z as i32,
+        },
+        vk::Extent3D {
+            width,
+            height,
+            depth,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(mip_level: u32, x: u32, y: u32) -> SparseTile {
+        SparseTile {
+            mip_level,
+            array_layer: 0,
+            x,
+            y,
+            z: 0,
+        }
+    }
+
+    #[test]
+    fn test_edge_tiles_are_clipped() {
+        let mip_extent = vk::Extent3D {
+            width: 300,
+            height: 128,
+            depth: 1,
+        };
+        let tile_extent = vk::Extent3D {
+            width: 128,
+            height: 128,
+            depth: 1,
+        };
+
+        let (offset, extent) =
+            tile_region(mip_extent, tile_extent, &tile(0, 2, 0)).unwrap();
+        assert_eq!((offset.x, offset.y), (256, 0));
+        assert_eq!((extent.width, extent.height), (44, 128));
+
+        assert!(tile_region(mip_extent, tile_extent, &tile(0, 3, 0)).is_none());
+        assert!(tile_region(mip_extent, tile_extent, &tile(0, 0, 1)).is_none());
+        assert_eq!(tiles_to_cover(300, 128), 3);
+        assert_eq!(tiles_to_cover(256, 128), 2);
+    }
+}
@@ -0,0 +1,295 @@
+use {
+    crate::{AllocatorError, GpuCompletion, MemoryAllocator, OwnedBuffer},
+    anyhow::anyhow,
+    ash::vk,
+    std::collections::VecDeque,
+};
+
+/// The alignment of every staging slice. This satisfies
+/// optimalBufferCopyOffsetAlignment on common hardware and the texel size of
+/// every format.
+const SLICE_ALIGNMENT: u64 = 16;
+
+/// Uploads data to device-local memory through a ring of host-visible
+/// staging memory.
+///
+/// Each upload writes into the next free slice of the ring and records a copy
+/// into an application-provided command buffer. Once the command buffer is
+/// submitted, [Self::finish] records what signals that the GPU is done with
+/// the slices. Space is recycled by [Self::recall] after the GPU signals
+/// completion.
+pub struct StagingBelt {
+    buffer: OwnedBuffer,
+    ring: Ring,
+    in_flight: VecDeque<(GpuCompletion, u64)>,
+}
+
+// Public API
+// ----------
+
+impl StagingBelt {
+    /// Create a staging belt.
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the staging memory, which is freed when the belt
+    ///   is dropped.
+    /// * size_in_bytes: the size of the ring. Uploads must be no larger than
+    ///   this.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the belt must not be dropped while uploads are in flight
+    ///   - the belt must be dropped before the device is destroyed
+    pub unsafe fn new(
+        allocator: &MemoryAllocator,
+        size_in_bytes: u64,
+    ) -> Result<Self, AllocatorError> {
+        let (buffer, allocation, _ptr) = allocator.allocate_mapped_buffer(
+            &vk::BufferCreateInfo {
+                size: size_in_bytes,
+                usage: vk::BufferUsageFlags::TRANSFER_SRC,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        Ok(Self {
+            buffer: OwnedBuffer::from_raw(
+                allocator.clone(),
+                buffer,
+                allocation,
+            ),
+            ring: Ring::new(size_in_bytes),
+            in_flight: VecDeque::new(),
+        })
+    }
+
+    /// Write data into the ring and record a copy to the destination buffer.
+    ///
+    /// Completed uploads are recalled automatically when the ring is full.
+    ///
+    /// # Params
+    ///
+    /// * command_buffer: a command buffer in the recording state. The copy is
+    ///   recorded into it.
+    /// * dst: the buffer to copy into. It must have TRANSFER_DST usage.
+    /// * dst_offset: where to copy the data in the destination buffer.
+    /// * data: the data to upload.
+    ///
+    /// # Returns
+    ///
+    /// An error when the ring doesn't have room for the data, even after
+    /// recalling completed uploads.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the command buffer must be submitted before the next call to
+    ///     [Self::finish]
+    ///   - the application must synchronize access to the command buffer
+    pub unsafe fn upload<T: Copy>(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        dst: vk::Buffer,
+        dst_offset: u64,
+        data: &[T],
+    ) -> Result<(), AllocatorError> {
+        let size_in_bytes = std::mem::size_of_val(data) as u64;
+        if size_in_bytes == 0 {
+            return Ok(());
+        }
+        let offset = match self.ring.allocate(size_in_bytes, SLICE_ALIGNMENT) {
+            Some(offset) => offset,
+            None => {
+                self.recall()?;
+                self.ring
+                    .allocate(size_in_bytes, SLICE_ALIGNMENT)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "The staging belt has no room for {} bytes, {} \
+                             of {} bytes are in flight",
+                            size_in_bytes,
+                            self.ring.used_bytes(),
+                            self.ring.size_in_bytes,
+                        )
+                    })?
+            }
+        };
+
+        let ptr = self.buffer.allocation().mapped_ptr().unwrap() as *mut u8;
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr() as *const u8,
+            ptr.add(offset as usize),
+            size_in_bytes as usize,
+        );
+        self.buffer.allocator().device().cmd_copy_buffer(
+            command_buffer,
+            self.buffer.raw(),
+            dst,
+            &[vk::BufferCopy {
+                src_offset: offset,
+                dst_offset,
+                size: size_in_bytes,
+            }],
+        );
+        Ok(())
+    }
+
+    /// Mark every upload since the last call to finish as in flight.
+    ///
+    /// # Params
+    ///
+    /// * completion: signaled when the GPU is done with the submitted copies.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the fence or semaphore must not be destroyed until the uploads are
+    ///     recalled
+    pub unsafe fn finish(&mut self, completion: GpuCompletion) {
+        self.in_flight.push_back((completion, self.ring.head));
+    }
+
+    /// Recycle the space used by uploads which the GPU has completed.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the fences and semaphores passed to [Self::finish] must not have
+    ///     been destroyed
+    pub unsafe fn recall(&mut self) -> Result<(), AllocatorError> {
+        let device = self.buffer.allocator().device();
+        while let Some(&(completion, end)) = self.in_flight.front() {
+            let is_complete =
+                completion.is_signaled(device).map_err(|err| {
+                    AllocatorError::from_vk_result(
+                        err,
+                        "Error checking staging belt completion",
+                    )
+                })?;
+            if !is_complete {
+                break;
+            }
+            self.ring.release(end);
+            self.in_flight.pop_front();
+        }
+        Ok(())
+    }
+
+    /// The number of bytes which are written but not yet recalled.
+    pub fn used_bytes(&self) -> u64 {
+        self.ring.used_bytes()
+    }
+
+    /// The number of finished batches which haven't been recalled.
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+impl std::fmt::Debug for StagingBelt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StagingBelt")
+            .field("buffer", &self.buffer)
+            .field("used_bytes", &self.used_bytes())
+            .field("in_flight_count", &self.in_flight_count())
+            .finish()
+    }
+}
+
+// Private API
+// -----------
+
+/// Bookkeeping for a ring of memory.
+///
+/// Head and tail only ever increase, and positions in the ring are computed
+/// modulo the ring's size. Everything between tail and head is in use.
+struct Ring {
+    size_in_bytes: u64,
+    head: u64,
+    tail: u64,
+}
+
+impl Ring {
+    fn new(size_in_bytes: u64) -> Self {
+        Self {
+            size_in_bytes,
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// Take the next slice of the ring.
+    ///
+    /// # Returns
+    ///
+    /// The slice's offset in the ring, or None when there is no room.
+    fn allocate(&mut self, size_in_bytes: u64, alignment: u64) -> Option<u64> {
+        let position = self.head % self.size_in_bytes;
+        let mut start =
+            self.head + (alignment - position % alignment) % alignment;
+
+        // Slices can't wrap around the end of the ring, so skip to the start.
+        if start % self.size_in_bytes + size_in_bytes > self.size_in_bytes
+            || start % self.size_in_bytes < position
+        {
+            start = self.head + (self.size_in_bytes - position);
+        }
+
+        let end = start + size_in_bytes;
+        if end - self.tail > self.size_in_bytes {
+            return None;
+        }
+        self.head = end;
+        Some(start % self.size_in_bytes)
+    }
+
+    /// Release every slice which ends at or before the given head.
+    fn release(&mut self, head: u64) {
+        self.tail = self.tail.max(head);
+    }
+
+    /// The number of bytes between tail and head.
+    fn used_bytes(&self) -> u64 {
+        self.head - self.tail
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Ring;
+
+    #[test]
+    fn test_ring_allocates_until_full() {
+        let mut ring = Ring::new(64);
+        assert_eq!(ring.allocate(16, 16), Some(0));
+        assert_eq!(ring.allocate(8, 16), Some(16));
+        assert_eq!(ring.allocate(16, 16), Some(32));
+        assert_eq!(ring.allocate(32, 16), None);
+        assert_eq!(ring.used_bytes(), 48);
+    }
+
+    #[test]
+    fn test_ring_wraps_after_release() {
+        let mut ring = Ring::new(64);
+        assert_eq!(ring.allocate(48, 16), Some(0));
+        let first_batch = ring.head;
+        assert_eq!(ring.allocate(8, 16), Some(48));
+
+        // There is no room at the end of the ring, and the start is in use.
+        assert_eq!(ring.allocate(32, 16), None);
+
+        ring.release(first_batch);
+        assert_eq!(ring.allocate(32, 16), Some(0));
+    }
+
+    #[test]
+    fn test_ring_rejects_slices_larger_than_the_ring() {
+        let mut ring = Ring::new(64);
+        assert_eq!(ring.allocate(65, 16), None);
+        assert_eq!(ring.used_bytes(), 0);
+    }
+}
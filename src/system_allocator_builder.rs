@@ -0,0 +1,373 @@
+use {
+    crate::{
+        into_shared, system_allocator_config::check_tiers, ComposableAllocator,
+        DedicatedAllocator, DeviceAllocator, HeapClass, HeapUsageAllocator,
+        IdGenerator, MemoryAllocator, MemoryProperties, PoolAllocator,
+        SizedAllocator, SystemAllocatorConfig, TraceAllocator,
+    },
+    ash::vk,
+    std::sync::{Arc, Mutex},
+};
+
+type SharedAllocator = Arc<Mutex<Box<dyn ComposableAllocator + Send>>>;
+
+const KB: u64 = 1024;
+const MB: u64 = 1024 * KB;
+
+/// The (page size, chunk size) for the small, medium, and large tiers on
+/// discrete devices.
+const DISCRETE_TIERS: [(u64, u64); 3] =
+    [(KB, 64 * KB), (64 * KB, 4 * MB), (4 * MB, 512 * MB)];
+
+/// Pool chunks are at most this fraction of their heap, so small heaps like
+/// the 256 MB DEVICE_LOCAL | HOST_VISIBLE heap get appropriately sized chunks.
+const HEAP_CHUNK_DIVISOR: u64 = 8;
+
+/// Builds the allocator returned by [crate::create_system_allocator] with
+/// custom tier sizes.
+///
+/// The system allocator is a hierarchy of pools. Each tier carves pages out of
+/// chunks which come from the next largest tier, and the largest tier gets its
+/// chunks from the device. Any size which isn't set uses the default for the
+/// device's [HeapClass].
+///
+/// Each page size must divide its tier's chunk size, and each chunk size must
+/// be no larger than the next tier's chunk size, see
+/// [SystemAllocatorConfig::validate]. Invalid sizes are logged and replaced by
+/// the defaults when the allocator is built.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemAllocatorBuilder {
+    config: SystemAllocatorConfig,
+    ids: Option<IdGenerator>,
+}
+
+// Public API
+// ----------
+
+impl SystemAllocatorBuilder {
+    /// Create a builder which uses the default sizes for every tier.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder which starts with the values in a config.
+    pub fn from_config(config: SystemAllocatorConfig) -> Self {
+        Self { config, ids: None }
+    }
+
+    /// Override the config with `ASH_ALLOC_` environment variables, see
+    /// [SystemAllocatorConfig::with_env_overrides]. Invalid values are logged
+    /// and ignored.
+    pub fn env_overrides(self) -> Self {
+        Self {
+            config: self.config.with_env_overrides(),
+            ..self
+        }
+    }
+
+    /// The config the allocator will be built with.
+    pub fn config(&self) -> &SystemAllocatorConfig {
+        &self.config
+    }
+
+    /// Set the page size for the small tier.
+    pub fn small_page(self, small_page: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                small_page: Some(small_page),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Set the chunk size for the small tier.
+    pub fn small_chunk(self, small_chunk: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                small_chunk: Some(small_chunk),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Set the page size for the medium tier.
+    pub fn medium_page(self, medium_page: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                medium_page: Some(medium_page),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Set the chunk size for the medium tier.
+    pub fn medium_chunk(self, medium_chunk: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                medium_chunk: Some(medium_chunk),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Set the page size for the large tier. Devices whose heap class doesn't
+    /// use a large tier get one.
+    pub fn large_page(self, large_page: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                large_page: Some(large_page),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Set the chunk size for the large tier. Devices whose heap class doesn't
+    /// use a large tier get one.
+    pub fn large_chunk(self, large_chunk: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                large_chunk: Some(large_chunk),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Give allocations at least this large their own device allocation
+    /// instead of taking them from the pools. By default only resources which
+    /// prefer or require a dedicated allocation get one.
+    pub fn dedicated_threshold(self, dedicated_threshold: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                dedicated_threshold: Some(dedicated_threshold),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Use smaller chunks for HOST_VISIBLE memory types, see
+    /// [PoolAllocator::with_host_visible_chunk_size].
+    pub fn host_visible_chunk_size(self, host_visible_chunk_size: u64) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                host_visible_chunk_size: Some(host_visible_chunk_size),
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Enable or disable the [TraceAllocator]s which log device allocations
+    /// and allocations given to the application. Tracing is enabled by
+    /// default.
+    pub fn tracing(self, tracing: bool) -> Self {
+        Self {
+            config: SystemAllocatorConfig {
+                tracing,
+                ..self.config
+            },
+            ..self
+        }
+    }
+
+    /// Generate the ids for device memory and pool chunks with a copy of this
+    /// generator, see [DeviceAllocator::with_memory_ids] and
+    /// [PoolAllocator::with_chunk_ids]. Ids are sequential by default.
+    pub fn ids(self, ids: IdGenerator) -> Self {
+        Self {
+            ids: Some(ids),
+            ..self
+        }
+    }
+
+    /// The (page size, chunk size) for each tier, from smallest to largest.
+    ///
+    /// # Params
+    ///
+    /// * heap_class: the kind of memory the device has.
+    /// * memory_type_count: the number of memory types on the device.
+    pub fn tiers(
+        &self,
+        heap_class: HeapClass,
+        memory_type_count: usize,
+    ) -> Vec<(u64, u64)> {
+        let mut tiers: Vec<(u64, u64)> = match heap_class {
+            _ if memory_type_count <= 1 => vec![(KB, 4 * MB)],
+            HeapClass::Discrete => DISCRETE_TIERS.to_vec(),
+            HeapClass::Unified => vec![(KB, 64 * KB), (64 * KB, 4 * MB)],
+            HeapClass::MobileTbdr => vec![(KB, 32 * KB), (32 * KB, 2 * MB)],
+        };
+        let config = &self.config;
+        let overrides = [
+            (config.small_page, config.small_chunk),
+            (config.medium_page, config.medium_chunk),
+            (config.large_page, config.large_chunk),
+        ];
+        for (index, (page_size, chunk_size)) in overrides.iter().enumerate() {
+            if page_size.is_none() && chunk_size.is_none() {
+                continue;
+            }
+            while tiers.len() <= index {
+                tiers.push(DISCRETE_TIERS[tiers.len()]);
+            }
+            if let Some(page_size) = page_size {
+                tiers[index].0 = *page_size;
+            }
+            if let Some(chunk_size) = chunk_size {
+                tiers[index].1 = *chunk_size;
+            }
+        }
+        tiers
+    }
+
+    /// Create the system allocator.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    /// - The application must keep the device alive for as long as the
+    ///   allocator is alive.
+    /// - The application must free any memory it allocates prior to dropping
+    ///   the memory allocator or device.
+    pub unsafe fn build(
+        &self,
+        instance: &ash::Instance,
+        device: ash::Device,
+        physical_device: vk::PhysicalDevice,
+    ) -> MemoryAllocator {
+        let memory_properties =
+            MemoryProperties::new(instance, physical_device);
+        let heap_class = HeapClass::detect(&memory_properties);
+        log::debug!("Creating a system allocator for {:?} memory", heap_class);
+
+        let limits = instance
+            .get_physical_device_properties(physical_device)
+            .limits;
+        let mut device_allocator = DeviceAllocator::new(device.clone())
+            .with_max_allocation_count(limits.max_memory_allocation_count)
+            .with_non_coherent_atom_size(limits.non_coherent_atom_size)
+            .with_debug_names(instance);
+        if let Some(ids) = self.ids {
+            device_allocator = device_allocator.with_memory_ids(ids);
+        }
+
+        // Warn before the device runs out of memory rather than after. The
+        // budgets are queried again for each device allocation because the OS
+        // changes them at runtime.
+        let device_allocator = HeapUsageAllocator::new(
+            device_allocator,
+            memory_properties.clone(),
+        )
+        .with_budget_query(instance, physical_device);
+        let device_allocator: SharedAllocator = if self.config.tracing {
+            into_shared(Box::new(TraceAllocator::new(
+                instance,
+                physical_device,
+                device_allocator,
+                "Device Allocator",
+            )))
+        } else {
+            into_shared(Box::new(device_allocator))
+        };
+
+        if memory_properties.types().len() <= 1 {
+            log::debug!("Collapsing pool tiers for a single memory type");
+        }
+        let mut tiers = self.tiers(heap_class, memory_properties.types().len());
+        if let Err(err) = check_tiers(&tiers) {
+            log::error!("Using the default tier sizes: {}", err);
+            tiers = Self::default()
+                .tiers(heap_class, memory_properties.types().len());
+        }
+
+        // Build the tiers from largest to smallest. Each tier gets chunks from
+        // the next largest tier and sends requests which are too big for its
+        // chunks to that tier too.
+        let mut pool_allocator: SharedAllocator =
+            into_shared(Box::new(device_allocator.clone()));
+        for &(page_size, chunk_size) in tiers.iter().rev() {
+            let mut pool = PoolAllocator::new(
+                memory_properties.clone(),
+                chunk_size,
+                page_size,
+                pool_allocator.clone(),
+            )
+            .with_heap_chunk_divisor(HEAP_CHUNK_DIVISOR);
+            if let Some(host_visible_chunk_size) =
+                self.config.host_visible_chunk_size
+            {
+                pool =
+                    pool.with_host_visible_chunk_size(host_visible_chunk_size);
+            }
+            if let Some(ids) = self.ids {
+                pool = pool.with_chunk_ids(ids);
+            }
+            let tier: Box<dyn ComposableAllocator + Send> =
+                Box::new(SizedAllocator::new(chunk_size, pool, pool_allocator));
+            pool_allocator = into_shared(tier);
+        }
+
+        let mut dedicated_allocator =
+            DedicatedAllocator::new(pool_allocator, device_allocator);
+        if let Some(dedicated_threshold) = self.config.dedicated_threshold {
+            dedicated_allocator =
+                dedicated_allocator.with_size_threshold(dedicated_threshold);
+        }
+
+        let system_allocator: Box<dyn ComposableAllocator + Send> =
+            if self.config.tracing {
+                Box::new(TraceAllocator::new(
+                    instance,
+                    physical_device,
+                    dedicated_allocator,
+                    "Application Allocator",
+                ))
+            } else {
+                Box::new(dedicated_allocator)
+            };
+
+        MemoryAllocator::new(
+            instance,
+            device,
+            physical_device,
+            system_allocator,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_overrides_replace_default_sizes() {
+        let tiers = SystemAllocatorBuilder::new()
+            .small_page(2048)
+            .medium_chunk(8 * MB)
+            .tiers(HeapClass::Discrete, 4);
+        assert_eq!(
+            tiers,
+            vec![(2048, 64 * KB), (64 * KB, 8 * MB), (4 * MB, 512 * MB)]
+        );
+    }
+
+    #[test]
+    fn test_overrides_add_missing_tiers() {
+        let tiers = SystemAllocatorBuilder::new()
+            .large_chunk(128 * MB)
+            .tiers(HeapClass::Unified, 4);
+        assert_eq!(
+            tiers,
+            vec![(KB, 64 * KB), (64 * KB, 4 * MB), (4 * MB, 128 * MB)]
+        );
+
+        let tiers = SystemAllocatorBuilder::new().tiers(HeapClass::Unified, 1);
+        assert_eq!(tiers, vec![(KB, 4 * MB)]);
+    }
+}
@@ -0,0 +1,410 @@
+use {
+    crate::{AllocatorError, HeapClass, SystemAllocatorBuilder},
+    anyhow::anyhow,
+};
+
+/// The settings used to build the system allocator, see
+/// [crate::create_system_allocator_with_config].
+///
+/// Any size which is None uses the default for the device's
+/// [crate::HeapClass]. [crate::SystemAllocatorBuilder] sets the same values
+/// with builder methods.
+///
+/// The config only tunes the system allocator's fixed hierarchy of dedicated
+/// allocations, pool tiers, and trace decorators. It doesn't describe
+/// arbitrary compositions, e.g. more than three tiers or other
+/// allocators in the tree. Those are built in code, see
+/// [crate::MemoryAllocator::new].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SystemAllocatorConfig {
+    /// The page size for the small tier.
+    pub small_page: Option<u64>,
+
+    /// The chunk size for the small tier.
+    pub small_chunk: Option<u64>,
+
+    /// The page size for the medium tier.
+    pub medium_page: Option<u64>,
+
+    /// The chunk size for the medium tier.
+    pub medium_chunk: Option<u64>,
+
+    /// The page size for the large tier. Devices whose heap class doesn't use
+    /// a large tier get one when this is set.
+    pub large_page: Option<u64>,
+
+    /// The chunk size for the large tier. Devices whose heap class doesn't use
+    /// a large tier get one when this is set.
+    pub large_chunk: Option<u64>,
+
+    /// Allocations at least this large get their own device allocation.
+    pub dedicated_threshold: Option<u64>,
+
+    /// The largest chunk size for HOST_VISIBLE memory types in any tier.
+    pub host_visible_chunk_size: Option<u64>,
+
+    /// Log every device allocation and every allocation given to the
+    /// application with a [crate::TraceAllocator].
+    pub tracing: bool,
+}
+
+impl Default for SystemAllocatorConfig {
+    fn default() -> Self {
+        Self {
+            small_page: None,
+            small_chunk: None,
+            medium_page: None,
+            medium_chunk: None,
+            large_page: None,
+            large_chunk: None,
+            dedicated_threshold: None,
+            host_visible_chunk_size: None,
+            tracing: true,
+        }
+    }
+}
+
+// Public API
+// ----------
+
+impl SystemAllocatorConfig {
+    /// Parse a config from a small subset of TOML.
+    ///
+    /// Each line is blank, a `#` comment, or a `key = value` pair where the
+    /// key is one of the field names and the value is an integer or a
+    /// boolean. For example:
+    ///
+    /// ```toml
+    /// # Smaller tiers for a device with a 256 MB heap
+    /// small_page = 2048
+    /// medium_chunk = 8_388_608
+    /// large_chunk = 67_108_864
+    /// tracing = false
+    /// ```
+    ///
+    /// Fields which aren't in the text keep their default values. The result
+    /// is checked with [Self::validate].
+    pub fn parse(text: &str) -> Result<Self, AllocatorError> {
+        let mut config = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((before_comment, _comment)) => before_comment,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => config.set(key.trim(), value.trim()),
+                None => Err(AllocatorError::RuntimeError(anyhow!(
+                    "Expected a `key = value` pair"
+                ))),
+            };
+            result.map_err(|err| {
+                AllocatorError::RuntimeError(anyhow!(
+                    "Invalid system allocator config on line {}: {}",
+                    index + 1,
+                    err
+                ))
+            })?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that the system allocator can be built with this config on any
+    /// device.
+    ///
+    /// Sizes which aren't set are replaced by the defaults for each
+    /// [HeapClass], then every tier's page and chunk size must be nonzero,
+    /// each page size must divide its chunk size, and chunk sizes must not
+    /// shrink from one tier to the next.
+    pub fn validate(&self) -> Result<(), AllocatorError> {
+        if self.host_visible_chunk_size == Some(0) {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "`host_visible_chunk_size` must not be zero"
+            )));
+        }
+        let builder = SystemAllocatorBuilder::from_config(*self);
+        let heap_classes = [
+            HeapClass::Discrete,
+            HeapClass::Unified,
+            HeapClass::MobileTbdr,
+        ];
+        for heap_class in heap_classes {
+            // A single memory type gets a single tier, so check both shapes.
+            for memory_type_count in [1, 2] {
+                check_tiers(&builder.tiers(heap_class, memory_type_count))
+                    .map_err(|err| {
+                        AllocatorError::RuntimeError(anyhow!(
+                            "Invalid system allocator config for {:?} \
+                             devices with {} memory types: {}",
+                            heap_class,
+                            memory_type_count,
+                            err
+                        ))
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a field from its name and a text value.
+    ///
+    /// # Params
+    ///
+    /// * key: the field name, e.g. `small_page`.
+    /// * value: an integer for sizes, which can use `_` separators, or
+    ///   `true`/`false` for flags.
+    pub fn set(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> Result<(), AllocatorError> {
+        let size = || parse_size(key, value).map(Some);
+        match key {
+            "small_page" => self.small_page = size()?,
+            "small_chunk" => self.small_chunk = size()?,
+            "medium_page" => self.medium_page = size()?,
+            "medium_chunk" => self.medium_chunk = size()?,
+            "large_page" => self.large_page = size()?,
+            "large_chunk" => self.large_chunk = size()?,
+            "dedicated_threshold" => self.dedicated_threshold = size()?,
+            "host_visible_chunk_size" => self.host_visible_chunk_size = size()?,
+            "tracing" => self.tracing = parse_flag(key, value)?,
+            _ => {
+                return Err(AllocatorError::RuntimeError(anyhow!(
+                    "Unknown system allocator setting `{}`",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The prefix for environment variables which override config values.
+const ENV_PREFIX: &str = "ASH_ALLOC_";
+
+impl SystemAllocatorConfig {
+    /// Override fields with environment variables. Each variable is the
+    /// field name in upper case with an `ASH_ALLOC_` prefix, e.g.
+    /// `ASH_ALLOC_SMALL_PAGE=4096`. `ASH_ALLOC_TRACE` is accepted for
+    /// `tracing`.
+    ///
+    /// This lets allocator issues be diagnosed on end-user machines without
+    /// shipping a special build.
+    ///
+    /// Each variable is applied on its own. A variable which can't be parsed,
+    /// or which would make the config invalid, see [Self::validate], is
+    /// logged and ignored without affecting the others. So are unknown
+    /// `ASH_ALLOC_` variables.
+    pub fn with_env_overrides(self) -> Self {
+        self.with_overrides(std::env::vars())
+    }
+}
+
+// Private API
+// -----------
+
+impl SystemAllocatorConfig {
+    /// Override fields with `ASH_ALLOC_` variables, see
+    /// [Self::with_env_overrides].
+    fn with_overrides(
+        self,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Self {
+        let mut config = self;
+        for (name, value) in vars {
+            let key = match name.strip_prefix(ENV_PREFIX) {
+                Some("TRACE") => "tracing".to_owned(),
+                Some(key) => key.to_lowercase(),
+                None => continue,
+            };
+            if !key_is_known(&key) {
+                log::warn!("Ignoring unknown environment variable {}", name);
+                continue;
+            }
+            let mut overridden = config;
+            let result = overridden
+                .set(&key, value.trim())
+                .and_then(|()| overridden.validate());
+            match result {
+                Ok(()) => {
+                    log::info!("Using {}={} from the environment", name, value);
+                    config = overridden;
+                }
+                Err(err) => log::warn!(
+                    "Ignoring invalid environment variable {}: {}",
+                    name,
+                    err
+                ),
+            }
+        }
+        config
+    }
+}
+
+/// The names of the tiers, from smallest to largest.
+const TIER_NAMES: [&str; 3] = ["small", "medium", "large"];
+
+/// Check the (page size, chunk size) for each tier, from smallest to largest.
+pub(crate) fn check_tiers(tiers: &[(u64, u64)]) -> Result<(), AllocatorError> {
+    let mut previous_chunk_size = 0;
+    for (&(page_size, chunk_size), name) in tiers.iter().zip(TIER_NAMES) {
+        if page_size == 0 || chunk_size == 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "The {} tier's page and chunk sizes must not be zero",
+                name
+            )));
+        }
+        if chunk_size % page_size != 0 {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "The {} tier's page size, {}, does not divide its chunk size, \
+                 {}",
+                name,
+                page_size,
+                chunk_size
+            )));
+        }
+        if chunk_size < previous_chunk_size {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "The {} tier's chunk size, {}, is smaller than the previous \
+                 tier's chunk size, {}",
+                name,
+                chunk_size,
+                previous_chunk_size
+            )));
+        }
+        previous_chunk_size = chunk_size;
+    }
+    Ok(())
+}
+
+/// Returns true when `key` names a config field.
+fn key_is_known(key: &str) -> bool {
+    SystemAllocatorConfig::default().set(key, "0").is_ok()
+}
+
+fn parse_size(key: &str, value: &str) -> Result<u64, AllocatorError> {
+    value.replace('_', "").parse::<u64>().map_err(|_| {
+        AllocatorError::RuntimeError(anyhow!(
+            "`{}` must be a size in bytes, found `{}`",
+            key,
+            value
+        ))
+    })
+}
+
+fn parse_flag(key: &str, value: &str) -> Result<bool, AllocatorError> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(AllocatorError::RuntimeError(anyhow!(
+            "`{}` must be true or false, found `{}`",
+            key,
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = SystemAllocatorConfig::parse(
+            "
+            # Smaller tiers
+            small_page = 2048
+            medium_chunk = 8_388_608 # 8 MB
+            tracing = false
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            SystemAllocatorConfig {
+                small_page: Some(2048),
+                medium_chunk: Some(8_388_608),
+                tracing: false,
+                ..SystemAllocatorConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let vars = [
+            ("ASH_ALLOC_SMALL_PAGE", "4096"),
+            ("ASH_ALLOC_TRACE", "0"),
+            ("ASH_ALLOC_UNKNOWN", "1"),
+            ("PATH", "/bin"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value.to_owned()));
+        let config = SystemAllocatorConfig::default().with_overrides(vars);
+        assert_eq!(config.small_page, Some(4096));
+        assert!(!config.tracing);
+    }
+
+    #[test]
+    fn test_invalid_overrides_are_skipped() {
+        let vars = [
+            ("ASH_ALLOC_LARGE_CHUNK", "huge"),
+            ("ASH_ALLOC_SMALL_PAGE", "0"),
+            ("ASH_ALLOC_MEDIUM_PAGE", "3000"),
+            ("ASH_ALLOC_MEDIUM_CHUNK", "8_388_608"),
+            ("ASH_ALLOC_TRACE", "maybe"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value.to_owned()));
+        let config = SystemAllocatorConfig::default().with_overrides(vars);
+        assert_eq!(
+            config,
+            SystemAllocatorConfig {
+                medium_chunk: Some(8_388_608),
+                ..SystemAllocatorConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let err = SystemAllocatorConfig::parse("tracing = true\nbig_page = 4")
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+
+        assert!(SystemAllocatorConfig::parse("small_page = lots").is_err());
+        assert!(SystemAllocatorConfig::parse("small_page").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_sizes_which_cant_be_built() {
+        assert!(SystemAllocatorConfig::parse("small_page = 0").is_err());
+        assert!(SystemAllocatorConfig::parse("small_page = 3000").is_err());
+        assert!(SystemAllocatorConfig::parse(
+            "medium_page = 4096\nmedium_chunk = 10000"
+        )
+        .is_err());
+        assert!(SystemAllocatorConfig::parse(
+            "small_chunk = 1_048_576\nmedium_chunk = 65536"
+        )
+        .is_err());
+        assert!(SystemAllocatorConfig::parse("host_visible_chunk_size = 0")
+            .is_err());
+
+        assert!(SystemAllocatorConfig::parse("small_page = 4096").is_ok());
+    }
+
+    #[test]
+    fn test_check_tiers() {
+        assert!(check_tiers(&[(1024, 65536), (65536, 4_194_304)]).is_ok());
+        assert!(check_tiers(&[(0, 65536)]).is_err());
+        assert!(check_tiers(&[(1024, 0)]).is_err());
+        assert!(check_tiers(&[(3000, 65536)]).is_err());
+        assert!(check_tiers(&[(1024, 65536), (1024, 32768)]).is_err());
+    }
+}
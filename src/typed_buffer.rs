@@ -0,0 +1,262 @@
+use {
+    crate::{Allocation, AllocatorError, MemoryAllocator, OwnedBuffer},
+    anyhow::anyhow,
+    ash::vk,
+    std::marker::PhantomData,
+};
+
+/// A buffer which holds a fixed number of elements of type T.
+///
+/// Each element starts at a multiple of the buffer's stride. The stride is
+/// the size of T unless a larger alignment is needed, e.g. for dynamic
+/// uniform buffer offsets. The buffer and its memory are freed when dropped,
+/// see [OwnedBuffer].
+pub struct Buffer<T> {
+    buffer: OwnedBuffer,
+    len: usize,
+    stride: u64,
+    _element: PhantomData<T>,
+}
+
+// Public API
+// ----------
+
+impl<T: Copy> Buffer<T> {
+    /// Allocate a buffer with room for `len` tightly packed elements.
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the buffer's memory and frees it on drop.
+    /// * len: the number of elements in the buffer.
+    /// * usage: how the buffer will be used.
+    /// * memory_property_flags: used to pick the buffer's memory type.
+    ///   HOST_VISIBLE is needed for [Self::write] and [Self::read].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be in use by the GPU when it is dropped
+    ///   - the buffer must be dropped before the device is destroyed
+    pub unsafe fn new(
+        allocator: &MemoryAllocator,
+        len: usize,
+        usage: vk::BufferUsageFlags,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Self, AllocatorError> {
+        Self::with_element_alignment(
+            allocator,
+            len,
+            1,
+            usage,
+            memory_property_flags,
+        )
+    }
+
+    /// Allocate a buffer where every element starts at a multiple of
+    /// `element_alignment`.
+    ///
+    /// # Params
+    ///
+    /// * allocator: allocates the buffer's memory and frees it on drop.
+    /// * len: the number of elements in the buffer.
+    /// * element_alignment: the alignment of each element's offset, e.g.
+    ///   minUniformBufferOffsetAlignment. Must be a power of two.
+    /// * usage: how the buffer will be used.
+    /// * memory_property_flags: used to pick the buffer's memory type.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the buffer must not be in use by the GPU when it is dropped
+    ///   - the buffer must be dropped before the device is destroyed
+    pub unsafe fn with_element_alignment(
+        allocator: &MemoryAllocator,
+        len: usize,
+        element_alignment: u64,
+        usage: vk::BufferUsageFlags,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Self, AllocatorError> {
+        if !element_alignment.is_power_of_two() {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "The element alignment must be a power of two, got {}",
+                element_alignment
+            )));
+        }
+        let stride = element_stride::<T>(element_alignment);
+        let buffer = OwnedBuffer::new(
+            allocator,
+            &vk::BufferCreateInfo {
+                size: (stride * len as u64).max(1),
+                usage,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            memory_property_flags,
+        )?;
+        Ok(Self {
+            buffer,
+            len,
+            stride,
+            _element: PhantomData,
+        })
+    }
+
+    /// The number of elements in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true when the buffer has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The distance in bytes between the start of consecutive elements.
+    pub fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    /// The offset of an element from the start of the buffer, e.g. for a
+    /// dynamic uniform buffer offset.
+    pub fn offset_of(&self, index: usize) -> u64 {
+        self.stride * index as u64
+    }
+
+    /// The raw Vulkan buffer handle.
+    pub fn raw(&self) -> vk::Buffer {
+        self.buffer.raw()
+    }
+
+    /// The buffer's memory.
+    pub fn allocation(&self) -> &Allocation {
+        self.buffer.allocation()
+    }
+
+    /// Write elements to the start of the buffer.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must not be accessing the buffer
+    pub unsafe fn write(&self, data: &[T]) -> Result<(), AllocatorError> {
+        self.write_at(0, data)
+    }
+
+    /// Write elements starting at the given index.
+    ///
+    /// # Params
+    ///
+    /// * first_index: the index of the first element to write.
+    /// * data: the elements to write.
+    ///
+    /// # Returns
+    ///
+    /// An error when the elements don't fit in the buffer or the buffer's
+    /// memory isn't HOST_VISIBLE.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - the GPU must not be accessing the written elements
+    pub unsafe fn write_at(
+        &self,
+        first_index: usize,
+        data: &[T],
+    ) -> Result<(), AllocatorError> {
+        self.check_range(first_index, data.len())?;
+        let device = self.buffer.allocator().device();
+        let offset = self.offset_of(first_index);
+        if self.stride == std::mem::size_of::<T>() as u64 {
+            return self.allocation().write_slice(device, offset, data);
+        }
+
+        let mut bytes = vec![0u8; self.stride as usize * data.len()];
+        for (element, chunk) in data
+            .iter()
+            .zip(bytes.chunks_exact_mut(self.stride as usize))
+        {
+            std::ptr::write_unaligned(chunk.as_mut_ptr() as *mut T, *element);
+        }
+        self.allocation().write_bytes(device, offset, &bytes)
+    }
+
+    /// Read every element in the buffer.
+    ///
+    /// # Returns
+    ///
+    /// An error when the buffer's memory isn't HOST_VISIBLE.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe because:
+    ///   - GPU writes to the buffer must be complete
+    ///   - every element must have been written, e.g. with [Self::write]
+    pub unsafe fn read(&self) -> Result<Vec<T>, AllocatorError> {
+        let device = self.buffer.allocator().device();
+        let mut bytes = vec![0u8; self.stride as usize * self.len];
+        self.allocation().read_bytes(device, 0, &mut bytes)?;
+        Ok(bytes
+            .chunks_exact(self.stride as usize)
+            .map(|chunk| std::ptr::read_unaligned(chunk.as_ptr() as *const T))
+            .collect())
+    }
+
+    /// Give up the typed view and return the owned buffer.
+    pub fn into_owned_buffer(self) -> OwnedBuffer {
+        self.buffer
+    }
+}
+
+impl<T> std::fmt::Debug for Buffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Buffer")
+            .field("element", &std::any::type_name::<T>())
+            .field("len", &self.len)
+            .field("stride", &self.stride)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+// Private API
+// -----------
+
+impl<T: Copy> Buffer<T> {
+    /// Fail when elements would be outside of the buffer.
+    fn check_range(
+        &self,
+        first_index: usize,
+        count: usize,
+    ) -> Result<(), AllocatorError> {
+        if first_index + count > self.len {
+            return Err(AllocatorError::RuntimeError(anyhow!(
+                "Unable to access elements {}..{} of a buffer with {} elements",
+                first_index,
+                first_index + count,
+                self.len
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The distance between elements of type T whose offsets are aligned to
+/// `element_alignment`.
+fn element_stride<T>(element_alignment: u64) -> u64 {
+    let size = (std::mem::size_of::<T>() as u64).max(1);
+    size + (element_alignment - size % element_alignment) % element_alignment
+}
+
+#[cfg(test)]
+mod test {
+    use super::element_stride;
+
+    #[test]
+    fn test_element_stride() {
+        assert_eq!(element_stride::<u32>(1), 4);
+        assert_eq!(element_stride::<[f32; 3]>(1), 12);
+        assert_eq!(element_stride::<[f32; 3]>(16), 16);
+        assert_eq!(element_stride::<[f32; 5]>(16), 32);
+        assert_eq!(element_stride::<u8>(256), 256);
+    }
+}
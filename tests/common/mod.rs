@@ -9,3 +9,13 @@ pub fn setup() -> Result<TestDevice> {
     setup_logger();
     TestDevice::new(PhysicalDeviceFeatures::default())
 }
+
+/// Setup logging and create a Vulkan test device whose instance has
+/// VK_EXT_debug_utils enabled.
+pub fn setup_with_debug_utils() -> Result<TestDevice> {
+    setup_logger();
+    TestDevice::with_instance_extensions(
+        PhysicalDeviceFeatures::default(),
+        &["VK_EXT_debug_utils".to_owned()],
+    )
+}
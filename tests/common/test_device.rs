@@ -14,6 +14,7 @@ use {
 #[derive(Debug)]
 pub struct TestDevice {
     pub transfer_queue: vk::Queue,
+    pub transfer_queue_family_index: u32,
     pub logical_device: LogicalDevice,
     pub instance: VulkanInstance,
 }
@@ -29,9 +30,22 @@ impl TestDevice {
     ///
     /// * `features` - The physical device features required by the test.
     pub fn new(features: PhysicalDeviceFeatures) -> Result<Self> {
+        Self::with_instance_extensions(features, &[])
+    }
+
+    /// Create a new TestDevice whose instance has extra extensions enabled.
+    ///
+    /// # Params
+    ///
+    /// * `features` - The physical device features required by the test.
+    /// * `extensions` - The instance extensions required by the test.
+    pub fn with_instance_extensions(
+        features: PhysicalDeviceFeatures,
+        extensions: &[String],
+    ) -> Result<Self> {
         let instance = unsafe {
             let with_layer = VulkanInstance::new(
-                &[],
+                extensions,
                 &["VK_LAYER_KHRONOS_validation".to_owned()],
             );
             if let Ok(instance) = with_layer {
@@ -39,7 +53,7 @@ impl TestDevice {
             } else {
                 log::warn!("Validation layer is not available!");
                 log::warn!("Falling back to an instance without the layer.");
-                VulkanInstance::new(&[], &[]).context(
+                VulkanInstance::new(extensions, &[]).context(
                     "Error creating the Vulkan Instance for the test device",
                 )?
             }
@@ -70,6 +84,7 @@ impl TestDevice {
 
         Ok(Self {
             transfer_queue,
+            transfer_queue_family_index: transfer_queue_family_index as u32,
             instance,
             logical_device: device,
         })
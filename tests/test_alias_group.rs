@@ -0,0 +1,104 @@
+//! Tests for aliasing transient resources in shared memory.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{create_system_allocator, AliasGroup},
+    ccthw_ash_instance::VulkanHandle,
+};
+
+mod common;
+
+fn attachment_create_info(size: u32) -> vk::ImageCreateInfo {
+    vk::ImageCreateInfo {
+        image_type: vk::ImageType::TYPE_2D,
+        format: vk::Format::R8G8B8A8_UNORM,
+        extent: vk::Extent3D {
+            width: size,
+            height: size,
+            depth: 1,
+        },
+        mip_levels: 1,
+        array_layers: 1,
+        samples: vk::SampleCountFlags::TYPE_1,
+        tiling: vk::ImageTiling::OPTIMAL,
+        usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | vk::ImageUsageFlags::SAMPLED,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    }
+}
+
+#[test]
+pub fn test_alias_group_shares_memory() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let (small, large, buffer) = unsafe {
+        (
+            device.create_image(&attachment_create_info(128), None)?,
+            device.create_image(&attachment_create_info(512), None)?,
+            device.create_buffer(
+                &vk::BufferCreateInfo {
+                    size: 4096,
+                    usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                    sharing_mode: vk::SharingMode::EXCLUSIVE,
+                    ..Default::default()
+                },
+                None,
+            )?,
+        )
+    };
+    let large_size =
+        unsafe { device.get_image_memory_requirements(large).size };
+
+    let allocation = unsafe {
+        AliasGroup::new(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .add_image(small)
+            .add_image(large)
+            .add_buffer(buffer)
+            .resolve(&allocator)?
+    };
+
+    // The group only needs enough memory for its largest resource.
+    assert!(allocation.size_in_bytes() >= large_size);
+    assert!(allocation.size_in_bytes() < 2 * large_size);
+
+    unsafe {
+        device.destroy_image(small, None);
+        device.destroy_image(large, None);
+        device.destroy_buffer(buffer, None);
+        allocator.free(allocation);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_empty_alias_group_fails() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let result = unsafe {
+        AliasGroup::new(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .resolve(&allocator)
+    };
+    assert!(result.is_err());
+
+    Ok(())
+}
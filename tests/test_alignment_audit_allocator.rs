@@ -0,0 +1,205 @@
+use {
+    ash::vk,
+    ccthw_ash_allocator::{
+        into_shared, AlignmentAuditAllocator, AlignmentLimits,
+        AllocationRequirements, ComposableAllocator, FakeAllocator,
+        MemoryProperties, MemoryTypePoolAllocator,
+    },
+};
+
+mod common;
+
+const DEVICE_LOCAL: usize = 0;
+const HOST_VISIBLE: usize = 1;
+
+fn memory_properties() -> MemoryProperties {
+    let types = [
+        vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            heap_index: 0,
+        },
+        vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE,
+            heap_index: 0,
+        },
+    ];
+    let heaps = [vk::MemoryHeap {
+        size: 1024,
+        flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+    }];
+    unsafe { MemoryProperties::from_raw(&types, &heaps) }
+}
+
+fn limits() -> AlignmentLimits {
+    AlignmentLimits {
+        min_memory_map_alignment: 16,
+        non_coherent_atom_size: 64,
+        min_texel_buffer_offset_alignment: 32,
+    }
+}
+
+fn requirements(
+    memory_type_index: usize,
+    size_in_bytes: u64,
+    alignment: u64,
+) -> AllocationRequirements {
+    AllocationRequirements {
+        memory_type_index,
+        size_in_bytes,
+        alignment,
+        ..AllocationRequirements::default()
+    }
+}
+
+#[test]
+fn test_aligned_allocations_pass() {
+    common::setup_logger();
+
+    let mut allocator = AlignmentAuditAllocator::new(
+        FakeAllocator::default(),
+        memory_properties(),
+        limits(),
+    );
+    unsafe {
+        allocator
+            .allocate(requirements(DEVICE_LOCAL, 8, 4))
+            .unwrap();
+        allocator
+            .allocate(requirements(DEVICE_LOCAL, 8, 8))
+            .unwrap();
+    }
+
+    assert!(allocator.audit().is_empty());
+    assert!(allocator.validate().is_ok());
+}
+
+#[test]
+fn test_misaligned_allocations_are_reported() {
+    common::setup_logger();
+
+    let mut allocator = AlignmentAuditAllocator::new(
+        FakeAllocator::default(),
+        memory_properties(),
+        limits(),
+    );
+    unsafe {
+        // The fake allocator packs allocations without respecting alignment.
+        allocator
+            .allocate(requirements(DEVICE_LOCAL, 3, 1))
+            .unwrap();
+        allocator
+            .allocate(requirements(DEVICE_LOCAL, 4, 4))
+            .unwrap();
+    }
+
+    let violations = allocator.audit();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].offset_in_bytes, 3);
+    assert_eq!(violations[0].required_alignment, 4);
+    assert!(allocator.validate().is_err());
+}
+
+#[test]
+fn test_host_visible_memory_is_checked_against_device_limits() {
+    common::setup_logger();
+
+    let mut allocator = AlignmentAuditAllocator::new(
+        FakeAllocator::default(),
+        memory_properties(),
+        limits(),
+    );
+    unsafe {
+        allocator
+            .allocate(requirements(HOST_VISIBLE, 16, 1))
+            .unwrap();
+        allocator
+            .allocate(requirements(HOST_VISIBLE, 16, 1))
+            .unwrap();
+    }
+
+    // The second allocation is at offset 16 which satisfies the map alignment
+    // but not the atom size for non-coherent memory.
+    let violations = allocator.audit();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].offset_in_bytes, 16);
+    assert_eq!(violations[0].constraint, "nonCoherentAtomSize");
+}
+
+#[test]
+fn test_texel_buffer_alignment_is_opt_in() {
+    common::setup_logger();
+
+    let mut allocator = AlignmentAuditAllocator::new(
+        FakeAllocator::default(),
+        memory_properties(),
+        limits(),
+    );
+    let mut texel_allocator = AlignmentAuditAllocator::new(
+        FakeAllocator::default(),
+        memory_properties(),
+        limits(),
+    )
+    .with_texel_buffer_alignment();
+    unsafe {
+        for allocator in [&mut allocator, &mut texel_allocator] {
+            allocator
+                .allocate(requirements(DEVICE_LOCAL, 16, 1))
+                .unwrap();
+            allocator
+                .allocate(requirements(DEVICE_LOCAL, 16, 1))
+                .unwrap();
+        }
+    }
+
+    assert!(allocator.audit().is_empty());
+    let violations = texel_allocator.audit();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].constraint, "minTexelBufferOffsetAlignment");
+}
+
+#[test]
+fn test_freed_allocations_are_not_audited() {
+    common::setup_logger();
+
+    let mut allocator = AlignmentAuditAllocator::new(
+        FakeAllocator::default(),
+        memory_properties(),
+        limits(),
+    );
+    unsafe {
+        let _ = allocator
+            .allocate(requirements(DEVICE_LOCAL, 3, 1))
+            .unwrap();
+        let misaligned = allocator
+            .allocate(requirements(DEVICE_LOCAL, 4, 4))
+            .unwrap();
+        allocator.free(misaligned);
+    }
+
+    assert_eq!(allocator.live_allocations(), 1);
+    assert!(allocator.audit().is_empty());
+}
+
+#[test]
+fn test_pool_allocations_from_an_offset_chunk_are_aligned() {
+    common::setup_logger();
+
+    // Shift the fake allocator so the pool's chunk starts at offset 4.
+    let mut fake = into_shared(FakeAllocator::default());
+    unsafe {
+        fake.allocate(requirements(DEVICE_LOCAL, 4, 1)).unwrap();
+    }
+
+    let mut allocator = AlignmentAuditAllocator::new(
+        MemoryTypePoolAllocator::new(DEVICE_LOCAL, 64, 4, fake),
+        memory_properties(),
+        AlignmentLimits::default(),
+    );
+    unsafe {
+        allocator
+            .allocate(requirements(DEVICE_LOCAL, 4, 8))
+            .unwrap();
+    }
+
+    assert!(allocator.audit().is_empty());
+}
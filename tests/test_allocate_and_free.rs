@@ -3,7 +3,10 @@
 use {
     anyhow::Result,
     ash::vk,
-    ccthw_ash_allocator::{create_system_allocator, Allocation},
+    ccthw_ash_allocator::{
+        create_system_allocator, Allocation, AllocationRequirements,
+        AllocatorError, DeviceAllocator, MemoryAllocator, MemoryProperties,
+    },
     ccthw_ash_instance::VulkanHandle,
     scopeguard::defer,
     std::sync::Arc,
@@ -16,7 +19,7 @@ pub fn allocate_buffer() -> Result<()> {
     let device = common::setup()?;
     log::info!("{}", device);
 
-    let mut allocator = unsafe {
+    let allocator = unsafe {
         create_system_allocator(
             device.instance.ash(),
             device.logical_device.raw().clone(),
@@ -42,16 +45,405 @@ pub fn allocate_buffer() -> Result<()> {
     defer! { unsafe { allocator.free_buffer(buffer, allocation.clone()) }; }
 
     log::info!("{:#?}", &allocation);
+    assert!(allocation.device_address().is_none());
 
     Ok(())
 }
 
+#[test]
+pub fn allocate_buffer_with_min_alignment() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
+        size: 100,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let mut buffers = vec![];
+    for _ in 0..4 {
+        buffers.push(unsafe {
+            allocator.allocate_buffer_aligned(
+                &create_info,
+                vk::MemoryPropertyFlags::HOST_VISIBLE,
+                256,
+            )?
+        });
+    }
+    for (_, allocation) in &buffers {
+        assert_eq!(allocation.offset_in_bytes() % 256, 0);
+    }
+    for (buffer, allocation) in buffers {
+        unsafe { allocator.free_buffer(buffer, allocation) };
+    }
+
+    let result = unsafe {
+        allocator.allocate_buffer_aligned(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            100,
+        )
+    };
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_buffer_with_preferred_flags() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let (buffer, allocation) = unsafe {
+        let create_info = vk::BufferCreateInfo {
+            usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
+            size: 1024,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        allocator.allocate_buffer_preferring(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    defer! { unsafe { allocator.free_buffer(buffer, allocation.clone()) }; }
+
+    // Either memory type is fine, but the required flags must be present.
+    log::info!("{:#?}", allocation.allocation_requirements());
+    assert!(allocation
+        .allocation_requirements()
+        .memory_properties
+        .contains(vk::MemoryPropertyFlags::HOST_VISIBLE));
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_named_buffer() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::VERTEX_BUFFER,
+        size: 1024,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let (buffer, mut allocation) = unsafe {
+        allocator.allocate_named_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            "Terrain Vertices",
+        )?
+    };
+    assert_eq!(allocation.name(), Some("Terrain Vertices"));
+
+    allocator.name_allocation(&mut allocation, "Water Vertices");
+    assert_eq!(allocation.name(), Some("Water Vertices"));
+
+    unsafe { allocator.free_buffer(buffer, allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn device_allocation_count_is_limited() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        MemoryAllocator::new(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+            DeviceAllocator::new(device.logical_device.raw().clone())
+                .with_max_allocation_count(1),
+        )
+    };
+
+    let requirements = AllocationRequirements {
+        size_in_bytes: 256,
+        alignment: 1,
+        memory_type_bits: 1,
+        memory_type_index: 0,
+        ..AllocationRequirements::default()
+    };
+    let allocation = unsafe { allocator.allocate(requirements)? };
+    let result = unsafe { allocator.allocate(requirements) };
+    assert!(matches!(
+        result,
+        Err(AllocatorError::TooManyDeviceAllocations {
+            allocation_count: 1,
+            max_allocation_count: 1,
+        })
+    ));
+
+    let stats = allocator.stats();
+    assert_eq!(stats.total.device_allocation_count, 1);
+    assert_eq!(stats.max_device_allocation_count, Some(1));
+
+    unsafe { allocator.free(allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn device_memory_can_be_named_with_debug_utils() -> Result<()> {
+    let device = common::setup_with_debug_utils()?;
+
+    let device_allocator = unsafe {
+        DeviceAllocator::new(device.logical_device.raw().clone())
+            .with_debug_names(device.instance.ash())
+    };
+    assert!(device_allocator.has_debug_names());
+
+    let allocator = unsafe {
+        MemoryAllocator::new(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+            device_allocator,
+        )
+    };
+
+    let requirements = AllocationRequirements {
+        size_in_bytes: 256,
+        alignment: 1,
+        memory_type_bits: 1,
+        memory_type_index: 0,
+        ..AllocationRequirements::default()
+    };
+    let mut allocation = unsafe { allocator.allocate(requirements)? };
+    allocator.name_allocation(&mut allocation, "Named Device Memory");
+    assert_eq!(allocation.name(), Some("Named Device Memory"));
+
+    unsafe { allocator.free(allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn stats_include_heap_budgets_when_supported() -> Result<()> {
+    let device = common::setup()?;
+    let allocator = unsafe {
+        MemoryAllocator::new(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+            DeviceAllocator::new(device.logical_device.raw().clone()),
+        )
+    };
+
+    let stats = allocator.stats();
+    if let Some(heap_budgets) = stats.heap_budgets {
+        assert_eq!(heap_budgets.len(), stats.memory_heaps.len());
+        for budget in heap_budgets {
+            assert!(budget.budget_bytes > 0);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn set_priority_requires_pageable_memory() -> Result<()> {
+    let device = common::setup()?;
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let (buffer, allocation) = unsafe {
+        let create_info = vk::BufferCreateInfo {
+            usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+            size: 64_000,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    defer! { unsafe { allocator.free_buffer(buffer, allocation.clone()) }; }
+
+    let result = unsafe { allocator.set_priority(&allocation, 0.25) };
+    assert_eq!(result.is_ok(), allocator.supports_pageable_memory());
+
+    let result = unsafe { allocator.set_priority(&allocation, 2.0) };
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+pub fn oversize_allocations_are_rejected_or_split() -> Result<()> {
+    let device = common::setup()?;
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let memory_properties = unsafe {
+        MemoryProperties::new(
+            device.instance.ash(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let max_size = match memory_properties.max_memory_allocation_size() {
+        Some(max_size) => max_size,
+        None => return Ok(()),
+    };
+
+    let requirements = AllocationRequirements {
+        size_in_bytes: max_size + 1,
+        alignment: 1,
+        memory_type_bits: 1,
+        memory_type_index: 0,
+        ..AllocationRequirements::default()
+    };
+    let result = unsafe { allocator.allocate(requirements) };
+    assert!(matches!(
+        result,
+        Err(AllocatorError::AllocationTooLarge { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+pub fn split_buffers_are_no_larger_than_the_limit() -> Result<()> {
+    let device = common::setup()?;
+    let mib = 1024 * 1024;
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+        .with_max_memory_allocation_size(mib + 1)
+    };
+
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+        size: 5 * mib / 2,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let parts = unsafe {
+        allocator.allocate_split_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+
+    // Parts are the limit rounded down to a whole MiB, except the last.
+    let sizes: Vec<u64> = parts
+        .iter()
+        .map(|(_, allocation)| {
+            allocation.allocation_requirements().size_in_bytes
+        })
+        .collect();
+    assert_eq!(parts.len(), 3);
+    assert!(sizes[0] >= mib && sizes[1] >= mib && sizes[2] >= mib / 2);
+    for (_, allocation) in &parts {
+        assert!(allocation.size_in_bytes() <= mib + 1);
+    }
+
+    // The limit applies to every allocation.
+    let requirements = AllocationRequirements {
+        size_in_bytes: 2 * mib,
+        alignment: 1,
+        memory_type_bits: 1,
+        memory_type_index: 0,
+        ..AllocationRequirements::default()
+    };
+    assert!(matches!(
+        unsafe { allocator.allocate(requirements) },
+        Err(AllocatorError::AllocationTooLarge { .. })
+    ));
+
+    for (buffer, allocation) in parts {
+        unsafe { allocator.free_buffer(buffer, allocation) };
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn allocations_record_the_calling_line() -> Result<()> {
+    let device = common::setup()?;
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+        size: 1024,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let flags = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+
+    // Each call must stay on the line before line!(). The second buffer
+    // comes from the first one's chunk, so it checks that suballocations
+    // don't keep the location of the call which created the chunk.
+    let first = unsafe { allocator.allocate_buffer(&info, flags) };
+    let first_line = line!() - 1;
+    let second = unsafe { allocator.allocate_buffer(&info, flags) };
+    let second_line = line!() - 1;
+
+    let mut buffers = vec![];
+    for (allocated, line) in [(first, first_line), (second, second_line)] {
+        let (buffer, allocation) = allocated?;
+        let location = allocation.allocation_requirements().location.unwrap();
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), line);
+        buffers.push((buffer, allocation));
+    }
+
+    for (buffer, allocation) in buffers {
+        unsafe { allocator.free_buffer(buffer, allocation) };
+    }
+    Ok(())
+}
+
 #[test]
 pub fn allocate_image() -> Result<()> {
     let device = common::setup()?;
     log::info!("{}", device);
 
-    let mut allocator = unsafe {
+    let allocator = unsafe {
         create_system_allocator(
             device.instance.ash(),
             device.logical_device.raw().clone(),
@@ -92,19 +484,473 @@ pub fn allocate_image() -> Result<()> {
     Ok(())
 }
 
+#[test]
+pub fn allocate_transient_attachment() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    // Transient attachments should succeed whether or not the device has
+    // lazily-allocated memory.
+    let (image, allocation) = unsafe {
+        let create_info = vk::ImageCreateInfo {
+            image_type: vk::ImageType::TYPE_2D,
+            format: vk::Format::R8G8B8A8_UNORM,
+            extent: vk::Extent3D {
+                width: 1920,
+                height: 1080,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        allocator.allocate_image(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    defer! { unsafe { allocator.free_image(image, allocation.clone()) }; }
+
+    log::info!("Transient Attachment Memory {}", &allocation);
+
+    Ok(())
+}
+
+#[test]
+pub fn frozen_allocator_rejects_allocations() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+        size: 1024,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+
+    allocator.freeze();
+    assert!(allocator.is_frozen());
+    let result = unsafe {
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    };
+    assert!(matches!(result, Err(AllocatorError::Frozen)));
+
+    // Frees are still allowed while frozen.
+    unsafe { allocator.free_buffer(buffer, allocation) };
+
+    allocator.thaw();
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    unsafe { allocator.free_buffer(buffer, allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_for_external_buffer() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    // The buffer is created by the application rather than the allocator.
+    let buffer = unsafe {
+        device.create_buffer(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 4096,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_buffer(buffer, None) }; }
+
+    let memory_properties = unsafe {
+        MemoryProperties::new(
+            device.instance.ash(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let requirements = AllocationRequirements::for_buffer(
+        &device,
+        memory_properties.types(),
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        buffer,
+    )?;
+    let allocation = unsafe { allocator.allocate(requirements)? };
+    unsafe {
+        device.bind_buffer_memory(
+            buffer,
+            allocation.memory(),
+            allocation.offset_in_bytes(),
+        )?;
+        allocator.free(allocation);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_and_bind_external_buffer() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let buffer = unsafe {
+        device.create_buffer(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
+                size: 256,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    let allocation = unsafe {
+        allocator.allocate_for_buffer(
+            buffer,
+            vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?
+    };
+    assert!(allocation.size_in_bytes() >= 256);
+
+    unsafe {
+        device.destroy_buffer(buffer, None);
+        allocator.free(allocation);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_external_buffer_in_explicit_memory_type() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let buffer = unsafe {
+        device.create_buffer(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
+                size: 256,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_buffer(buffer, None) }; }
+
+    let memory_type_bits =
+        unsafe { device.get_buffer_memory_requirements(buffer) }
+            .memory_type_bits;
+    let memory_type_index = (0..32)
+        .rev()
+        .find(|index| memory_type_bits & (1 << index) != 0)
+        .unwrap();
+
+    let allocation = unsafe {
+        allocator
+            .allocate_for_buffer_in_memory_type(buffer, memory_type_index)?
+    };
+    assert_eq!(
+        allocation.allocation_requirements().memory_type_index,
+        memory_type_index
+    );
+    unsafe { allocator.free(allocation) };
+
+    // A memory type which the buffer doesn't support is rejected.
+    let unsupported =
+        (0..32).find(|index| memory_type_bits & (1 << index) == 0);
+    if let Some(index) = unsupported {
+        let result = unsafe {
+            allocator.allocate_for_buffer_in_memory_type(buffer, index)
+        };
+        assert!(result.is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_and_bind_external_image() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let image = unsafe {
+        device.create_image(
+            &vk::ImageCreateInfo {
+                image_type: vk::ImageType::TYPE_2D,
+                format: vk::Format::R8G8B8A8_UNORM,
+                extent: vk::Extent3D {
+                    width: 512,
+                    height: 512,
+                    depth: 1,
+                },
+                mip_levels: 1,
+                array_layers: 1,
+                samples: vk::SampleCountFlags::TYPE_1,
+                tiling: vk::ImageTiling::OPTIMAL,
+                usage: vk::ImageUsageFlags::SAMPLED,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    let allocation = unsafe {
+        allocator
+            .allocate_for_image(image, vk::MemoryPropertyFlags::DEVICE_LOCAL)?
+    };
+
+    unsafe {
+        device.destroy_image(image, None);
+        allocator.free(allocation);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_buffers_in_a_batch() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let create_infos: Vec<vk::BufferCreateInfo> = (1..=32)
+        .map(|i| vk::BufferCreateInfo {
+            size: (i % 5 + 1) * 1000,
+            usage: vk::BufferUsageFlags::VERTEX_BUFFER,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        })
+        .collect();
+    let buffers = unsafe {
+        allocator.allocate_buffers(
+            &create_infos,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+
+    assert_eq!(buffers.len(), create_infos.len());
+    for ((_, allocation), create_info) in buffers.iter().zip(&create_infos) {
+        assert!(allocation.size_in_bytes() >= create_info.size);
+    }
+
+    for (buffer, allocation) in buffers {
+        unsafe { allocator.free_buffer(buffer, allocation) };
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn allocate_images_in_a_batch() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let create_infos: Vec<vk::ImageCreateInfo> = [64, 256, 128, 32]
+        .into_iter()
+        .map(|size| vk::ImageCreateInfo {
+            image_type: vk::ImageType::TYPE_2D,
+            format: vk::Format::R8G8B8A8_UNORM,
+            extent: vk::Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::SAMPLED,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        })
+        .collect();
+    let images = unsafe {
+        allocator.allocate_images(
+            &create_infos,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    assert_eq!(images.len(), create_infos.len());
+
+    for (image, allocation) in images {
+        unsafe { allocator.free_image(image, allocation) };
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn query_requirements_without_allocating() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let create_info = vk::BufferCreateInfo {
+        size: 10_000,
+        usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let requirements = unsafe {
+        allocator.query_buffer_requirements(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    assert!(requirements.size_in_bytes >= create_info.size);
+
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    assert_eq!(
+        allocation.allocation_requirements().memory_type_index,
+        requirements.memory_type_index
+    );
+    unsafe { allocator.free_buffer(buffer, allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn resource_cache_reuses_freed_buffers() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+        .with_resource_cache(1)
+    };
+
+    let create_info = vk::BufferCreateInfo {
+        size: 4096,
+        usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let flags = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+
+    unsafe {
+        let (buffer, allocation) =
+            allocator.allocate_buffer(&create_info, flags)?;
+        allocator.free_buffer(buffer, allocation);
+
+        let (reused, allocation) =
+            allocator.allocate_buffer(&create_info, flags)?;
+        assert_eq!(reused, buffer);
+        allocator.free_buffer(reused, allocation);
+
+        allocator.end_frame();
+        allocator.end_frame();
+        allocator.clear_resource_cache();
+    }
+
+    Ok(())
+}
+
 #[test]
 pub fn allocate_buffer_on_thread() -> Result<()> {
     let device = Arc::new(common::setup()?);
     log::info!("{}", device);
 
-    let mut allocator = unsafe {
+    let allocator = unsafe {
         create_system_allocator(
             device.instance.ash(),
             device.logical_device.raw().clone(),
             *device.logical_device.physical_device().raw(),
         )
     };
-    let mut a2 = allocator.clone();
+    let a2 = allocator.clone();
 
     let thread =
         std::thread::spawn(move || -> Result<(vk::Buffer, Allocation)> {
@@ -136,3 +982,79 @@ pub fn allocate_buffer_on_thread() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+pub fn allocate_buffers_through_a_shared_reference() -> Result<()> {
+    let device = Arc::new(common::setup()?);
+
+    let allocator = Arc::new(unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    });
+
+    let allocate = |allocator: &MemoryAllocator| unsafe {
+        allocator.allocate_buffer(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 1024,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    };
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let allocator = allocator.clone();
+            std::thread::spawn(move || -> Result<(vk::Buffer, Allocation)> {
+                Ok(allocate(&allocator)?)
+            })
+        })
+        .collect();
+    for thread in threads {
+        let (buffer, allocation) = thread.join().unwrap()?;
+        unsafe { allocator.free_buffer(buffer, allocation) };
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn clones_share_allocator_state() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let handle = allocator.clone();
+
+    handle.freeze();
+    assert!(allocator.is_frozen());
+    allocator.thaw();
+    assert!(!handle.is_frozen());
+
+    let (buffer, allocation) = unsafe {
+        handle.allocate_buffer(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 1024,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    drop(handle);
+    unsafe { allocator.free_buffer(buffer, allocation) };
+    allocator.validate()?;
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Tests for creating device-local buffers with initial contents.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        create_system_allocator, MemoryAllocator, UploadPath,
+    },
+    ccthw_ash_instance::VulkanHandle,
+    scopeguard::defer,
+};
+
+mod common;
+
+/// Copy the start of a buffer into host-visible memory and read it.
+unsafe fn read_back(
+    device: &common::TestDevice,
+    allocator: &MemoryAllocator,
+    command_pool: vk::CommandPool,
+    src: vk::Buffer,
+    len: usize,
+) -> Result<Vec<u32>> {
+    let size = (len * std::mem::size_of::<u32>()) as u64;
+    let (buffer, allocation) = allocator.allocate_buffer(
+        &vk::BufferCreateInfo {
+            usage: vk::BufferUsageFlags::TRANSFER_DST,
+            size,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        },
+        vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+
+    let command_buffer =
+        device.allocate_command_buffers(&vk::CommandBufferAllocateInfo {
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            ..Default::default()
+        })?[0];
+    device.begin_command_buffer(
+        command_buffer,
+        &vk::CommandBufferBeginInfo {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        },
+    )?;
+    device.cmd_copy_buffer(
+        command_buffer,
+        src,
+        buffer,
+        &[vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size,
+        }],
+    );
+    device.end_command_buffer(command_buffer)?;
+    device.queue_submit(
+        device.transfer_queue,
+        &[vk::SubmitInfo {
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            ..Default::default()
+        }],
+        vk::Fence::null(),
+    )?;
+    device.queue_wait_idle(device.transfer_queue)?;
+    device.free_command_buffers(command_pool, &[command_buffer]);
+
+    let ptr = allocation.map(device.logical_device.raw())? as *const u32;
+    let values = std::slice::from_raw_parts(ptr, len).to_vec();
+    allocation.unmap(device.logical_device.raw())?;
+    allocator.free_buffer(buffer, allocation);
+
+    Ok(values)
+}
+
+#[test]
+pub fn test_allocate_buffer_with_data() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo {
+                queue_family_index: device.transfer_queue_family_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_command_pool(command_pool, None) }; }
+
+    let values: Vec<u32> = (0..256).collect();
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer_with_data(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::TRANSFER_SRC,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            &values,
+            device.transfer_queue,
+            command_pool,
+        )?
+    };
+    assert!(
+        allocation.size_in_bytes()
+            >= std::mem::size_of_val(values.as_slice()) as u64
+    );
+
+    // With resizable BAR the data is written directly into device-local
+    // memory.
+    let memory_properties =
+        allocation.allocation_requirements().memory_properties;
+    assert!(memory_properties.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL));
+    if allocator.uses_direct_uploads() {
+        assert!(
+            memory_properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+        );
+    }
+
+    let copied = unsafe {
+        read_back(&device, &allocator, command_pool, buffer, values.len())?
+    };
+    assert_eq!(copied, values);
+
+    unsafe { allocator.free_buffer(buffer, allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn test_staged_upload_path() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+        .with_upload_path(UploadPath::Staged)
+    };
+    assert!(!allocator.uses_direct_uploads());
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo {
+                queue_family_index: device.transfer_queue_family_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_command_pool(command_pool, None) }; }
+
+    let values: Vec<u32> = (0..64).collect();
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer_with_data(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::TRANSFER_SRC,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            &values,
+            device.transfer_queue,
+            command_pool,
+        )?
+    };
+    let copied = unsafe {
+        read_back(&device, &allocator, command_pool, buffer, values.len())?
+    };
+    assert_eq!(copied, values);
+
+    unsafe { allocator.free_buffer(buffer, allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn test_allocate_buffer_with_no_data_fails() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let result = unsafe {
+        allocator.allocate_buffer_with_data::<u32>(
+            &vk::BufferCreateInfo::default(),
+            &[],
+            device.transfer_queue,
+            vk::CommandPool::null(),
+        )
+    };
+    assert!(result.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Tests for migrating allocations between allocators.
+
+use {
+    anyhow::Result,
+    ccthw_ash_allocator::{
+        into_shared, AllocationMigration, AllocationRequirements,
+        ComposableAllocator, FailingAllocator, FailureMode, FakeAllocator,
+    },
+};
+
+mod common;
+
+#[test]
+fn test_finish_migration() -> Result<()> {
+    common::setup_logger();
+
+    let mut streaming = into_shared(FakeAllocator::default());
+    let mut persistent = into_shared(FakeAllocator::default());
+
+    let allocation = unsafe {
+        streaming.allocate(AllocationRequirements {
+            size_in_bytes: 128,
+            alignment: 16,
+            memory_type_index: 1,
+            ..AllocationRequirements::default()
+        })?
+    };
+
+    let requirements = *allocation.allocation_requirements();
+    let migration = unsafe {
+        AllocationMigration::new(allocation, &mut persistent)
+            .map_err(|(err, _)| err)?
+    };
+    assert_eq!(migration.buffer_copy().size, 128);
+    assert_eq!(
+        migration.destination().allocation_requirements(),
+        &requirements
+    );
+    assert_eq!(persistent.lock().unwrap().active_allocations, 1);
+
+    let promoted = unsafe { migration.finish(&mut streaming) };
+    assert_eq!(streaming.lock().unwrap().active_allocations, 0);
+    assert_eq!(persistent.lock().unwrap().active_allocations, 1);
+
+    unsafe { persistent.free(promoted) };
+    assert_eq!(persistent.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_cancel_migration() -> Result<()> {
+    common::setup_logger();
+
+    let mut streaming = into_shared(FakeAllocator::default());
+    let mut persistent = into_shared(FakeAllocator::default());
+
+    let allocation = unsafe {
+        streaming.allocate(AllocationRequirements {
+            size_in_bytes: 128,
+            alignment: 16,
+            ..AllocationRequirements::default()
+        })?
+    };
+
+    let allocation = unsafe {
+        let migration = AllocationMigration::new(allocation, &mut persistent)
+            .map_err(|(err, _)| err)?;
+        migration.cancel(&mut persistent)
+    };
+    assert_eq!(streaming.lock().unwrap().active_allocations, 1);
+    assert_eq!(persistent.lock().unwrap().active_allocations, 0);
+
+    unsafe { streaming.free(allocation) };
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_migration_returns_the_source() -> Result<()> {
+    common::setup_logger();
+
+    let mut streaming = into_shared(FakeAllocator::default());
+    let mut persistent = FailingAllocator::new(
+        FakeAllocator::default(),
+        FailureMode::NthAllocation(1),
+    );
+
+    let allocation = unsafe {
+        streaming.allocate(AllocationRequirements {
+            size_in_bytes: 128,
+            alignment: 16,
+            ..AllocationRequirements::default()
+        })?
+    };
+
+    let result =
+        unsafe { AllocationMigration::new(allocation, &mut persistent) };
+    let allocation = match result {
+        Err((_, allocation)) => allocation,
+        Ok(_) => panic!("The destination allocation should fail"),
+    };
+    assert_eq!(allocation.size_in_bytes(), 128);
+
+    unsafe { streaming.free(allocation) };
+    assert_eq!(streaming.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
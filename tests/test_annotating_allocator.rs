@@ -0,0 +1,45 @@
+//! Tests for the annotating allocator.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        AllocationRequirements, AnnotatingAllocator, ComposableAllocator,
+        FakeAllocator, MemoryAnnotations,
+    },
+};
+
+mod common;
+
+#[test]
+fn test_live_allocations_are_annotated() -> Result<()> {
+    common::setup_logger();
+
+    let annotations = MemoryAnnotations::default();
+    let mut allocator =
+        AnnotatingAllocator::new(FakeAllocator::default(), annotations.clone());
+
+    // The fake allocator always uses the null memory handle.
+    let memory = vk::DeviceMemory::null();
+    assert!(annotations.describe(memory).is_none());
+
+    let requirements = AllocationRequirements {
+        size_in_bytes: 64,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    };
+    let a1 = unsafe { allocator.allocate(requirements)? };
+    let a2 = unsafe { allocator.allocate(requirements)? };
+
+    let description = annotations.describe(memory).unwrap();
+    assert!(description.contains("2 allocation(s)"), "{}", description);
+
+    unsafe { allocator.free(a1) };
+    let description = annotations.describe(memory).unwrap();
+    assert!(description.contains("1 allocation(s)"), "{}", description);
+
+    unsafe { allocator.free(a2) };
+    assert!(annotations.describe(memory).is_none());
+
+    Ok(())
+}
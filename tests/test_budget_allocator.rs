@@ -0,0 +1,264 @@
+//! Tests for the budget allocator.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, AllocatorError, AllocatorStats,
+        BudgetAllocator, BudgetForecast, BudgetTarget, ComposableAllocator,
+        FakeAllocator, MemoryProperties,
+    },
+};
+
+mod common;
+
+fn memory_properties() -> MemoryProperties {
+    unsafe {
+        // Safe because the fake allocator never allocates real memory.
+        MemoryProperties::from_raw(
+            &[
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE,
+                    heap_index: 1,
+                },
+            ],
+            &[
+                vk::MemoryHeap {
+                    size: 1024,
+                    flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+                },
+                vk::MemoryHeap {
+                    size: 1024,
+                    flags: vk::MemoryHeapFlags::empty(),
+                },
+            ],
+        )
+    }
+}
+
+fn requirements(
+    memory_type_index: usize,
+    size_in_bytes: u64,
+) -> AllocationRequirements {
+    AllocationRequirements {
+        memory_type_index,
+        size_in_bytes,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    }
+}
+
+#[test]
+fn test_heap_limit() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = BudgetAllocator::new(fake.clone(), memory_properties())
+        .with_heap_limit(0, 100);
+
+    // Both memory types share heap 0.
+    let a1 = unsafe { allocator.allocate(requirements(0, 60))? };
+    let result = unsafe { allocator.allocate(requirements(1, 60)) };
+    assert!(matches!(
+        result,
+        Err(AllocatorError::BudgetExceeded {
+            target: BudgetTarget::MemoryHeap(0),
+            size_in_bytes: 60,
+            used_bytes: 60,
+            limit_bytes: 100,
+        })
+    ));
+    assert_eq!(fake.lock().unwrap().active_allocations, 1);
+
+    // Other heaps are unaffected.
+    let a2 = unsafe { allocator.allocate(requirements(2, 500))? };
+    assert_eq!(allocator.usage(BudgetTarget::MemoryHeap(1)), 500);
+
+    // Freeing memory makes room in the budget again.
+    unsafe { allocator.free(a1) };
+    assert_eq!(allocator.usage(BudgetTarget::MemoryHeap(0)), 0);
+    let a3 = unsafe { allocator.allocate(requirements(1, 60))? };
+
+    unsafe {
+        allocator.free(a2);
+        allocator.free(a3);
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_memory_type_limit() -> Result<()> {
+    common::setup_logger();
+
+    let mut allocator =
+        BudgetAllocator::new(FakeAllocator::default(), memory_properties())
+            .with_memory_type_limit(1, 32);
+
+    let a1 = unsafe { allocator.allocate(requirements(0, 500))? };
+    let a2 = unsafe { allocator.allocate(requirements(1, 32))? };
+    let result = unsafe { allocator.allocate(requirements(1, 1)) };
+    assert!(matches!(
+        result,
+        Err(AllocatorError::BudgetExceeded {
+            target: BudgetTarget::MemoryType(1),
+            ..
+        })
+    ));
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_forecast_projects_frames_until_exhausted() -> Result<()> {
+    common::setup_logger();
+
+    let mut allocator =
+        BudgetAllocator::new(FakeAllocator::default(), memory_properties())
+            .with_heap_limit(0, 1000);
+    let target = BudgetTarget::MemoryHeap(0);
+
+    allocator.end_frame();
+    assert!(allocator.forecast(target).is_none());
+
+    for _ in 0..3 {
+        unsafe { allocator.allocate(requirements(0, 100))? };
+        allocator.end_frame();
+    }
+
+    assert_eq!(
+        allocator.forecast(target),
+        Some(BudgetForecast {
+            target,
+            used_bytes: 300,
+            limit_bytes: 1000,
+            bytes_per_frame: 100.0,
+            frames_until_exhausted: Some(7),
+        })
+    );
+    assert!(allocator.forecast(BudgetTarget::MemoryHeap(1)).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_forecast_only_uses_recent_frames() -> Result<()> {
+    common::setup_logger();
+
+    let mut allocator =
+        BudgetAllocator::new(FakeAllocator::default(), memory_properties())
+            .with_memory_type_limit(0, 1000)
+            .with_forecast_window(2);
+    let target = BudgetTarget::MemoryType(0);
+
+    // Usage grows, then levels off for longer than the window.
+    allocator.end_frame();
+    unsafe { allocator.allocate(requirements(0, 500))? };
+    for _ in 0..3 {
+        allocator.end_frame();
+    }
+
+    let forecast = allocator.forecast(target).unwrap();
+    assert_eq!(forecast.bytes_per_frame, 0.0);
+    assert_eq!(forecast.frames_until_exhausted, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_tag_limit() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = BudgetAllocator::new(fake.clone(), memory_properties())
+        .with_tag_limit("textures", 100)
+        .with_soft_limit(BudgetTarget::Tag("meshes"), 10);
+
+    let texture = AllocationRequirements {
+        tag: Some("textures"),
+        ..requirements(0, 60)
+    };
+    let mesh = AllocationRequirements {
+        tag: Some("meshes"),
+        ..requirements(0, 60)
+    };
+
+    let a1 = unsafe { allocator.allocate(texture)? };
+    let result = unsafe { allocator.allocate(texture) };
+    assert!(matches!(
+        result,
+        Err(AllocatorError::BudgetExceeded {
+            target: BudgetTarget::Tag("textures"),
+            ..
+        })
+    ));
+
+    // Soft limits and untagged allocations never fail.
+    let a2 = unsafe { allocator.allocate(mesh)? };
+    let a3 = unsafe { allocator.allocate(requirements(0, 60))? };
+    assert_eq!(allocator.usage(BudgetTarget::Tag("textures")), 60);
+    assert_eq!(allocator.usage(BudgetTarget::Tag("meshes")), 60);
+    assert_eq!(allocator.usage(BudgetTarget::MemoryType(0)), 180);
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+        allocator.free(a3);
+    }
+    assert_eq!(allocator.usage(BudgetTarget::Tag("textures")), 0);
+    assert_eq!(allocator.usage(BudgetTarget::Tag("meshes")), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_tag_forecasts_are_added_to_stats() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = BudgetAllocator::new(fake.clone(), memory_properties())
+        .with_tag_limit("textures", 100)
+        .with_heap_limit(0, 1000);
+
+    let texture = AllocationRequirements {
+        tag: Some("textures"),
+        ..requirements(0, 20)
+    };
+    allocator.end_frame();
+    let a1 = unsafe { allocator.allocate(texture)? };
+    allocator.end_frame();
+
+    let mut stats = AllocatorStats::default();
+    allocator.stats(&mut stats);
+    assert_eq!(
+        stats.tag_forecasts.get("textures"),
+        Some(&BudgetForecast {
+            target: BudgetTarget::Tag("textures"),
+            used_bytes: 20,
+            limit_bytes: 100,
+            bytes_per_frame: 20.0,
+            frames_until_exhausted: Some(4),
+        })
+    );
+
+    // Only tag budgets are keyed by tag.
+    assert_eq!(stats.tag_forecasts.len(), 1);
+
+    unsafe { allocator.free(a1) };
+
+    Ok(())
+}
@@ -110,3 +110,42 @@ fn test_requires_dedicated_allocation() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_size_threshold() -> Result<()> {
+    common::setup_logger();
+
+    let shared_allocator = into_shared(FakeAllocator::default());
+    let device_allocator = into_shared(FakeAllocator::default());
+    let mut allocator = DedicatedAllocator::new(
+        shared_allocator.clone(),
+        device_allocator.clone(),
+    )
+    .with_size_threshold(1024);
+
+    let (small, large) = unsafe {
+        let small = allocator.allocate(AllocationRequirements {
+            size_in_bytes: 1023,
+            alignment: 8,
+            ..AllocationRequirements::default()
+        })?;
+        let large = allocator.allocate(AllocationRequirements {
+            size_in_bytes: 1024,
+            alignment: 8,
+            ..AllocationRequirements::default()
+        })?;
+        (small, large)
+    };
+    assert_eq!(shared_allocator.lock().unwrap().active_allocations, 1);
+    assert_eq!(device_allocator.lock().unwrap().active_allocations, 1);
+
+    unsafe {
+        allocator.free(small);
+        allocator.free(large);
+    }
+
+    assert_eq!(shared_allocator.lock().unwrap().active_allocations, 0);
+    assert_eq!(device_allocator.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
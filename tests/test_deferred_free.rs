@@ -0,0 +1,103 @@
+//! Tests for freeing resources once the GPU signals completion.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{create_system_allocator, FrameClock, GpuCompletion},
+    ccthw_ash_instance::VulkanHandle,
+    scopeguard::defer,
+};
+
+mod common;
+
+#[test]
+pub fn test_free_buffer_deferred_waits_for_the_fence() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let fence =
+        unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
+    defer! { unsafe { device.destroy_fence(fence, None) }; }
+
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 1024,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    unsafe {
+        allocator.free_buffer_deferred(
+            buffer,
+            allocation,
+            GpuCompletion::Fence(fence),
+        )
+    };
+
+    // The fence hasn't been signaled, so nothing is freed.
+    unsafe { allocator.collect()? };
+    assert_eq!(allocator.pending_free_count(), 1);
+
+    // Signal the fence with an empty submission.
+    unsafe {
+        device.queue_submit(device.transfer_queue, &[], fence)?;
+        device.wait_for_fences(&[fence], true, u64::MAX)?;
+        allocator.collect()?;
+    }
+    assert_eq!(allocator.pending_free_count(), 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_retired_buffers_wait_for_their_frame() -> Result<()> {
+    let device = common::setup()?;
+
+    let clock = FrameClock::new();
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    }
+    .with_clock(clock.clone());
+
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+        size: 1024,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    for _ in 0..3 {
+        unsafe {
+            let (buffer, allocation) = allocator.allocate_buffer(
+                &create_info,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            allocator.retire(buffer, allocation);
+        }
+        clock.advance();
+    }
+    assert_eq!(allocator.retired_count(), 3);
+
+    // Buffers were retired in frames 0, 1, and 2.
+    unsafe { allocator.advance_frame(1) };
+    assert_eq!(allocator.retired_count(), 1);
+
+    unsafe { device.device_wait_idle()? };
+    unsafe { allocator.advance_frame(clock.frame()) };
+    assert_eq!(allocator.retired_count(), 0);
+
+    Ok(())
+}
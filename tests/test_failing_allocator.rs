@@ -0,0 +1,95 @@
+//! Tests for the failing allocator.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, AllocatorError,
+        ComposableAllocator, FailingAllocator, FailureMode, FakeAllocator,
+    },
+};
+
+mod common;
+
+fn requirements(size_in_bytes: u64) -> AllocationRequirements {
+    AllocationRequirements {
+        size_in_bytes,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    }
+}
+
+#[test]
+fn test_fail_nth_allocation() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator =
+        FailingAllocator::new(fake.clone(), FailureMode::NthAllocation(2));
+
+    let a1 = unsafe { allocator.allocate(requirements(32)) };
+    let a2 = unsafe { allocator.allocate(requirements(32)) };
+    let a3 = unsafe { allocator.allocate(requirements(32)) };
+
+    assert!(a1.is_ok());
+    match a2 {
+        Err(AllocatorError::RuntimeError(err)) => assert_eq!(
+            err.downcast_ref::<vk::Result>(),
+            Some(&vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)
+        ),
+        _ => panic!("The second allocation should fail with out of memory"),
+    }
+    assert!(a3.is_ok());
+    assert_eq!(allocator.failure_count(), 1);
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    unsafe {
+        allocator.free(a1?);
+        allocator.free(a3?);
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_fail_larger_than() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator =
+        FailingAllocator::new(fake.clone(), FailureMode::LargerThan(64));
+
+    let small = unsafe { allocator.allocate(requirements(64))? };
+    assert!(unsafe { allocator.allocate(requirements(65)) }.is_err());
+    assert_eq!(fake.lock().unwrap().active_allocations, 1);
+
+    unsafe { allocator.free(small) };
+
+    Ok(())
+}
+
+#[test]
+fn test_fail_with_probability_is_reproducible() -> Result<()> {
+    common::setup_logger();
+
+    let run = |seed: u64| -> Vec<bool> {
+        let mut allocator = FailingAllocator::new(
+            FakeAllocator::default(),
+            FailureMode::Probability {
+                probability: 0.5,
+                seed,
+            },
+        );
+        (0..32)
+            .map(|_| unsafe { allocator.allocate(requirements(8)) }.is_err())
+            .collect()
+    };
+
+    let first = run(1234);
+    assert_eq!(first, run(1234));
+    assert!(first.iter().any(|&failed| failed));
+    assert!(first.iter().any(|&failed| !failed));
+
+    Ok(())
+}
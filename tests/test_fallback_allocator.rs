@@ -0,0 +1,64 @@
+//! Tests for the fallback allocator.
+
+use {
+    anyhow::Result,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, ComposableAllocator,
+        FailingAllocator, FailureMode, FakeAllocator, FallbackAllocator,
+    },
+};
+
+mod common;
+
+fn requirements(size_in_bytes: u64) -> AllocationRequirements {
+    AllocationRequirements {
+        size_in_bytes,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    }
+}
+
+#[test]
+fn test_fallback_on_failure() -> Result<()> {
+    common::setup_logger();
+
+    let primary = into_shared(FakeAllocator::default());
+    let fallback = into_shared(FakeAllocator::default());
+    let mut allocator = FallbackAllocator::new(
+        FailingAllocator::new(primary.clone(), FailureMode::LargerThan(64)),
+        fallback.clone(),
+    );
+
+    let small = unsafe { allocator.allocate(requirements(64))? };
+    let large = unsafe { allocator.allocate(requirements(128))? };
+
+    assert_eq!(primary.lock().unwrap().active_allocations, 1);
+    assert_eq!(fallback.lock().unwrap().active_allocations, 1);
+
+    unsafe { allocator.free(large) };
+    assert_eq!(primary.lock().unwrap().active_allocations, 1);
+    assert_eq!(fallback.lock().unwrap().active_allocations, 0);
+
+    unsafe { allocator.free(small) };
+    assert_eq!(primary.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_both_allocators_fail() {
+    common::setup_logger();
+
+    let mut allocator = FallbackAllocator::new(
+        FailingAllocator::new(
+            FakeAllocator::default(),
+            FailureMode::LargerThan(0),
+        ),
+        FailingAllocator::new(
+            FakeAllocator::default(),
+            FailureMode::LargerThan(0),
+        ),
+    );
+
+    assert!(unsafe { allocator.allocate(requirements(1)) }.is_err());
+}
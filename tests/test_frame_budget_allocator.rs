@@ -0,0 +1,96 @@
+//! Tests for the frame budget allocator.
+
+use {
+    anyhow::Result,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, ComposableAllocator,
+        FakeAllocator, FrameBudget, FrameBudgetAllocator,
+    },
+};
+
+mod common;
+
+#[test]
+fn test_allocation_count_budget() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = FrameBudgetAllocator::new(
+        fake.clone(),
+        FrameBudget {
+            max_allocations: Some(1),
+            ..FrameBudget::default()
+        },
+        "Test Budget",
+    );
+
+    let allocation_requirements = AllocationRequirements {
+        size_in_bytes: 32,
+        alignment: 8,
+        ..AllocationRequirements::default()
+    };
+    let a1 = unsafe { allocator.allocate(allocation_requirements)? };
+    assert!(!allocator.is_over_budget());
+
+    // Over-budget requests still succeed, they're just reported.
+    let a2 = unsafe { allocator.allocate(allocation_requirements)? };
+    assert!(allocator.is_over_budget());
+    assert_eq!(allocator.frame_allocations(), 2);
+    assert_eq!(
+        allocator.frame_requests(),
+        &[allocation_requirements, allocation_requirements]
+    );
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    // The frame's warning is logged once, then the requests are forgotten.
+    allocator.end_frame();
+    assert!(!allocator.is_over_budget());
+    assert_eq!(allocator.frame_allocations(), 0);
+    assert!(allocator.frame_requests().is_empty());
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_allocation_bytes_budget() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = FrameBudgetAllocator::new(
+        fake,
+        FrameBudget {
+            max_bytes: Some(64),
+            ..FrameBudget::default()
+        },
+        "Test Budget",
+    );
+
+    let allocation_requirements = AllocationRequirements {
+        size_in_bytes: 48,
+        alignment: 8,
+        ..AllocationRequirements::default()
+    };
+    let a1 = unsafe { allocator.allocate(allocation_requirements)? };
+    assert!(!allocator.is_over_budget());
+    assert_eq!(allocator.frame_bytes(), 48);
+
+    let a2 = unsafe { allocator.allocate(allocation_requirements)? };
+    assert!(allocator.is_over_budget());
+    assert_eq!(allocator.frame_bytes(), 96);
+
+    allocator.end_frame();
+    assert_eq!(allocator.frame_bytes(), 0);
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+    }
+
+    Ok(())
+}
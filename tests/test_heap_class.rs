@@ -0,0 +1,105 @@
+//! Tests for heap class detection.
+
+use {
+    ash::vk,
+    ccthw_ash_allocator::{HeapClass, MemoryProperties},
+};
+
+fn heap_class(
+    types: &[vk::MemoryPropertyFlags],
+    heaps: &[vk::MemoryHeapFlags],
+) -> HeapClass {
+    let types: Vec<vk::MemoryType> = types
+        .iter()
+        .map(|&property_flags| vk::MemoryType {
+            property_flags,
+            heap_index: 0,
+        })
+        .collect();
+    let heaps: Vec<vk::MemoryHeap> = heaps
+        .iter()
+        .map(|&flags| vk::MemoryHeap { size: 1024, flags })
+        .collect();
+    let memory_properties =
+        unsafe { MemoryProperties::from_raw(&types, &heaps) };
+    HeapClass::detect(&memory_properties)
+}
+
+#[test]
+fn test_discrete() {
+    let class = heap_class(
+        &[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        ],
+        &[
+            vk::MemoryHeapFlags::DEVICE_LOCAL,
+            vk::MemoryHeapFlags::empty(),
+        ],
+    );
+    assert_eq!(class, HeapClass::Discrete);
+}
+
+#[test]
+fn test_unified() {
+    let class = heap_class(
+        &[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        ],
+        &[vk::MemoryHeapFlags::DEVICE_LOCAL],
+    );
+    assert_eq!(class, HeapClass::Unified);
+}
+
+#[test]
+fn test_mobile_tbdr() {
+    let class = heap_class(
+        &[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+        ],
+        &[vk::MemoryHeapFlags::DEVICE_LOCAL],
+    );
+    assert_eq!(class, HeapClass::MobileTbdr);
+}
+
+#[test]
+fn test_no_memory() {
+    assert_eq!(heap_class(&[], &[]), HeapClass::Discrete);
+}
+
+#[test]
+fn test_has_lazily_allocated_memory() {
+    let types = [
+        vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            heap_index: 0,
+        },
+        vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+            heap_index: 0,
+        },
+    ];
+    let heaps = [vk::MemoryHeap {
+        size: 1024,
+        flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+    }];
+
+    let with_lazy = unsafe { MemoryProperties::from_raw(&types, &heaps) };
+    assert!(with_lazy.has_lazily_allocated_memory());
+
+    let without_lazy =
+        unsafe { MemoryProperties::from_raw(&types[..1], &heaps) };
+    assert!(!without_lazy.has_lazily_allocated_memory());
+}
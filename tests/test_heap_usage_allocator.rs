@@ -0,0 +1,81 @@
+//! Tests for the heap usage allocator.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, ComposableAllocator,
+        FakeAllocator, HeapUsageAllocator, HeapUsageWarning, MemoryProperties,
+    },
+    std::sync::{Arc, Mutex},
+};
+
+mod common;
+
+#[test]
+fn test_warns_once_when_crossing_the_threshold() -> Result<()> {
+    common::setup_logger();
+
+    let memory_properties = unsafe {
+        // Safe because the fake allocator will never actually attempt to
+        // allocate real memory.
+        MemoryProperties::from_raw(
+            &[vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                heap_index: 0,
+            }],
+            &[vk::MemoryHeap {
+                size: 1000,
+                flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+            }],
+        )
+    };
+    let warnings: Arc<Mutex<Vec<HeapUsageWarning>>> = Arc::default();
+    let mut allocator = HeapUsageAllocator::new(
+        into_shared(FakeAllocator::default()),
+        memory_properties,
+    )
+    .with_warning_threshold(0.5)
+    .with_warning_hook({
+        let warnings = warnings.clone();
+        move |warning| warnings.lock().unwrap().push(*warning)
+    });
+
+    let requirements = AllocationRequirements {
+        size_in_bytes: 300,
+        alignment: 1,
+        memory_type_index: 0,
+        ..AllocationRequirements::default()
+    };
+    let first = unsafe { allocator.allocate(requirements)? };
+    assert!(warnings.lock().unwrap().is_empty());
+
+    let second = unsafe { allocator.allocate(requirements)? };
+    let third = unsafe { allocator.allocate(requirements)? };
+    assert_eq!(allocator.heap_usage(0), 900);
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        vec![HeapUsageWarning {
+            heap_index: 0,
+            used_bytes: 600,
+            budget_bytes: 1000,
+            threshold: 0.5,
+        }]
+    );
+
+    // Dropping below the threshold re-arms the warning.
+    unsafe {
+        allocator.free(second);
+        allocator.free(third);
+    }
+    let fourth = unsafe { allocator.allocate(requirements)? };
+    assert_eq!(warnings.lock().unwrap().len(), 2);
+
+    unsafe {
+        allocator.free(first);
+        allocator.free(fourth);
+    }
+    assert_eq!(allocator.heap_usage(0), 0);
+
+    Ok(())
+}
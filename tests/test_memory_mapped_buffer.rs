@@ -2,8 +2,14 @@
 //! be written and read from the buffer.
 
 use {
-    anyhow::Result, ash::vk, ccthw_ash_allocator::create_system_allocator,
-    ccthw_ash_instance::VulkanHandle, scopeguard::defer,
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        create_system_allocator, AllocationRequirements, AllocatorError,
+        MappedMemory, MemoryProperties,
+    },
+    ccthw_ash_instance::VulkanHandle,
+    scopeguard::defer,
 };
 
 mod common;
@@ -18,7 +24,7 @@ pub fn test_mapped_buffer() -> Result<()> {
     let device = common::setup()?;
     log::info!("{}", device);
 
-    let mut allocator = unsafe {
+    let allocator = unsafe {
         create_system_allocator(
             device.instance.ash(),
             device.logical_device.raw().clone(),
@@ -95,7 +101,7 @@ pub fn test_repeated_mapping() -> Result<()> {
     let device = common::setup()?;
     log::info!("{}", device);
 
-    let mut allocator = unsafe {
+    let allocator = unsafe {
         create_system_allocator(
             device.instance.ash(),
             device.logical_device.raw().clone(),
@@ -137,3 +143,201 @@ pub fn test_repeated_mapping() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+pub fn test_allocator_mapping() -> Result<()> {
+    let device = common::setup()?;
+    log::info!("{}", device);
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let (buffer, allocation) = unsafe {
+        let create_info = vk::BufferCreateInfo {
+            flags: vk::BufferCreateFlags::empty(),
+            usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+            size: std::mem::size_of::<ExampleData>() as u64,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: std::ptr::null(),
+            ..Default::default()
+        };
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+    };
+
+    // Write-combined memory only exposes writes, everything else is a raw
+    // pointer.
+    match unsafe { allocator.map(&allocation)? } {
+        MappedMemory::WriteOnly(mut memory) => unsafe {
+            memory.write(0, 1337_i32);
+        },
+        MappedMemory::ReadWrite(ptr) => unsafe {
+            std::ptr::write_unaligned(ptr as *mut i32, 1337);
+        },
+    }
+    unsafe {
+        allocator.unmap(&allocation)?;
+        allocator.free_buffer(buffer, allocation);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_write_and_read_cached_memory() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let (buffer, allocation) = unsafe {
+        let create_info = vk::BufferCreateInfo {
+            usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+            size: 64,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_CACHED,
+        )?
+    };
+    defer! { unsafe { allocator.free_buffer(buffer, allocation.clone()) }; }
+
+    let data: Vec<u32> = (0..8).collect();
+    unsafe {
+        allocation.write_slice(device.logical_device.raw(), 4, &data)?;
+    }
+
+    let mut bytes = [0u8; 32];
+    unsafe {
+        allocation.read_bytes(device.logical_device.raw(), 4, &mut bytes)?;
+    }
+    assert_eq!(bytes[4..8], 1u32.to_ne_bytes());
+    assert_eq!(bytes[28..32], 7u32.to_ne_bytes());
+
+    // The write doesn't fit in the allocation.
+    let result = unsafe {
+        allocation.write_bytes(
+            device.logical_device.raw(),
+            allocation.size_in_bytes() - 4,
+            &[0; 8],
+        )
+    };
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+pub fn test_persistently_mapped_allocation() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let memory_properties = unsafe {
+        MemoryProperties::new(
+            device.instance.ash(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let host_visible = vk::MemoryPropertyFlags::HOST_VISIBLE
+        | vk::MemoryPropertyFlags::HOST_COHERENT;
+    let memory_type_index = memory_properties
+        .types()
+        .iter()
+        .position(|memory_type| {
+            memory_type.property_flags.contains(host_visible)
+        })
+        .unwrap();
+
+    let allocation = unsafe {
+        allocator.allocate(AllocationRequirements {
+            size_in_bytes: 256,
+            alignment: 4,
+            memory_type_bits: 1 << memory_type_index,
+            memory_type_index,
+            memory_properties: host_visible,
+            persistently_mapped: true,
+            ..AllocationRequirements::default()
+        })?
+    };
+    defer! { unsafe { allocator.free(allocation.clone()) }; }
+
+    let ptr = allocation.mapped_ptr().unwrap() as *mut u32;
+    unsafe { ptr.write(1337) };
+
+    let mut bytes = [0u8; 4];
+    unsafe {
+        allocation.read_bytes(device.logical_device.raw(), 0, &mut bytes)?;
+    }
+    assert_eq!(bytes, 1337u32.to_ne_bytes());
+
+    Ok(())
+}
+
+#[test]
+pub fn test_allocate_mapped_buffer() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::TRANSFER_SRC,
+        size: 16,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+
+    let result = unsafe {
+        allocator.allocate_mapped_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    };
+    assert!(matches!(result, Err(AllocatorError::NotHostVisible(_))));
+
+    let (buffer, allocation, ptr) = unsafe {
+        allocator.allocate_mapped_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+    };
+    defer! { unsafe { allocator.free_buffer(buffer, allocation.clone()) }; }
+    assert_eq!(allocation.mapped_ptr(), Some(ptr));
+
+    unsafe { (ptr as *mut u32).write(42) };
+    let mut bytes = [0u8; 4];
+    unsafe {
+        allocation.read_bytes(device.logical_device.raw(), 0, &mut bytes)?;
+    }
+    assert_eq!(bytes, 42u32.to_ne_bytes());
+
+    Ok(())
+}
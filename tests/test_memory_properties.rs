@@ -0,0 +1,157 @@
+//! Tests for the memory properties queries.
+
+use {ash::vk, ccthw_ash_allocator::MemoryProperties};
+
+const MB: u64 = 1024 * 1024;
+
+fn discrete(bar_size: u64) -> MemoryProperties {
+    unsafe {
+        MemoryProperties::from_raw(
+            &[
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    heap_index: 1,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
+                        | vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    heap_index: 2,
+                },
+            ],
+            &[
+                vk::MemoryHeap {
+                    size: 8192 * MB,
+                    flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+                },
+                vk::MemoryHeap {
+                    size: 16384 * MB,
+                    flags: vk::MemoryHeapFlags::empty(),
+                },
+                vk::MemoryHeap {
+                    size: bar_size,
+                    flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+                },
+            ],
+        )
+    }
+}
+
+#[test]
+fn test_find_type() {
+    let properties = discrete(256 * MB);
+    let host_visible = vk::MemoryPropertyFlags::HOST_VISIBLE;
+    let device_local = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+
+    assert_eq!(
+        properties.find_type(host_visible, device_local, u32::MAX),
+        Some(2)
+    );
+    assert_eq!(
+        properties.find_type(
+            host_visible,
+            vk::MemoryPropertyFlags::empty(),
+            u32::MAX
+        ),
+        Some(1)
+    );
+
+    // The preferred properties are dropped when the allowed types lack them.
+    assert_eq!(
+        properties.find_type(host_visible, device_local, 0b011),
+        Some(1)
+    );
+    assert_eq!(
+        properties.find_type(host_visible, device_local, 0b001),
+        None
+    );
+}
+
+#[test]
+fn test_heap_queries() {
+    let properties = discrete(256 * MB);
+    assert!(!properties.is_unified_memory());
+    assert_eq!(properties.device_local_heap_size(), 8192 * MB);
+    assert!(!properties.has_rebar());
+
+    assert!(discrete(8192 * MB).has_rebar());
+}
+
+#[test]
+fn test_unified_memory() {
+    let properties = unsafe {
+        MemoryProperties::from_raw(
+            &[vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
+                    | vk::MemoryPropertyFlags::HOST_VISIBLE,
+                heap_index: 0,
+            }],
+            &[vk::MemoryHeap {
+                size: 4096 * MB,
+                flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+            }],
+        )
+    };
+    assert!(properties.is_unified_memory());
+    assert!(!properties.has_rebar());
+}
+
+#[test]
+fn test_device_coherent_types_are_opt_in() {
+    let device_coherent = vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD
+        | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD;
+    let host_visible = vk::MemoryPropertyFlags::HOST_VISIBLE
+        | vk::MemoryPropertyFlags::HOST_COHERENT;
+    let properties = unsafe {
+        MemoryProperties::from_raw(
+            &[
+                vk::MemoryType {
+                    property_flags: host_visible | device_coherent,
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: host_visible,
+                    heap_index: 0,
+                },
+            ],
+            &[vk::MemoryHeap {
+                size: 256 * MB,
+                flags: vk::MemoryHeapFlags::empty(),
+            }],
+        )
+    };
+
+    assert_eq!(
+        properties.find_type(
+            host_visible,
+            vk::MemoryPropertyFlags::empty(),
+            u32::MAX
+        ),
+        Some(1)
+    );
+    assert_eq!(
+        properties.find_type(
+            host_visible | device_coherent,
+            vk::MemoryPropertyFlags::empty(),
+            u32::MAX
+        ),
+        Some(0)
+    );
+    assert_eq!(
+        properties.find_type(host_visible, device_coherent, u32::MAX),
+        Some(0)
+    );
+    assert_eq!(
+        properties.find_type(
+            host_visible,
+            vk::MemoryPropertyFlags::empty(),
+            0b01
+        ),
+        None
+    );
+}
@@ -1,8 +1,11 @@
 use {
     anyhow::Result,
+    ash::vk,
     ccthw_ash_allocator::{
-        into_shared, AllocationRequirements, AllocatorError,
-        ComposableAllocator, FakeAllocator, MemoryTypePoolAllocator,
+        into_shared, AllocationExtensions, AllocationRequirements,
+        AllocatorError, AllocatorStats, ComposableAllocator, FakeAllocator,
+        IdGenerator, MemoryTypePoolAllocator, ResourceKind, SizedAllocator,
+        ValidationAllocator,
     },
     pretty_assertions::assert_eq,
 };
@@ -110,6 +113,210 @@ pub fn test_allocate_and_free() -> Result<()> {
     Ok(())
 }
 
+#[test]
+pub fn test_reset_keeps_chunks_for_reuse() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone());
+
+    let allocation_requirements = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 256,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    };
+    unsafe {
+        allocator.allocate(allocation_requirements)?;
+        allocator.allocate(allocation_requirements)?;
+        allocator.allocate(allocation_requirements)?;
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    unsafe { allocator.reset() };
+
+    // Chunks are kept, but all of their space is available again.
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+    assert!(allocator.validate().is_ok());
+
+    let allocations = unsafe {
+        [
+            allocator.allocate(allocation_requirements)?,
+            allocator.allocate(allocation_requirements)?,
+            allocator.allocate(allocation_requirements)?,
+            allocator.allocate(allocation_requirements)?,
+        ]
+    };
+    assert_eq!(fake.lock().unwrap().allocations.len(), 2);
+
+    for allocation in allocations {
+        unsafe { allocator.free(allocation) };
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_trim_releases_retained_chunks() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone());
+
+    let allocation_requirements = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 256,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    };
+    let allocation = unsafe {
+        allocator.allocate(allocation_requirements)?;
+        allocator.allocate(allocation_requirements)?;
+        allocator.allocate(allocation_requirements)?;
+        allocator.reset();
+        allocator.allocate(allocation_requirements)?
+    };
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    // Only the chunk without any allocations is released.
+    unsafe { allocator.trim() };
+    assert_eq!(fake.lock().unwrap().active_allocations, 1);
+    assert!(allocator.validate().is_ok());
+
+    unsafe { allocator.free(allocation) };
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_reserve_creates_chunks_ahead_of_time() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone());
+
+    let reservation = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 600,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    };
+    unsafe { allocator.reserve(reservation)? };
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+    assert!(allocator.validate().is_ok());
+
+    // Existing free space counts towards the reservation.
+    unsafe { allocator.reserve(reservation)? };
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    let allocation = unsafe {
+        allocator.allocate(AllocationRequirements {
+            size_in_bytes: 256,
+            ..reservation
+        })?
+    };
+    assert_eq!(fake.lock().unwrap().allocation_count, 2);
+
+    unsafe {
+        allocator.free(allocation);
+        allocator.trim();
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_seeded_chunk_ids_are_reproducible() -> Result<()> {
+    common::setup_logger();
+
+    let chunk_paths = |chunk_ids: IdGenerator| -> Result<Vec<String>> {
+        let mut allocator = MemoryTypePoolAllocator::new(
+            0,
+            512,
+            8,
+            into_shared(FakeAllocator::default()),
+        )
+        .with_chunk_ids(chunk_ids);
+        let allocation_requirements = AllocationRequirements {
+            memory_type_index: 0,
+            size_in_bytes: 256,
+            alignment: 1,
+            ..AllocationRequirements::default()
+        };
+        let mut paths = vec![];
+        for _ in 0..4 {
+            let allocation =
+                unsafe { allocator.allocate(allocation_requirements)? };
+            paths.push(allocation.path().to_owned());
+        }
+        Ok(paths)
+    };
+
+    assert_eq!(
+        chunk_paths(IdGenerator::sequential())?,
+        &[
+            "Pool[type 0]/chunk 0",
+            "Pool[type 0]/chunk 0",
+            "Pool[type 0]/chunk 1",
+            "Pool[type 0]/chunk 1",
+        ]
+    );
+
+    let seeded = chunk_paths(IdGenerator::seeded(42))?;
+    assert_eq!(seeded, chunk_paths(IdGenerator::seeded(42))?);
+    assert_ne!(seeded, chunk_paths(IdGenerator::seeded(7))?);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_linear_and_optimal_resources_respect_granularity() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake)
+        .with_buffer_image_granularity(64);
+
+    let requirements = |resource_kind| AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 8,
+        alignment: 1,
+        resource_kind,
+        ..AllocationRequirements::default()
+    };
+    let unknown =
+        unsafe { allocator.allocate(requirements(ResourceKind::Unknown))? };
+    let linear =
+        unsafe { allocator.allocate(requirements(ResourceKind::Linear))? };
+    let optimal =
+        unsafe { allocator.allocate(requirements(ResourceKind::Optimal))? };
+
+    // Resources of a known kind get whole granularity pages of their own.
+    assert_eq!(unknown.offset_in_bytes(), 0);
+    assert_eq!(linear.offset_in_bytes(), 64);
+    assert_eq!(linear.size_in_bytes(), 8);
+    assert_eq!(optimal.offset_in_bytes() % 64, 0);
+    assert!(optimal.offset_in_bytes() >= 128);
+    assert_eq!(optimal.size_in_bytes(), 8);
+
+    // The padding is released along with the resource.
+    unsafe { allocator.free(linear) };
+    let linear =
+        unsafe { allocator.allocate(requirements(ResourceKind::Linear))? };
+    assert_eq!(linear.offset_in_bytes(), 64);
+
+    unsafe {
+        allocator.free(unknown);
+        allocator.free(linear);
+        allocator.free(optimal);
+    }
+
+    Ok(())
+}
+
 #[test]
 pub fn test_allocate_with_mismatching_type_index_should_fail() -> Result<()> {
     common::setup_logger();
@@ -164,3 +371,294 @@ pub fn test_allocate_with_oversized_allocation_requirements() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+pub fn test_random_placement() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = ValidationAllocator::new(
+        MemoryTypePoolAllocator::new(0, 1024, 8, fake.clone())
+            .with_random_placement(1234),
+    );
+
+    let mut allocations = vec![];
+    for i in 0..64 {
+        let requirements = AllocationRequirements {
+            memory_type_index: 0,
+            size_in_bytes: 8 + (i % 5) * 12,
+            alignment: 1 << (i % 6),
+            ..AllocationRequirements::default()
+        };
+        allocations.push(unsafe { allocator.allocate(requirements)? });
+    }
+
+    for allocation in allocations.drain(..).rev() {
+        unsafe { allocator.free(allocation) };
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_device_address_allocations_use_device_address_chunks() -> Result<()>
+{
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone());
+
+    let plain = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 64,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    };
+    let device_address = AllocationRequirements {
+        extensions: AllocationExtensions::default().with_memory_allocate_flags(
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS,
+        ),
+        ..plain
+    };
+
+    let allocations = unsafe {
+        vec![
+            allocator.allocate(plain)?,
+            allocator.allocate(device_address)?,
+            allocator.allocate(plain)?,
+            allocator.allocate(device_address)?,
+        ]
+    };
+
+    // The device address allocation needs its own chunk, but plain
+    // allocations can share it.
+    let chunk_flags: Vec<_> = fake
+        .lock()
+        .unwrap()
+        .allocations
+        .iter()
+        .map(|requirements| requirements.extensions.memory_allocate_flags())
+        .collect();
+    assert_eq!(
+        chunk_flags,
+        &[
+            vk::MemoryAllocateFlags::empty(),
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        ]
+    );
+    // The fake allocator places the second chunk at offset 512.
+    let in_device_address_chunk: Vec<bool> = allocations
+        .iter()
+        .map(|allocation| allocation.offset_in_bytes() >= 512)
+        .collect();
+    assert_eq!(in_device_address_chunk, &[false, true, false, true]);
+
+    for allocation in allocations {
+        unsafe { allocator.free(allocation) };
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_device_masks_use_separate_chunks() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone());
+
+    let first_device = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 64,
+        alignment: 1,
+        extensions: AllocationExtensions::default().with_device_mask(0b01),
+        ..AllocationRequirements::default()
+    };
+    let second_device = AllocationRequirements {
+        extensions: AllocationExtensions::default().with_device_mask(0b10),
+        ..first_device
+    };
+
+    let allocations = unsafe {
+        vec![
+            allocator.allocate(first_device)?,
+            allocator.allocate(second_device)?,
+            allocator.allocate(first_device)?,
+            allocator.allocate(second_device)?,
+        ]
+    };
+
+    // Each device mask needs its own chunk.
+    let chunk_masks: Vec<_> = fake
+        .lock()
+        .unwrap()
+        .allocations
+        .iter()
+        .map(|requirements| requirements.extensions.device_mask())
+        .collect();
+    assert_eq!(chunk_masks, &[Some(0b01), Some(0b10)]);
+    let in_second_chunk: Vec<bool> = allocations
+        .iter()
+        .map(|allocation| allocation.offset_in_bytes() >= 512)
+        .collect();
+    assert_eq!(in_second_chunk, &[false, true, false, true]);
+
+    for allocation in allocations {
+        unsafe { allocator.free(allocation) };
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_stats_report_chunks_and_free_space() -> Result<()> {
+    common::setup_logger();
+
+    let pool = into_shared(MemoryTypePoolAllocator::new(
+        0,
+        512,
+        64,
+        FakeAllocator::default(),
+    ));
+
+    // The same pool is reachable twice, but its chunks are only counted once.
+    let allocator = SizedAllocator::new(512, pool.clone(), pool.clone());
+
+    let requirements = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 128,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    };
+    let allocation = unsafe { pool.lock().unwrap().allocate(requirements)? };
+
+    let mut stats = AllocatorStats::default();
+    allocator.stats(&mut stats);
+    assert_eq!(stats.memory_types.len(), 1);
+    assert_eq!(stats.memory_types[0].chunk_count, 1);
+    assert_eq!(stats.memory_types[0].largest_free_block, 512 - 128);
+
+    unsafe { pool.lock().unwrap().free(allocation) };
+
+    let mut stats = AllocatorStats::default();
+    allocator.stats(&mut stats);
+    assert_eq!(stats.memory_types[0].chunk_count, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_chunk_growth() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone())
+        .with_chunk_growth(2048);
+
+    // The chunks hold 1, 2, and 5 allocations, so the ninth allocation needs
+    // a fourth chunk which is capped at the maximum size.
+    let requirements = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 384,
+        alignment: 8,
+        ..AllocationRequirements::default()
+    };
+    let mut allocations = vec![];
+    for _ in 0..9 {
+        allocations.push(unsafe { allocator.allocate(requirements)? });
+    }
+
+    let chunk_sizes: Vec<u64> = fake
+        .lock()
+        .unwrap()
+        .allocations
+        .iter()
+        .map(|requirements| requirements.size_in_bytes)
+        .collect();
+    assert_eq!(chunk_sizes, vec![512, 1024, 2048, 2048]);
+
+    for allocation in allocations {
+        unsafe { allocator.free(allocation) };
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_max_pool_size() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone())
+        .with_max_pool_size(1024);
+
+    let requirements = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 384,
+        alignment: 8,
+        ..AllocationRequirements::default()
+    };
+    let first = unsafe { allocator.allocate(requirements)? };
+    let second = unsafe { allocator.allocate(requirements)? };
+    assert_eq!(allocator.pool_bytes(), 1024);
+
+    let result = unsafe { allocator.allocate(requirements) };
+    assert!(matches!(
+        result,
+        Err(AllocatorError::PoolLimitExceeded {
+            memory_type_index: 0,
+            chunk_size: 512,
+            pool_bytes: 1024,
+            max_pool_bytes: 1024,
+        })
+    ));
+
+    unsafe {
+        allocator.free(first);
+        allocator.free(second);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_empty_chunk_retention() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = MemoryTypePoolAllocator::new(0, 512, 8, fake.clone())
+        .with_empty_chunk_retention(1);
+
+    let requirements = AllocationRequirements {
+        memory_type_index: 0,
+        size_in_bytes: 384,
+        alignment: 8,
+        ..AllocationRequirements::default()
+    };
+    let first = unsafe { allocator.allocate(requirements)? };
+    let second = unsafe { allocator.allocate(requirements)? };
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    // The first empty chunk is kept, the second is freed.
+    unsafe {
+        allocator.free(first);
+        allocator.free(second);
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 1);
+    allocator.validate()?;
+
+    // The kept chunk is reused.
+    let third = unsafe { allocator.allocate(requirements)? };
+    assert_eq!(fake.lock().unwrap().allocation_count, 2);
+
+    unsafe {
+        allocator.free(third);
+        allocator.trim();
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Tests for allocation paths through named nodes in the allocator tree.
+
+use {
+    anyhow::Result,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, ChunkReport, ComposableAllocator,
+        FakeAllocator, MemoryReport, MemoryRun, MemoryTypePoolAllocator,
+        NamedAllocator, SizedAllocator,
+    },
+    pretty_assertions::assert_eq,
+};
+
+mod common;
+
+fn requirements(size_in_bytes: u64) -> AllocationRequirements {
+    AllocationRequirements {
+        size_in_bytes,
+        alignment: 1,
+        ..AllocationRequirements::default()
+    }
+}
+
+#[test]
+pub fn test_paths_name_every_node() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator = NamedAllocator::new(
+        SizedAllocator::new(
+            64,
+            NamedAllocator::new(
+                MemoryTypePoolAllocator::new(0, 128, 64, fake.clone()),
+                "Sized<64b>",
+            ),
+            NamedAllocator::new(fake.clone(), "Device"),
+        ),
+        "App",
+    );
+
+    let small_1 = unsafe { allocator.allocate(requirements(48))? };
+    let small_2 = unsafe { allocator.allocate(requirements(48))? };
+    let small_3 = unsafe { allocator.allocate(requirements(48))? };
+    let large = unsafe { allocator.allocate(requirements(1024))? };
+
+    assert_eq!(small_1.path(), "App/Sized<64b>/Pool[type 0]/chunk 0");
+    assert_eq!(small_2.path(), "App/Sized<64b>/Pool[type 0]/chunk 0");
+    assert_eq!(small_3.path(), "App/Sized<64b>/Pool[type 0]/chunk 1");
+    assert_eq!(large.path(), "App/Device");
+
+    unsafe {
+        allocator.free(small_1);
+        allocator.free(small_2);
+        allocator.free(small_3);
+        allocator.free(large);
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_unnamed_allocations_have_empty_paths() -> Result<()> {
+    let mut allocator = FakeAllocator::default();
+    let allocation = unsafe { allocator.allocate(requirements(48))? };
+    assert_eq!(allocation.path(), "");
+    unsafe { allocator.free(allocation) };
+    Ok(())
+}
+
+#[test]
+pub fn test_report_names_chunk_owners() -> Result<()> {
+    common::setup_logger();
+
+    let mut allocator = NamedAllocator::new(
+        NamedAllocator::new(
+            MemoryTypePoolAllocator::new(0, 256, 64, FakeAllocator::default()),
+            "Pool",
+        ),
+        "Application",
+    );
+
+    let allocation = unsafe { allocator.allocate(requirements(100))? };
+
+    let mut report = MemoryReport::default();
+    allocator.report(&mut report);
+    assert_eq!(
+        report.chunks,
+        vec![ChunkReport {
+            owner: "Application/Pool".to_owned(),
+            name: "Pool[type 0]/chunk 0".to_owned(),
+            memory_id: 0,
+            memory_type_index: 0,
+            offset_in_bytes: 0,
+            size_in_bytes: 256,
+            runs: vec![
+                MemoryRun {
+                    offset_in_bytes: 0,
+                    size_in_bytes: 128,
+                    used: true,
+                },
+                MemoryRun {
+                    offset_in_bytes: 128,
+                    size_in_bytes: 128,
+                    used: false,
+                },
+            ],
+        }]
+    );
+
+    unsafe { allocator.free(allocation) };
+    Ok(())
+}
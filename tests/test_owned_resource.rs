@@ -0,0 +1,144 @@
+//! Tests for buffers and images which free themselves when dropped.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        create_system_allocator, Buffer, OwnedBuffer, OwnedImage,
+    },
+    ccthw_ash_instance::VulkanHandle,
+};
+
+mod common;
+
+#[test]
+pub fn test_owned_resources_are_freed_on_drop() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let buffer = unsafe {
+        OwnedBuffer::new(
+            &allocator,
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 256,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    let image = unsafe {
+        OwnedImage::new(
+            &allocator,
+            &vk::ImageCreateInfo {
+                image_type: vk::ImageType::TYPE_2D,
+                format: vk::Format::R8G8B8A8_UNORM,
+                extent: vk::Extent3D {
+                    width: 64,
+                    height: 64,
+                    depth: 1,
+                },
+                mip_levels: 1,
+                array_layers: 1,
+                samples: vk::SampleCountFlags::TYPE_1,
+                tiling: vk::ImageTiling::OPTIMAL,
+                usage: vk::ImageUsageFlags::SAMPLED,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    assert_ne!(buffer.raw(), vk::Buffer::null());
+    assert_ne!(image.raw(), vk::Image::null());
+    assert_eq!(allocator.stats().total.allocation_count, 2);
+
+    drop(buffer);
+    drop(image);
+    assert_eq!(allocator.stats().total.allocation_count, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_into_raw_gives_up_ownership() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let buffer = unsafe {
+        OwnedBuffer::new(
+            &allocator,
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 256,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+    let (buffer, allocation) = buffer.into_raw();
+    assert_eq!(allocator.stats().total.allocation_count, 1);
+
+    unsafe { allocator.free_buffer(buffer, allocation) };
+    assert_eq!(allocator.stats().total.allocation_count, 0);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_typed_buffer_round_trip() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let buffer = unsafe {
+        Buffer::<[f32; 3]>::with_element_alignment(
+            &allocator,
+            4,
+            16,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+    };
+    assert_eq!(buffer.len(), 4);
+    assert_eq!(buffer.stride(), 16);
+    assert_eq!(buffer.offset_of(2), 32);
+
+    let data = [
+        [0.0, 1.0, 2.0],
+        [3.0, 4.0, 5.0],
+        [6.0, 7.0, 8.0],
+        [9.0, 10.0, 11.0],
+    ];
+    unsafe { buffer.write(&data)? };
+    assert_eq!(unsafe { buffer.read()? }, data);
+
+    // Too many elements for the buffer.
+    assert!(unsafe { buffer.write_at(3, &data[0..2]) }.is_err());
+
+    Ok(())
+}
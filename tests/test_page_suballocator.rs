@@ -60,7 +60,7 @@ pub fn test_paged_suballocator() -> Result<()> {
     let device = common::setup()?;
     log::info!("{}", device);
 
-    let mut allocator = unsafe {
+    let allocator = unsafe {
         create_allocator(
             device.instance.ash(),
             device.logical_device.raw().clone(),
@@ -4,9 +4,11 @@ use {
     anyhow::Result,
     ash::vk,
     ccthw_ash_allocator::{
-        into_shared, AllocationRequirements, ComposableAllocator,
-        FakeAllocator, MemoryProperties, PoolAllocator,
+        create_system_allocator, into_shared, AllocationRequirements,
+        ComposableAllocator, FakeAllocator, MemoryProperties, PoolAllocator,
+        PoolConfigurator, PoolSettings,
     },
+    ccthw_ash_instance::VulkanHandle,
 };
 
 mod common;
@@ -132,7 +134,6 @@ fn test_allocation_should_fail_when_too_big() {
 }
 
 #[test]
-#[should_panic]
 fn test_allocation_should_fail_when_using_an_invalid_memory_type_index() {
     common::setup_logger();
 
@@ -154,12 +155,356 @@ fn test_allocation_should_fail_when_using_an_invalid_memory_type_index() {
     let mut allocator =
         PoolAllocator::new(memory_properties, 64, 1, fake_allocator);
 
-    unsafe {
-        let _result = allocator.allocate(AllocationRequirements {
+    let result = unsafe {
+        allocator.allocate(AllocationRequirements {
             memory_type_index: 1,
             size_in_bytes: 20,
             alignment: 1,
             ..AllocationRequirements::default()
-        });
+        })
+    };
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_no_memory_types() {
+    common::setup_logger();
+
+    let memory_properties = unsafe {
+        // Safe because the fake allocator never allocates real memory.
+        MemoryProperties::from_raw(&[], &[])
+    };
+    let mut allocator =
+        PoolAllocator::new(memory_properties, 64, 1, FakeAllocator::default());
+
+    let result = unsafe {
+        allocator.allocate(AllocationRequirements {
+            memory_type_index: 0,
+            alignment: 1,
+            size_in_bytes: 32,
+            ..AllocationRequirements::default()
+        })
+    };
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_zero_alignment() -> Result<()> {
+    common::setup_logger();
+
+    let memory_properties = unsafe {
+        // Safe because the fake allocator never allocates real memory.
+        MemoryProperties::from_raw(
+            &[vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::empty(),
+                heap_index: 0,
+            }],
+            &[vk::MemoryHeap {
+                size: 128_000,
+                flags: vk::MemoryHeapFlags::empty(),
+            }],
+        )
+    };
+    let mut allocator =
+        PoolAllocator::new(memory_properties, 64, 1, FakeAllocator::default());
+
+    let allocation = unsafe {
+        allocator.allocate(AllocationRequirements {
+            memory_type_index: 0,
+            alignment: 0,
+            size_in_bytes: 32,
+            ..AllocationRequirements::default()
+        })?
+    };
+    unsafe { allocator.free(allocation) };
+
+    Ok(())
+}
+
+#[test]
+fn test_host_visible_chunk_size() -> Result<()> {
+    common::setup_logger();
+
+    let fake_allocator = into_shared(FakeAllocator::default());
+    let memory_properties = unsafe {
+        // Safe because the fake_allocater will never actually attempt to
+        // allocate real memory.
+        MemoryProperties::from_raw(
+            &[
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE,
+                    heap_index: 0,
+                },
+            ],
+            &[vk::MemoryHeap {
+                size: 128_000,
+                flags: vk::MemoryHeapFlags::empty(),
+            }],
+        )
+    };
+    let mut allocator =
+        PoolAllocator::new(memory_properties, 64, 1, fake_allocator.clone())
+            .with_host_visible_chunk_size(16);
+
+    let requirements = AllocationRequirements {
+        alignment: 1,
+        size_in_bytes: 32,
+        ..AllocationRequirements::default()
+    };
+    let (device_local, host_visible) = unsafe {
+        let device_local = allocator.allocate(AllocationRequirements {
+            memory_type_index: 0,
+            ..requirements
+        })?;
+        let host_visible = allocator.allocate(AllocationRequirements {
+            memory_type_index: 1,
+            ..requirements
+        })?;
+        (device_local, host_visible)
+    };
+
+    // The device-local allocation comes from a 64 byte chunk, and the
+    // host-visible allocation is too big for a 16 byte chunk.
+    assert_eq!(fake_allocator.lock().unwrap().active_allocations, 2);
+    assert_eq!(host_visible.size_in_bytes(), 32);
+
+    unsafe {
+        allocator.free(device_local);
+        allocator.free(host_visible);
+    }
+    assert_eq!(fake_allocator.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_pools_are_created_lazily() -> Result<()> {
+    common::setup_logger();
+
+    let memory_properties = unsafe {
+        // Safe because the fake_allocater will never actually attempt to
+        // allocate real memory.
+        MemoryProperties::from_raw(
+            &[
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::empty(),
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::empty(),
+                    heap_index: 0,
+                },
+            ],
+            &[vk::MemoryHeap {
+                size: 128_000,
+                flags: vk::MemoryHeapFlags::empty(),
+            }],
+        )
+    };
+    let mut allocator =
+        PoolAllocator::new(memory_properties, 64, 1, FakeAllocator::default());
+    assert_eq!(allocator.pool_count(), 0);
+
+    let allocation = unsafe {
+        allocator.allocate(AllocationRequirements {
+            memory_type_index: 1,
+            alignment: 1,
+            size_in_bytes: 32,
+            ..AllocationRequirements::default()
+        })?
+    };
+    assert_eq!(allocator.pool_count(), 1);
+
+    let result = unsafe {
+        allocator.allocate(AllocationRequirements {
+            memory_type_index: 2,
+            alignment: 1,
+            size_in_bytes: 32,
+            ..AllocationRequirements::default()
+        })
+    };
+    assert!(result.is_err());
+    assert_eq!(allocator.pool_count(), 1);
+
+    unsafe { allocator.free(allocation) };
+
+    Ok(())
+}
+
+#[test]
+fn test_reconfigure_pools() -> Result<()> {
+    common::setup_logger();
+
+    let fake_allocator = into_shared(FakeAllocator::default());
+    let memory_properties = unsafe {
+        // Safe because the fake_allocater will never actually attempt to
+        // allocate real memory.
+        MemoryProperties::from_raw(
+            &[vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::empty(),
+                heap_index: 0,
+            }],
+            &[vk::MemoryHeap {
+                size: 128_000,
+                flags: vk::MemoryHeapFlags::empty(),
+            }],
+        )
+    };
+    let mut allocator =
+        PoolAllocator::new(memory_properties, 64, 1, fake_allocator.clone());
+    let requirements = AllocationRequirements {
+        memory_type_index: 0,
+        alignment: 1,
+        size_in_bytes: 48,
+        ..AllocationRequirements::default()
+    };
+    let first = unsafe { allocator.allocate(requirements)? };
+
+    let mut double = |settings: &mut PoolSettings| settings.chunk_size *= 2;
+    allocator.reconfigure_pools(&mut PoolConfigurator::new(&mut double))?;
+    assert_eq!(allocator.settings().chunk_size, 128);
+
+    // The existing chunk is too full, so the next allocation creates a chunk
+    // with the new size.
+    let second = unsafe { allocator.allocate(requirements)? };
+    let chunk_sizes: Vec<u64> = fake_allocator
+        .lock()
+        .unwrap()
+        .allocations
+        .iter()
+        .map(|requirements| requirements.size_in_bytes)
+        .collect();
+    assert_eq!(chunk_sizes, vec![64, 128]);
+
+    // Invalid settings are rejected.
+    let mut uneven = |settings: &mut PoolSettings| settings.page_size = 3;
+    let result =
+        allocator.reconfigure_pools(&mut PoolConfigurator::new(&mut uneven));
+    assert!(result.is_err());
+    assert_eq!(allocator.settings().page_size, 1);
+
+    unsafe {
+        allocator.free(first);
+        allocator.free(second);
+    }
+    assert_eq!(fake_allocator.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_heap_chunk_divisor() -> Result<()> {
+    common::setup_logger();
+
+    let fake_allocator = into_shared(FakeAllocator::default());
+    let memory_properties = unsafe {
+        // Safe because the fake_allocater will never actually attempt to
+        // allocate real memory.
+        MemoryProperties::from_raw(
+            &[
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::empty(),
+                    heap_index: 0,
+                },
+                vk::MemoryType {
+                    property_flags: vk::MemoryPropertyFlags::empty(),
+                    heap_index: 1,
+                },
+            ],
+            &[
+                vk::MemoryHeap {
+                    size: 128_000,
+                    flags: vk::MemoryHeapFlags::empty(),
+                },
+                vk::MemoryHeap {
+                    size: 256,
+                    flags: vk::MemoryHeapFlags::empty(),
+                },
+            ],
+        )
+    };
+    let mut allocator =
+        PoolAllocator::new(memory_properties, 128, 8, fake_allocator.clone())
+            .with_heap_chunk_divisor(4);
+
+    let (large_heap, small_heap) = unsafe {
+        let requirements = AllocationRequirements {
+            alignment: 1,
+            size_in_bytes: 32,
+            ..AllocationRequirements::default()
+        };
+        (
+            allocator.allocate(requirements)?,
+            allocator.allocate(AllocationRequirements {
+                memory_type_index: 1,
+                ..requirements
+            })?,
+        )
+    };
+
+    // The small heap's chunks are clamped to a quarter of the heap.
+    let chunk_sizes: Vec<u64> = fake_allocator
+        .lock()
+        .unwrap()
+        .allocations
+        .iter()
+        .map(|requirements| requirements.size_in_bytes)
+        .collect();
+    assert_eq!(chunk_sizes, vec![128, 64]);
+
+    unsafe {
+        allocator.free(large_heap);
+        allocator.free(small_heap);
+    }
+    assert_eq!(fake_allocator.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_application_pools_can_be_reset() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let pool = unsafe { allocator.create_pool(64_000, 256)? };
+
+    let requirements = unsafe {
+        allocator.query_buffer_requirements(
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+                size: 1024,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?
+    };
+
+    let (first, second) =
+        unsafe { (pool.allocate(requirements)?, pool.allocate(requirements)?) };
+    assert_eq!(unsafe { first.memory() }, unsafe { second.memory() });
+    assert_ne!(first.offset_in_bytes(), second.offset_in_bytes());
+
+    // Reset invalidates both allocations at once and keeps the chunk.
+    unsafe { pool.reset() };
+    let reused = unsafe { pool.allocate(requirements)? };
+    assert_eq!(unsafe { reused.memory() }, unsafe { first.memory() });
+    assert_eq!(reused.offset_in_bytes(), first.offset_in_bytes());
+
+    unsafe {
+        pool.free(reused);
+        pool.trim();
     }
+
+    Ok(())
 }
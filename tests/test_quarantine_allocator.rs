@@ -0,0 +1,108 @@
+//! Tests for the quarantine allocator.
+
+use {
+    anyhow::Result,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, ComposableAllocator,
+        FakeAllocator, FrameClock, QuarantineAllocator, QuarantinePolicy,
+    },
+};
+
+mod common;
+
+fn requirements() -> AllocationRequirements {
+    AllocationRequirements {
+        size_in_bytes: 32,
+        alignment: 8,
+        ..AllocationRequirements::default()
+    }
+}
+
+#[test]
+fn test_quarantine_for_frees() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator =
+        QuarantineAllocator::new(fake.clone(), QuarantinePolicy::Frees(2));
+
+    let a1 = unsafe { allocator.allocate(requirements())? };
+    let a2 = unsafe { allocator.allocate(requirements())? };
+    let a3 = unsafe { allocator.allocate(requirements())? };
+    assert_eq!(fake.lock().unwrap().active_allocations, 3);
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+    }
+    assert_eq!(allocator.quarantined_allocations(), 2);
+    assert_eq!(fake.lock().unwrap().active_allocations, 3);
+
+    // The third free pushes the oldest allocation out of quarantine.
+    unsafe { allocator.free(a3) };
+    assert_eq!(allocator.quarantined_allocations(), 2);
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    drop(allocator);
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_quarantine_for_frames() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator =
+        QuarantineAllocator::new(fake.clone(), QuarantinePolicy::Frames(2));
+
+    let a1 = unsafe { allocator.allocate(requirements())? };
+    let a2 = unsafe { allocator.allocate(requirements())? };
+
+    unsafe {
+        allocator.free(a1);
+        allocator.end_frame();
+        allocator.free(a2);
+    }
+    assert_eq!(fake.lock().unwrap().active_allocations, 2);
+
+    unsafe { allocator.end_frame() };
+    assert_eq!(allocator.quarantined_allocations(), 1);
+    assert_eq!(fake.lock().unwrap().active_allocations, 1);
+
+    unsafe { allocator.end_frame() };
+    assert_eq!(allocator.quarantined_allocations(), 0);
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_quarantine_with_shared_clock() -> Result<()> {
+    common::setup_logger();
+
+    let clock = FrameClock::new();
+    let fake = into_shared(FakeAllocator::default());
+    let mut allocator =
+        QuarantineAllocator::new(fake.clone(), QuarantinePolicy::Frames(2))
+            .with_clock(clock.clone());
+
+    let allocation = unsafe { allocator.allocate(requirements())? };
+    unsafe { allocator.free(allocation) };
+
+    // A shared clock is only advanced by the application.
+    unsafe {
+        allocator.end_frame();
+        allocator.end_frame();
+    }
+    assert_eq!(clock.frame(), 0);
+    assert_eq!(allocator.quarantined_allocations(), 1);
+
+    clock.set_frame(2);
+    unsafe { allocator.end_frame() };
+    assert_eq!(allocator.quarantined_allocations(), 0);
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Tests for reading GPU memory back to the host.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{create_system_allocator, OwnedBuffer, Readback},
+    ccthw_ash_instance::VulkanHandle,
+    scopeguard::defer,
+};
+
+mod common;
+
+#[test]
+pub fn test_readback_copies_a_buffer_range() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo {
+                queue_family_index: device.transfer_queue_family_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_command_pool(command_pool, None) }; }
+
+    let values: Vec<u8> = (0..=255).collect();
+    let src = unsafe {
+        OwnedBuffer::new(
+            &allocator,
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::TRANSFER_SRC,
+                size: values.len() as u64,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+    };
+    unsafe {
+        src.allocation().write_bytes(
+            device.logical_device.raw(),
+            0,
+            &values,
+        )?;
+    }
+
+    let readback = unsafe {
+        Readback::from_buffer(
+            &allocator,
+            src.raw(),
+            64,
+            128,
+            device.transfer_queue,
+            command_pool,
+        )?
+    };
+    assert_eq!(readback.size_in_bytes(), 128);
+    assert_eq!(readback.read()?, &values[64..192]);
+    assert!(readback.is_ready()?);
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Tests for resizing buffers with a GPU copy.
+
+use {
+    anyhow::Result, ash::vk, ccthw_ash_allocator::create_system_allocator,
+    ccthw_ash_instance::VulkanHandle, scopeguard::defer,
+};
+
+mod common;
+
+#[test]
+pub fn test_resize_buffer_preserves_contents() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo {
+                queue_family_index: device.transfer_queue_family_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_command_pool(command_pool, None) }; }
+
+    let values: Vec<u32> = (0..256).collect();
+    let create_info = vk::BufferCreateInfo {
+        usage: vk::BufferUsageFlags::TRANSFER_SRC
+            | vk::BufferUsageFlags::TRANSFER_DST,
+        size: std::mem::size_of_val(values.as_slice()) as u64,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let (buffer, allocation) = unsafe {
+        allocator.allocate_buffer(
+            &create_info,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+    };
+    unsafe {
+        let ptr = allocation.map(device.logical_device.raw())? as *mut u32;
+        std::ptr::copy_nonoverlapping(values.as_ptr(), ptr, values.len());
+        allocation.unmap(device.logical_device.raw())?;
+    }
+
+    let (resized_buffer, resized_allocation) = unsafe {
+        allocator
+            .resize_buffer(
+                buffer,
+                allocation,
+                &create_info,
+                create_info.size * 2,
+                device.transfer_queue,
+                command_pool,
+            )
+            .map_err(|(err, _)| err)?
+    };
+    assert!(resized_allocation.size_in_bytes() >= create_info.size * 2);
+
+    unsafe {
+        device.queue_wait_idle(device.transfer_queue)?;
+        allocator.collect()?;
+    }
+
+    let copied = unsafe {
+        let ptr =
+            resized_allocation.map(device.logical_device.raw())? as *const u32;
+        let copied = std::slice::from_raw_parts(ptr, values.len()).to_vec();
+        resized_allocation.unmap(device.logical_device.raw())?;
+        copied
+    };
+    assert_eq!(copied, values);
+
+    unsafe { allocator.free_buffer(resized_buffer, resized_allocation) };
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Tests for the soak test allocator.
+
+use {
+    anyhow::{anyhow, Result},
+    ccthw_ash_allocator::{
+        into_shared, Allocation, AllocationRequirements, AllocatorError,
+        ComposableAllocator, FakeAllocator, MemoryTypePoolAllocator,
+        SoakTestAllocator,
+    },
+    std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+mod common;
+
+fn requirements(size_in_bytes: u64) -> AllocationRequirements {
+    AllocationRequirements {
+        size_in_bytes,
+        alignment: 4,
+        ..AllocationRequirements::default()
+    }
+}
+
+/// An allocator which always reports inconsistent bookkeeping.
+#[derive(Default)]
+struct BrokenAllocator {
+    fake: FakeAllocator,
+}
+
+impl ComposableAllocator for BrokenAllocator {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.fake.allocate(allocation_requirements)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) {
+        self.fake.free(allocation)
+    }
+
+    fn validate(&self) -> Result<(), AllocatorError> {
+        Err(AllocatorError::RuntimeError(anyhow!("broken")))
+    }
+}
+
+#[test]
+fn test_soak_test_with_pools() -> Result<()> {
+    common::setup_logger();
+
+    let fake = into_shared(FakeAllocator::default());
+    let failures = Arc::new(AtomicU64::new(0));
+    let hook_failures = failures.clone();
+    let mut allocator = SoakTestAllocator::new(
+        MemoryTypePoolAllocator::new(0, 1024, 16, fake.clone())
+            .with_random_placement(99),
+        8,
+    )
+    .with_failure_hook(move |_| {
+        hook_failures.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let mut allocations = vec![];
+    for i in 0..200_u64 {
+        if i % 3 == 2 {
+            let allocation = allocations.swap_remove((i as usize) % 7 % 2);
+            unsafe { allocator.free(allocation) };
+        } else {
+            allocations
+                .push(unsafe { allocator.allocate(requirements(i % 50 + 1))? });
+        }
+    }
+    for allocation in allocations.drain(..) {
+        unsafe { allocator.free(allocation) };
+    }
+
+    assert_eq!(failures.load(Ordering::SeqCst), 0);
+    assert_eq!(allocator.failure_count(), 0);
+    assert!(allocator.validate().is_ok());
+    assert_eq!(fake.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_failure_hook_is_called_periodically() -> Result<()> {
+    common::setup_logger();
+
+    let mut allocator = SoakTestAllocator::new(BrokenAllocator::default(), 2);
+
+    let a1 = unsafe { allocator.allocate(requirements(16))? };
+    assert_eq!(allocator.failure_count(), 0);
+
+    let a2 = unsafe { allocator.allocate(requirements(16))? };
+    assert_eq!(allocator.failure_count(), 1);
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+    }
+    assert_eq!(allocator.failure_count(), 2);
+    assert_eq!(allocator.operation_count(), 4);
+
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "Soak test validation failed")]
+fn test_panic_on_failure() {
+    let mut allocator = SoakTestAllocator::new(BrokenAllocator::default(), 1)
+        .panic_on_failure();
+    let _ = unsafe { allocator.allocate(requirements(16)) };
+}
@@ -0,0 +1,73 @@
+//! Tests for allocating memory pages for sparse resources.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        create_system_allocator, sparse_memory_binds, AllocatorError,
+    },
+    ccthw_ash_instance::VulkanHandle,
+};
+
+mod common;
+
+const PAGE_SIZE: u64 = 65536;
+
+#[test]
+pub fn sparse_pages_are_aligned_to_the_page_size() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let pages = unsafe { allocator.allocate_sparse_pages(4, PAGE_SIZE, 0)? };
+    assert_eq!(pages.len(), 4);
+    for page in &pages {
+        assert_eq!(page.size_in_bytes(), PAGE_SIZE);
+        assert_eq!(page.offset_in_bytes() % PAGE_SIZE, 0);
+        assert_eq!(page.allocation_requirements().memory_type_index, 0);
+    }
+
+    let binds = sparse_memory_binds(0, &pages);
+    assert_eq!(binds[3].resource_offset, 3 * PAGE_SIZE);
+    assert!(binds
+        .iter()
+        .all(|bind| bind.memory != vk::DeviceMemory::null()));
+
+    for page in pages {
+        unsafe { allocator.free(page) };
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn sparse_pages_are_not_allocated_while_frozen() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    allocator.freeze();
+    let result = unsafe { allocator.allocate_sparse_pages(4, PAGE_SIZE, 0) };
+    assert!(matches!(result, Err(AllocatorError::Frozen)));
+    assert_eq!(allocator.stats().total.allocation_count, 0);
+
+    allocator.thaw();
+    let pages = unsafe { allocator.allocate_sparse_pages(1, PAGE_SIZE, 0)? };
+    for page in pages {
+        unsafe { allocator.free(page) };
+    }
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Tests for uploading data through a staging belt.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        create_system_allocator, GpuCompletion, OwnedBuffer, StagingBelt,
+    },
+    ccthw_ash_instance::VulkanHandle,
+    scopeguard::defer,
+};
+
+mod common;
+
+#[test]
+pub fn test_staging_belt_uploads_and_recalls() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo {
+                queue_family_index: device.transfer_queue_family_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_command_pool(command_pool, None) }; }
+    let fence =
+        unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
+    defer! { unsafe { device.destroy_fence(fence, None) }; }
+
+    let values: Vec<u32> = (0..64).collect();
+    let dst = unsafe {
+        OwnedBuffer::new(
+            &allocator,
+            &vk::BufferCreateInfo {
+                usage: vk::BufferUsageFlags::TRANSFER_DST,
+                size: std::mem::size_of_val(values.as_slice()) as u64,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+    };
+    let mut belt = unsafe { StagingBelt::new(&allocator, 1024)? };
+
+    unsafe {
+        let command_buffer = device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo {
+                command_pool,
+                level: vk::CommandBufferLevel::PRIMARY,
+                command_buffer_count: 1,
+                ..Default::default()
+            },
+        )?[0];
+        device.begin_command_buffer(
+            command_buffer,
+            &vk::CommandBufferBeginInfo {
+                flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                ..Default::default()
+            },
+        )?;
+        belt.upload(command_buffer, dst.raw(), 0, &values[0..32])?;
+        belt.upload(command_buffer, dst.raw(), 128, &values[32..64])?;
+        device.end_command_buffer(command_buffer)?;
+        device.queue_submit(
+            device.transfer_queue,
+            &[vk::SubmitInfo {
+                command_buffer_count: 1,
+                p_command_buffers: &command_buffer,
+                ..Default::default()
+            }],
+            fence,
+        )?;
+        belt.finish(GpuCompletion::Fence(fence));
+        assert_eq!(belt.in_flight_count(), 1);
+
+        device.wait_for_fences(&[fence], true, u64::MAX)?;
+        belt.recall()?;
+    }
+    assert_eq!(belt.in_flight_count(), 0);
+    assert_eq!(belt.used_bytes(), 0);
+
+    let mut bytes = vec![0u8; 256];
+    unsafe {
+        dst.allocation().read_bytes(
+            device.logical_device.raw(),
+            0,
+            &mut bytes,
+        )?;
+    }
+    let uploaded: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect();
+    assert_eq!(uploaded, values);
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Tests for the tiling allocator.
+
+use {
+    anyhow::Result,
+    ash::vk,
+    ccthw_ash_allocator::{
+        into_shared, AllocationRequirements, ComposableAllocator,
+        FakeAllocator, MemoryProperties, PoolAllocator, ResourceKind,
+        TilingAllocator,
+    },
+};
+
+mod common;
+
+#[test]
+fn test_resources_are_routed_by_tiling() -> Result<()> {
+    common::setup_logger();
+
+    let linear_allocator = into_shared(FakeAllocator::default());
+    let optimal_allocator = into_shared(FakeAllocator::default());
+    let mut allocator = TilingAllocator::new(
+        linear_allocator.clone(),
+        optimal_allocator.clone(),
+    );
+
+    let (buffer, image, raw) = unsafe {
+        let requirements = AllocationRequirements {
+            size_in_bytes: 32,
+            alignment: 8,
+            ..AllocationRequirements::default()
+        };
+        (
+            allocator.allocate(AllocationRequirements {
+                resource_kind: ResourceKind::Linear,
+                ..requirements
+            })?,
+            allocator.allocate(AllocationRequirements {
+                resource_kind: ResourceKind::Optimal,
+                ..requirements
+            })?,
+            allocator.allocate(requirements)?,
+        )
+    };
+    assert_eq!(linear_allocator.lock().unwrap().active_allocations, 2);
+    assert_eq!(optimal_allocator.lock().unwrap().active_allocations, 1);
+
+    unsafe {
+        allocator.free(image);
+    }
+    assert_eq!(linear_allocator.lock().unwrap().active_allocations, 2);
+    assert_eq!(optimal_allocator.lock().unwrap().active_allocations, 0);
+
+    unsafe {
+        allocator.free(buffer);
+        allocator.free(raw);
+    }
+    assert_eq!(linear_allocator.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_pooled_resources_use_separate_chunks() -> Result<()> {
+    common::setup_logger();
+
+    let memory_properties = unsafe {
+        // Safe because the fake_allocater will never actually attempt to
+        // allocate real memory.
+        MemoryProperties::from_raw(
+            &[vk::MemoryType {
+                property_flags: vk::MemoryPropertyFlags::empty(),
+                heap_index: 0,
+            }],
+            &[vk::MemoryHeap {
+                size: 128_000,
+                flags: vk::MemoryHeapFlags::empty(),
+            }],
+        )
+    };
+    let device_allocator = into_shared(FakeAllocator::default());
+    let mut allocator = TilingAllocator::new(
+        PoolAllocator::new(
+            memory_properties.clone(),
+            1024,
+            64,
+            device_allocator.clone(),
+        ),
+        PoolAllocator::new(
+            memory_properties,
+            1024,
+            64,
+            device_allocator.clone(),
+        ),
+    );
+
+    let requirements = AllocationRequirements {
+        size_in_bytes: 64,
+        alignment: 64,
+        memory_type_index: 0,
+        ..AllocationRequirements::default()
+    };
+    let (buffer, image) = unsafe {
+        (
+            allocator.allocate(AllocationRequirements {
+                resource_kind: ResourceKind::Linear,
+                ..requirements
+            })?,
+            allocator.allocate(AllocationRequirements {
+                resource_kind: ResourceKind::Optimal,
+                ..requirements
+            })?,
+        )
+    };
+
+    // Each kind of resource gets its own chunk.
+    assert_eq!(device_allocator.lock().unwrap().active_allocations, 2);
+
+    unsafe {
+        allocator.free(buffer);
+        allocator.free(image);
+    }
+    assert_eq!(device_allocator.lock().unwrap().active_allocations, 0);
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Tests for creating device-local images with initial contents.
+
+use {
+    anyhow::Result, ash::vk, ccthw_ash_allocator::create_system_allocator,
+    ccthw_ash_instance::VulkanHandle, scopeguard::defer,
+};
+
+mod common;
+
+fn image_create_info() -> vk::ImageCreateInfo {
+    vk::ImageCreateInfo {
+        image_type: vk::ImageType::TYPE_2D,
+        format: vk::Format::R8G8B8A8_UNORM,
+        extent: vk::Extent3D {
+            width: 16,
+            height: 16,
+            depth: 1,
+        },
+        mip_levels: 1,
+        array_layers: 1,
+        samples: vk::SampleCountFlags::TYPE_1,
+        tiling: vk::ImageTiling::OPTIMAL,
+        usage: vk::ImageUsageFlags::SAMPLED,
+        sharing_mode: vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    }
+}
+
+#[test]
+pub fn test_upload_image() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+    let command_pool = unsafe {
+        device.create_command_pool(
+            &vk::CommandPoolCreateInfo {
+                queue_family_index: device.transfer_queue_family_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    defer! { unsafe { device.destroy_command_pool(command_pool, None) }; }
+
+    let texels: Vec<[u8; 4]> = (0..256).map(|i| [i as u8, 0, 0, 255]).collect();
+    let (image, allocation) = unsafe {
+        allocator.upload_image(
+            &image_create_info(),
+            &texels,
+            device.transfer_queue,
+            command_pool,
+        )?
+    };
+    assert!(
+        allocation.size_in_bytes()
+            >= std::mem::size_of_val(texels.as_slice()) as u64
+    );
+
+    unsafe { allocator.free_image(image, allocation) };
+
+    Ok(())
+}
+
+#[test]
+pub fn test_upload_image_with_no_data_fails() -> Result<()> {
+    let device = common::setup()?;
+
+    let allocator = unsafe {
+        create_system_allocator(
+            device.instance.ash(),
+            device.logical_device.raw().clone(),
+            *device.logical_device.physical_device().raw(),
+        )
+    };
+
+    let result = unsafe {
+        allocator.upload_image::<u32>(
+            &image_create_info(),
+            &[],
+            device.transfer_queue,
+            vk::CommandPool::null(),
+        )
+    };
+    assert!(result.is_err());
+
+    Ok(())
+}
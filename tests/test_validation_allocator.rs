@@ -0,0 +1,122 @@
+//! Tests for the validation allocator.
+
+use {
+    anyhow::Result,
+    ccthw_ash_allocator::{
+        Allocation, AllocationRequirements, AllocatorError,
+        ComposableAllocator, FakeAllocator, ValidationAllocator,
+    },
+};
+
+mod common;
+
+fn requirements(size_in_bytes: u64, alignment: u64) -> AllocationRequirements {
+    AllocationRequirements {
+        size_in_bytes,
+        alignment,
+        ..AllocationRequirements::default()
+    }
+}
+
+/// A broken allocator which hands out the same memory from two independent
+/// fake allocators.
+#[derive(Default)]
+struct AliasingAllocator {
+    first: FakeAllocator,
+    second: FakeAllocator,
+    use_second: bool,
+}
+
+impl ComposableAllocator for AliasingAllocator {
+    unsafe fn allocate(
+        &mut self,
+        allocation_requirements: AllocationRequirements,
+    ) -> Result<Allocation, AllocatorError> {
+        self.use_second = !self.use_second;
+        if self.use_second {
+            self.second.allocate(allocation_requirements)
+        } else {
+            self.first.allocate(allocation_requirements)
+        }
+    }
+
+    unsafe fn free(&mut self, _allocation: Allocation) {}
+}
+
+#[test]
+fn test_valid_usage() -> Result<()> {
+    common::setup_logger();
+
+    let mut allocator = ValidationAllocator::new(FakeAllocator::default());
+
+    let a1 = unsafe { allocator.allocate(requirements(16, 8))? };
+    let a2 = unsafe { allocator.allocate(requirements(16, 8))? };
+    assert_eq!(allocator.outstanding_allocations(), 2);
+
+    unsafe {
+        allocator.free(a1);
+        allocator.free(a2);
+    }
+    assert_eq!(allocator.outstanding_allocations(), 0);
+
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "Double free")]
+fn test_double_free() {
+    let mut allocator = ValidationAllocator::new(FakeAllocator::default());
+    unsafe {
+        let allocation = allocator.allocate(requirements(16, 1)).unwrap();
+        allocator.free(allocation.clone());
+        allocator.free(allocation);
+    }
+}
+
+#[test]
+#[should_panic(expected = "not produced by this allocator")]
+fn test_old_frees_are_forgotten() {
+    let mut allocator =
+        ValidationAllocator::new(FakeAllocator::default()).with_free_history(2);
+    unsafe {
+        let first = allocator.allocate(requirements(16, 1)).unwrap();
+        allocator.free(first.clone());
+        for _ in 0..2 {
+            let allocation = allocator.allocate(requirements(16, 1)).unwrap();
+            allocator.free(allocation);
+        }
+        allocator.free(first);
+    }
+}
+
+#[test]
+#[should_panic(expected = "not produced by this allocator")]
+fn test_foreign_free() {
+    let mut allocator = ValidationAllocator::new(FakeAllocator::default());
+    let mut other = FakeAllocator::default();
+    unsafe {
+        let allocation = other.allocate(requirements(16, 1)).unwrap();
+        allocator.free(allocation);
+    }
+}
+
+#[test]
+#[should_panic(expected = "misaligned")]
+fn test_misaligned_allocation() {
+    let mut allocator = ValidationAllocator::new(FakeAllocator::default());
+    unsafe {
+        // The fake allocator packs allocations without respecting alignment.
+        let _ = allocator.allocate(requirements(3, 1)).unwrap();
+        let _ = allocator.allocate(requirements(4, 4)).unwrap();
+    }
+}
+
+#[test]
+#[should_panic(expected = "overlaps")]
+fn test_overlapping_allocation() {
+    let mut allocator = ValidationAllocator::new(AliasingAllocator::default());
+    unsafe {
+        let _ = allocator.allocate(requirements(16, 1)).unwrap();
+        let _ = allocator.allocate(requirements(16, 1)).unwrap();
+    }
+}
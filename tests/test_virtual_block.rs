@@ -0,0 +1,81 @@
+//! Tests for virtual blocks, which manage offsets without device memory.
+
+use {anyhow::Result, ccthw_ash_allocator::VirtualBlock};
+
+#[test]
+pub fn test_allocate_and_free() -> Result<()> {
+    let mut block = VirtualBlock::new(1024, 64);
+    assert!(block.is_empty());
+
+    let a = block.allocate(100, 1)?;
+    let b = block.allocate(64, 1)?;
+    assert_eq!(a.offset_in_bytes(), 0);
+    assert_eq!(a.size_in_bytes(), 100);
+    assert_eq!(b.offset_in_bytes(), 128);
+    assert!(!block.is_empty());
+    block.validate()?;
+
+    block.free(a);
+    let c = block.allocate(128, 1)?;
+    assert_eq!(c.offset_in_bytes(), 0);
+
+    block.free(b);
+    block.free(c);
+    assert!(block.is_empty());
+    block.validate()?;
+
+    Ok(())
+}
+
+#[test]
+pub fn test_out_of_space() -> Result<()> {
+    let mut block = VirtualBlock::new(256, 64);
+    let a = block.allocate(256, 1)?;
+    assert!(block.allocate(1, 1).is_err());
+
+    block.free(a);
+    assert!(block.allocate(1, 1).is_ok());
+
+    Ok(())
+}
+
+#[test]
+pub fn test_alignment_larger_than_pages() -> Result<()> {
+    let mut block = VirtualBlock::new(4096, 16);
+    let _padding = block.allocate(16, 1)?;
+
+    let aligned = block.allocate(100, 256)?;
+    assert_eq!(aligned.offset_in_bytes() % 256, 0);
+    assert!(aligned.offset_in_bytes() + 100 <= block.size_in_bytes());
+
+    block.free(aligned);
+    block.validate()?;
+
+    Ok(())
+}
+
+#[test]
+pub fn test_random_placement_never_overlaps() -> Result<()> {
+    let mut block = VirtualBlock::new(64 * 32, 32).with_random_placement(7);
+    let mut allocations = vec![];
+    for i in 0..16 {
+        allocations.push(block.allocate(1 + i * 7, 1 << (i % 7))?);
+    }
+    allocations.sort_by_key(|allocation| allocation.offset_in_bytes());
+    for pair in allocations.windows(2) {
+        assert!(
+            pair[0].offset_in_bytes() + pair[0].size_in_bytes()
+                <= pair[1].offset_in_bytes()
+        );
+    }
+    for (i, allocation) in allocations.iter().enumerate() {
+        assert_eq!(allocation.offset_in_bytes() % (1 << (i % 7)), 0, "{i}");
+    }
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "multiple of page_size_in_bytes")]
+pub fn test_size_must_be_a_multiple_of_page_size() {
+    let _ = VirtualBlock::new(1000, 64);
+}